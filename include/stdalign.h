@@ -0,0 +1,10 @@
+/* <stdalign.h> bundled with zcc */
+#ifndef __ZCC_STDALIGN_H
+#define __ZCC_STDALIGN_H
+
+#define alignas _Alignas
+#define alignof _Alignof
+#define __alignas_is_defined 1
+#define __alignof_is_defined 1
+
+#endif
@@ -0,0 +1,10 @@
+/* <stdbool.h> bundled with zcc */
+#ifndef __ZCC_STDBOOL_H
+#define __ZCC_STDBOOL_H
+
+#define bool _Bool
+#define true 1
+#define false 0
+#define __bool_true_false_are_defined 1
+
+#endif
@@ -0,0 +1,20 @@
+/* <stddef.h> bundled with zcc */
+#ifndef __ZCC_STDDEF_H
+#define __ZCC_STDDEF_H
+
+typedef __SIZE_TYPE__ size_t;
+typedef __PTRDIFF_TYPE__ ptrdiff_t;
+typedef __WCHAR_TYPE__ wchar_t;
+
+#define NULL ((void *)0)
+
+#define offsetof(type, member) __builtin_offsetof(type, member)
+
+#if defined(__STDC_VERSION__) && __STDC_VERSION__ >= 201112L
+typedef struct {
+    long long __max_align_ll;
+    long double __max_align_ld;
+} max_align_t;
+#endif
+
+#endif
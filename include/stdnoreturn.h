@@ -0,0 +1,7 @@
+/* <stdnoreturn.h> bundled with zcc */
+#ifndef __ZCC_STDNORETURN_H
+#define __ZCC_STDNORETURN_H
+
+#define noreturn _Noreturn
+
+#endif
@@ -0,0 +1,1075 @@
+use crate::lang::{Constant, Operator};
+use crate::source::Span;
+use crate::types::CType;
+
+mod dump;
+mod unparse;
+pub mod visit;
+
+pub use dump::dump;
+pub use unparse::unparse;
+
+// Every node carries the span of source it was parsed from, which
+// for nodes that came out of macros is the invocation
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct TranslationUnit {
+    pub declarations: Vec<ExternalDeclaration>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum ExternalDeclaration {
+    Function(FunctionDefinition),
+    Declaration(Declaration),
+    StaticAssert(StaticAssert),
+    // Tokens skipped after a syntax error
+    Error(Span),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct FunctionDefinition {
+    pub specifiers: DeclarationSpecifiers,
+    pub declarator: Declarator,
+    // An old-style definition declares its parameters between the
+    // declarator and the body, `int f(a) char a; { ... }`
+    pub declarations: Vec<Declaration>,
+    pub body: CompoundStatement,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+// Declarations
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Declaration {
+    pub specifiers: DeclarationSpecifiers,
+    pub declarators: Vec<InitDeclarator>,
+    pub span: Span,
+}
+
+// `_Static_assert(condition, "message");`, which the parser checks.
+// The message can be left out since C23.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct StaticAssert {
+    pub condition: Expression,
+    pub message: Option<String>,
+    pub span: Span,
+}
+
+// One attribute of a `[[...]]` sequence, `[[gnu::aligned(8)]]` has the
+// prefix `gnu`. The arguments are kept as the tokens they were written
+// as, separated by spaces.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Attribute {
+    pub syntax: AttributeSyntax,
+    pub prefix: Option<Identifier>,
+    pub name: Identifier,
+    pub arguments: Option<String>,
+    // What GNU `aligned` asks for, worked out when it's parsed
+    pub alignment: Option<u64>,
+    pub span: Span,
+}
+
+impl Attribute {
+    // Whether it's a GNU attribute, as `__attribute__((x))` or `[[gnu::x]]`
+    pub fn is_gnu(&self) -> bool {
+        match self.prefix {
+            Some(ref prefix) => matches!(prefix.name.as_str(), "gnu" | "__gnu__"),
+            None => self.syntax == AttributeSyntax::Gnu,
+        }
+    }
+
+    // The name of a GNU attribute zcc knows about, written either way
+    pub fn gnu_name(&self) -> Option<&'static str> {
+        self.is_gnu()
+            .then(|| crate::lang::gnu_attribute(&self.name.name))
+            .flatten()
+    }
+}
+
+// C23 `[[packed]]` or GNU `__attribute__((packed))`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum AttributeSyntax {
+    Standard,
+    Gnu,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct InitDeclarator {
+    pub declarator: Declarator,
+    pub initializer: Option<Initializer>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum Initializer {
+    Expression(Expression),
+    List(InitializerList),
+}
+
+impl Initializer {
+    pub fn span(&self) -> Span {
+        match *self {
+            Self::Expression(ref expression) => expression.span,
+            Self::List(ref list) => list.span,
+        }
+    }
+}
+
+// A brace enclosed list, which can leave out the braces of nested
+// aggregates, so `{1, 2, 3, 4}` and `{{1, 2}, {3, 4}}` both initialize
+// an `int[2][2]`. Matching items to members is left to a later pass.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct InitializerList {
+    pub items: Vec<InitializerItem>,
+    pub span: Span,
+}
+
+// `[1].x = 2` has two designators, without any the item initializes
+// whatever comes after the previous one
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct InitializerItem {
+    pub designators: Vec<Designator>,
+    pub initializer: Initializer,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Designator {
+    pub kind: DesignatorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum DesignatorKind {
+    Index(Expression),
+    Member(Identifier),
+}
+
+// Specifiers are kept in the order they were written
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct DeclarationSpecifiers {
+    pub specifiers: Vec<DeclarationSpecifier>,
+    // The type the type specifiers and qualifiers name, worked out as
+    // they're parsed
+    pub ty: CType,
+    pub span: Span,
+}
+
+impl DeclarationSpecifiers {
+    pub fn storage_classes(&self) -> impl Iterator<Item = StorageClass> + '_ {
+        self.specifiers.iter().filter_map(|spec| match spec.kind {
+            DeclarationSpecifierKind::StorageClass(class) => Some(class),
+            _ => None,
+        })
+    }
+
+    pub fn is_inline(&self) -> bool {
+        self.specifiers.iter().any(|spec| {
+            spec.kind == DeclarationSpecifierKind::FunctionSpecifier(FunctionSpecifier::Inline)
+        })
+    }
+
+    pub fn is_typedef(&self) -> bool {
+        self.storage_classes()
+            .any(|class| class == StorageClass::Typedef)
+    }
+
+    pub fn qualifiers(&self) -> impl Iterator<Item = TypeQualifier> + '_ {
+        self.specifiers.iter().filter_map(|spec| match spec.kind {
+            DeclarationSpecifierKind::TypeQualifier(qualifier) => Some(qualifier),
+            _ => None,
+        })
+    }
+
+    pub fn type_specifiers(&self) -> impl Iterator<Item = &TypeSpecifier> + '_ {
+        self.specifiers.iter().filter_map(|spec| match spec.kind {
+            DeclarationSpecifierKind::TypeSpecifier(ref specifier) => Some(specifier),
+            _ => None,
+        })
+    }
+
+    // The type the specifiers name, or the first specifier that can't
+    // be combined with the ones before it
+    pub fn base_type(&self) -> Result<BaseType<'_>, &DeclarationSpecifier> {
+        let mut seen = Vec::new();
+        for spec in &self.specifiers {
+            if let DeclarationSpecifierKind::TypeSpecifier(ref specifier) = spec.kind {
+                seen.push(specifier);
+                if BaseType::from_specifiers(&seen).is_none() {
+                    return Err(spec);
+                }
+            }
+        }
+        Ok(BaseType::from_specifiers(&seen).expect("Checked as specifiers were added"))
+    }
+
+    // The full type of something declared with these specifiers. The
+    // parser rejects invalid specifiers, so they always name a type.
+    pub fn describe<'a>(&'a self, declarator: &'a Declarator) -> TypeDescription<'a> {
+        TypeDescription {
+            base: self
+                .base_type()
+                .expect("Specifiers are checked by the parser"),
+            qualifiers: self.qualifiers().collect(),
+            derivations: declarator.derivations(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct DeclarationSpecifier {
+    pub kind: DeclarationSpecifierKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum DeclarationSpecifierKind {
+    StorageClass(StorageClass),
+    TypeQualifier(TypeQualifier),
+    TypeSpecifier(TypeSpecifier),
+    FunctionSpecifier(FunctionSpecifier),
+    AlignmentSpecifier(AlignmentSpecifier),
+    // `[[...]]`, which C23 allows before and after the specifiers
+    Attributes(Vec<Attribute>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum StorageClass {
+    Typedef,
+    Extern,
+    Static,
+    Auto,
+    Register,
+    ThreadLocal,
+    Constexpr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum TypeQualifier {
+    Const,
+    Volatile,
+    Restrict,
+    Atomic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum FunctionSpecifier {
+    Inline,
+    Noreturn,
+}
+
+// `_Alignas(type)` aligns like the type, `_Alignas(expression)` to
+// the value of the expression
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum AlignmentSpecifier {
+    Type(TypeName),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum TypeSpecifier {
+    Void,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    Signed,
+    Unsigned,
+    Bool,
+    Struct(StructSpecifier),
+    Enum(EnumSpecifier),
+    TypedefName(String),
+    Typeof(TypeofSpecifier),
+    Atomic(AtomicSpecifier),
+    // `__builtin_va_list`, what `va_list` is defined as
+    VaList,
+}
+
+// `typeof(T)` or `typeof(expression)`, and `typeof_unqual` which drops
+// the qualifiers of the type
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct TypeofSpecifier {
+    pub unqualified: bool,
+    pub operand: TypeofOperand,
+    pub span: Span,
+}
+
+// `_Atomic(T)`, the same type as `_Atomic T`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct AtomicSpecifier {
+    pub type_name: Box<TypeName>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum TypeofOperand {
+    Type(Box<TypeName>),
+    Expression(Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum StructKind {
+    Struct,
+    Union,
+}
+
+// `members` is `None` when the struct is only referred to by its tag
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct StructSpecifier {
+    pub kind: StructKind,
+    pub attributes: Vec<Attribute>,
+    pub tag: Option<Identifier>,
+    pub members: Option<Vec<StructDeclaration>>,
+    pub span: Span,
+}
+
+// A C11 anonymous struct or union member has no declarators, its
+// members belong to the struct it's in
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct StructDeclaration {
+    pub specifiers: DeclarationSpecifiers,
+    pub declarators: Vec<StructDeclarator>,
+    pub span: Span,
+}
+
+// A member, or a bit-field when it has a width. Bit-fields without a
+// name have an abstract declarator.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct StructDeclarator {
+    pub declarator: Declarator,
+    pub width: Option<Expression>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct EnumSpecifier {
+    pub attributes: Vec<Attribute>,
+    pub tag: Option<Identifier>,
+    pub enumerators: Option<Vec<Enumerator>>,
+    pub span: Span,
+}
+
+// `value` is the expression as written, `constant` is what it
+// evaluated to or one more than the enumerator before
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Enumerator {
+    pub name: Identifier,
+    pub value: Option<Expression>,
+    pub constant: Constant,
+    pub span: Span,
+}
+
+// Declarators nest inside out, `*x[3]` is an array of three pointers
+// so it's a pointer declarator around an array declarator around `x`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Declarator {
+    pub kind: DeclaratorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum DeclaratorKind {
+    // No name, as in type names and unnamed parameters
+    Abstract,
+    Identifier(Identifier),
+    Pointer {
+        qualifiers: Vec<TypeQualifier>,
+        inner: Box<Declarator>,
+    },
+    Array {
+        inner: Box<Declarator>,
+        size: Option<Box<Expression>>,
+    },
+    Function {
+        inner: Box<Declarator>,
+        parameters: Vec<ParameterDeclaration>,
+        variadic: bool,
+    },
+    // `f(a, b)`, the parameter names of an old-style definition without
+    // their types. `f()` is a `Function` without parameters instead.
+    OldStyleFunction {
+        inner: Box<Declarator>,
+        identifiers: Vec<Identifier>,
+    },
+    // GNU attributes after a declarator, `int f(void)
+    // __attribute__((noreturn))`. They don't change the type, other than
+    // `aligned` on a typedef.
+    Attributed {
+        inner: Box<Declarator>,
+        attributes: Vec<Attribute>,
+    },
+}
+
+impl Declarator {
+    pub fn name(&self) -> Option<&Identifier> {
+        match self.kind {
+            DeclaratorKind::Abstract => None,
+            DeclaratorKind::Identifier(ref name) => Some(name),
+            DeclaratorKind::Pointer { ref inner, .. }
+            | DeclaratorKind::Array { ref inner, .. }
+            | DeclaratorKind::Function { ref inner, .. }
+            | DeclaratorKind::OldStyleFunction { ref inner, .. }
+            | DeclaratorKind::Attributed { ref inner, .. } => inner.name(),
+        }
+    }
+
+    // The GNU attributes written after the declarator or any of the
+    // declarators inside it
+    pub fn attributes(&self) -> Vec<&Attribute> {
+        let mut attributes = Vec::new();
+        let mut declarator = self;
+        loop {
+            declarator = match declarator.kind {
+                DeclaratorKind::Abstract | DeclaratorKind::Identifier(_) => return attributes,
+                DeclaratorKind::Attributed {
+                    ref inner,
+                    attributes: ref these,
+                } => {
+                    attributes.extend(these);
+                    inner
+                }
+                DeclaratorKind::Pointer { ref inner, .. }
+                | DeclaratorKind::Array { ref inner, .. }
+                | DeclaratorKind::Function { ref inner, .. }
+                | DeclaratorKind::OldStyleFunction { ref inner, .. } => inner,
+            };
+        }
+    }
+
+    // The parameter names of an old-style function declarator, if that
+    // is the first derivation of the name
+    pub fn identifier_list(&self) -> Option<&[Identifier]> {
+        let mut list = None;
+        let mut declarator = self;
+        loop {
+            declarator = match declarator.kind {
+                DeclaratorKind::Abstract | DeclaratorKind::Identifier(_) => return list,
+                DeclaratorKind::OldStyleFunction {
+                    ref inner,
+                    ref identifiers,
+                } => {
+                    list = Some(identifiers.as_slice());
+                    inner
+                }
+                DeclaratorKind::Attributed { ref inner, .. } => inner,
+                DeclaratorKind::Pointer { ref inner, .. }
+                | DeclaratorKind::Array { ref inner, .. }
+                | DeclaratorKind::Function { ref inner, .. } => {
+                    list = None;
+                    inner
+                }
+            };
+        }
+    }
+
+    // The pointers, arrays and functions applied to the base type, in
+    // the order they are read out loud starting from the name. The
+    // nesting is inside out, so this is the path to the name reversed.
+    pub fn derivations(&self) -> Vec<Derivation<'_>> {
+        let mut derivations = Vec::new();
+        let mut declarator = self;
+        loop {
+            let (derivation, inner) = match declarator.kind {
+                DeclaratorKind::Abstract | DeclaratorKind::Identifier(_) => break,
+                DeclaratorKind::Attributed { ref inner, .. } => {
+                    declarator = inner;
+                    continue;
+                }
+                DeclaratorKind::Pointer {
+                    ref qualifiers,
+                    ref inner,
+                } => (Derivation::Pointer(qualifiers), inner),
+                DeclaratorKind::Array {
+                    ref size,
+                    ref inner,
+                } => (Derivation::Array(size.as_deref()), inner),
+                DeclaratorKind::Function {
+                    ref parameters,
+                    variadic,
+                    ref inner,
+                } => (
+                    Derivation::Function {
+                        parameters,
+                        variadic,
+                    },
+                    inner,
+                ),
+                // The types of the parameters aren't part of the
+                // function's type, as if it was declared `f()`
+                DeclaratorKind::OldStyleFunction { ref inner, .. } => (
+                    Derivation::Function {
+                        parameters: &[],
+                        variadic: false,
+                    },
+                    inner,
+                ),
+            };
+            derivations.push(derivation);
+            declarator = inner;
+        }
+        derivations.reverse();
+        derivations
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct ParameterDeclaration {
+    pub specifiers: DeclarationSpecifiers,
+    pub declarator: Declarator,
+    // Adjusted, so a parameter declared as an array or a function is a
+    // pointer
+    pub ty: CType,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct TypeName {
+    pub specifiers: DeclarationSpecifiers,
+    pub declarator: Declarator,
+    pub ty: CType,
+    pub span: Span,
+}
+
+// Types as written, before any checking of what they refer to
+
+// The combination of type specifiers at the start of a declaration.
+// Keywords that mean the same type are merged, `short int` and
+// `signed short` are both `Short`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BaseType<'a> {
+    Void,
+    Bool,
+    Char,
+    SignedChar,
+    UnsignedChar,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    Float,
+    Double,
+    LongDouble,
+    Struct(&'a StructSpecifier),
+    Enum(&'a EnumSpecifier),
+    TypedefName(&'a str),
+    Typeof(&'a TypeofSpecifier),
+    Atomic(&'a AtomicSpecifier),
+    VaList,
+}
+
+impl<'a> BaseType<'a> {
+    // Enumerations count as integer types, their constants are `int`
+    pub fn is_integer(&self) -> bool {
+        !matches!(
+            self,
+            Self::Void
+                | Self::Float
+                | Self::Double
+                | Self::LongDouble
+                | Self::Struct(_)
+                | Self::TypedefName(_)
+                | Self::Typeof(_)
+                | Self::Atomic(_)
+                | Self::VaList
+        )
+    }
+
+    // `None` when the specifiers don't go together. No specifiers at
+    // all is an implicit `int`.
+    fn from_specifiers(specifiers: &[&'a TypeSpecifier]) -> Option<Self> {
+        if let [specifier] = *specifiers {
+            match *specifier {
+                TypeSpecifier::Struct(ref specifier) => return Some(Self::Struct(specifier)),
+                TypeSpecifier::Enum(ref specifier) => return Some(Self::Enum(specifier)),
+                TypeSpecifier::TypedefName(ref name) => return Some(Self::TypedefName(name)),
+                TypeSpecifier::Typeof(ref specifier) => return Some(Self::Typeof(specifier)),
+                TypeSpecifier::Atomic(ref specifier) => return Some(Self::Atomic(specifier)),
+                TypeSpecifier::VaList => return Some(Self::VaList),
+                _ => (),
+            }
+        }
+
+        // How many times each keyword appears
+        let mut counts = [0u8; 10];
+        for specifier in specifiers {
+            let index = match **specifier {
+                TypeSpecifier::Void => 0,
+                TypeSpecifier::Char => 1,
+                TypeSpecifier::Short => 2,
+                TypeSpecifier::Int => 3,
+                TypeSpecifier::Long => 4,
+                TypeSpecifier::Float => 5,
+                TypeSpecifier::Double => 6,
+                TypeSpecifier::Signed => 7,
+                TypeSpecifier::Unsigned => 8,
+                TypeSpecifier::Bool => 9,
+                _ => return None,
+            };
+            counts[index] += 1;
+        }
+        let [void, char, short, int, long, float, double, signed, unsigned, bool] = counts;
+        let unsigned = match (signed, unsigned) {
+            (0, 0) => None,
+            (1, 0) => Some(false),
+            (0, 1) => Some(true),
+            _ => return None,
+        };
+
+        if bool > 0 {
+            let alone = bool == 1 && counts.iter().sum::<u8>() == 1;
+            return alone.then_some(Self::Bool);
+        }
+        let base = match (void, char, short, int, long, float, double, unsigned) {
+            (1, 0, 0, 0, 0, 0, 0, None) => Self::Void,
+            (0, 1, 0, 0, 0, 0, 0, None) => Self::Char,
+            (0, 1, 0, 0, 0, 0, 0, Some(false)) => Self::SignedChar,
+            (0, 1, 0, 0, 0, 0, 0, Some(true)) => Self::UnsignedChar,
+            (0, 0, 1, 0..=1, 0, 0, 0, unsigned) => match unsigned {
+                Some(true) => Self::UnsignedShort,
+                _ => Self::Short,
+            },
+            (0, 0, 0, 0..=1, 0, 0, 0, unsigned) => match unsigned {
+                Some(true) => Self::UnsignedInt,
+                _ => Self::Int,
+            },
+            (0, 0, 0, 0..=1, 1, 0, 0, unsigned) => match unsigned {
+                Some(true) => Self::UnsignedLong,
+                _ => Self::Long,
+            },
+            (0, 0, 0, 0..=1, 2, 0, 0, unsigned) => match unsigned {
+                Some(true) => Self::UnsignedLongLong,
+                _ => Self::LongLong,
+            },
+            (0, 0, 0, 0, 0, 1, 0, None) => Self::Float,
+            (0, 0, 0, 0, 0, 0, 1, None) => Self::Double,
+            (0, 0, 0, 0, 1, 0, 1, None) => Self::LongDouble,
+            _ => return None,
+        };
+        Some(base)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Derivation<'a> {
+    Pointer(&'a [TypeQualifier]),
+    Array(Option<&'a Expression>),
+    Function {
+        parameters: &'a [ParameterDeclaration],
+        variadic: bool,
+    },
+}
+
+// A declared type split into its parts, `int *const x[3]` is an array
+// of 3 const pointers to int
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDescription<'a> {
+    pub base: BaseType<'a>,
+    pub qualifiers: Vec<TypeQualifier>,
+    pub derivations: Vec<Derivation<'a>>,
+}
+
+impl std::fmt::Display for TypeDescription<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for derivation in &self.derivations {
+            match *derivation {
+                Derivation::Pointer(qualifiers) => {
+                    write_qualifiers(f, qualifiers)?;
+                    write!(f, "pointer to ")?;
+                }
+                Derivation::Array(Some(Expression {
+                    kind: ExpressionKind::Constant(Constant::Integer(size, _)),
+                    ..
+                })) => write!(f, "array of {} ", size)?,
+                Derivation::Array(_) => write!(f, "array of ")?,
+                Derivation::Function {
+                    parameters,
+                    variadic,
+                } => {
+                    write!(f, "function (")?;
+                    for (i, parameter) in parameters.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        let description = parameter.specifiers.describe(&parameter.declarator);
+                        write!(f, "{}", description)?;
+                    }
+                    if variadic {
+                        let separator = if parameters.is_empty() { "" } else { ", " };
+                        write!(f, "{}...", separator)?;
+                    }
+                    write!(f, ") returning ")?;
+                }
+            }
+        }
+        write_qualifiers(f, &self.qualifiers)?;
+        write!(f, "{}", self.base)
+    }
+}
+
+fn write_qualifiers(
+    f: &mut std::fmt::Formatter<'_>,
+    qualifiers: &[TypeQualifier],
+) -> std::fmt::Result {
+    for qualifier in qualifiers {
+        match qualifier {
+            TypeQualifier::Const => write!(f, "const ")?,
+            TypeQualifier::Volatile => write!(f, "volatile ")?,
+            TypeQualifier::Restrict => write!(f, "restrict ")?,
+            TypeQualifier::Atomic => write!(f, "_Atomic ")?,
+        }
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for BaseType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match *self {
+            Self::Void => "void",
+            Self::Bool => "_Bool",
+            Self::Char => "char",
+            Self::SignedChar => "signed char",
+            Self::UnsignedChar => "unsigned char",
+            Self::Short => "short",
+            Self::UnsignedShort => "unsigned short",
+            Self::Int => "int",
+            Self::UnsignedInt => "unsigned int",
+            Self::Long => "long",
+            Self::UnsignedLong => "unsigned long",
+            Self::LongLong => "long long",
+            Self::UnsignedLongLong => "unsigned long long",
+            Self::Float => "float",
+            Self::Double => "double",
+            Self::LongDouble => "long double",
+            Self::Struct(specifier) => {
+                let kind = match specifier.kind {
+                    StructKind::Struct => "struct",
+                    StructKind::Union => "union",
+                };
+                return match specifier.tag {
+                    Some(ref tag) => write!(f, "{} {}", kind, tag.name),
+                    None => write!(f, "anonymous {}", kind),
+                };
+            }
+            Self::Enum(specifier) => {
+                return match specifier.tag {
+                    Some(ref tag) => write!(f, "enum {}", tag.name),
+                    None => write!(f, "anonymous enum"),
+                };
+            }
+            Self::Typeof(specifier) => {
+                let name = match specifier.unqualified {
+                    true => "typeof_unqual",
+                    false => "typeof",
+                };
+                return match specifier.operand {
+                    TypeofOperand::Type(ref type_name) => write!(
+                        f,
+                        "{}({})",
+                        name,
+                        type_name.specifiers.describe(&type_name.declarator)
+                    ),
+                    TypeofOperand::Expression(_) => write!(f, "{}(expression)", name),
+                };
+            }
+            Self::Atomic(specifier) => {
+                let type_name = &specifier.type_name;
+                return write!(
+                    f,
+                    "_Atomic({})",
+                    type_name.specifiers.describe(&type_name.declarator)
+                );
+            }
+            Self::TypedefName(name) => name,
+            Self::VaList => "__builtin_va_list",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Statements
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct CompoundStatement {
+    pub items: Vec<BlockItem>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum BlockItem {
+    Declaration(Declaration),
+    Statement(Statement),
+    StaticAssert(StaticAssert),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum StatementKind {
+    Compound(CompoundStatement),
+    Expression(Expression),
+    Null,
+    If {
+        condition: Expression,
+        then: Box<Statement>,
+        otherwise: Option<Box<Statement>>,
+    },
+    Switch {
+        condition: Expression,
+        body: Box<Statement>,
+    },
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+    },
+    DoWhile {
+        body: Box<Statement>,
+        condition: Expression,
+    },
+    // Any of the three clauses can be left out
+    For {
+        init: Option<Box<ForInit>>,
+        condition: Option<Expression>,
+        step: Option<Expression>,
+        body: Box<Statement>,
+    },
+    Case {
+        value: Expression,
+        body: Box<Statement>,
+    },
+    // GNU `case low ... high:`, which includes both ends
+    CaseRange {
+        low: Expression,
+        high: Expression,
+        body: Box<Statement>,
+    },
+    Default(Box<Statement>),
+    Labeled {
+        label: Identifier,
+        body: Box<Statement>,
+    },
+    // `[[attributes]] statement`, where `[[fallthrough]];` has a null
+    // statement
+    Attributed {
+        attributes: Vec<Attribute>,
+        statement: Box<Statement>,
+    },
+    Goto(Identifier),
+    Continue,
+    Break,
+    Return(Option<Expression>),
+    // Tokens skipped after a syntax error
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum ForInit {
+    Declaration(Declaration),
+    Expression(Expression),
+}
+
+// Expressions
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    // Worked out as the expression is parsed, from the types of its
+    // operands
+    pub ty: CType,
+    pub category: ValueCategory,
+    pub span: Span,
+}
+
+// An lvalue designates an object, and its value is only read when it's
+// converted to a value. Functions are designators of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum ValueCategory {
+    Value,
+    Lvalue,
+    Function,
+}
+
+// What an `ImplicitCast` does to its operand to give a value of the
+// cast's type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum Conversion {
+    // Reads the value of an lvalue, without its qualifiers
+    LvalueToValue,
+    ArrayToPointer,
+    FunctionToPointer,
+    Integer,
+    IntegerToFloating,
+    FloatingToInteger,
+    Floating,
+    // Any scalar to `_Bool`, which is 1 unless it compares equal to 0
+    ToBool,
+    NullToPointer,
+    Pointer,
+    IntegerToPointer,
+    PointerToInteger,
+}
+
+impl std::fmt::Display for Conversion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum ExpressionKind {
+    // Where an expression was expected but something else was found
+    Error,
+    Identifier(String),
+    Constant(Constant),
+    StringLiteral(String),
+    Call {
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+    },
+    // `operator` is `Period` or `Arrow`
+    Member {
+        object: Box<Expression>,
+        operator: Operator,
+        member: Identifier,
+    },
+    Subscript {
+        array: Box<Expression>,
+        index: Box<Expression>,
+    },
+    // `x++` and `x--`
+    Postfix {
+        operator: Operator,
+        operand: Box<Expression>,
+    },
+    // Prefix operators, including `++x` and `--x`
+    Unary {
+        operator: Operator,
+        operand: Box<Expression>,
+    },
+    SizeofExpression(Box<Expression>),
+    SizeofType(Box<TypeName>),
+    AlignofType(Box<TypeName>),
+    Cast {
+        type_name: Box<TypeName>,
+        operand: Box<Expression>,
+    },
+    // A conversion that isn't written out, such as the usual arithmetic
+    // conversions of the operands of `+`. It converts to the type of the
+    // expression.
+    ImplicitCast {
+        conversion: Conversion,
+        operand: Box<Expression>,
+    },
+    // `(struct S){ ... }`, an unnamed object that is initialized like
+    // a declaration would be
+    CompoundLiteral {
+        type_name: Box<TypeName>,
+        initializers: InitializerList,
+    },
+    // Also the comma operator, as `Operator::Comma`
+    Binary {
+        operator: Operator,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    // GNU C can leave out the middle operand, `x ?: y` is `x` if it
+    // isn't zero, without evaluating it twice
+    Conditional {
+        condition: Box<Expression>,
+        then: Option<Box<Expression>>,
+        otherwise: Box<Expression>,
+    },
+    // Simple and compound assignment
+    Assignment {
+        operator: Operator,
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    // `_Generic(controlling, type: expression, default: expression)`
+    Generic {
+        controlling: Box<Expression>,
+        associations: Vec<GenericAssociation>,
+    },
+    // GNU `({ ... })`, the value of the last expression statement
+    Statement(CompoundStatement),
+    // `__builtin_va_arg(list, T)`, which `va_arg` expands to
+    VaArg {
+        list: Box<Expression>,
+        type_name: Box<TypeName>,
+    },
+    // `__builtin_offsetof(T, a.b[1])`, which `offsetof` expands to. The
+    // first designator is always a member.
+    Offsetof {
+        type_name: Box<TypeName>,
+        designators: Vec<Designator>,
+    },
+}
+
+// The type is `None` for the `default` association
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct GenericAssociation {
+    pub type_name: Option<TypeName>,
+    pub expression: Expression,
+    pub span: Span,
+}
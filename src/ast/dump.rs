@@ -0,0 +1,540 @@
+use crate::lang::{self, Constant};
+use crate::lexer::escape_char;
+use crate::source::{SourceManager, Span};
+use crate::types::TypeTable;
+
+use super::visit::*;
+use super::*;
+
+// A tree of every node, one per line and indented under its parent,
+// with where it came from and the type of anything declared or worked
+// out for an expression
+pub fn dump(sources: &SourceManager, types: &TypeTable, unit: &TranslationUnit) -> String {
+    let mut dumper = Dumper {
+        sources,
+        types,
+        output: String::new(),
+        depth: 0,
+    };
+    dumper.visit_translation_unit(unit);
+    dumper.output
+}
+
+struct Dumper<'a> {
+    sources: &'a SourceManager,
+    types: &'a TypeTable,
+    output: String,
+    depth: usize,
+}
+
+impl Dumper<'_> {
+    fn node(&mut self, label: &str, span: Span, children: impl FnOnce(&mut Self)) {
+        for _ in 0..self.depth {
+            self.output.push_str("  ");
+        }
+        let buffer = self.sources.buffer(span.file_id);
+        let (lo_line, lo_col) = buffer.line_col(span.lo);
+        let (hi_line, hi_col) = buffer.line_col(span.hi);
+        self.output.push_str(&format!(
+            "{} <{}:{}:{}, {}:{}>\n",
+            label,
+            buffer.name(),
+            lo_line,
+            lo_col,
+            hi_line,
+            hi_col
+        ));
+
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+    }
+
+    fn leaf(&mut self, label: &str, span: Span) {
+        self.node(label, span, |_| ());
+    }
+
+    fn typeof_specifier(&mut self, specifier: &TypeofSpecifier) {
+        let label = match specifier.unqualified {
+            true => "TypeofUnqualSpecifier",
+            false => "TypeofSpecifier",
+        };
+        self.node(label, specifier.span, |dumper| match specifier.operand {
+            TypeofOperand::Type(ref type_name) => dumper.visit_type_name(type_name),
+            TypeofOperand::Expression(ref expression) => dumper.visit_expression(expression),
+        });
+    }
+
+    fn attributes(&mut self, attributes: &[Attribute], span: Span) {
+        self.node("Attributes", span, |dumper| {
+            for attribute in attributes {
+                dumper.attribute(attribute);
+            }
+        });
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        let mut label = match attribute.syntax {
+            AttributeSyntax::Standard => "Attribute ".to_string(),
+            AttributeSyntax::Gnu => "GnuAttribute ".to_string(),
+        };
+        if let Some(ref prefix) = attribute.prefix {
+            label.push_str(&prefix.name);
+            label.push_str("::");
+        }
+        label.push_str(&attribute.name.name);
+        if let Some(ref arguments) = attribute.arguments {
+            label.push_str(&format!("({})", arguments));
+        }
+        self.leaf(&label, attribute.span);
+    }
+
+    fn designators(&mut self, designators: &[Designator]) {
+        for designator in designators {
+            match designator.kind {
+                DesignatorKind::Index(ref index) => {
+                    self.node("IndexDesignator", designator.span, |dumper| {
+                        dumper.visit_expression(index)
+                    })
+                }
+                DesignatorKind::Member(ref member) => {
+                    let label = format!("MemberDesignator {}", member.name);
+                    self.leaf(&label, designator.span);
+                }
+            }
+        }
+    }
+
+    fn none(&mut self) {
+        for _ in 0..self.depth {
+            self.output.push_str("  ");
+        }
+        self.output.push_str("<none>\n");
+    }
+
+    // Labelled with the type of the expression, and whether it's an
+    // lvalue or a function
+    fn expression_node(
+        &mut self,
+        label: &str,
+        expression: &Expression,
+        children: impl FnOnce(&mut Self),
+    ) {
+        let category = match expression.category {
+            ValueCategory::Value => "",
+            ValueCategory::Lvalue => " lvalue",
+            ValueCategory::Function => " function",
+        };
+        let label = format!(
+            "{} '{}'{}",
+            label,
+            self.types.display(expression.ty),
+            category
+        );
+        self.node(&label, expression.span, children);
+    }
+}
+
+// Each node is written with its children indented under it. Names
+// aren't nodes of their own, they go in the label of whatever declares
+// or uses them.
+impl<'ast> Visitor<'ast> for Dumper<'_> {
+    fn visit_translation_unit(&mut self, unit: &'ast TranslationUnit) {
+        self.node("TranslationUnit", unit.span, |dumper| {
+            walk_translation_unit(dumper, unit)
+        });
+    }
+
+    fn visit_external_declaration(&mut self, declaration: &'ast ExternalDeclaration) {
+        match *declaration {
+            ExternalDeclaration::Error(span) => self.leaf("Error", span),
+            _ => walk_external_declaration(self, declaration),
+        }
+    }
+
+    fn visit_function_definition(&mut self, function: &'ast FunctionDefinition) {
+        let description = function.specifiers.describe(&function.declarator);
+        let label = format!("FunctionDefinition '{}'", description);
+        self.node(&label, function.span, |dumper| {
+            walk_function_definition(dumper, function)
+        });
+    }
+
+    // Declarators are labelled with what they declare, which takes the
+    // specifiers they share
+    fn visit_declaration(&mut self, declaration: &'ast Declaration) {
+        self.node("Declaration", declaration.span, |dumper| {
+            dumper.visit_declaration_specifiers(&declaration.specifiers);
+            for init in &declaration.declarators {
+                let description = declaration.specifiers.describe(&init.declarator);
+                let label = format!("InitDeclarator '{}'", description);
+                dumper.node(&label, init.span, |dumper| {
+                    walk_init_declarator(dumper, init)
+                });
+            }
+        });
+    }
+
+    fn visit_static_assert(&mut self, assertion: &'ast StaticAssert) {
+        let label = match assertion.message {
+            Some(ref message) => format!("StaticAssert \"{}\"", message),
+            None => "StaticAssert".to_string(),
+        };
+        self.node(&label, assertion.span, |dumper| {
+            walk_static_assert(dumper, assertion)
+        });
+    }
+
+    fn visit_initializer_list(&mut self, list: &'ast InitializerList) {
+        self.node("InitializerList", list.span, |dumper| {
+            for item in &list.items {
+                dumper.node("InitializerItem", item.span, |dumper| {
+                    dumper.designators(&item.designators);
+                    dumper.visit_initializer(&item.initializer);
+                });
+            }
+        });
+    }
+
+    fn visit_declaration_specifiers(&mut self, specifiers: &'ast DeclarationSpecifiers) {
+        self.node("DeclarationSpecifiers", specifiers.span, |dumper| {
+            for specifier in &specifiers.specifiers {
+                match specifier.kind {
+                    DeclarationSpecifierKind::StorageClass(class) => {
+                        let label = format!("StorageClass {:?}", class);
+                        dumper.leaf(&label, specifier.span);
+                    }
+                    DeclarationSpecifierKind::TypeQualifier(qualifier) => {
+                        let label = format!("TypeQualifier {:?}", qualifier);
+                        dumper.leaf(&label, specifier.span);
+                    }
+                    DeclarationSpecifierKind::FunctionSpecifier(function_specifier) => {
+                        let label = format!("FunctionSpecifier {:?}", function_specifier);
+                        dumper.leaf(&label, specifier.span);
+                    }
+                    DeclarationSpecifierKind::AlignmentSpecifier(ref alignment) => dumper.node(
+                        "AlignmentSpecifier",
+                        specifier.span,
+                        |dumper| match *alignment {
+                            AlignmentSpecifier::Type(ref type_name) => {
+                                dumper.visit_type_name(type_name)
+                            }
+                            AlignmentSpecifier::Expression(ref expression) => {
+                                dumper.visit_expression(expression)
+                            }
+                        },
+                    ),
+                    DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Struct(ref s)) => {
+                        dumper.visit_struct_specifier(s)
+                    }
+                    DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Enum(ref e)) => {
+                        dumper.visit_enum_specifier(e)
+                    }
+                    DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::TypedefName(
+                        ref name,
+                    )) => {
+                        let label = format!("TypedefName {}", name);
+                        dumper.leaf(&label, specifier.span);
+                    }
+                    DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Typeof(ref t)) => {
+                        dumper.typeof_specifier(t)
+                    }
+                    DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Atomic(ref a)) => dumper
+                        .node("AtomicSpecifier", a.span, |dumper| {
+                            dumper.visit_type_name(&a.type_name)
+                        }),
+                    DeclarationSpecifierKind::Attributes(ref attributes) => {
+                        dumper.attributes(attributes, specifier.span)
+                    }
+                    DeclarationSpecifierKind::TypeSpecifier(ref specifier_kind) => {
+                        let label = format!("TypeSpecifier {:?}", specifier_kind);
+                        dumper.leaf(&label, specifier.span);
+                    }
+                }
+            }
+        });
+    }
+
+    fn visit_struct_specifier(&mut self, specifier: &'ast StructSpecifier) {
+        let mut label = format!("{:?}Specifier", specifier.kind);
+        if let Some(ref tag) = specifier.tag {
+            label.push(' ');
+            label.push_str(&tag.name);
+        }
+        if specifier.members.is_none() {
+            label.push_str(" reference");
+        }
+        self.node(&label, specifier.span, |dumper| {
+            for attribute in &specifier.attributes {
+                dumper.attribute(attribute);
+            }
+            walk_struct_specifier(dumper, specifier);
+        });
+    }
+
+    fn visit_struct_declaration(&mut self, declaration: &'ast StructDeclaration) {
+        self.node("StructDeclaration", declaration.span, |dumper| {
+            dumper.visit_declaration_specifiers(&declaration.specifiers);
+            for declarator in &declaration.declarators {
+                let description = declaration.specifiers.describe(&declarator.declarator);
+                let label = format!("StructDeclarator '{}'", description);
+                dumper.node(&label, declarator.span, |dumper| {
+                    walk_struct_declarator(dumper, declarator)
+                });
+            }
+        });
+    }
+
+    fn visit_enum_specifier(&mut self, specifier: &'ast EnumSpecifier) {
+        let mut label = "EnumSpecifier".to_string();
+        if let Some(ref tag) = specifier.tag {
+            label.push(' ');
+            label.push_str(&tag.name);
+        }
+        if specifier.enumerators.is_none() {
+            label.push_str(" reference");
+        }
+        self.node(&label, specifier.span, |dumper| {
+            for attribute in &specifier.attributes {
+                dumper.attribute(attribute);
+            }
+            walk_enum_specifier(dumper, specifier);
+        });
+    }
+
+    fn visit_enumerator(&mut self, enumerator: &'ast Enumerator) {
+        let value = match enumerator.constant {
+            Constant::Enumeration(value) => value,
+            _ => unreachable!("Enumerators are enumeration constants"),
+        };
+        let label = format!("Enumerator {} = {}", enumerator.name.name, value);
+        self.node(&label, enumerator.span, |dumper| {
+            walk_enumerator(dumper, enumerator)
+        });
+    }
+
+    fn visit_declarator(&mut self, declarator: &'ast Declarator) {
+        let label = match declarator.kind {
+            DeclaratorKind::Abstract => "AbstractDeclarator".to_string(),
+            DeclaratorKind::Identifier(ref name) => format!("IdentifierDeclarator {}", name.name),
+            DeclaratorKind::Pointer { ref qualifiers, .. } => {
+                let mut label = "PointerDeclarator".to_string();
+                for qualifier in qualifiers {
+                    label.push_str(&format!(" {:?}", qualifier));
+                }
+                label
+            }
+            DeclaratorKind::Array { .. } => "ArrayDeclarator".to_string(),
+            DeclaratorKind::Function { variadic: true, .. } => {
+                "FunctionDeclarator variadic".to_string()
+            }
+            DeclaratorKind::Function { .. } => "FunctionDeclarator".to_string(),
+            DeclaratorKind::OldStyleFunction { .. } => "OldStyleFunctionDeclarator".to_string(),
+            DeclaratorKind::Attributed { .. } => "AttributedDeclarator".to_string(),
+        };
+        self.node(&label, declarator.span, |dumper| {
+            walk_declarator(dumper, declarator);
+            // What follows the declarator it's part of
+            match declarator.kind {
+                DeclaratorKind::OldStyleFunction {
+                    ref identifiers, ..
+                } => {
+                    for identifier in identifiers {
+                        let label = format!("Identifier {}", identifier.name);
+                        dumper.leaf(&label, identifier.span);
+                    }
+                }
+                DeclaratorKind::Attributed { ref attributes, .. } => {
+                    for attribute in attributes {
+                        dumper.attribute(attribute);
+                    }
+                }
+                _ => (),
+            }
+        });
+    }
+
+    fn visit_parameter_declaration(&mut self, parameter: &'ast ParameterDeclaration) {
+        let description = parameter.specifiers.describe(&parameter.declarator);
+        let label = format!("ParameterDeclaration '{}'", description);
+        self.node(&label, parameter.span, |dumper| {
+            walk_parameter_declaration(dumper, parameter)
+        });
+    }
+
+    fn visit_type_name(&mut self, type_name: &'ast TypeName) {
+        let description = type_name.specifiers.describe(&type_name.declarator);
+        let label = format!("TypeName '{}'", description);
+        self.node(&label, type_name.span, |dumper| {
+            walk_type_name(dumper, type_name)
+        });
+    }
+
+    fn visit_compound_statement(&mut self, compound: &'ast CompoundStatement) {
+        self.node("CompoundStatement", compound.span, |dumper| {
+            walk_compound_statement(dumper, compound)
+        });
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        let label = match statement.kind {
+            // Blocks are their own node
+            StatementKind::Compound(ref compound) => {
+                return self.visit_compound_statement(compound)
+            }
+            StatementKind::Expression(_) => "ExpressionStatement".to_string(),
+            StatementKind::Null => "NullStatement".to_string(),
+            StatementKind::If {
+                otherwise: Some(_), ..
+            } => "IfStatement has_else".to_string(),
+            StatementKind::If { .. } => "IfStatement".to_string(),
+            StatementKind::Switch { .. } => "SwitchStatement".to_string(),
+            StatementKind::While { .. } => "WhileStatement".to_string(),
+            StatementKind::DoWhile { .. } => "DoStatement".to_string(),
+            StatementKind::For {
+                ref init,
+                ref condition,
+                ref step,
+                ref body,
+            } => {
+                // Clauses that were left out are written as `<none>`
+                return self.node("ForStatement", statement.span, |dumper| {
+                    match init.as_deref() {
+                        Some(ForInit::Declaration(declaration)) => {
+                            dumper.visit_declaration(declaration)
+                        }
+                        Some(ForInit::Expression(expression)) => {
+                            dumper.visit_expression(expression)
+                        }
+                        None => dumper.none(),
+                    }
+                    for clause in [condition, step] {
+                        match *clause {
+                            Some(ref expression) => dumper.visit_expression(expression),
+                            None => dumper.none(),
+                        }
+                    }
+                    dumper.visit_statement(body);
+                });
+            }
+            StatementKind::Case { .. } => "CaseStatement".to_string(),
+            StatementKind::CaseRange { .. } => "CaseRangeStatement".to_string(),
+            StatementKind::Default(_) => "DefaultStatement".to_string(),
+            StatementKind::Labeled { ref label, .. } => format!("LabelStatement {}", label.name),
+            StatementKind::Attributed { ref attributes, .. } => {
+                return self.node("AttributedStatement", statement.span, |dumper| {
+                    for attribute in attributes {
+                        dumper.attribute(attribute);
+                    }
+                    walk_statement(dumper, statement);
+                });
+            }
+            StatementKind::Goto(ref label) => format!("GotoStatement {}", label.name),
+            StatementKind::Continue => "ContinueStatement".to_string(),
+            StatementKind::Break => "BreakStatement".to_string(),
+            StatementKind::Return(_) => "ReturnStatement".to_string(),
+            StatementKind::Error => "Error".to_string(),
+        };
+        self.node(&label, statement.span, |dumper| {
+            walk_statement(dumper, statement)
+        });
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        let label = match expression.kind {
+            ExpressionKind::Error => "Error".to_string(),
+            ExpressionKind::Identifier(ref name) => format!("Identifier {}", name),
+            ExpressionKind::Constant(ref constant) => match *constant {
+                Constant::Integer(value, _) => format!("IntegerConstant {}", value as u64),
+                Constant::Enumeration(value) => format!("EnumerationConstant {}", value),
+                Constant::Floating(value, _) => format!("FloatingConstant {:?}", value),
+                Constant::Character(c) => {
+                    format!("CharacterConstant '{}'", escape_char(c, '\''))
+                }
+                Constant::Bool(value) => format!("BoolConstant {}", value),
+                Constant::Nullptr => "NullptrConstant".to_string(),
+            },
+            ExpressionKind::StringLiteral(ref literal) => format!("StringLiteral \"{}\"", literal),
+            ExpressionKind::Call { .. } => "Call".to_string(),
+            ExpressionKind::Member {
+                operator,
+                ref member,
+                ..
+            } => format!(
+                "Member '{}' {}",
+                lang::operator_spelling(operator),
+                member.name
+            ),
+            ExpressionKind::Subscript { .. } => "Subscript".to_string(),
+            ExpressionKind::Postfix { operator, .. } => {
+                format!("PostfixOperator '{}'", lang::operator_spelling(operator))
+            }
+            ExpressionKind::Unary { operator, .. } => {
+                format!("UnaryOperator '{}'", lang::operator_spelling(operator))
+            }
+            ExpressionKind::SizeofExpression(_) => "SizeofExpression".to_string(),
+            ExpressionKind::SizeofType(_) => "SizeofType".to_string(),
+            ExpressionKind::AlignofType(_) => "AlignofType".to_string(),
+            ExpressionKind::Cast { .. } => "Cast".to_string(),
+            ExpressionKind::ImplicitCast { conversion, .. } => {
+                format!("ImplicitCast <{}>", conversion)
+            }
+            ExpressionKind::CompoundLiteral { .. } => "CompoundLiteral".to_string(),
+            ExpressionKind::Binary { operator, .. } => {
+                format!("BinaryOperator '{}'", lang::operator_spelling(operator))
+            }
+            // A left out middle operand is written as `<none>`
+            ExpressionKind::Conditional {
+                ref condition,
+                ref then,
+                ref otherwise,
+            } => {
+                return self.expression_node("ConditionalOperator", expression, |dumper| {
+                    dumper.visit_expression(condition);
+                    match *then {
+                        Some(ref then) => dumper.visit_expression(then),
+                        None => dumper.none(),
+                    }
+                    dumper.visit_expression(otherwise);
+                });
+            }
+            ExpressionKind::Assignment { operator, .. } => {
+                format!("Assignment '{}'", lang::operator_spelling(operator))
+            }
+            ExpressionKind::Generic {
+                ref controlling,
+                ref associations,
+            } => {
+                return self.expression_node("GenericSelection", expression, |dumper| {
+                    dumper.visit_expression(controlling);
+                    for association in associations {
+                        let label = match association.type_name {
+                            Some(_) => "GenericAssociation",
+                            None => "GenericAssociation default",
+                        };
+                        dumper.node(label, association.span, |dumper| {
+                            if let Some(ref type_name) = association.type_name {
+                                dumper.visit_type_name(type_name);
+                            }
+                            dumper.visit_expression(&association.expression);
+                        });
+                    }
+                });
+            }
+            ExpressionKind::Statement(_) => "StatementExpression".to_string(),
+            ExpressionKind::VaArg { .. } => "VaArg".to_string(),
+            ExpressionKind::Offsetof {
+                ref type_name,
+                ref designators,
+            } => {
+                return self.expression_node("Offsetof", expression, |dumper| {
+                    dumper.visit_type_name(type_name);
+                    dumper.designators(designators);
+                });
+            }
+        };
+        self.expression_node(&label, expression, |dumper| {
+            walk_expression(dumper, expression)
+        });
+    }
+}
@@ -0,0 +1,919 @@
+use crate::lang::{self, Constant, Operator};
+use crate::lexer::escape_char;
+
+use super::visit::{fold_translation_unit, Fold};
+use super::*;
+
+// Turns an AST back into C that parses to the same tree. None of the
+// original formatting is kept, and parentheses are only written where
+// precedence needs them.
+pub fn unparse(unit: &TranslationUnit) -> String {
+    let mut unit = unit.clone();
+    fold_translation_unit(&mut AsWritten, &mut unit);
+    let mut printer = Printer::default();
+    for declaration in &unit.declarations {
+        printer.external_declaration(declaration);
+        printer.output.push('\n');
+    }
+    printer.output
+}
+
+// Takes out the conversions the parser added, which were never written
+struct AsWritten;
+
+impl Fold for AsWritten {
+    fn fold_expression(&mut self, expression: Expression) -> Expression {
+        match expression.kind {
+            ExpressionKind::ImplicitCast { operand, .. } => *operand,
+            _ => expression,
+        }
+    }
+}
+
+// Precedence of each kind of expression, higher binds tighter. Binary
+// operators fit between conditional and cast.
+const COMMA: u8 = 1;
+const ASSIGNMENT: u8 = 2;
+const CONDITIONAL: u8 = 3;
+const LOGICAL_OR: u8 = 4;
+const CAST: u8 = 14;
+const POSTFIX: u8 = 15;
+const PRIMARY: u8 = 16;
+
+fn precedence(expression: &Expression) -> u8 {
+    match expression.kind {
+        ExpressionKind::Error
+        | ExpressionKind::Identifier(_)
+        | ExpressionKind::Constant(_)
+        | ExpressionKind::StringLiteral(_)
+        | ExpressionKind::Generic { .. }
+        | ExpressionKind::Statement(_)
+        | ExpressionKind::VaArg { .. }
+        | ExpressionKind::Offsetof { .. } => PRIMARY,
+        ExpressionKind::Call { .. }
+        | ExpressionKind::Member { .. }
+        | ExpressionKind::Subscript { .. }
+        | ExpressionKind::Postfix { .. }
+        | ExpressionKind::CompoundLiteral { .. } => POSTFIX,
+        ExpressionKind::Unary { .. }
+        | ExpressionKind::SizeofExpression(_)
+        | ExpressionKind::SizeofType(_)
+        | ExpressionKind::AlignofType(_)
+        | ExpressionKind::Cast { .. } => CAST,
+        ExpressionKind::Binary {
+            operator: Operator::Comma,
+            ..
+        } => COMMA,
+        ExpressionKind::Binary { operator, .. } => {
+            lang::binary_precedence(operator).expect("Binary operators have a precedence")
+                + CONDITIONAL
+        }
+        ExpressionKind::Conditional { .. } => CONDITIONAL,
+        ExpressionKind::Assignment { .. } => ASSIGNMENT,
+        ExpressionKind::ImplicitCast { .. } => unreachable!("Implicit casts are folded away"),
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    output: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn write(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn newline(&mut self) {
+        self.output.push('\n');
+        for _ in 0..self.indent {
+            self.output.push_str("    ");
+        }
+    }
+
+    fn external_declaration(&mut self, declaration: &ExternalDeclaration) {
+        match *declaration {
+            ExternalDeclaration::Function(ref function) => {
+                // Specifiers left out are an implicit `int`
+                if !function.specifiers.specifiers.is_empty() {
+                    self.specifiers(&function.specifiers);
+                    self.write(" ");
+                }
+                self.declarator(&function.declarator);
+                // The parameter declarations of an old-style definition
+                // go on their own lines, indented like the body
+                if function.declarations.is_empty() {
+                    self.write(" ");
+                } else {
+                    self.indent += 1;
+                    for declaration in &function.declarations {
+                        self.newline();
+                        self.declaration(declaration);
+                    }
+                    self.indent -= 1;
+                    self.newline();
+                }
+                self.compound_statement(&function.body);
+                self.newline();
+            }
+            ExternalDeclaration::Declaration(ref declaration) => self.declaration(declaration),
+            ExternalDeclaration::StaticAssert(ref assertion) => self.static_assert(assertion),
+            ExternalDeclaration::Error(_) => self.write("/* error */"),
+        }
+    }
+
+    fn static_assert(&mut self, assertion: &StaticAssert) {
+        self.write("_Static_assert(");
+        self.expression(&assertion.condition, ASSIGNMENT);
+        if let Some(ref message) = assertion.message {
+            self.write(", \"");
+            self.write(message);
+            self.write("\"");
+        }
+        self.write(");");
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) {
+        self.specifiers(&declaration.specifiers);
+        for (i, init) in declaration.declarators.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            } else if !declaration.specifiers.specifiers.is_empty() {
+                self.write(" ");
+            }
+            self.declarator(&init.declarator);
+            if let Some(ref initializer) = init.initializer {
+                self.write(" = ");
+                self.initializer(initializer);
+            }
+        }
+        self.write(";");
+    }
+
+    fn specifiers(&mut self, specifiers: &DeclarationSpecifiers) {
+        for (i, specifier) in specifiers.specifiers.iter().enumerate() {
+            if i > 0 {
+                self.write(" ");
+            }
+            match specifier.kind {
+                DeclarationSpecifierKind::StorageClass(class) => self.write(match class {
+                    StorageClass::Typedef => "typedef",
+                    StorageClass::Extern => "extern",
+                    StorageClass::Static => "static",
+                    StorageClass::Auto => "auto",
+                    StorageClass::Register => "register",
+                    StorageClass::ThreadLocal => "_Thread_local",
+                    StorageClass::Constexpr => "constexpr",
+                }),
+                DeclarationSpecifierKind::Attributes(ref attributes) => self.attributes(attributes),
+                DeclarationSpecifierKind::TypeQualifier(qualifier) => self.qualifier(qualifier),
+                DeclarationSpecifierKind::TypeSpecifier(ref specifier) => {
+                    self.type_specifier(specifier)
+                }
+                DeclarationSpecifierKind::FunctionSpecifier(function_specifier) => {
+                    self.write(match function_specifier {
+                        FunctionSpecifier::Inline => "inline",
+                        FunctionSpecifier::Noreturn => "_Noreturn",
+                    })
+                }
+                DeclarationSpecifierKind::AlignmentSpecifier(ref alignment) => {
+                    self.write("_Alignas(");
+                    match *alignment {
+                        AlignmentSpecifier::Type(ref type_name) => self.type_name(type_name),
+                        AlignmentSpecifier::Expression(ref expression) => {
+                            self.expression(expression, CONDITIONAL)
+                        }
+                    }
+                    self.write(")");
+                }
+            }
+        }
+    }
+
+    fn qualifier(&mut self, qualifier: TypeQualifier) {
+        self.write(match qualifier {
+            TypeQualifier::Const => "const",
+            TypeQualifier::Volatile => "volatile",
+            TypeQualifier::Restrict => "restrict",
+            TypeQualifier::Atomic => "_Atomic",
+        });
+    }
+
+    fn type_specifier(&mut self, specifier: &TypeSpecifier) {
+        let keyword = match *specifier {
+            TypeSpecifier::Void => "void",
+            TypeSpecifier::Char => "char",
+            TypeSpecifier::Short => "short",
+            TypeSpecifier::Int => "int",
+            TypeSpecifier::Long => "long",
+            TypeSpecifier::Float => "float",
+            TypeSpecifier::Double => "double",
+            TypeSpecifier::Signed => "signed",
+            TypeSpecifier::Unsigned => "unsigned",
+            TypeSpecifier::Bool => "_Bool",
+            TypeSpecifier::Struct(ref specifier) => return self.struct_specifier(specifier),
+            TypeSpecifier::Enum(ref specifier) => return self.enum_specifier(specifier),
+            TypeSpecifier::TypedefName(ref name) => name,
+            TypeSpecifier::VaList => "__builtin_va_list",
+            // Unlike `typeof`, `__typeof__` is a keyword before C23 too
+            TypeSpecifier::Typeof(ref specifier) => {
+                self.write(match specifier.unqualified {
+                    true => "typeof_unqual(",
+                    false => "__typeof__(",
+                });
+                match specifier.operand {
+                    TypeofOperand::Type(ref type_name) => self.type_name(type_name),
+                    TypeofOperand::Expression(ref expression) => self.expression(expression, COMMA),
+                }
+                return self.write(")");
+            }
+            TypeSpecifier::Atomic(ref specifier) => {
+                self.write("_Atomic(");
+                self.type_name(&specifier.type_name);
+                return self.write(")");
+            }
+        };
+        self.write(keyword);
+    }
+
+    // Each run of attributes is written in the syntax it was parsed from
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        let runs = attributes.chunk_by(|a, b| a.syntax == b.syntax);
+        for (i, run) in runs.enumerate() {
+            if i > 0 {
+                self.write(" ");
+            }
+            let (open, close) = match run[0].syntax {
+                AttributeSyntax::Standard => ("[[", "]]"),
+                AttributeSyntax::Gnu => ("__attribute__((", "))"),
+            };
+            self.write(open);
+            for (i, attribute) in run.iter().enumerate() {
+                if i > 0 {
+                    self.write(", ");
+                }
+                if let Some(ref prefix) = attribute.prefix {
+                    self.write(&prefix.name);
+                    self.write("::");
+                }
+                self.write(&attribute.name.name);
+                if let Some(ref arguments) = attribute.arguments {
+                    self.write("(");
+                    self.write(arguments);
+                    self.write(")");
+                }
+            }
+            self.write(close);
+        }
+    }
+
+    fn struct_specifier(&mut self, specifier: &StructSpecifier) {
+        self.write(match specifier.kind {
+            StructKind::Struct => "struct",
+            StructKind::Union => "union",
+        });
+        if !specifier.attributes.is_empty() {
+            self.write(" ");
+            self.attributes(&specifier.attributes);
+        }
+        if let Some(ref tag) = specifier.tag {
+            self.write(" ");
+            self.write(&tag.name);
+        }
+        let Some(ref members) = specifier.members else {
+            return;
+        };
+
+        self.write(" {");
+        self.indent += 1;
+        for member in members {
+            self.newline();
+            self.specifiers(&member.specifiers);
+            for (i, declarator) in member.declarators.iter().enumerate() {
+                self.write(if i == 0 { " " } else { ", " });
+                self.declarator(&declarator.declarator);
+                if let Some(ref width) = declarator.width {
+                    // Unnamed bit-fields are only a width
+                    if declarator.declarator.kind != DeclaratorKind::Abstract {
+                        self.write(" ");
+                    }
+                    self.write(": ");
+                    self.expression(width, CONDITIONAL);
+                }
+            }
+            self.write(";");
+        }
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    fn enum_specifier(&mut self, specifier: &EnumSpecifier) {
+        self.write("enum");
+        if !specifier.attributes.is_empty() {
+            self.write(" ");
+            self.attributes(&specifier.attributes);
+        }
+        if let Some(ref tag) = specifier.tag {
+            self.write(" ");
+            self.write(&tag.name);
+        }
+        let Some(ref enumerators) = specifier.enumerators else {
+            return;
+        };
+
+        self.write(" {");
+        self.indent += 1;
+        for (i, enumerator) in enumerators.iter().enumerate() {
+            if i > 0 {
+                self.write(",");
+            }
+            self.newline();
+            self.write(&enumerator.name.name);
+            if let Some(ref value) = enumerator.value {
+                self.write(" = ");
+                self.expression(value, CONDITIONAL);
+            }
+        }
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    // Declarators are written the way they nest, except that a pointer
+    // inside an array or function needs parentheses, `(*x)[3]` is a
+    // pointer to an array while `*x[3]` is an array of pointers
+    fn declarator(&mut self, declarator: &Declarator) {
+        match declarator.kind {
+            DeclaratorKind::Abstract => (),
+            DeclaratorKind::Identifier(ref name) => self.write(&name.name),
+            DeclaratorKind::Pointer {
+                ref qualifiers,
+                ref inner,
+            } => {
+                self.write("*");
+                for (i, &qualifier) in qualifiers.iter().enumerate() {
+                    if i > 0 {
+                        self.write(" ");
+                    }
+                    self.qualifier(qualifier);
+                }
+                if !qualifiers.is_empty() && inner.kind != DeclaratorKind::Abstract {
+                    self.write(" ");
+                }
+                self.declarator(inner);
+            }
+            DeclaratorKind::Array {
+                ref inner,
+                ref size,
+            } => {
+                self.inner_declarator(inner);
+                self.write("[");
+                if let Some(ref size) = *size {
+                    self.expression(size, ASSIGNMENT);
+                }
+                self.write("]");
+            }
+            DeclaratorKind::Function {
+                ref inner,
+                ref parameters,
+                variadic,
+            } => {
+                self.inner_declarator(inner);
+                self.write("(");
+                for (i, parameter) in parameters.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.specifiers(&parameter.specifiers);
+                    self.optional_declarator(&parameter.declarator);
+                }
+                if variadic {
+                    self.write(if parameters.is_empty() {
+                        "..."
+                    } else {
+                        ", ..."
+                    });
+                }
+                self.write(")");
+            }
+            DeclaratorKind::OldStyleFunction {
+                ref inner,
+                ref identifiers,
+            } => {
+                self.inner_declarator(inner);
+                self.write("(");
+                for (i, identifier) in identifiers.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.write(&identifier.name);
+                }
+                self.write(")");
+            }
+            DeclaratorKind::Attributed {
+                ref inner,
+                ref attributes,
+            } => {
+                self.inner_declarator(inner);
+                if inner.kind != DeclaratorKind::Abstract {
+                    self.write(" ");
+                }
+                self.attributes(attributes);
+            }
+        }
+    }
+
+    // Attributes end a declarator, so one that has them needs
+    // parentheses inside another as well
+    fn inner_declarator(&mut self, inner: &Declarator) {
+        if let DeclaratorKind::Pointer { .. } | DeclaratorKind::Attributed { .. } = inner.kind {
+            self.write("(");
+            self.declarator(inner);
+            self.write(")");
+        } else {
+            self.declarator(inner);
+        }
+    }
+
+    // After specifiers, where an abstract declarator writes nothing
+    fn optional_declarator(&mut self, declarator: &Declarator) {
+        if declarator.kind != DeclaratorKind::Abstract {
+            self.write(" ");
+            self.declarator(declarator);
+        }
+    }
+
+    fn type_name(&mut self, type_name: &TypeName) {
+        self.specifiers(&type_name.specifiers);
+        self.optional_declarator(&type_name.declarator);
+    }
+
+    fn initializer(&mut self, initializer: &Initializer) {
+        match *initializer {
+            Initializer::Expression(ref expression) => self.expression(expression, ASSIGNMENT),
+            Initializer::List(ref list) => self.initializer_list(list),
+        }
+    }
+
+    fn initializer_list(&mut self, list: &InitializerList) {
+        self.write("{ ");
+        for (i, item) in list.items.iter().enumerate() {
+            if i > 0 {
+                self.write(", ");
+            }
+            for designator in &item.designators {
+                match designator.kind {
+                    DesignatorKind::Index(ref index) => {
+                        self.write("[");
+                        self.expression(index, CONDITIONAL);
+                        self.write("]");
+                    }
+                    DesignatorKind::Member(ref member) => {
+                        self.write(".");
+                        self.write(&member.name);
+                    }
+                }
+            }
+            if !item.designators.is_empty() {
+                self.write(" = ");
+            }
+            self.initializer(&item.initializer);
+        }
+        self.write(" }");
+    }
+
+    fn compound_statement(&mut self, compound: &CompoundStatement) {
+        self.write("{");
+        self.indent += 1;
+        for item in &compound.items {
+            self.newline();
+            match *item {
+                BlockItem::Declaration(ref declaration) => self.declaration(declaration),
+                BlockItem::Statement(ref statement) => self.statement(statement),
+                BlockItem::StaticAssert(ref assertion) => self.static_assert(assertion),
+            }
+        }
+        self.indent -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement.kind {
+            StatementKind::Compound(ref compound) => self.compound_statement(compound),
+            StatementKind::Expression(ref expression) => {
+                self.expression(expression, COMMA);
+                self.write(";");
+            }
+            StatementKind::Null => self.write(";"),
+            StatementKind::If {
+                ref condition,
+                ref then,
+                ref otherwise,
+            } => {
+                self.write("if (");
+                self.expression(condition, COMMA);
+                self.write(")");
+                self.body(then);
+                if let Some(ref otherwise) = *otherwise {
+                    self.after_body(then, "else");
+                    // Keeps `else if` chains flat
+                    if let StatementKind::If { .. } = otherwise.kind {
+                        self.write(" ");
+                        self.statement(otherwise);
+                    } else {
+                        self.body(otherwise);
+                    }
+                }
+            }
+            StatementKind::Switch {
+                ref condition,
+                ref body,
+            } => {
+                self.write("switch (");
+                self.expression(condition, COMMA);
+                self.write(")");
+                self.body(body);
+            }
+            StatementKind::While {
+                ref condition,
+                ref body,
+            } => {
+                self.write("while (");
+                self.expression(condition, COMMA);
+                self.write(")");
+                self.body(body);
+            }
+            StatementKind::DoWhile {
+                ref body,
+                ref condition,
+            } => {
+                self.write("do");
+                self.body(body);
+                self.after_body(body, "while (");
+                self.expression(condition, COMMA);
+                self.write(");");
+            }
+            StatementKind::For {
+                ref init,
+                ref condition,
+                ref step,
+                ref body,
+            } => {
+                self.write("for (");
+                match init.as_deref() {
+                    Some(ForInit::Declaration(declaration)) => self.declaration(declaration),
+                    Some(ForInit::Expression(expression)) => {
+                        self.expression(expression, COMMA);
+                        self.write(";");
+                    }
+                    None => self.write(";"),
+                }
+                if let Some(ref condition) = *condition {
+                    self.write(" ");
+                    self.expression(condition, COMMA);
+                }
+                self.write(";");
+                if let Some(ref step) = *step {
+                    self.write(" ");
+                    self.expression(step, COMMA);
+                }
+                self.write(")");
+                self.body(body);
+            }
+            StatementKind::Case {
+                ref value,
+                ref body,
+            } => {
+                self.write("case ");
+                self.expression(value, CONDITIONAL);
+                self.write(":");
+                self.newline();
+                self.statement(body);
+            }
+            StatementKind::CaseRange {
+                ref low,
+                ref high,
+                ref body,
+            } => {
+                self.write("case ");
+                self.expression(low, CONDITIONAL);
+                self.write(" ... ");
+                self.expression(high, CONDITIONAL);
+                self.write(":");
+                self.newline();
+                self.statement(body);
+            }
+            StatementKind::Default(ref body) => {
+                self.write("default:");
+                self.newline();
+                self.statement(body);
+            }
+            StatementKind::Labeled {
+                ref label,
+                ref body,
+            } => {
+                self.write(&label.name);
+                self.write(":");
+                self.newline();
+                self.statement(body);
+            }
+            StatementKind::Attributed {
+                ref attributes,
+                ref statement,
+            } => {
+                self.attributes(attributes);
+                if statement.kind != StatementKind::Null {
+                    self.write(" ");
+                }
+                self.statement(statement);
+            }
+            StatementKind::Goto(ref label) => {
+                self.write("goto ");
+                self.write(&label.name);
+                self.write(";");
+            }
+            StatementKind::Continue => self.write("continue;"),
+            StatementKind::Break => self.write("break;"),
+            StatementKind::Return(ref value) => {
+                self.write("return");
+                if let Some(ref value) = *value {
+                    self.write(" ");
+                    self.expression(value, COMMA);
+                }
+                self.write(";");
+            }
+            StatementKind::Error => self.write("/* error */"),
+        }
+    }
+
+    // Blocks go on the same line as the statement they belong to,
+    // anything else on its own indented line
+    fn body(&mut self, body: &Statement) {
+        if let StatementKind::Compound(ref compound) = body.kind {
+            self.write(" ");
+            self.compound_statement(compound);
+        } else {
+            self.indent += 1;
+            self.newline();
+            self.statement(body);
+            self.indent -= 1;
+        }
+    }
+
+    // Continues a statement after a body, like the `else` of an `if`
+    fn after_body(&mut self, body: &Statement, text: &str) {
+        if let StatementKind::Compound(_) = body.kind {
+            self.write(" ");
+        } else {
+            self.newline();
+        }
+        self.write(text);
+    }
+
+    // Writes `expression`, in parentheses if it binds less tightly
+    // than `min_precedence`
+    fn expression(&mut self, expression: &Expression, min_precedence: u8) {
+        let parenthesize = precedence(expression) < min_precedence;
+        if parenthesize {
+            self.write("(");
+        }
+
+        match expression.kind {
+            ExpressionKind::Error => self.write("/* error */"),
+            ExpressionKind::Identifier(ref name) => self.write(name),
+            ExpressionKind::Constant(ref constant) => self.constant(constant),
+            // Kept with its escapes as written
+            ExpressionKind::StringLiteral(ref literal) => {
+                self.write("\"");
+                self.write(literal);
+                self.write("\"");
+            }
+            ExpressionKind::Call {
+                ref function,
+                ref arguments,
+            } => {
+                self.expression(function, POSTFIX);
+                self.write("(");
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.expression(argument, ASSIGNMENT);
+                }
+                self.write(")");
+            }
+            ExpressionKind::Member {
+                ref object,
+                operator,
+                ref member,
+            } => {
+                self.expression(object, POSTFIX);
+                self.write(lang::operator_spelling(operator));
+                self.write(&member.name);
+            }
+            ExpressionKind::Subscript {
+                ref array,
+                ref index,
+            } => {
+                self.expression(array, POSTFIX);
+                self.write("[");
+                self.expression(index, COMMA);
+                self.write("]");
+            }
+            ExpressionKind::Postfix {
+                operator,
+                ref operand,
+            } => {
+                self.expression(operand, POSTFIX);
+                self.write(lang::operator_spelling(operator));
+            }
+            ExpressionKind::Unary {
+                operator,
+                ref operand,
+            } => {
+                let spelling = lang::operator_spelling(operator);
+                self.write(spelling);
+                let start = self.output.len();
+                if matches!(operator, Operator::DoublePlus | Operator::DoubleMinus) {
+                    self.unary_operand(operand);
+                } else {
+                    self.expression(operand, CAST);
+                }
+                // `- -x` would lex as `--x`
+                let next = self.output[start..].chars().next();
+                if next.is_some_and(|c| "+-&".contains(c) && spelling.ends_with(c)) {
+                    self.output.insert(start, ' ');
+                }
+            }
+            ExpressionKind::SizeofExpression(ref operand) => {
+                self.write("sizeof ");
+                self.unary_operand(operand);
+            }
+            ExpressionKind::SizeofType(ref type_name) => {
+                self.write("sizeof(");
+                self.type_name(type_name);
+                self.write(")");
+            }
+            ExpressionKind::AlignofType(ref type_name) => {
+                self.write("_Alignof(");
+                self.type_name(type_name);
+                self.write(")");
+            }
+            ExpressionKind::Cast {
+                ref type_name,
+                ref operand,
+            } => {
+                self.write("(");
+                self.type_name(type_name);
+                self.write(")");
+                self.expression(operand, CAST);
+            }
+            ExpressionKind::ImplicitCast { .. } => unreachable!("Implicit casts are folded away"),
+            ExpressionKind::CompoundLiteral {
+                ref type_name,
+                ref initializers,
+            } => {
+                self.write("(");
+                self.type_name(type_name);
+                self.write(")");
+                self.initializer_list(initializers);
+            }
+            ExpressionKind::Binary {
+                operator: Operator::Comma,
+                ref lhs,
+                ref rhs,
+            } => {
+                self.expression(lhs, COMMA);
+                self.write(", ");
+                self.expression(rhs, ASSIGNMENT);
+            }
+            ExpressionKind::Binary {
+                operator,
+                ref lhs,
+                ref rhs,
+            } => {
+                // Left associative, so only the right side needs
+                // parentheses at the same precedence
+                let precedence = precedence(expression);
+                self.expression(lhs, precedence);
+                self.write(" ");
+                self.write(lang::operator_spelling(operator));
+                self.write(" ");
+                self.expression(rhs, precedence + 1);
+            }
+            ExpressionKind::Conditional {
+                ref condition,
+                ref then,
+                ref otherwise,
+            } => {
+                self.expression(condition, LOGICAL_OR);
+                match *then {
+                    Some(ref then) => {
+                        self.write(" ? ");
+                        self.expression(then, COMMA);
+                        self.write(" : ");
+                    }
+                    None => self.write(" ?: "),
+                }
+                self.expression(otherwise, CONDITIONAL);
+            }
+            ExpressionKind::Assignment {
+                operator,
+                ref lhs,
+                ref rhs,
+            } => {
+                self.expression(lhs, CAST);
+                self.write(" ");
+                self.write(lang::operator_spelling(operator));
+                self.write(" ");
+                self.expression(rhs, ASSIGNMENT);
+            }
+            ExpressionKind::Generic {
+                ref controlling,
+                ref associations,
+            } => {
+                self.write("_Generic(");
+                self.expression(controlling, ASSIGNMENT);
+                for association in associations {
+                    self.write(", ");
+                    match association.type_name {
+                        Some(ref type_name) => self.type_name(type_name),
+                        None => self.write("default"),
+                    }
+                    self.write(": ");
+                    self.expression(&association.expression, ASSIGNMENT);
+                }
+                self.write(")");
+            }
+            ExpressionKind::Statement(ref compound) => {
+                self.write("(");
+                self.compound_statement(compound);
+                self.write(")");
+            }
+            ExpressionKind::VaArg {
+                ref list,
+                ref type_name,
+            } => {
+                self.write("__builtin_va_arg(");
+                self.expression(list, ASSIGNMENT);
+                self.write(", ");
+                self.type_name(type_name);
+                self.write(")");
+            }
+            ExpressionKind::Offsetof {
+                ref type_name,
+                ref designators,
+            } => {
+                self.write("__builtin_offsetof(");
+                self.type_name(type_name);
+                self.write(", ");
+                for (i, designator) in designators.iter().enumerate() {
+                    match designator.kind {
+                        DesignatorKind::Index(ref index) => {
+                            self.write("[");
+                            self.expression(index, COMMA);
+                            self.write("]");
+                        }
+                        DesignatorKind::Member(ref member) => {
+                            if i > 0 {
+                                self.write(".");
+                            }
+                            self.write(&member.name);
+                        }
+                    }
+                }
+                self.write(")");
+            }
+        }
+
+        if parenthesize {
+            self.write(")");
+        }
+    }
+
+    // The operand of `++`, `--` and `sizeof` is a unary expression, so
+    // unlike other prefix operators a cast needs parentheses
+    fn unary_operand(&mut self, operand: &Expression) {
+        if let ExpressionKind::Cast { .. } = operand.kind {
+            self.expression(operand, POSTFIX);
+        } else {
+            self.expression(operand, CAST);
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        let text = match *constant {
+            // Values that didn't fit in `i64` wrapped when they were read
+            Constant::Integer(value, kind) => format!("{}{}", value as u64, kind.suffix()),
+            Constant::Enumeration(value) => value.to_string(),
+            Constant::Floating(value, kind) => format!("{:?}{}", value, kind.suffix()),
+            Constant::Character(c) => format!("'{}'", escape_char(c, '\'')),
+            Constant::Bool(value) => value.to_string(),
+            Constant::Nullptr => "nullptr".to_string(),
+        };
+        self.write(&text);
+    }
+}
@@ -0,0 +1,1083 @@
+use super::*;
+
+// Traversals over the AST. A visitor overrides the `visit_` methods for
+// the nodes it cares about, and calls the matching `walk_` function
+// from them to carry on into the children. The defaults only walk.
+//
+// `Visitor` sees the tree through shared references that live as long
+// as it does, `VisitorMut` can change nodes in place.
+
+pub trait Visitor<'ast> {
+    fn visit_translation_unit(&mut self, unit: &'ast TranslationUnit) {
+        walk_translation_unit(self, unit)
+    }
+
+    fn visit_external_declaration(&mut self, declaration: &'ast ExternalDeclaration) {
+        walk_external_declaration(self, declaration)
+    }
+
+    fn visit_function_definition(&mut self, function: &'ast FunctionDefinition) {
+        walk_function_definition(self, function)
+    }
+
+    fn visit_declaration(&mut self, declaration: &'ast Declaration) {
+        walk_declaration(self, declaration)
+    }
+
+    fn visit_static_assert(&mut self, assertion: &'ast StaticAssert) {
+        walk_static_assert(self, assertion)
+    }
+
+    fn visit_init_declarator(&mut self, init: &'ast InitDeclarator) {
+        walk_init_declarator(self, init)
+    }
+
+    fn visit_initializer(&mut self, initializer: &'ast Initializer) {
+        walk_initializer(self, initializer)
+    }
+
+    fn visit_initializer_list(&mut self, list: &'ast InitializerList) {
+        walk_initializer_list(self, list)
+    }
+
+    fn visit_declaration_specifiers(&mut self, specifiers: &'ast DeclarationSpecifiers) {
+        walk_declaration_specifiers(self, specifiers)
+    }
+
+    fn visit_struct_specifier(&mut self, specifier: &'ast StructSpecifier) {
+        walk_struct_specifier(self, specifier)
+    }
+
+    fn visit_struct_declaration(&mut self, declaration: &'ast StructDeclaration) {
+        walk_struct_declaration(self, declaration)
+    }
+
+    fn visit_struct_declarator(&mut self, declarator: &'ast StructDeclarator) {
+        walk_struct_declarator(self, declarator)
+    }
+
+    fn visit_enum_specifier(&mut self, specifier: &'ast EnumSpecifier) {
+        walk_enum_specifier(self, specifier)
+    }
+
+    fn visit_enumerator(&mut self, enumerator: &'ast Enumerator) {
+        walk_enumerator(self, enumerator)
+    }
+
+    fn visit_declarator(&mut self, declarator: &'ast Declarator) {
+        walk_declarator(self, declarator)
+    }
+
+    fn visit_parameter_declaration(&mut self, parameter: &'ast ParameterDeclaration) {
+        walk_parameter_declaration(self, parameter)
+    }
+
+    fn visit_type_name(&mut self, type_name: &'ast TypeName) {
+        walk_type_name(self, type_name)
+    }
+
+    fn visit_compound_statement(&mut self, compound: &'ast CompoundStatement) {
+        walk_compound_statement(self, compound)
+    }
+
+    fn visit_statement(&mut self, statement: &'ast Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_expression(&mut self, expression: &'ast Expression) {
+        walk_expression(self, expression)
+    }
+
+    // Names of declared things, tags, members and labels
+    fn visit_identifier(&mut self, _identifier: &'ast Identifier) {}
+}
+
+pub fn walk_translation_unit<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    unit: &'ast TranslationUnit,
+) {
+    for declaration in &unit.declarations {
+        visitor.visit_external_declaration(declaration);
+    }
+}
+
+pub fn walk_external_declaration<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    declaration: &'ast ExternalDeclaration,
+) {
+    match *declaration {
+        ExternalDeclaration::Function(ref function) => visitor.visit_function_definition(function),
+        ExternalDeclaration::Declaration(ref declaration) => visitor.visit_declaration(declaration),
+        ExternalDeclaration::StaticAssert(ref assertion) => visitor.visit_static_assert(assertion),
+        ExternalDeclaration::Error(_) => (),
+    }
+}
+
+pub fn walk_function_definition<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    function: &'ast FunctionDefinition,
+) {
+    visitor.visit_declaration_specifiers(&function.specifiers);
+    visitor.visit_declarator(&function.declarator);
+    for declaration in &function.declarations {
+        visitor.visit_declaration(declaration);
+    }
+    visitor.visit_compound_statement(&function.body);
+}
+
+pub fn walk_declaration<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    declaration: &'ast Declaration,
+) {
+    visitor.visit_declaration_specifiers(&declaration.specifiers);
+    for init in &declaration.declarators {
+        visitor.visit_init_declarator(init);
+    }
+}
+
+pub fn walk_static_assert<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    assertion: &'ast StaticAssert,
+) {
+    visitor.visit_expression(&assertion.condition);
+}
+
+pub fn walk_init_declarator<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    init: &'ast InitDeclarator,
+) {
+    visitor.visit_declarator(&init.declarator);
+    if let Some(ref initializer) = init.initializer {
+        visitor.visit_initializer(initializer);
+    }
+}
+
+pub fn walk_initializer<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    initializer: &'ast Initializer,
+) {
+    match *initializer {
+        Initializer::Expression(ref expression) => visitor.visit_expression(expression),
+        Initializer::List(ref list) => visitor.visit_initializer_list(list),
+    }
+}
+
+pub fn walk_initializer_list<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    list: &'ast InitializerList,
+) {
+    for item in &list.items {
+        for designator in &item.designators {
+            match designator.kind {
+                DesignatorKind::Index(ref index) => visitor.visit_expression(index),
+                DesignatorKind::Member(ref member) => visitor.visit_identifier(member),
+            }
+        }
+        visitor.visit_initializer(&item.initializer);
+    }
+}
+
+pub fn walk_declaration_specifiers<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    specifiers: &'ast DeclarationSpecifiers,
+) {
+    for specifier in &specifiers.specifiers {
+        match specifier.kind {
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Struct(ref specifier)) => {
+                visitor.visit_struct_specifier(specifier)
+            }
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Enum(ref specifier)) => {
+                visitor.visit_enum_specifier(specifier)
+            }
+            DeclarationSpecifierKind::AlignmentSpecifier(AlignmentSpecifier::Type(
+                ref type_name,
+            )) => visitor.visit_type_name(type_name),
+            DeclarationSpecifierKind::AlignmentSpecifier(AlignmentSpecifier::Expression(
+                ref expression,
+            )) => visitor.visit_expression(expression),
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Typeof(ref specifier)) => {
+                match specifier.operand {
+                    TypeofOperand::Type(ref type_name) => visitor.visit_type_name(type_name),
+                    TypeofOperand::Expression(ref expression) => {
+                        visitor.visit_expression(expression)
+                    }
+                }
+            }
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Atomic(ref specifier)) => {
+                visitor.visit_type_name(&specifier.type_name)
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn walk_struct_specifier<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    specifier: &'ast StructSpecifier,
+) {
+    if let Some(ref tag) = specifier.tag {
+        visitor.visit_identifier(tag);
+    }
+    for member in specifier.members.iter().flatten() {
+        visitor.visit_struct_declaration(member);
+    }
+}
+
+pub fn walk_struct_declaration<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    declaration: &'ast StructDeclaration,
+) {
+    visitor.visit_declaration_specifiers(&declaration.specifiers);
+    for declarator in &declaration.declarators {
+        visitor.visit_struct_declarator(declarator);
+    }
+}
+
+pub fn walk_struct_declarator<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    declarator: &'ast StructDeclarator,
+) {
+    visitor.visit_declarator(&declarator.declarator);
+    if let Some(ref width) = declarator.width {
+        visitor.visit_expression(width);
+    }
+}
+
+pub fn walk_enum_specifier<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    specifier: &'ast EnumSpecifier,
+) {
+    if let Some(ref tag) = specifier.tag {
+        visitor.visit_identifier(tag);
+    }
+    for enumerator in specifier.enumerators.iter().flatten() {
+        visitor.visit_enumerator(enumerator);
+    }
+}
+
+pub fn walk_enumerator<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    enumerator: &'ast Enumerator,
+) {
+    visitor.visit_identifier(&enumerator.name);
+    if let Some(ref value) = enumerator.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_declarator<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    declarator: &'ast Declarator,
+) {
+    match declarator.kind {
+        DeclaratorKind::Abstract => (),
+        DeclaratorKind::Identifier(ref name) => visitor.visit_identifier(name),
+        DeclaratorKind::Pointer { ref inner, .. } => visitor.visit_declarator(inner),
+        DeclaratorKind::Array {
+            ref inner,
+            ref size,
+        } => {
+            visitor.visit_declarator(inner);
+            if let Some(size) = size {
+                visitor.visit_expression(size);
+            }
+        }
+        DeclaratorKind::Function {
+            ref inner,
+            ref parameters,
+            ..
+        } => {
+            visitor.visit_declarator(inner);
+            for parameter in parameters {
+                visitor.visit_parameter_declaration(parameter);
+            }
+        }
+        DeclaratorKind::OldStyleFunction { ref inner, .. }
+        | DeclaratorKind::Attributed { ref inner, .. } => visitor.visit_declarator(inner),
+    }
+}
+
+pub fn walk_parameter_declaration<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    parameter: &'ast ParameterDeclaration,
+) {
+    visitor.visit_declaration_specifiers(&parameter.specifiers);
+    visitor.visit_declarator(&parameter.declarator);
+}
+
+pub fn walk_type_name<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, type_name: &'ast TypeName) {
+    visitor.visit_declaration_specifiers(&type_name.specifiers);
+    visitor.visit_declarator(&type_name.declarator);
+}
+
+pub fn walk_compound_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    compound: &'ast CompoundStatement,
+) {
+    for item in &compound.items {
+        match *item {
+            BlockItem::Declaration(ref declaration) => visitor.visit_declaration(declaration),
+            BlockItem::Statement(ref statement) => visitor.visit_statement(statement),
+            BlockItem::StaticAssert(ref assertion) => visitor.visit_static_assert(assertion),
+        }
+    }
+}
+
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    statement: &'ast Statement,
+) {
+    match statement.kind {
+        StatementKind::Compound(ref compound) => visitor.visit_compound_statement(compound),
+        StatementKind::Expression(ref expression) => visitor.visit_expression(expression),
+        StatementKind::Null
+        | StatementKind::Continue
+        | StatementKind::Break
+        | StatementKind::Error => (),
+        StatementKind::If {
+            ref condition,
+            ref then,
+            ref otherwise,
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_statement(otherwise);
+            }
+        }
+        StatementKind::Switch {
+            ref condition,
+            ref body,
+        }
+        | StatementKind::While {
+            ref condition,
+            ref body,
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        }
+        StatementKind::DoWhile {
+            ref body,
+            ref condition,
+        } => {
+            visitor.visit_statement(body);
+            visitor.visit_expression(condition);
+        }
+        StatementKind::For {
+            ref init,
+            ref condition,
+            ref step,
+            ref body,
+        } => {
+            match init.as_deref() {
+                Some(ForInit::Declaration(declaration)) => visitor.visit_declaration(declaration),
+                Some(ForInit::Expression(expression)) => visitor.visit_expression(expression),
+                None => (),
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expression(condition);
+            }
+            if let Some(step) = step {
+                visitor.visit_expression(step);
+            }
+            visitor.visit_statement(body);
+        }
+        StatementKind::Case {
+            ref value,
+            ref body,
+        } => {
+            visitor.visit_expression(value);
+            visitor.visit_statement(body);
+        }
+        StatementKind::CaseRange {
+            ref low,
+            ref high,
+            ref body,
+        } => {
+            visitor.visit_expression(low);
+            visitor.visit_expression(high);
+            visitor.visit_statement(body);
+        }
+        StatementKind::Default(ref body) => visitor.visit_statement(body),
+        StatementKind::Labeled {
+            ref label,
+            ref body,
+        } => {
+            visitor.visit_identifier(label);
+            visitor.visit_statement(body);
+        }
+        StatementKind::Attributed { ref statement, .. } => visitor.visit_statement(statement),
+        StatementKind::Goto(ref label) => visitor.visit_identifier(label),
+        StatementKind::Return(ref value) => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+    }
+}
+
+pub fn walk_expression<'ast, V: Visitor<'ast> + ?Sized>(
+    visitor: &mut V,
+    expression: &'ast Expression,
+) {
+    match expression.kind {
+        ExpressionKind::Error
+        | ExpressionKind::Identifier(_)
+        | ExpressionKind::Constant(_)
+        | ExpressionKind::StringLiteral(_) => (),
+        ExpressionKind::Call {
+            ref function,
+            ref arguments,
+        } => {
+            visitor.visit_expression(function);
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ExpressionKind::Member {
+            ref object,
+            ref member,
+            ..
+        } => {
+            visitor.visit_expression(object);
+            visitor.visit_identifier(member);
+        }
+        ExpressionKind::Subscript {
+            ref array,
+            ref index,
+        } => {
+            visitor.visit_expression(array);
+            visitor.visit_expression(index);
+        }
+        ExpressionKind::Postfix { ref operand, .. }
+        | ExpressionKind::Unary { ref operand, .. }
+        | ExpressionKind::ImplicitCast { ref operand, .. }
+        | ExpressionKind::SizeofExpression(ref operand) => visitor.visit_expression(operand),
+        ExpressionKind::SizeofType(ref type_name) | ExpressionKind::AlignofType(ref type_name) => {
+            visitor.visit_type_name(type_name)
+        }
+        ExpressionKind::Cast {
+            ref type_name,
+            ref operand,
+        } => {
+            visitor.visit_type_name(type_name);
+            visitor.visit_expression(operand);
+        }
+        ExpressionKind::CompoundLiteral {
+            ref type_name,
+            ref initializers,
+        } => {
+            visitor.visit_type_name(type_name);
+            visitor.visit_initializer_list(initializers);
+        }
+        ExpressionKind::Binary {
+            ref lhs, ref rhs, ..
+        }
+        | ExpressionKind::Assignment {
+            ref lhs, ref rhs, ..
+        } => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        }
+        ExpressionKind::Conditional {
+            ref condition,
+            ref then,
+            ref otherwise,
+        } => {
+            visitor.visit_expression(condition);
+            if let Some(then) = then {
+                visitor.visit_expression(then);
+            }
+            visitor.visit_expression(otherwise);
+        }
+        ExpressionKind::Generic {
+            ref controlling,
+            ref associations,
+        } => {
+            visitor.visit_expression(controlling);
+            for association in associations {
+                if let Some(ref type_name) = association.type_name {
+                    visitor.visit_type_name(type_name);
+                }
+                visitor.visit_expression(&association.expression);
+            }
+        }
+        ExpressionKind::Statement(ref compound) => visitor.visit_compound_statement(compound),
+        ExpressionKind::VaArg {
+            ref list,
+            ref type_name,
+        } => {
+            visitor.visit_expression(list);
+            visitor.visit_type_name(type_name);
+        }
+        ExpressionKind::Offsetof {
+            ref type_name,
+            ref designators,
+        } => {
+            visitor.visit_type_name(type_name);
+            for designator in designators {
+                match designator.kind {
+                    DesignatorKind::Index(ref index) => visitor.visit_expression(index),
+                    DesignatorKind::Member(ref member) => visitor.visit_identifier(member),
+                }
+            }
+        }
+    }
+}
+
+pub trait VisitorMut {
+    fn visit_translation_unit(&mut self, unit: &mut TranslationUnit) {
+        walk_translation_unit_mut(self, unit)
+    }
+
+    fn visit_external_declaration(&mut self, declaration: &mut ExternalDeclaration) {
+        walk_external_declaration_mut(self, declaration)
+    }
+
+    fn visit_function_definition(&mut self, function: &mut FunctionDefinition) {
+        walk_function_definition_mut(self, function)
+    }
+
+    fn visit_declaration(&mut self, declaration: &mut Declaration) {
+        walk_declaration_mut(self, declaration)
+    }
+
+    fn visit_static_assert(&mut self, assertion: &mut StaticAssert) {
+        walk_static_assert_mut(self, assertion)
+    }
+
+    fn visit_init_declarator(&mut self, init: &mut InitDeclarator) {
+        walk_init_declarator_mut(self, init)
+    }
+
+    fn visit_initializer(&mut self, initializer: &mut Initializer) {
+        walk_initializer_mut(self, initializer)
+    }
+
+    fn visit_initializer_list(&mut self, list: &mut InitializerList) {
+        walk_initializer_list_mut(self, list)
+    }
+
+    fn visit_declaration_specifiers(&mut self, specifiers: &mut DeclarationSpecifiers) {
+        walk_declaration_specifiers_mut(self, specifiers)
+    }
+
+    fn visit_struct_specifier(&mut self, specifier: &mut StructSpecifier) {
+        walk_struct_specifier_mut(self, specifier)
+    }
+
+    fn visit_struct_declaration(&mut self, declaration: &mut StructDeclaration) {
+        walk_struct_declaration_mut(self, declaration)
+    }
+
+    fn visit_struct_declarator(&mut self, declarator: &mut StructDeclarator) {
+        walk_struct_declarator_mut(self, declarator)
+    }
+
+    fn visit_enum_specifier(&mut self, specifier: &mut EnumSpecifier) {
+        walk_enum_specifier_mut(self, specifier)
+    }
+
+    fn visit_enumerator(&mut self, enumerator: &mut Enumerator) {
+        walk_enumerator_mut(self, enumerator)
+    }
+
+    fn visit_declarator(&mut self, declarator: &mut Declarator) {
+        walk_declarator_mut(self, declarator)
+    }
+
+    fn visit_parameter_declaration(&mut self, parameter: &mut ParameterDeclaration) {
+        walk_parameter_declaration_mut(self, parameter)
+    }
+
+    fn visit_type_name(&mut self, type_name: &mut TypeName) {
+        walk_type_name_mut(self, type_name)
+    }
+
+    fn visit_compound_statement(&mut self, compound: &mut CompoundStatement) {
+        walk_compound_statement_mut(self, compound)
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+
+    // Names of declared things, tags, members and labels
+    fn visit_identifier(&mut self, _identifier: &mut Identifier) {}
+}
+
+pub fn walk_translation_unit_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    unit: &mut TranslationUnit,
+) {
+    for declaration in &mut unit.declarations {
+        visitor.visit_external_declaration(declaration);
+    }
+}
+
+pub fn walk_external_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut ExternalDeclaration,
+) {
+    match *declaration {
+        ExternalDeclaration::Function(ref mut function) => {
+            visitor.visit_function_definition(function)
+        }
+        ExternalDeclaration::Declaration(ref mut declaration) => {
+            visitor.visit_declaration(declaration)
+        }
+        ExternalDeclaration::StaticAssert(ref mut assertion) => {
+            visitor.visit_static_assert(assertion)
+        }
+        ExternalDeclaration::Error(_) => (),
+    }
+}
+
+pub fn walk_function_definition_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    function: &mut FunctionDefinition,
+) {
+    visitor.visit_declaration_specifiers(&mut function.specifiers);
+    visitor.visit_declarator(&mut function.declarator);
+    for declaration in &mut function.declarations {
+        visitor.visit_declaration(declaration);
+    }
+    visitor.visit_compound_statement(&mut function.body);
+}
+
+pub fn walk_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut Declaration,
+) {
+    visitor.visit_declaration_specifiers(&mut declaration.specifiers);
+    for init in &mut declaration.declarators {
+        visitor.visit_init_declarator(init);
+    }
+}
+
+pub fn walk_static_assert_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    assertion: &mut StaticAssert,
+) {
+    visitor.visit_expression(&mut assertion.condition);
+}
+
+pub fn walk_init_declarator_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    init: &mut InitDeclarator,
+) {
+    visitor.visit_declarator(&mut init.declarator);
+    if let Some(ref mut initializer) = init.initializer {
+        visitor.visit_initializer(initializer);
+    }
+}
+
+pub fn walk_initializer_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    initializer: &mut Initializer,
+) {
+    match *initializer {
+        Initializer::Expression(ref mut expression) => visitor.visit_expression(expression),
+        Initializer::List(ref mut list) => visitor.visit_initializer_list(list),
+    }
+}
+
+pub fn walk_initializer_list_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    list: &mut InitializerList,
+) {
+    for item in &mut list.items {
+        for designator in &mut item.designators {
+            match designator.kind {
+                DesignatorKind::Index(ref mut index) => visitor.visit_expression(index),
+                DesignatorKind::Member(ref mut member) => visitor.visit_identifier(member),
+            }
+        }
+        visitor.visit_initializer(&mut item.initializer);
+    }
+}
+
+pub fn walk_declaration_specifiers_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    specifiers: &mut DeclarationSpecifiers,
+) {
+    for specifier in &mut specifiers.specifiers {
+        match specifier.kind {
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Struct(ref mut specifier)) => {
+                visitor.visit_struct_specifier(specifier)
+            }
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Enum(ref mut specifier)) => {
+                visitor.visit_enum_specifier(specifier)
+            }
+            DeclarationSpecifierKind::AlignmentSpecifier(AlignmentSpecifier::Type(
+                ref mut type_name,
+            )) => visitor.visit_type_name(type_name),
+            DeclarationSpecifierKind::AlignmentSpecifier(AlignmentSpecifier::Expression(
+                ref mut expression,
+            )) => visitor.visit_expression(expression),
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Typeof(ref mut specifier)) => {
+                match specifier.operand {
+                    TypeofOperand::Type(ref mut type_name) => visitor.visit_type_name(type_name),
+                    TypeofOperand::Expression(ref mut expression) => {
+                        visitor.visit_expression(expression)
+                    }
+                }
+            }
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Atomic(ref mut specifier)) => {
+                visitor.visit_type_name(&mut specifier.type_name)
+            }
+            _ => (),
+        }
+    }
+}
+
+pub fn walk_struct_specifier_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    specifier: &mut StructSpecifier,
+) {
+    if let Some(ref mut tag) = specifier.tag {
+        visitor.visit_identifier(tag);
+    }
+    for member in specifier.members.iter_mut().flatten() {
+        visitor.visit_struct_declaration(member);
+    }
+}
+
+pub fn walk_struct_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declaration: &mut StructDeclaration,
+) {
+    visitor.visit_declaration_specifiers(&mut declaration.specifiers);
+    for declarator in &mut declaration.declarators {
+        visitor.visit_struct_declarator(declarator);
+    }
+}
+
+pub fn walk_struct_declarator_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    declarator: &mut StructDeclarator,
+) {
+    visitor.visit_declarator(&mut declarator.declarator);
+    if let Some(ref mut width) = declarator.width {
+        visitor.visit_expression(width);
+    }
+}
+
+pub fn walk_enum_specifier_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    specifier: &mut EnumSpecifier,
+) {
+    if let Some(ref mut tag) = specifier.tag {
+        visitor.visit_identifier(tag);
+    }
+    for enumerator in specifier.enumerators.iter_mut().flatten() {
+        visitor.visit_enumerator(enumerator);
+    }
+}
+
+pub fn walk_enumerator_mut<V: VisitorMut + ?Sized>(visitor: &mut V, enumerator: &mut Enumerator) {
+    visitor.visit_identifier(&mut enumerator.name);
+    if let Some(ref mut value) = enumerator.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_declarator_mut<V: VisitorMut + ?Sized>(visitor: &mut V, declarator: &mut Declarator) {
+    match declarator.kind {
+        DeclaratorKind::Abstract => (),
+        DeclaratorKind::Identifier(ref mut name) => visitor.visit_identifier(name),
+        DeclaratorKind::Pointer { ref mut inner, .. } => visitor.visit_declarator(inner),
+        DeclaratorKind::Array {
+            ref mut inner,
+            ref mut size,
+        } => {
+            visitor.visit_declarator(inner);
+            if let Some(size) = size {
+                visitor.visit_expression(size);
+            }
+        }
+        DeclaratorKind::Function {
+            ref mut inner,
+            ref mut parameters,
+            ..
+        } => {
+            visitor.visit_declarator(inner);
+            for parameter in parameters {
+                visitor.visit_parameter_declaration(parameter);
+            }
+        }
+        DeclaratorKind::OldStyleFunction { ref mut inner, .. }
+        | DeclaratorKind::Attributed { ref mut inner, .. } => visitor.visit_declarator(inner),
+    }
+}
+
+pub fn walk_parameter_declaration_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    parameter: &mut ParameterDeclaration,
+) {
+    visitor.visit_declaration_specifiers(&mut parameter.specifiers);
+    visitor.visit_declarator(&mut parameter.declarator);
+}
+
+pub fn walk_type_name_mut<V: VisitorMut + ?Sized>(visitor: &mut V, type_name: &mut TypeName) {
+    visitor.visit_declaration_specifiers(&mut type_name.specifiers);
+    visitor.visit_declarator(&mut type_name.declarator);
+}
+
+pub fn walk_compound_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    compound: &mut CompoundStatement,
+) {
+    for item in &mut compound.items {
+        match *item {
+            BlockItem::Declaration(ref mut declaration) => visitor.visit_declaration(declaration),
+            BlockItem::Statement(ref mut statement) => visitor.visit_statement(statement),
+            BlockItem::StaticAssert(ref mut assertion) => visitor.visit_static_assert(assertion),
+        }
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement.kind {
+        StatementKind::Compound(ref mut compound) => visitor.visit_compound_statement(compound),
+        StatementKind::Expression(ref mut expression) => visitor.visit_expression(expression),
+        StatementKind::Null
+        | StatementKind::Continue
+        | StatementKind::Break
+        | StatementKind::Error => (),
+        StatementKind::If {
+            ref mut condition,
+            ref mut then,
+            ref mut otherwise,
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(then);
+            if let Some(otherwise) = otherwise {
+                visitor.visit_statement(otherwise);
+            }
+        }
+        StatementKind::Switch {
+            ref mut condition,
+            ref mut body,
+        }
+        | StatementKind::While {
+            ref mut condition,
+            ref mut body,
+        } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        }
+        StatementKind::DoWhile {
+            ref mut body,
+            ref mut condition,
+        } => {
+            visitor.visit_statement(body);
+            visitor.visit_expression(condition);
+        }
+        StatementKind::For {
+            ref mut init,
+            ref mut condition,
+            ref mut step,
+            ref mut body,
+        } => {
+            match init.as_deref_mut() {
+                Some(ForInit::Declaration(declaration)) => visitor.visit_declaration(declaration),
+                Some(ForInit::Expression(expression)) => visitor.visit_expression(expression),
+                None => (),
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expression(condition);
+            }
+            if let Some(step) = step {
+                visitor.visit_expression(step);
+            }
+            visitor.visit_statement(body);
+        }
+        StatementKind::Case {
+            ref mut value,
+            ref mut body,
+        } => {
+            visitor.visit_expression(value);
+            visitor.visit_statement(body);
+        }
+        StatementKind::CaseRange {
+            ref mut low,
+            ref mut high,
+            ref mut body,
+        } => {
+            visitor.visit_expression(low);
+            visitor.visit_expression(high);
+            visitor.visit_statement(body);
+        }
+        StatementKind::Default(ref mut body) => visitor.visit_statement(body),
+        StatementKind::Labeled {
+            ref mut label,
+            ref mut body,
+        } => {
+            visitor.visit_identifier(label);
+            visitor.visit_statement(body);
+        }
+        StatementKind::Attributed {
+            ref mut statement, ..
+        } => visitor.visit_statement(statement),
+        StatementKind::Goto(ref mut label) => visitor.visit_identifier(label),
+        StatementKind::Return(ref mut value) => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match expression.kind {
+        ExpressionKind::Error
+        | ExpressionKind::Identifier(_)
+        | ExpressionKind::Constant(_)
+        | ExpressionKind::StringLiteral(_) => (),
+        ExpressionKind::Call {
+            ref mut function,
+            ref mut arguments,
+        } => {
+            visitor.visit_expression(function);
+            for argument in arguments {
+                visitor.visit_expression(argument);
+            }
+        }
+        ExpressionKind::Member {
+            ref mut object,
+            ref mut member,
+            ..
+        } => {
+            visitor.visit_expression(object);
+            visitor.visit_identifier(member);
+        }
+        ExpressionKind::Subscript {
+            ref mut array,
+            ref mut index,
+        } => {
+            visitor.visit_expression(array);
+            visitor.visit_expression(index);
+        }
+        ExpressionKind::Postfix {
+            ref mut operand, ..
+        }
+        | ExpressionKind::Unary {
+            ref mut operand, ..
+        }
+        | ExpressionKind::ImplicitCast {
+            ref mut operand, ..
+        }
+        | ExpressionKind::SizeofExpression(ref mut operand) => visitor.visit_expression(operand),
+        ExpressionKind::SizeofType(ref mut type_name)
+        | ExpressionKind::AlignofType(ref mut type_name) => visitor.visit_type_name(type_name),
+        ExpressionKind::Cast {
+            ref mut type_name,
+            ref mut operand,
+        } => {
+            visitor.visit_type_name(type_name);
+            visitor.visit_expression(operand);
+        }
+        ExpressionKind::CompoundLiteral {
+            ref mut type_name,
+            ref mut initializers,
+        } => {
+            visitor.visit_type_name(type_name);
+            visitor.visit_initializer_list(initializers);
+        }
+        ExpressionKind::Binary {
+            ref mut lhs,
+            ref mut rhs,
+            ..
+        }
+        | ExpressionKind::Assignment {
+            ref mut lhs,
+            ref mut rhs,
+            ..
+        } => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        }
+        ExpressionKind::Conditional {
+            ref mut condition,
+            ref mut then,
+            ref mut otherwise,
+        } => {
+            visitor.visit_expression(condition);
+            if let Some(then) = then {
+                visitor.visit_expression(then);
+            }
+            visitor.visit_expression(otherwise);
+        }
+        ExpressionKind::Generic {
+            ref mut controlling,
+            ref mut associations,
+        } => {
+            visitor.visit_expression(controlling);
+            for association in associations {
+                if let Some(ref mut type_name) = association.type_name {
+                    visitor.visit_type_name(type_name);
+                }
+                visitor.visit_expression(&mut association.expression);
+            }
+        }
+        ExpressionKind::Statement(ref mut compound) => visitor.visit_compound_statement(compound),
+        ExpressionKind::VaArg {
+            ref mut list,
+            ref mut type_name,
+        } => {
+            visitor.visit_expression(list);
+            visitor.visit_type_name(type_name);
+        }
+        ExpressionKind::Offsetof {
+            ref mut type_name,
+            ref mut designators,
+        } => {
+            visitor.visit_type_name(type_name);
+            for designator in designators {
+                match designator.kind {
+                    DesignatorKind::Index(ref mut index) => visitor.visit_expression(index),
+                    DesignatorKind::Member(ref mut member) => visitor.visit_identifier(member),
+                }
+            }
+        }
+    }
+}
+
+// Rewrites expressions from the leaves up, `fold_expression` is given
+// each one after everything inside it has been folded
+pub trait Fold {
+    fn fold_expression(&mut self, expression: Expression) -> Expression;
+}
+
+// Folds every expression in the tree, including the ones in
+// declarators, initializers and type names
+pub fn fold_translation_unit<F: Fold + ?Sized>(folder: &mut F, unit: &mut TranslationUnit) {
+    Folder(folder).visit_translation_unit(unit);
+}
+
+pub fn fold_expression<F: Fold + ?Sized>(folder: &mut F, mut expression: Expression) -> Expression {
+    Folder(folder).visit_expression(&mut expression);
+    expression
+}
+
+struct Folder<'a, F: ?Sized>(&'a mut F);
+
+impl<F: Fold + ?Sized> VisitorMut for Folder<'_, F> {
+    fn visit_expression(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        // Stands in while the expression is moved out to be folded
+        let placeholder = Expression {
+            kind: ExpressionKind::Error,
+            ty: CType::ERROR,
+            category: ValueCategory::Value,
+            span: expression.span,
+        };
+        let folded = std::mem::replace(expression, placeholder);
+        *expression = self.0.fold_expression(folded);
+    }
+}
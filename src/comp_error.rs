@@ -1,3 +1,7 @@
+use std::rc::Rc;
+
+use crate::source::{SourceBuffer, Span};
+
 #[derive(Debug, Default, Clone, Copy)]
 pub enum ErrorCode {
     #[default]
@@ -6,6 +10,42 @@ pub enum ErrorCode {
     UnterminatedCharConstant = 1,
     UnterminatedStringLiteral = 2,
     UnterminatedHeaderName = 3,
+    UnterminatedComment = 4,
+    InvalidConstant = 5,
+    StrayCharacter = 6,
+
+    InvalidDirective = 100,
+    InvalidMacroName = 101,
+    InvalidMacroDefinition = 102,
+    MacroArgumentCount = 103,
+    UnterminatedMacroInvocation = 104,
+    InvalidTokenPaste = 105,
+    IncludeNotFound = 106,
+    IncludeDepthExceeded = 107,
+    UnterminatedConditional = 108,
+    UnmatchedConditional = 109,
+    InvalidConditionalExpression = 110,
+    ErrorDirective = 111,
+
+    UnexpectedToken = 200,
+    InvalidDeclarationSpecifiers = 201,
+    InvalidConstantExpression = 202,
+    InvalidDeclaration = 203,
+    NewerStandardRequired = 204,
+    StaticAssertionFailed = 205,
+    InvalidGenericSelection = 206,
+    InvalidAttribute = 207,
+    GnuExtensionRequired = 208,
+    InvalidExpression = 209,
+    MissingTypeSpecifier = 210,
+    IncompatibleTypes = 211,
+    UndeclaredIdentifier = 212,
+    InvalidInitializer = 213,
+    InvalidStatement = 214,
+
+    InvalidCommandLineArgument = 900,
+    InputFileUnreadable = 901,
+    OutputFileUnwritable = 902,
 }
 
 impl std::fmt::Display for ErrorCode {
@@ -17,75 +57,201 @@ impl std::fmt::Display for ErrorCode {
             }
             Self::UnterminatedStringLiteral => write!(f, "Failed to find end of string literal"),
             Self::UnterminatedHeaderName => write!(f, "Failed to find end of header name"),
+            Self::UnterminatedComment => write!(f, "Failed to find end of comment"),
+            Self::InvalidConstant => write!(f, "Invalid constant"),
+            Self::StrayCharacter => write!(f, "Stray character in program"),
+            Self::InvalidDirective => write!(f, "Invalid preprocessing directive"),
+            Self::InvalidMacroName => write!(f, "Invalid macro name"),
+            Self::InvalidMacroDefinition => write!(f, "Invalid macro definition"),
+            Self::MacroArgumentCount => {
+                write!(f, "Wrong number of arguments to function-like macro")
+            }
+            Self::UnterminatedMacroInvocation => {
+                write!(f, "Failed to find end of macro invocation")
+            }
+            Self::InvalidTokenPaste => write!(f, "Token pasting produced an invalid token"),
+            Self::IncludeNotFound => write!(f, "Failed to find included file"),
+            Self::IncludeDepthExceeded => write!(f, "Includes are nested too deeply"),
+            Self::UnterminatedConditional => write!(f, "Failed to find end of conditional"),
+            Self::UnmatchedConditional => write!(f, "Conditional directive without `#if`"),
+            Self::InvalidConditionalExpression => {
+                write!(f, "Invalid preprocessor conditional expression")
+            }
+            Self::ErrorDirective => write!(f, "`#error` directive"),
+            Self::UnexpectedToken => write!(f, "Unexpected token"),
+            Self::InvalidDeclarationSpecifiers => write!(f, "Invalid declaration specifiers"),
+            Self::InvalidConstantExpression => write!(f, "Invalid constant expression"),
+            Self::InvalidDeclaration => write!(f, "Invalid declaration"),
+            Self::NewerStandardRequired => {
+                write!(f, "Not supported by the selected language standard")
+            }
+            Self::StaticAssertionFailed => write!(f, "Static assertion failed"),
+            Self::InvalidGenericSelection => write!(f, "Invalid generic selection"),
+            Self::InvalidAttribute => write!(f, "Invalid attribute"),
+            Self::GnuExtensionRequired => write!(f, "GNU extensions are not enabled"),
+            Self::InvalidExpression => write!(f, "Invalid expression"),
+            Self::MissingTypeSpecifier => write!(f, "Missing type specifier"),
+            Self::IncompatibleTypes => write!(f, "Incompatible types"),
+            Self::UndeclaredIdentifier => write!(f, "Use of undeclared identifier"),
+            Self::InvalidInitializer => write!(f, "Invalid initializer"),
+            Self::InvalidStatement => write!(f, "Invalid statement"),
+            Self::InvalidCommandLineArgument => write!(f, "Invalid command line argument"),
+            Self::InputFileUnreadable => write!(f, "Failed to read input file"),
+            Self::OutputFileUnwritable => write!(f, "Failed to write output file"),
         }
     }
 }
 
+// Where in a buffer an error or note points. The buffer is shared
+// rather than copied, it's only read when the error is displayed.
+#[derive(Debug, Clone)]
+pub struct ErrorLocation {
+    pub buffer: Rc<SourceBuffer>,
+    pub span: Span,
+}
+
+impl ErrorLocation {
+    fn line(&self) -> usize {
+        self.buffer.line_col(self.span.lo).0
+    }
+}
+
+// Extra context attached to an error, such as the macro
+// definitions a token was expanded from
+#[derive(Debug, Clone)]
+pub struct CompNote {
+    pub message: String,
+    pub location: Option<ErrorLocation>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct CompError {
     pub code: ErrorCode,
+    // Warnings are reported the same way, but compilation carries on
+    pub warning: bool,
     pub message: Option<String>,
-    pub src: Option<(String, usize)>,      // actual line, line num
-    pub highlight: Option<(usize, usize)>, // range
+    pub file: Option<String>, // for errors about a whole file
+    pub location: Option<ErrorLocation>,
     pub highlight_message: Option<String>,
+    pub notes: Vec<CompNote>,
 }
 
 impl std::fmt::Display for CompError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.warning { "Warning" } else { "Error" };
         writeln!(
             f,
-            "Compilation Error [E{:04}]: {}",
-            self.code as i32, self.code
+            "Compilation {} [E{:04}]: {}",
+            kind, self.code as i32, self.code
         )?;
 
         // We need to leave a buffer on the left side
         // of the message for the line number. This will
         // calculate the size of that.
-        let mut space_count = 0;
-        if let Some((_, ref line_num)) = self.src {
-            space_count = line_num.to_string().chars().count();
-        }
+        let space_count = std::iter::once(&self.location)
+            .chain(self.notes.iter().map(|note| &note.location))
+            .flatten()
+            .map(|location| location.line().to_string().chars().count())
+            .max()
+            .unwrap_or(0);
 
+        match (&self.location, &self.file) {
+            (Some(location), _) => write_file(f, location, space_count)?,
+            (None, Some(file)) => writeln!(f, "{:buffer$}--> {}", "", file, buffer = space_count)?,
+            (None, None) => (),
+        }
         if let Some(ref message) = self.message {
             writeln!(f, "{:buffer$} | {}", "", message, buffer = space_count)?;
         }
-        if let Some((ref src, ref line_num)) = self.src {
-            writeln!(f, "{:buffer$} |", "", buffer = space_count)?;
+        if let Some(ref location) = self.location {
+            write_source(f, location, &self.highlight_message, space_count)?;
+        }
+
+        for note in &self.notes {
             writeln!(
                 f,
-                "{} | {}",
-                line_num,
-                src.split('\n').nth(line_num - 1).unwrap()
+                "{:buffer$} = note: {}",
+                "",
+                note.message,
+                buffer = space_count
             )?;
-            if let Some((ref low, ref high)) = self.highlight {
-                let highlight = high - low;
-                writeln!(
-                    f,
-                    "{:buffer$} | {:low_buffer$}{:^>highlight_chars$}",
-                    "",
-                    "",
-                    "",
-                    buffer = space_count,
-                    low_buffer = low,
-                    highlight_chars = highlight
-                )?;
-                if let Some(ref message) = self.highlight_message {
-                    writeln!(
-                        f,
-                        "{:buffer$} | {:low_buffer$}{}",
-                        "",
-                        "",
-                        message,
-                        buffer = space_count,
-                        low_buffer = low
-                    )?;
-                }
+            if let Some(ref location) = note.location {
+                write_file(f, location, space_count)?;
+                write_source(f, location, &None, space_count)?;
             }
         }
         Ok(())
     }
 }
 
+fn write_file(
+    f: &mut std::fmt::Formatter<'_>,
+    location: &ErrorLocation,
+    space_count: usize,
+) -> std::fmt::Result {
+    writeln!(
+        f,
+        "{:buffer$}--> {}:{}",
+        "",
+        location.buffer.name(),
+        location.line(),
+        buffer = space_count
+    )
+}
+
+fn write_source(
+    f: &mut std::fmt::Formatter<'_>,
+    location: &ErrorLocation,
+    highlight_message: &Option<String>,
+    space_count: usize,
+) -> std::fmt::Result {
+    let (line_num, col) = location.buffer.line_col(location.span.lo);
+    let line = location.buffer.line(line_num);
+
+    // Spans that continue onto later lines are only underlined
+    // up to the end of the first one
+    let low = col - 1;
+    let end = location.buffer.line_col(location.span.hi);
+    let high = if end.0 == line_num {
+        end.1 - 1
+    } else {
+        line.chars().count()
+    };
+    let highlight = high.saturating_sub(low).max(1);
+    // Tabs before the span are kept, so that the carets line up with it
+    // however wide the terminal shows a tab
+    let padding: String = line
+        .chars()
+        .take(low)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    writeln!(f, "{:buffer$} |", "", buffer = space_count)?;
+    writeln!(f, "{:>buffer$} | {}", line_num, line, buffer = space_count)?;
+    writeln!(
+        f,
+        "{:buffer$} | {:low_buffer$}{:^>highlight_chars$}",
+        "",
+        padding,
+        "",
+        buffer = space_count,
+        low_buffer = low,
+        highlight_chars = highlight
+    )?;
+    if let Some(ref message) = highlight_message {
+        writeln!(
+            f,
+            "{:buffer$} | {:low_buffer$}{}",
+            "",
+            padding,
+            message,
+            buffer = space_count,
+            low_buffer = low
+        )?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Default)]
 pub struct CompErrorBuilder {
     error: CompError,
@@ -101,18 +267,26 @@ impl CompErrorBuilder {
         self
     }
 
+    pub fn warning(mut self) -> Self {
+        self.error.warning = true;
+        self
+    }
+
     pub fn message(mut self, msg: String) -> Self {
         self.error.message = Some(msg);
         self
     }
 
-    pub fn source(mut self, src: String, line: usize) -> Self {
-        self.error.src = Some((src, line));
+    pub fn file(mut self, name: String) -> Self {
+        self.error.file = Some(name);
         self
     }
 
-    pub fn highlight(mut self, begin: usize, end: usize) -> Self {
-        self.error.highlight = Some((begin, end));
+    pub fn span(mut self, buffer: &Rc<SourceBuffer>, span: Span) -> Self {
+        self.error.location = Some(ErrorLocation {
+            buffer: buffer.clone(),
+            span,
+        });
         self
     }
 
@@ -121,6 +295,29 @@ impl CompErrorBuilder {
         self
     }
 
+    pub fn note(mut self, note: CompNote) -> Self {
+        self.error.notes.push(note);
+        self
+    }
+
+    // A note pointing at somewhere else in the source, like where a
+    // name was declared before
+    pub fn note_at(mut self, message: String, buffer: &Rc<SourceBuffer>, span: Span) -> Self {
+        self.error.notes.push(CompNote {
+            message,
+            location: Some(ErrorLocation {
+                buffer: buffer.clone(),
+                span,
+            }),
+        });
+        self
+    }
+
+    pub fn notes(mut self, notes: Vec<CompNote>) -> Self {
+        self.error.notes.extend(notes);
+        self
+    }
+
     pub fn build(&self) -> Box<CompError> {
         Box::new(self.error.clone())
     }
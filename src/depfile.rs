@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use crate::preprocessor::Dependency;
+
+// Rules are wrapped once a line would get longer than this
+const LINE_WIDTH: usize = 76;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyHeaders {
+    All,  // -M, -MD
+    User, // -MM, -MMD
+}
+
+#[derive(Debug, Default)]
+pub struct DependencyOptions {
+    pub headers: Option<DependencyHeaders>,
+    // -MD and -MMD write the rule as a side effect instead of replacing
+    // the normal output
+    pub side_effect: bool,
+    pub output: Option<PathBuf>,
+    pub targets: Vec<String>,
+    pub phony_targets: bool,
+}
+
+impl DependencyOptions {
+    pub fn enabled(&self) -> bool {
+        self.headers.is_some()
+    }
+
+    // Where the rule goes, `None` meaning standard output
+    pub fn output_path(&self, input: &Path) -> Option<PathBuf> {
+        if self.output.is_some() || !self.side_effect {
+            return self.output.clone();
+        }
+        Some(PathBuf::from(input.file_name().unwrap_or_default()).with_extension("d"))
+    }
+
+    // Without `-MT` or `-MQ` the target is the object file that
+    // compiling the input would produce
+    pub fn targets(&self, input: &Path) -> Vec<String> {
+        if !self.targets.is_empty() {
+            return self.targets.clone();
+        }
+        let object = PathBuf::from(input.file_name().unwrap_or_default()).with_extension("o");
+        vec![escape(&object.display().to_string())]
+    }
+}
+
+// Writes a Make rule for the input file and the headers it opened,
+// with `-MP` adding an empty rule for each header
+pub fn render(options: &DependencyOptions, input: &Path, dependencies: &[Dependency]) -> String {
+    let headers: Vec<String> = dependencies
+        .iter()
+        .filter(|dep| dep.path != input)
+        .filter(|dep| !dep.system || options.headers == Some(DependencyHeaders::All))
+        .map(|dep| escape(&dep.path.display().to_string()))
+        .collect();
+
+    let mut rule = RuleWriter::default();
+    for target in options.targets(input) {
+        rule.push_word(&target);
+    }
+    rule.output.push(':');
+    rule.width += 1;
+    rule.push_word(&escape(&input.display().to_string()));
+    for header in &headers {
+        rule.push_word(header);
+    }
+
+    let mut output = rule.output;
+    output.push('\n');
+
+    if options.phony_targets {
+        for header in &headers {
+            output.push_str(&format!("\n{}:\n", header));
+        }
+    }
+
+    output
+}
+
+#[derive(Debug, Default)]
+struct RuleWriter {
+    output: String,
+    width: usize,
+}
+
+impl RuleWriter {
+    fn push_word(&mut self, word: &str) {
+        if self.output.is_empty() {
+            // The first target needs no separator
+        } else if self.width + 1 + word.len() > LINE_WIDTH {
+            self.output.push_str(" \\\n ");
+            self.width = 1;
+        } else {
+            self.output.push(' ');
+            self.width += 1;
+        }
+        self.output.push_str(word);
+        self.width += word.len();
+    }
+}
+
+// Quotes the characters that are special to Make, which is what
+// `-MQ` does to its target
+pub fn escape(path: &str) -> String {
+    let mut escaped = String::new();
+    for c in path.chars() {
+        match c {
+            ' ' | '\t' | '#' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '$' => escaped.push_str("$$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
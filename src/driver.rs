@@ -0,0 +1,326 @@
+use std::path::PathBuf;
+
+use crate::ast;
+use crate::comp_error::*;
+use crate::depfile::{self, DependencyHeaders, DependencyOptions};
+#[cfg(feature = "json")]
+use crate::json;
+use crate::lang::Standard;
+use crate::lexer::{self, PreprocessToken};
+use crate::parser::Parser;
+use crate::preprocessor::{self, Preprocessor};
+use crate::source::SourceManager;
+
+// Name of the synthetic buffer holding definitions from the command line
+pub const COMMAND_LINE_BUFFER: &str = "<command line>";
+pub const BUILT_IN_BUFFER: &str = "<built-in>";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandLineMacro {
+    Define(String),
+    Undefine(String),
+}
+
+// What `-emit-json` writes out instead of compiling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonOutput {
+    PreprocessTokens,
+    Tokens,
+    Ast,
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub input: Option<PathBuf>,
+    pub include_dirs: Vec<PathBuf>,
+    pub system_dirs: Vec<PathBuf>,
+    pub macros: Vec<CommandLineMacro>,
+    pub forced_includes: Vec<String>,
+    pub preprocess_only: bool,
+    pub standard: Standard,
+    // `-std=gnu17` and the like turn on GNU extensions
+    pub gnu: bool,
+    // Print the AST as a tree or as C, instead of compiling
+    pub ast_dump: bool,
+    pub ast_print: bool,
+    pub json: Option<JsonOutput>,
+    pub dependencies: DependencyOptions,
+}
+
+impl Options {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> CompResult<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Options that take a value accept it either attached
+            // (`-DFOO`) or as the next argument (`-D FOO`)
+            let mut value = |flag: &str| -> CompResult<String> {
+                match &arg[flag.len()..] {
+                    "" => args.next().ok_or_else(|| {
+                        CompErrorBuilder::new()
+                            .code(ErrorCode::InvalidCommandLineArgument)
+                            .message(format!("Missing argument to `{}`", flag))
+                            .build()
+                    }),
+                    attached => Ok(attached.into()),
+                }
+            };
+
+            if arg == "-include" {
+                let file = value("-include")?;
+                options.forced_includes.push(file);
+            } else if arg == "-isystem" {
+                let dir = value("-isystem")?;
+                options.system_dirs.push(dir.into());
+            } else if let Some((headers, side_effect)) = dependency_mode(&arg) {
+                options.dependencies.headers = Some(headers);
+                options.dependencies.side_effect = side_effect;
+            } else if arg == "-MP" {
+                options.dependencies.phony_targets = true;
+            } else if arg.starts_with("-MF") {
+                let file = value("-MF")?;
+                options.dependencies.output = Some(file.into());
+            } else if arg.starts_with("-MT") {
+                let target = value("-MT")?;
+                options.dependencies.targets.push(target);
+            } else if arg.starts_with("-MQ") {
+                let target = value("-MQ")?;
+                options.dependencies.targets.push(depfile::escape(&target));
+            } else if arg.starts_with("-D") {
+                let definition = value("-D")?;
+                options.macros.push(CommandLineMacro::Define(definition));
+            } else if arg.starts_with("-U") {
+                let name = value("-U")?;
+                options.macros.push(CommandLineMacro::Undefine(name));
+            } else if arg.starts_with("-I") {
+                let dir = value("-I")?;
+                options.include_dirs.push(dir.into());
+            } else if let Some(name) = arg.strip_prefix("-std=") {
+                let gnu = Standard::from_gnu_name(name);
+                options.gnu = gnu.is_some();
+                options.standard = gnu.or_else(|| Standard::from_name(name)).ok_or_else(|| {
+                    CompErrorBuilder::new()
+                        .code(ErrorCode::InvalidCommandLineArgument)
+                        .message(format!("Unknown language standard `{}`", name))
+                        .build()
+                })?;
+            } else if arg == "-E" {
+                options.preprocess_only = true;
+            } else if arg == "-ast-dump" {
+                options.ast_dump = true;
+            } else if arg == "-ast-print" {
+                options.ast_print = true;
+            } else if let Some(output) = arg.strip_prefix("-emit-json=") {
+                options.json = Some(json_output(output)?);
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(CompErrorBuilder::new()
+                    .code(ErrorCode::InvalidCommandLineArgument)
+                    .message(format!("Unknown option `{}`", arg))
+                    .build());
+            } else if let Some(ref input) = options.input {
+                return Err(CompErrorBuilder::new()
+                    .code(ErrorCode::InvalidCommandLineArgument)
+                    .message(format!(
+                        "Multiple input files given, `{}` and `{}`",
+                        input.display(),
+                        arg
+                    ))
+                    .build());
+            } else {
+                options.input = Some(arg.into());
+            }
+        }
+
+        Ok(options)
+    }
+
+    // `-D`, `-U` and `-include` become directives in a buffer that is
+    // preprocessed before the main file, in the order they were given
+    pub fn command_line_buffer(&self) -> String {
+        let mut buffer = String::new();
+        for mac in &self.macros {
+            match mac {
+                CommandLineMacro::Define(definition) => match definition.split_once('=') {
+                    Some((name, value)) => {
+                        buffer.push_str(&format!("#define {} {}\n", name, value))
+                    }
+                    None => buffer.push_str(&format!("#define {} 1\n", definition)),
+                },
+                CommandLineMacro::Undefine(name) => buffer.push_str(&format!("#undef {}\n", name)),
+            }
+        }
+        for file in &self.forced_includes {
+            buffer.push_str(&format!("#include \"{}\"\n", file));
+        }
+        buffer
+    }
+}
+
+pub fn run(options: &Options) -> CompResult<()> {
+    let Some(ref input) = options.input else {
+        return Err(CompErrorBuilder::new()
+            .code(ErrorCode::InvalidCommandLineArgument)
+            .message("No input file given".into())
+            .build());
+    };
+
+    // Forced includes are searched for relative to the working directory
+    let mut preprocessor = Preprocessor::new(
+        options.include_dirs.clone(),
+        options.system_dirs.clone(),
+        options.standard,
+    );
+    preprocessor.process_buffer(
+        BUILT_IN_BUFFER,
+        &PathBuf::new(),
+        &preprocessor::predefined_macros(options.standard, options.gnu),
+    )?;
+    preprocessor.process_buffer(
+        COMMAND_LINE_BUFFER,
+        &PathBuf::new(),
+        &options.command_line_buffer(),
+    )?;
+    preprocessor.process_file(input)?;
+
+    let deps = &options.dependencies;
+    if deps.enabled() {
+        let rule = depfile::render(deps, input, preprocessor.dependencies());
+        match deps.output_path(input) {
+            Some(path) => std::fs::write(&path, rule).map_err(|err| {
+                CompErrorBuilder::new()
+                    .code(ErrorCode::OutputFileUnwritable)
+                    .message(format!("Failed to write `{}`: {}", path.display(), err))
+                    .build()
+            })?,
+            None => print!("{}", rule),
+        }
+        // `-M` and `-MM` replace the preprocessed output
+        if !deps.side_effect {
+            return Ok(());
+        }
+    }
+
+    #[cfg(feature = "json")]
+    if options.json == Some(JsonOutput::PreprocessTokens) {
+        print!(
+            "{}",
+            json::to_json(preprocessor.sources(), None, preprocessor.output())
+        );
+        return Ok(());
+    }
+
+    if options.preprocess_only {
+        print!(
+            "{}",
+            render_preprocessed(preprocessor.sources(), preprocessor.output())
+        );
+        return Ok(());
+    }
+
+    let output = preprocessor.take_output();
+    let tokens = lexer::convert_tokens(
+        preprocessor.sources(),
+        output,
+        options.standard,
+        options.gnu,
+    )?;
+    #[cfg(feature = "json")]
+    if options.json == Some(JsonOutput::Tokens) {
+        print!("{}", json::to_json(preprocessor.sources(), None, &tokens));
+        return Ok(());
+    }
+    let mut parser = Parser::new(
+        preprocessor.sources(),
+        tokens,
+        options.standard,
+        options.gnu,
+    );
+    let unit = parser.parse_translation_unit();
+    // The tree is printed even if it has errors in it
+    if options.ast_dump {
+        print!(
+            "{}",
+            ast::dump(preprocessor.sources(), parser.types(), &unit)
+        );
+    }
+    if options.ast_print {
+        print!("{}", ast::unparse(&unit));
+    }
+    #[cfg(feature = "json")]
+    if options.json == Some(JsonOutput::Ast) {
+        print!(
+            "{}",
+            json::to_json(preprocessor.sources(), Some(parser.types()), &unit)
+        );
+    }
+    // Every error but the last is reported here along with the
+    // warnings, the last one is returned like any other
+    let mut errors = parser.take_errors();
+    let last = errors
+        .iter()
+        .rposition(|error| !error.warning)
+        .map(|index| errors.remove(index));
+    for error in errors {
+        eprint!("{}", error);
+    }
+    match last {
+        Some(last) => Err(Box::new(last)),
+        None => Ok(()),
+    }
+}
+
+fn json_output(output: &str) -> CompResult<JsonOutput> {
+    if cfg!(not(feature = "json")) {
+        return Err(CompErrorBuilder::new()
+            .code(ErrorCode::InvalidCommandLineArgument)
+            .message("`-emit-json` needs zcc to be built with the `json` feature".into())
+            .build());
+    }
+    match output {
+        "pp-tokens" => Ok(JsonOutput::PreprocessTokens),
+        "tokens" => Ok(JsonOutput::Tokens),
+        "ast" => Ok(JsonOutput::Ast),
+        _ => Err(CompErrorBuilder::new()
+            .code(ErrorCode::InvalidCommandLineArgument)
+            .message(format!(
+                "Unknown `-emit-json` output `{}`, expected `pp-tokens`, `tokens` or `ast`",
+                output
+            ))
+            .build()),
+    }
+}
+
+fn dependency_mode(arg: &str) -> Option<(DependencyHeaders, bool)> {
+    match arg {
+        "-M" => Some((DependencyHeaders::All, false)),
+        "-MM" => Some((DependencyHeaders::User, false)),
+        "-MD" => Some((DependencyHeaders::All, true)),
+        "-MMD" => Some((DependencyHeaders::User, true)),
+        _ => None,
+    }
+}
+
+// Spells out preprocessed tokens, keeping line breaks and
+// spacing close to the original source
+fn render_preprocessed(sources: &SourceManager, tokens: &[PreprocessToken]) -> String {
+    let mut output = String::new();
+    let mut line = None;
+    for token in tokens {
+        let meta = token.metadata();
+        let token_line = (meta.span.file_id, sources.line_col(meta.span).0);
+        if line != Some(token_line) {
+            if line.is_some() {
+                output.push('\n');
+            }
+            line = Some(token_line);
+        } else if meta.leading_space {
+            output.push(' ');
+        }
+        output.push_str(&token.spelling());
+    }
+    if line.is_some() {
+        output.push('\n');
+    }
+    output
+}
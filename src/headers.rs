@@ -0,0 +1,32 @@
+// Freestanding standard headers built into zcc. They live in a
+// virtual system include directory searched after every directory
+// given on the command line, and are written in terms of the target
+// macros from `target::predefined_macros`.
+
+use std::path::Path;
+
+pub const BUNDLED_INCLUDE_DIR: &str = "<zcc>/include";
+
+const BUNDLED_HEADERS: [(&str, &str); 8] = [
+    ("float.h", include_str!("../include/float.h")),
+    ("limits.h", include_str!("../include/limits.h")),
+    ("stdalign.h", include_str!("../include/stdalign.h")),
+    ("stdarg.h", include_str!("../include/stdarg.h")),
+    ("stdbool.h", include_str!("../include/stdbool.h")),
+    ("stddef.h", include_str!("../include/stddef.h")),
+    ("stdint.h", include_str!("../include/stdint.h")),
+    ("stdnoreturn.h", include_str!("../include/stdnoreturn.h")),
+];
+
+// Contents of a path inside of the virtual include directory
+pub fn bundled_header(path: &Path) -> Option<&'static str> {
+    let name = path.strip_prefix(BUNDLED_INCLUDE_DIR).ok()?;
+    BUNDLED_HEADERS
+        .iter()
+        .find(|(header, _)| name == Path::new(header))
+        .map(|(_, contents)| *contents)
+}
+
+pub fn is_bundled(path: &Path) -> bool {
+    path.starts_with(BUNDLED_INCLUDE_DIR)
+}
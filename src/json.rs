@@ -0,0 +1,73 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::source::{SourceManager, Span};
+use crate::types::{CType, TypeTable};
+
+// JSON for tools outside of zcc, of preprocessing tokens, tokens or the
+// AST. Spans are given as the name of their file and the line and
+// column, both starting at 1, of where they start and of the character
+// just past their end. Types in the AST are given as they're named in
+// errors, `pointer to const char` and so on.
+pub fn to_json<T: Serialize + ?Sized>(
+    sources: &SourceManager,
+    types: Option<&TypeTable>,
+    value: &T,
+) -> String {
+    let mut value = serde_json::to_value(value).expect("Tokens and the AST always serialize");
+    resolve(sources, types, &mut value);
+    let mut output = serde_json::to_string_pretty(&value).expect("JSON values always serialize");
+    output.push('\n');
+    output
+}
+
+// Spans serialize as their byte offsets and types as their index in the
+// table, which mean nothing without the buffers and the table, so they
+// are found in the tree and replaced
+fn resolve(sources: &SourceManager, types: Option<&TypeTable>, value: &mut Value) {
+    if let Some(span) = as_span(value) {
+        *value = location(sources, span);
+        return;
+    }
+    if let Some((types, ty)) = types.and_then(|types| Some((types, as_type(types, value)?))) {
+        *value = Value::String(types.display(ty).to_string());
+        return;
+    }
+    match value {
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| resolve(sources, types, value)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|value| resolve(sources, types, value)),
+        _ => (),
+    }
+}
+
+fn as_span(value: &Value) -> Option<Span> {
+    let fields = value.as_object()?;
+    if fields.len() != 3 {
+        return None;
+    }
+    let offset = |name| fields.get(name)?.as_u64().map(|offset| offset as usize);
+    Some(Span::new(offset("file_id")?, offset("lo")?, offset("hi")?))
+}
+
+fn as_type(types: &TypeTable, value: &Value) -> Option<CType> {
+    let fields = value.as_object()?;
+    if fields.len() != 1 {
+        return None;
+    }
+    types.by_id(fields.get("type_id")?.as_u64()?)
+}
+
+fn location(sources: &SourceManager, span: Span) -> Value {
+    let buffer = sources.buffer(span.file_id);
+    let (start_line, start_col) = buffer.line_col(span.lo);
+    let (end_line, end_col) = buffer.line_col(span.hi);
+    json!({
+        "file": buffer.name(),
+        "start": { "line": start_line, "col": start_col },
+        "end": { "line": end_line, "col": end_col },
+    })
+}
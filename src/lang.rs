@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum Keyword {
     Auto,
     Break,
@@ -20,9 +21,11 @@ pub enum Keyword {
     For,
     Goto,
     If,
+    Inline,
     Int,
     Long,
     Register,
+    Restrict,
     Return,
     Short,
     Signed,
@@ -36,17 +39,191 @@ pub enum Keyword {
     Void,
     Volatile,
     While,
+    Alignas,
+    Alignof,
+    Atomic,
+    Bool,
+    Generic,
+    Noreturn,
+    StaticAssert,
+    ThreadLocal,
+    Constexpr,
+    False,
+    Nullptr,
+    True,
+    Typeof,
+    TypeofUnqual,
+    // GNU extensions, and builtins `<stdarg.h>` is written in terms of
+    Attribute,
+    Extension,
+    BuiltinVaList,
+    BuiltinVaArg,
+    BuiltinOffsetof,
 }
 
-#[derive(Debug)]
+// Editions of the C standard, selected with `-std`. Later ones are
+// greater, so checks can ask for at least some edition.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Standard {
+    C89,
+    C99,
+    C11,
+    #[default]
+    C17,
+    C23,
+}
+
+impl Standard {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c89" | "c90" | "iso9899:1990" => Some(Self::C89),
+            "c99" | "iso9899:1999" => Some(Self::C99),
+            "c11" | "c1x" | "iso9899:2011" => Some(Self::C11),
+            "c17" | "c18" | "iso9899:2017" | "iso9899:2018" => Some(Self::C17),
+            "c23" | "c2x" | "iso9899:2024" => Some(Self::C23),
+            _ => None,
+        }
+    }
+
+    // `gnu17` is C17 with GNU extensions, there's one for each `c` name
+    pub fn from_gnu_name(name: &str) -> Option<Self> {
+        let version = name.strip_prefix("gnu")?;
+        Self::from_name(&format!("c{}", version))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::C89 => "C89",
+            Self::C99 => "C99",
+            Self::C11 => "C11",
+            Self::C17 => "C17",
+            Self::C23 => "C23",
+        }
+    }
+
+    // Value of `__STDC_VERSION__`, which C89 doesn't define
+    pub fn version(self) -> Option<&'static str> {
+        match self {
+            Self::C89 => None,
+            Self::C99 => Some("199901L"),
+            Self::C11 => Some("201112L"),
+            Self::C17 => Some("201710L"),
+            Self::C23 => Some("202311L"),
+        }
+    }
+}
+
+// The edition a keyword was added in, before it the keyword is an
+// ordinary identifier
+pub fn keyword_standard(keyword: Keyword) -> Standard {
+    match keyword {
+        Keyword::Inline | Keyword::Restrict | Keyword::Bool => Standard::C99,
+        Keyword::Alignas
+        | Keyword::Alignof
+        | Keyword::Atomic
+        | Keyword::Generic
+        | Keyword::Noreturn
+        | Keyword::StaticAssert
+        | Keyword::ThreadLocal => Standard::C11,
+        Keyword::Constexpr
+        | Keyword::False
+        | Keyword::Nullptr
+        | Keyword::True
+        | Keyword::Typeof
+        | Keyword::TypeofUnqual => Standard::C23,
+        _ => Standard::C89,
+    }
+}
+
+// Integer and floating constants keep the type their suffix gives
+// them
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum Constant {
-    Floating(f64),
-    Integer(i64),
+    Floating(f64, FloatingKind),
+    #[cfg_attr(feature = "json", serde(serialize_with = "serialize_integer"))]
+    Integer(i64, IntegerKind),
     Enumeration(i64),
     Character(char),
+    // `true`, `false` and `nullptr`, which are keywords in C23
+    Bool(bool),
+    Nullptr,
+}
+
+// The value of an unsigned constant is kept in the bits of an `i64`,
+// and written as the number it stands for
+#[cfg(feature = "json")]
+fn serialize_integer<S: serde::Serializer>(
+    value: &i64,
+    kind: &IntegerKind,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeTuple;
+    let mut integer = serializer.serialize_tuple(2)?;
+    match kind.is_unsigned() {
+        true => integer.serialize_element(&(*value as u64))?,
+        false => integer.serialize_element(value)?,
+    }
+    integer.serialize_element(kind)?;
+    integer.end()
 }
 
-#[derive(Debug)]
+// The type of an integer constant is the first of the ones its suffix
+// allows that can hold its value. Decimal constants without a `u` are
+// only ever signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum IntegerKind {
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+}
+
+impl IntegerKind {
+    pub fn is_unsigned(self) -> bool {
+        matches!(
+            self,
+            Self::UnsignedInt | Self::UnsignedLong | Self::UnsignedLongLong
+        )
+    }
+
+    // The suffix that gives a constant this type, whatever its value
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Int => "",
+            Self::UnsignedInt => "u",
+            Self::Long => "l",
+            Self::UnsignedLong => "ul",
+            Self::LongLong => "ll",
+            Self::UnsignedLongLong => "ull",
+        }
+    }
+}
+
+// `1.0f` is a `float` and `1.0l` a `long double`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum FloatingKind {
+    Float,
+    Double,
+    LongDouble,
+}
+
+impl FloatingKind {
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Float => "f",
+            Self::Double => "",
+            Self::LongDouble => "l",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum Operator {
     LBracket,
     RBracket,
@@ -95,7 +272,8 @@ pub enum Operator {
     DoublePound,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum Punctuator {
     LBracket,
     RBracket,
@@ -110,6 +288,83 @@ pub enum Punctuator {
     Semicolon,
     TripleDot,
     Pound,
+    DoubleColon,
+}
+
+// Builtin functions the compiler provides, reported by `__has_builtin`
+pub const BUILTIN_FUNCTIONS: [&str; 5] = [
+    "__builtin_va_start",
+    "__builtin_va_arg",
+    "__builtin_va_end",
+    "__builtin_va_copy",
+    "__builtin_offsetof",
+];
+
+// Attributes the compiler understands, reported by `__has_attribute`
+pub const ATTRIBUTES: [&str; 7] = [
+    "aligned", "format", "noreturn", "packed", "section", "unused", "weak",
+];
+
+// Kinds of format string the `format` attribute checks arguments against
+pub const FORMAT_ARCHETYPES: [&str; 4] = ["printf", "scanf", "strftime", "strfmon"];
+
+// The attributes C23 defines for `[[...]]`, with the value
+// `__has_c_attribute` gives for them
+pub const STANDARD_ATTRIBUTES: [(&str, u64); 8] = [
+    ("deprecated", 201904),
+    ("fallthrough", 201904),
+    ("maybe_unused", 201904),
+    ("nodiscard", 202003),
+    ("noreturn", 202202),
+    ("_Noreturn", 202202),
+    ("reproducible", 202207),
+    ("unsequenced", 202207),
+];
+
+// Attribute names may be written with surrounding underscores,
+// `__packed__` is the same as `packed`
+fn attribute_name(name: &str) -> &str {
+    name.strip_prefix("__")
+        .and_then(|name| name.strip_suffix("__"))
+        .unwrap_or(name)
+}
+
+// The standard attribute `name` is, if it is one
+pub fn standard_attribute(name: &str) -> Option<(&'static str, u64)> {
+    let name = attribute_name(name);
+    STANDARD_ATTRIBUTES
+        .into_iter()
+        .find(|(standard, _)| *standard == name)
+}
+
+// Language features reported by `__has_feature` and `__has_extension`,
+// with the standard that added them. `__has_feature` only counts the
+// ones in the standard being compiled, extensions are there in all
+pub const FEATURES: [(&str, Standard); 6] = [
+    ("c_alignas", Standard::C11),
+    ("c_alignof", Standard::C11),
+    ("c_atomic", Standard::C11),
+    ("c_generic_selections", Standard::C11),
+    ("c_static_assert", Standard::C11),
+    ("c_thread_local", Standard::C11),
+];
+
+// The standard that added the feature `name`, if zcc has it
+pub fn feature(name: &str) -> Option<Standard> {
+    FEATURES
+        .into_iter()
+        .find(|(feature, _)| *feature == name)
+        .map(|(_, standard)| standard)
+}
+
+pub fn is_attribute(name: &str) -> bool {
+    gnu_attribute(name).is_some()
+}
+
+// The GNU attribute `name` is, if zcc knows about it
+pub fn gnu_attribute(name: &str) -> Option<&'static str> {
+    let name = attribute_name(name);
+    ATTRIBUTES.into_iter().find(|attribute| *attribute == name)
 }
 
 lazy_static! {
@@ -130,9 +385,11 @@ lazy_static! {
         ("for", Keyword::For),
         ("goto", Keyword::Goto),
         ("if", Keyword::If),
+        ("inline", Keyword::Inline),
         ("int", Keyword::Int),
         ("long", Keyword::Long),
         ("register", Keyword::Register),
+        ("restrict", Keyword::Restrict),
         ("return", Keyword::Return),
         ("short", Keyword::Short),
         ("signed", Keyword::Signed),
@@ -146,6 +403,53 @@ lazy_static! {
         ("void", Keyword::Void),
         ("volatile", Keyword::Volatile),
         ("while", Keyword::While),
+        ("_Alignas", Keyword::Alignas),
+        ("_Alignof", Keyword::Alignof),
+        ("_Atomic", Keyword::Atomic),
+        ("_Bool", Keyword::Bool),
+        ("_Generic", Keyword::Generic),
+        ("_Noreturn", Keyword::Noreturn),
+        ("_Static_assert", Keyword::StaticAssert),
+        ("_Thread_local", Keyword::ThreadLocal),
+        ("constexpr", Keyword::Constexpr),
+        ("false", Keyword::False),
+        ("nullptr", Keyword::Nullptr),
+        ("true", Keyword::True),
+        ("typeof", Keyword::Typeof),
+        ("typeof_unqual", Keyword::TypeofUnqual),
+        ("__attribute__", Keyword::Attribute),
+        ("__extension__", Keyword::Extension),
+        ("__builtin_va_list", Keyword::BuiltinVaList),
+        ("__builtin_va_arg", Keyword::BuiltinVaArg),
+        ("__builtin_offsetof", Keyword::BuiltinOffsetof),
+    ]);
+    // C23 spells these keywords without the underscore and capital, the
+    // old spellings still work and are the ones printed
+    pub static ref C23_KEYWORD_ALIASES: HashMap<&'static str, Keyword> = HashMap::from([
+        ("alignas", Keyword::Alignas),
+        ("alignof", Keyword::Alignof),
+        ("bool", Keyword::Bool),
+        ("static_assert", Keyword::StaticAssert),
+        ("thread_local", Keyword::ThreadLocal),
+    ]);
+    // Spellings GCC accepts in every mode since they're reserved names,
+    // so headers can use them even with `-std=c99`
+    pub static ref GNU_KEYWORD_ALIASES: HashMap<&'static str, Keyword> = HashMap::from([
+        ("__attribute", Keyword::Attribute),
+        ("__alignof", Keyword::Alignof),
+        ("__alignof__", Keyword::Alignof),
+        ("__const", Keyword::Const),
+        ("__const__", Keyword::Const),
+        ("__inline", Keyword::Inline),
+        ("__inline__", Keyword::Inline),
+        ("__restrict", Keyword::Restrict),
+        ("__restrict__", Keyword::Restrict),
+        ("__signed", Keyword::Signed),
+        ("__signed__", Keyword::Signed),
+        ("__typeof", Keyword::Typeof),
+        ("__typeof__", Keyword::Typeof),
+        ("__volatile", Keyword::Volatile),
+        ("__volatile__", Keyword::Volatile),
     ]);
     pub static ref OPERATOR_MAP: HashMap<&'static str, Operator> = HashMap::from([
         ("[", Operator::LBracket),
@@ -178,7 +482,7 @@ lazy_static! {
         ("&&", Operator::BooleanAnd),
         ("||", Operator::BooleanOr),
         ("?", Operator::QuestionMark),
-        (";", Operator::Colon),
+        (":", Operator::Colon),
         ("=", Operator::Equals),
         ("*=", Operator::MultiplyEquals),
         ("/=", Operator::DivideEquals),
@@ -208,5 +512,54 @@ lazy_static! {
         (";", Punctuator::Semicolon),
         ("...", Punctuator::TripleDot),
         ("#", Punctuator::Pound),
+        ("::", Punctuator::DoubleColon),
     ]);
 }
+
+// Map lookups in reverse, for spelling tokens back out
+pub fn keyword_spelling(keyword: Keyword) -> &'static str {
+    KEYWORD_MAP
+        .iter()
+        .find(|(_, k)| **k == keyword)
+        .map(|(spelling, _)| *spelling)
+        .expect("Every keyword has a spelling")
+}
+
+pub fn operator_spelling(operator: Operator) -> &'static str {
+    OPERATOR_MAP
+        .iter()
+        .find(|(_, op)| **op == operator)
+        .map(|(spelling, _)| *spelling)
+        .expect("Every operator has a spelling")
+}
+
+pub fn punctuator_spelling(punctuator: Punctuator) -> &'static str {
+    PUNCTUATOR_MAP
+        .iter()
+        .find(|(_, p)| **p == punctuator)
+        .map(|(spelling, _)| *spelling)
+        .expect("Every punctuator has a spelling")
+}
+
+// How tightly a binary operator binds, higher is tighter. `None` for
+// operators that aren't binary or, like `,` and assignment, are
+// handled separately from the precedence table.
+pub fn binary_precedence(operator: Operator) -> Option<u8> {
+    let precedence = match operator {
+        Operator::Asterisk | Operator::ForwardSlash | Operator::Percent => 10,
+        Operator::Plus | Operator::Minus => 9,
+        Operator::BitwiseLeft | Operator::BitwiseRight => 8,
+        Operator::LessThan
+        | Operator::GreaterThan
+        | Operator::LessThanEquals
+        | Operator::GreaterThanEquals => 7,
+        Operator::Equality | Operator::NotEquality => 6,
+        Operator::Ampersand => 5,
+        Operator::BitwiseXor => 4,
+        Operator::BitwiseOr => 3,
+        Operator::BooleanAnd => 2,
+        Operator::BooleanOr => 1,
+        _ => return None,
+    };
+    Some(precedence)
+}
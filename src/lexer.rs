@@ -1,14 +1,71 @@
+use std::rc::Rc;
+
 use crate::comp_error::*;
 use crate::lang::*;
+use crate::source::{ExpansionId, SourceBuffer, SourceManager, Span};
+use crate::target;
 
-#[derive(Debug)]
+// Tokens after preprocessing, which is what the parser reads. String
+// literals keep their escapes as written, like preprocessing tokens do.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum Token {
-    Keyword(Keyword),
-    Identifier(String),
-    Constant(Constant),
-    StringLiteral(String),
-    Operator(Operator),
-    Punctuator(Punctuator),
+    Keyword(Keyword, PreprocessMetadata),
+    Identifier(String, PreprocessMetadata),
+    Constant(Constant, PreprocessMetadata),
+    StringLiteral(String, PreprocessMetadata),
+    Operator(Operator, PreprocessMetadata),
+    Punctuator(Punctuator, PreprocessMetadata),
+}
+
+impl Token {
+    pub fn metadata(&self) -> &PreprocessMetadata {
+        match self {
+            Self::Keyword(_, meta)
+            | Self::Identifier(_, meta)
+            | Self::Constant(_, meta)
+            | Self::StringLiteral(_, meta)
+            | Self::Operator(_, meta)
+            | Self::Punctuator(_, meta) => meta,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.metadata().span
+    }
+
+    // The token as it would be written in source
+    pub fn spelling(&self) -> String {
+        match self {
+            Self::Keyword(keyword, _) => keyword_spelling(*keyword).into(),
+            Self::Identifier(name, _) => name.clone(),
+            Self::Constant(Constant::Character(c), _) => format!("'{}'", escape_char(*c, '\'')),
+            Self::Constant(Constant::Floating(value, kind), _) => {
+                format!("{:?}{}", value, kind.suffix())
+            }
+            Self::Constant(Constant::Integer(value, kind), _) => {
+                format!("{}{}", *value as u64, kind.suffix())
+            }
+            Self::Constant(Constant::Enumeration(value), _) => value.to_string(),
+            Self::Constant(Constant::Bool(value), _) => value.to_string(),
+            Self::Constant(Constant::Nullptr, _) => "nullptr".into(),
+            Self::StringLiteral(s, _) => format!("\"{}\"", s),
+            Self::Operator(op, _) => operator_spelling(*op).into(),
+            Self::Punctuator(punct, _) => punctuator_spelling(*punct).into(),
+        }
+    }
+
+    pub fn is_keyword(&self, keyword: Keyword) -> bool {
+        matches!(self, Self::Keyword(k, _) if *k == keyword)
+    }
+
+    pub fn is_operator(&self, operator: Operator) -> bool {
+        matches!(self, Self::Operator(op, _) if *op == operator)
+    }
+
+    pub fn is_punctuator(&self, punctuator: Punctuator) -> bool {
+        matches!(self, Self::Punctuator(p, _) if *p == punctuator)
+    }
 }
 
 #[derive(Debug)]
@@ -18,47 +75,163 @@ pub enum PreprocessNumber {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub struct PreprocessMetadata {
-    pub line: usize,
-    pub col: usize,
-    pub index: usize,
+    pub span: Span,
+    pub leading_space: bool, // whitespace or start of line before the token
+    pub expansion: Option<ExpansionId>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum HeaderNameType {
     Included,
     Local,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
 pub enum PreprocessToken {
     HeaderName(String, HeaderNameType, PreprocessMetadata),
     Identifier(String, PreprocessMetadata),
     Number(String, PreprocessMetadata),
-    CharacterConstant(char, PreprocessMetadata),
+    // The value and the spelling between the quotes, with its escapes
+    // as written
+    CharacterConstant(char, String, PreprocessMetadata),
     StringLiteral(String, PreprocessMetadata),
     Operator(String, PreprocessMetadata),
     Punctuator(String, PreprocessMetadata),
     Other(char, PreprocessMetadata),
 }
 
+impl PreprocessToken {
+    pub fn metadata(&self) -> &PreprocessMetadata {
+        match self {
+            Self::HeaderName(_, _, meta)
+            | Self::Identifier(_, meta)
+            | Self::Number(_, meta)
+            | Self::CharacterConstant(_, _, meta)
+            | Self::StringLiteral(_, meta)
+            | Self::Operator(_, meta)
+            | Self::Punctuator(_, meta)
+            | Self::Other(_, meta) => meta,
+        }
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut PreprocessMetadata {
+        match self {
+            Self::HeaderName(_, _, meta)
+            | Self::Identifier(_, meta)
+            | Self::Number(_, meta)
+            | Self::CharacterConstant(_, _, meta)
+            | Self::StringLiteral(_, meta)
+            | Self::Operator(_, meta)
+            | Self::Punctuator(_, meta)
+            | Self::Other(_, meta) => meta,
+        }
+    }
+
+    // The token as it would be written in source
+    pub fn spelling(&self) -> String {
+        match self {
+            Self::HeaderName(name, HeaderNameType::Local, _) => format!("\"{}\"", name),
+            Self::HeaderName(name, HeaderNameType::Included, _) => format!("<{}>", name),
+            Self::Identifier(s, _)
+            | Self::Number(s, _)
+            | Self::Operator(s, _)
+            | Self::Punctuator(s, _) => s.clone(),
+            Self::CharacterConstant(_, s, _) => format!("'{}'", s),
+            Self::StringLiteral(s, _) => format!("\"{}\"", s),
+            Self::Other(c, _) => c.to_string(),
+        }
+    }
+
+    pub fn is_identifier(&self, name: &str) -> bool {
+        matches!(self, Self::Identifier(ident, _) if ident == name)
+    }
+
+    // Operators and punctuators overlap (`#`, `*`, `,`, ...), so
+    // this checks both
+    pub fn is_punctuation(&self, spelling: &str) -> bool {
+        match self {
+            Self::Operator(s, _) | Self::Punctuator(s, _) => s == spelling,
+            _ => false,
+        }
+    }
+
+    pub fn is_newline(&self) -> bool {
+        matches!(self, Self::Other('\n', _))
+    }
+}
+
+// Turns a character back into the escape sequence that
+// would produce it inside of a literal delimited by `quote`
+pub fn escape_char(c: char, quote: char) -> String {
+    match c {
+        '\n' => "\\n".into(),
+        '\t' => "\\t".into(),
+        '\r' => "\\r".into(),
+        '\0' => "\\0".into(),
+        '\\' => "\\\\".into(),
+        '\x07' => "\\a".into(),
+        '\x08' => "\\b".into(),
+        '\x0b' => "\\v".into(),
+        '\x0c' => "\\f".into(),
+        c if c == quote => format!("\\{}", c),
+        c if (c as u32) < 0x20 || c as u32 == 0x7f => format!("\\x{:02x}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+// The number of bytes a string literal kept with its escapes has,
+// without the terminating null. An escape is one byte, other
+// characters are as long as they are in UTF-8.
+pub fn string_length(literal: &str) -> u64 {
+    let mut chars = literal.chars().peekable();
+    let mut length = 0;
+    while let Some(c) = chars.next() {
+        length += match c {
+            '\\' => {
+                match chars.next() {
+                    Some('x') => while chars.next_if(char::is_ascii_hexdigit).is_some() {},
+                    Some('0'..='7') => {
+                        for _ in 0..2 {
+                            chars.next_if(|c| matches!(c, '0'..='7'));
+                        }
+                    }
+                    _ => (),
+                }
+                1
+            }
+            c => c.len_utf8() as u64,
+        };
+    }
+    length
+}
+
 #[derive(Debug)]
 pub struct Lexer {
+    buffer: Rc<SourceBuffer>,
     source: String,
-    line: usize,
-    col: usize,
+    // For every byte of `source`, the bytes of the original buffer it
+    // came from. Trigraphs and spliced lines make the two differ.
+    offsets: Vec<(usize, usize)>,
     index: usize,
+    leading_space: bool,
+    standard: Standard,
     tokens: Vec<Token>,
     pp_tokens: Vec<PreprocessToken>,
 }
 
-impl<'a> Lexer {
-    pub fn new(source: &'a str) -> Self {
+impl Lexer {
+    pub fn new(buffer: Rc<SourceBuffer>, standard: Standard) -> Self {
         Self {
-            source: String::from(source),
-            line: 1,
-            col: 1,
+            source: buffer.text().into(),
+            offsets: (0..buffer.text().len()).map(|i| (i, i + 1)).collect(),
+            buffer,
             index: 0,
+            leading_space: true,
+            standard,
             tokens: Vec::new(),
             pp_tokens: Vec::new(),
         }
@@ -71,6 +244,14 @@ impl<'a> Lexer {
         Ok(())
     }
 
+    pub fn pp_tokens(&self) -> &[PreprocessToken] {
+        &self.pp_tokens
+    }
+
+    pub fn take_pp_tokens(&mut self) -> Vec<PreprocessToken> {
+        std::mem::take(&mut self.pp_tokens)
+    }
+
     // Source character mapping and trigraph sequence
     // mapping
     fn phase_one(&mut self) {
@@ -86,67 +267,156 @@ impl<'a> Lexer {
     fn phase_three(&mut self) -> CompResult<()> {
         while let Some(c) = self.peek_next_char() {
             if c == '\n' {
-                let token = PreprocessToken::Other(
-                    c,
-                    PreprocessMetadata {
-                        index: self.index,
-                        col: self.col,
-                        line: self.line,
-                    },
-                );
-                self.pp_tokens.push(token);
+                let start = self.index;
                 _ = self.eat_next_char();
+                let token = PreprocessToken::Other(c, self.metadata_since(start));
+                self.pp_tokens.push(token);
+                self.leading_space = true;
                 continue;
             } else if c.is_whitespace() {
                 _ = self.eat_next_char();
+                self.leading_space = true;
+                continue;
+            } else if c == '/' && self.peek_offset_char(1) == Some('*') {
+                self.skip_block_comment()?;
+                self.leading_space = true;
+                continue;
+            } else if c == '/'
+                && self.peek_offset_char(1) == Some('/')
+                && self.standard >= Standard::C99
+            {
+                self.skip_line_comment();
+                self.leading_space = true;
                 continue;
             };
             self.pp_tokenize_next()?;
+            self.leading_space = false;
         }
 
         Ok(())
     }
 
     fn replace_newline_slashes(&mut self) {
-        self.source = self.source.replace("\\\n", "");
+        self.replace_sequences(|rest| rest.starts_with("\\\n").then_some(("", 2)));
     }
 
-    // This is so inefficient to be honest
     fn replace_trigraphs(&mut self) {
-        self.source = self
-            .source
-            .replace(r"??=", r"#")
-            .replace(r"??(", r"[")
-            .replace(r"??/", r"\") // this might be wrong
-            .replace(r"??)", r"]")
-            .replace(r"??'", r"^")
-            .replace(r"??<", r"{")
-            .replace(r"??!", r"|")
-            .replace(r"??>", r"}")
-            .replace(r"??-", r"~");
+        self.replace_sequences(|rest| {
+            let replacement = match rest.strip_prefix("??")?.chars().next()? {
+                '=' => "#",
+                '(' => "[",
+                '/' => "\\",
+                ')' => "]",
+                '\'' => "^",
+                '<' => "{",
+                '!' => "|",
+                '>' => "}",
+                '-' => "~",
+                _ => return None,
+            };
+            Some((replacement, 3))
+        });
     }
 
-    fn pp_tokenize_next(&mut self) -> CompResult<()> {
-        let next = self.peek_next_char().expect("Precondition");
+    // Rewrites the source in one pass, `replace` gets the remaining
+    // text and returns what to replace how many bytes of it with.
+    // Offsets into the original buffer are kept for every byte.
+    fn replace_sequences<F>(&mut self, replace: F)
+    where
+        F: Fn(&str) -> Option<(&'static str, usize)>,
+    {
+        let mut source = String::with_capacity(self.source.len());
+        let mut offsets = Vec::with_capacity(self.offsets.len());
+        let mut index = 0;
+        while let Some(c) = self.source[index..].chars().next() {
+            match replace(&self.source[index..]) {
+                Some((replacement, len)) => {
+                    let original = (self.offsets[index].0, self.offsets[index + len - 1].1);
+                    source.push_str(replacement);
+                    offsets.extend(std::iter::repeat_n(original, replacement.len()));
+                    index += len;
+                }
+                None => {
+                    source.push(c);
+                    offsets.extend_from_slice(&self.offsets[index..index + c.len_utf8()]);
+                    index += c.len_utf8();
+                }
+            }
+        }
+        self.source = source;
+        self.offsets = offsets;
+    }
 
-        if next == '\"' || next == '<' && self.pp_tokens.len() >= 2 {
-            if let Some(PreprocessToken::Identifier(ident, _)) = self.pp_tokens.last() {
-                if let Some(PreprocessToken::Punctuator(hash, _)) =
-                    self.pp_tokens.get(self.pp_tokens.len() - 2)
-                {
-                    if ident == "include" && hash == "#" {
-                        self.pp_tokenize_header_name()?;
-                        return Ok(());
-                    }
+    fn skip_block_comment(&mut self) -> CompResult<()> {
+        let start = self.index;
+
+        self.eat_chars(2);
+        loop {
+            match self.eat_next_char() {
+                Some('*') if self.peek_next_char() == Some('/') => {
+                    self.eat_next_char();
+                    return Ok(());
+                }
+                Some(_) => (),
+                None => {
+                    return Err(CompErrorBuilder::new()
+                        .code(ErrorCode::UnterminatedComment)
+                        .message("Expected `*/`, found end of source".into())
+                        .span(&self.buffer, self.span(start, start + 2))
+                        .highlight_message("Started here".into())
+                        .build());
                 }
             }
         }
+    }
+
+    // Up to the newline, which is left to end the line
+    fn skip_line_comment(&mut self) {
+        while self.peek_next_char().is_some_and(|c| c != '\n') {
+            self.eat_next_char();
+        }
+    }
+
+    // Header names are only tokens directly after `#include`,
+    // `#include_next` and `#embed`, or as the operand of
+    // `__has_include` and `__has_embed`
+    fn expecting_header_name(&self) -> bool {
+        let count = self.pp_tokens.len();
+        if count < 2 {
+            return false;
+        }
+        match (&self.pp_tokens[count - 2], &self.pp_tokens[count - 1]) {
+            (PreprocessToken::Punctuator(hash, _), PreprocessToken::Identifier(ident, _)) => {
+                hash == "#"
+                    && (ident == "include"
+                        || ident == "include_next"
+                        || (ident == "embed" && self.standard >= Standard::C23))
+            }
+            (PreprocessToken::Identifier(ident, _), paren) => {
+                (ident == "__has_include"
+                    || ident == "__has_include_next"
+                    || (ident == "__has_embed" && self.standard >= Standard::C23))
+                    && paren.is_punctuation("(")
+            }
+            _ => false,
+        }
+    }
+
+    fn pp_tokenize_next(&mut self) -> CompResult<()> {
+        let next = self.peek_next_char().expect("Precondition");
+
+        if (next == '\"' || next == '<') && self.expecting_header_name() {
+            self.pp_tokenize_header_name()?;
+            return Ok(());
+        }
 
         if next == '\'' {
             self.pp_tokenize_char_constant()?;
         } else if next == '\"' {
             self.pp_tokenize_string_literal()?;
-        } else if next.is_numeric() {
+        } else if next.is_ascii_digit()
+            || (next == '.' && self.peek_offset_char(1).is_some_and(|c| c.is_ascii_digit()))
+        {
             self.pp_tokenize_number();
         } else if self.is_identifier(next) {
             self.pp_tokenize_identifier();
@@ -158,9 +428,7 @@ impl<'a> Lexer {
     }
 
     fn pp_tokenize_header_name(&mut self) -> CompResult<()> {
-        let start_index = self.index;
-        let start_line = self.line;
-        let start_col = self.col;
+        let token_start = self.index;
 
         let begin = self.eat_next_char().expect("Precondition");
         assert!(begin == '\"' || begin == '<');
@@ -180,20 +448,11 @@ impl<'a> Lexer {
                     {
                         break;
                     }
+                    if c == '\n' {
+                        return Err(self.unterminated_header_name(token_start));
+                    }
                 }
-                None => {
-                    let expected = match header_type {
-                        HeaderNameType::Local => '\"',
-                        HeaderNameType::Included => '>',
-                    };
-                    return Err(CompErrorBuilder::new()
-                        .code(ErrorCode::UnterminatedHeaderName)
-                        .message(format!("Expected `{}`, found end of source", expected))
-                        .source(self.source.clone(), start_line)
-                        .highlight(start_col - 1, start_col)
-                        .highlight_message("Started here".into())
-                        .build());
-                }
+                None => return Err(self.unterminated_header_name(token_start)),
             };
         }
 
@@ -202,47 +461,97 @@ impl<'a> Lexer {
         self.pp_tokens.push(PreprocessToken::HeaderName(
             String::from(literal),
             header_type,
-            PreprocessMetadata {
-                index: start_index,
-                col: start_col,
-                line: start_line,
-            },
+            self.metadata_since(token_start),
         ));
 
         Ok(())
     }
 
+    fn unterminated_header_name(&self, start: usize) -> Box<CompError> {
+        let expected = if self.source[..self.index].ends_with('\n') {
+            "end of line"
+        } else {
+            "end of source"
+        };
+        CompErrorBuilder::new()
+            .code(ErrorCode::UnterminatedHeaderName)
+            .message(format!("Expected end of header name, found {}", expected))
+            .span(&self.buffer, self.span(start, start + 1))
+            .highlight_message("Started here".into())
+            .build()
+    }
+
+    // Reads a single, possibly escaped, character of a character
+    // constant or string literal
+    fn eat_literal_char(&mut self) -> Option<char> {
+        let c = self.eat_next_char()?;
+        if c != '\\' {
+            return Some(c);
+        }
+
+        let escaped = self.eat_next_char()?;
+        let value = match escaped {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\x07',
+            'b' => '\x08',
+            'v' => '\x0b',
+            'f' => '\x0c',
+            'x' => {
+                let mut value = 0u32;
+                while let Some(digit) = self.peek_next_char().and_then(|c| c.to_digit(16)) {
+                    value = value.wrapping_mul(16).wrapping_add(digit);
+                    self.eat_next_char();
+                }
+                char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+            '0'..='7' => {
+                let mut value = escaped.to_digit(8).expect("Octal digit");
+                for _ in 0..2 {
+                    match self.peek_next_char().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            self.eat_next_char();
+                        }
+                        None => break,
+                    }
+                }
+                char::from_u32(value).expect("Octal escapes are at most 0o777")
+            }
+            other => other,
+        };
+        Some(value)
+    }
+
     fn pp_tokenize_char_constant(&mut self) -> CompResult<()> {
-        let start_index = self.index;
-        let start_line = self.line;
-        let start_col = self.col;
+        let start = self.index;
 
         let begin = self.eat_next_char().expect("Precondition");
         assert_eq!(begin, '\'');
 
-        let literal = match self.eat_next_char() {
+        let literal_start = self.index;
+        let literal = match self.eat_literal_char() {
             Some(c) => c,
             None => {
                 return Err(CompErrorBuilder::new()
                     .code(ErrorCode::UnterminatedCharConstant)
                     .message("Expected character, found end of source".into())
-                    .source(self.source.clone(), start_line)
-                    .highlight(start_col - 1, start_col)
+                    .span(&self.buffer, self.span(start, start + 1))
                     .highlight_message("Started here".into())
                     .build())
             }
         };
 
-        let next_line = self.line;
-        let next_col = self.col;
+        let next = self.index;
+        let spelling = String::from(&self.source[literal_start..next]);
         match self.eat_next_char() {
             Some('\'') => (),
             Some(c) => {
                 return Err(CompErrorBuilder::new()
                     .code(ErrorCode::UnterminatedCharConstant)
                     .message(format!("Expected `\'`, found `{}`", c))
-                    .source(self.source.clone(), next_line)
-                    .highlight(start_col - 1, next_col)
+                    .span(&self.buffer, self.span(start, next))
                     .highlight_message("Invalid termination".into())
                     .build())
             }
@@ -250,8 +559,7 @@ impl<'a> Lexer {
                 return Err(CompErrorBuilder::new()
                     .code(ErrorCode::UnterminatedCharConstant)
                     .message("Expected `\'`, found end of source".into())
-                    .source(self.source.clone(), start_line)
-                    .highlight(start_col - 1, start_col)
+                    .span(&self.buffer, self.span(start, start + 1))
                     .highlight_message("Started here".into())
                     .build())
             }
@@ -259,38 +567,37 @@ impl<'a> Lexer {
 
         self.pp_tokens.push(PreprocessToken::CharacterConstant(
             literal,
-            PreprocessMetadata {
-                index: start_index,
-                col: start_col,
-                line: start_line,
-            },
+            spelling,
+            self.metadata_since(start),
         ));
 
         Ok(())
     }
 
     fn pp_tokenize_string_literal(&mut self) -> CompResult<()> {
-        let start_index = self.index;
-        let start_line = self.line;
-        let start_col = self.col;
+        let token_start = self.index;
 
         let begin = self.eat_next_char().expect("Precondition");
         assert_eq!(begin, '\"');
 
+        // The literal is kept with its escapes intact so that it
+        // can be spelled back out by the preprocessor
         let start = self.index;
         loop {
             match self.eat_next_char() {
                 Some('\"') => break,
-                Some(_) => (),
-                None => {
+                Some('\\') => {
+                    self.eat_next_char();
+                }
+                Some('\n') | None => {
                     return Err(CompErrorBuilder::new()
                         .code(ErrorCode::UnterminatedStringLiteral)
-                        .message("Expected character, found end of source".into())
-                        .source(self.source.clone(), start_line)
-                        .highlight(start_col - 1, start_col)
+                        .message("Expected `\"`, found end of line".into())
+                        .span(&self.buffer, self.span(token_start, token_start + 1))
                         .highlight_message("Started here".into())
                         .build())
                 }
+                Some(_) => (),
             };
         }
 
@@ -298,55 +605,51 @@ impl<'a> Lexer {
 
         self.pp_tokens.push(PreprocessToken::StringLiteral(
             String::from(literal),
-            PreprocessMetadata {
-                index: start_index,
-                col: start_col,
-                line: start_line,
-            },
+            self.metadata_since(token_start),
         ));
 
         Ok(())
     }
 
+    // Preprocessing numbers are deliberately loose, anything that
+    // starts like a number and continues with identifier characters,
+    // periods or signed exponents is one token. C99 added the binary
+    // exponents of hexadecimal floating constants, `0x1p-3`, and C23
+    // digit separators, `1'000`.
     fn pp_tokenize_number(&mut self) {
         let start_index = self.index;
-        let start_line = self.line;
-        let start_col = self.col;
 
         while let Some(c) = self.peek_next_char() {
-            if c.is_numeric() {
+            let exponent = matches!(c, 'e' | 'E')
+                || (matches!(c, 'p' | 'P') && self.standard >= Standard::C99);
+            let separator = c == '\''
+                && self.standard >= Standard::C23
+                && self
+                    .peek_offset_char(1)
+                    .is_some_and(|next| next.is_ascii_alphanumeric() || next == '_');
+            if (exponent && matches!(self.peek_offset_char(1), Some('+') | Some('-'))) || separator
+            {
+                self.eat_chars(2);
+            } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
                 _ = self.eat_next_char();
             } else {
                 break;
             }
         }
 
-        // TODO: Float literals
         let num_raw = &self.source[start_index..self.index];
 
-        /*
-        let num = num_raw
-            .parse::<i64>()
-            .expect("Should only ever be a number");
-        */
-
         self.pp_tokens.push(PreprocessToken::Number(
             String::from(num_raw),
-            PreprocessMetadata {
-                index: start_index,
-                line: start_line,
-                col: start_col,
-            },
+            self.metadata_since(start_index),
         ));
     }
 
     fn pp_tokenize_identifier(&mut self) {
         let start_index = self.index;
-        let start_col = self.col;
-        let start_line = self.line;
 
         while let Some(c) = self.peek_next_char() {
-            if self.is_identifier(c) {
+            if self.is_identifier(c) || c.is_ascii_digit() {
                 _ = self.eat_next_char();
             } else {
                 break;
@@ -357,76 +660,90 @@ impl<'a> Lexer {
 
         self.pp_tokens.push(PreprocessToken::Identifier(
             String::from(ident_raw),
-            PreprocessMetadata {
-                index: start_index,
-                col: start_col,
-                line: start_line,
-            },
+            self.metadata_since(start_index),
         ));
     }
 
     fn pp_tokenize_specials(&mut self) {
         let first = self.peek_next_char().expect("Precondition");
-        let highest: usize = if self.peek_offset_char(2).is_some() {
-            2
-        } else if self.peek_offset_char(1).is_some() {
-            1
-        } else {
-            0
-        };
-
-        let metadata = PreprocessMetadata {
-            index: self.index,
-            line: self.line,
-            col: self.col,
-        };
+        let start = self.index;
 
-        if first == '#' && self.col == 1 {
-            let raw = &self.source[self.index..(self.index + 1)];
-            self.pp_tokens
-                .push(PreprocessToken::Punctuator(raw.into(), metadata));
+        // A `#` that begins a line starts a directive
+        let line_start = self.pp_tokens.last().is_none_or(|t| t.is_newline());
+        if first == '#' && line_start && self.peek_offset_char(1) != Some('#') {
             self.eat_chars(1);
+            let metadata = self.metadata_since(start);
+            self.pp_tokens
+                .push(PreprocessToken::Punctuator("#".into(), metadata));
             return;
         };
 
-        // This is badly optimized
-        for i in (0..=highest).rev() {
-            let slice = &self.source[self.index..(self.index + i)];
-            if OPERATOR_MAP.get(slice).is_some() {
-                self.pp_tokens
-                    .push(PreprocessToken::Operator(slice.into(), metadata));
+        // Longest match first, nothing is longer than 3 characters
+        let remaining = &self.source[self.index..];
+        for i in (1..=3).rev() {
+            let Some(slice) = remaining.get(..i) else {
+                continue;
+            };
+            let slice = slice.to_string();
+            // `::` is only a token since C23, before it's two colons
+            if slice == "::" && self.standard < Standard::C23 {
+                continue;
+            }
+            if OPERATOR_MAP.get(slice.as_str()).is_some() {
                 self.eat_chars(i);
-                return;
-            } else if PUNCTUATOR_MAP.get(slice).is_some() {
+                let metadata = self.metadata_since(start);
                 self.pp_tokens
-                    .push(PreprocessToken::Punctuator(slice.into(), metadata));
+                    .push(PreprocessToken::Operator(slice, metadata));
+                return;
+            } else if PUNCTUATOR_MAP.get(slice.as_str()).is_some() {
                 self.eat_chars(i);
+                let metadata = self.metadata_since(start);
+                self.pp_tokens
+                    .push(PreprocessToken::Punctuator(slice, metadata));
                 return;
             }
         }
 
-        self.pp_tokens.push(PreprocessToken::Other(first, metadata));
         self.eat_next_char();
+        let metadata = self.metadata_since(start);
+        self.pp_tokens.push(PreprocessToken::Other(first, metadata));
+    }
+
+    // Span in the original buffer of the bytes from `start` up to
+    // `end` in the lexed source
+    fn span(&self, start: usize, end: usize) -> Span {
+        let lo = self
+            .offsets
+            .get(start)
+            .map_or(self.buffer.text().len(), |offset| offset.0);
+        let hi = match end.min(self.offsets.len()) {
+            end if end > start => self.offsets[end - 1].1,
+            _ => lo,
+        };
+        Span::new(self.buffer.id(), lo, hi)
+    }
+
+    // Metadata for a token that started at `start` and ends here
+    fn metadata_since(&self, start: usize) -> PreprocessMetadata {
+        PreprocessMetadata {
+            span: self.span(start, self.index),
+            leading_space: self.leading_space,
+            expansion: None,
+        }
     }
 
     fn peek_next_char(&self) -> Option<char> {
-        self.source.chars().nth(self.index)
+        self.source[self.index..].chars().next()
     }
 
-    fn peek_offset_char(&self, index: usize) -> Option<char> {
-        self.source.chars().nth(self.index + index)
+    fn peek_offset_char(&self, offset: usize) -> Option<char> {
+        self.source[self.index..].chars().nth(offset)
     }
 
     fn eat_next_char(&mut self) -> Option<char> {
-        let maybe_char = self.source.chars().nth(self.index);
-        self.index += 1;
+        let maybe_char = self.peek_next_char();
         if let Some(c) = maybe_char {
-            if c == '\n' {
-                self.line += 1;
-                self.col = 1;
-            } else {
-                self.col += 1;
-            }
+            self.index += c.len_utf8();
         }
         maybe_char
     }
@@ -446,3 +763,189 @@ impl<'a> Lexer {
         Some(*value)
     }
 }
+
+// Translation phases 6 and 7, adjacent string literals are joined and
+// preprocessing tokens become tokens
+pub fn convert_tokens(
+    sources: &SourceManager,
+    pp_tokens: Vec<PreprocessToken>,
+    standard: Standard,
+    gnu: bool,
+) -> CompResult<Vec<Token>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    for pp_token in pp_tokens {
+        let token = match pp_token {
+            PreprocessToken::Identifier(name, meta) => {
+                if let Some(&keyword) = GNU_KEYWORD_ALIASES.get(name.as_str()) {
+                    tokens.push(Token::Keyword(keyword, meta));
+                    continue;
+                }
+                let keyword = KEYWORD_MAP.get(name.as_str()).or_else(|| {
+                    C23_KEYWORD_ALIASES
+                        .get(name.as_str())
+                        .filter(|_| standard >= Standard::C23)
+                });
+                // GNU C has had `typeof` long before C23
+                match keyword {
+                    Some(&keyword)
+                        if keyword_standard(keyword) <= standard
+                            || (gnu && keyword == Keyword::Typeof) =>
+                    {
+                        Token::Keyword(keyword, meta)
+                    }
+                    _ => Token::Identifier(name, meta),
+                }
+            }
+            PreprocessToken::Number(ref number, meta) => {
+                let constant = convert_number(number, standard).ok_or_else(|| {
+                    sources
+                        .error_at(&meta, ErrorCode::InvalidConstant)
+                        .message(format!("`{}` is not a valid constant", number))
+                        .build()
+                })?;
+                let hex = number.starts_with("0x") || number.starts_with("0X");
+                if hex && matches!(constant, Constant::Floating(..)) && standard < Standard::C99 {
+                    return Err(sources
+                        .error_at(&meta, ErrorCode::NewerStandardRequired)
+                        .message(format!(
+                            "A hexadecimal floating constant requires {} or later",
+                            Standard::C99.name()
+                        ))
+                        .build());
+                }
+                Token::Constant(constant, meta)
+            }
+            PreprocessToken::CharacterConstant(c, _, meta) => {
+                Token::Constant(Constant::Character(c), meta)
+            }
+            PreprocessToken::StringLiteral(literal, meta) => {
+                if let Some(Token::StringLiteral(previous, previous_meta)) = tokens.last_mut() {
+                    previous.push_str(&literal);
+                    previous_meta.span = previous_meta.span.to(meta.span);
+                    continue;
+                }
+                Token::StringLiteral(literal, meta)
+            }
+            PreprocessToken::Operator(ref spelling, meta)
+            | PreprocessToken::Punctuator(ref spelling, meta) => {
+                match OPERATOR_MAP.get(spelling.as_str()) {
+                    Some(op) => Token::Operator(*op, meta),
+                    None => Token::Punctuator(PUNCTUATOR_MAP[spelling.as_str()], meta),
+                }
+            }
+            PreprocessToken::HeaderName(..) | PreprocessToken::Other(..) => {
+                return Err(sources
+                    .error_at(pp_token.metadata(), ErrorCode::StrayCharacter)
+                    .message(format!("Stray `{}` in program", pp_token.spelling()))
+                    .build())
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// Preprocessing numbers that aren't integer or floating constants,
+// like `1.2.3` or `0x`, give `None`. C23 added binary constants, and
+// digit separators which can only go between digits.
+fn convert_number(number: &str, standard: Standard) -> Option<Constant> {
+    if number.contains("''") || number.ends_with('\'') {
+        return None;
+    }
+    let lower = number.replace('\'', "").to_ascii_lowercase();
+    let hex = lower.starts_with("0x");
+    let binary = lower.starts_with("0b") && standard >= Standard::C23;
+    if number[2.min(number.len())..].starts_with('\'') && (hex || binary) {
+        return None;
+    }
+    let floating = if hex {
+        lower.contains(['.', 'p'])
+    } else {
+        lower.contains(['.', 'e'])
+    };
+
+    if floating {
+        let (digits, kind) = match lower.strip_suffix(['f', 'l']) {
+            Some(digits) if lower.ends_with('f') => (digits, FloatingKind::Float),
+            Some(digits) => (digits, FloatingKind::LongDouble),
+            None => (lower.as_str(), FloatingKind::Double),
+        };
+        let value = match hex {
+            true => convert_hex_float(&digits[2..]),
+            false => digits.parse().ok(),
+        };
+        return value.map(|value| Constant::Floating(value, kind));
+    }
+
+    let digits_end = lower.find(['u', 'l']).unwrap_or(lower.len());
+    let (digits, suffix) = lower.split_at(digits_end);
+    if !matches!(suffix, "" | "u" | "l" | "ul" | "lu" | "ll" | "ull" | "llu") {
+        return None;
+    }
+    let value = if hex {
+        u64::from_str_radix(&digits[2..], 16)
+    } else if binary {
+        u64::from_str_radix(&digits[2..], 2)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    let value = value.ok()?;
+    let kind = integer_kind(value, suffix, !hex && !binary && !digits.starts_with('0'));
+    Some(Constant::Integer(value as i64, kind))
+}
+
+// The first type that the suffix allows and that can hold the value.
+// One too big for any of them is `unsigned long long`, like GCC has it.
+fn integer_kind(value: u64, suffix: &str, decimal: bool) -> IntegerKind {
+    use IntegerKind::*;
+    let unsigned = suffix.contains('u');
+    let least = match suffix.trim_matches('u') {
+        "ll" => LongLong,
+        "l" => Long,
+        _ => Int,
+    };
+    [
+        Int,
+        UnsignedInt,
+        Long,
+        UnsignedLong,
+        LongLong,
+        UnsignedLongLong,
+    ]
+    .into_iter()
+    .filter(|&kind| kind >= least)
+    .filter(|kind| match kind.is_unsigned() {
+        true => unsigned || !decimal,
+        false => !unsigned,
+    })
+    .find(|&kind| {
+        let size = match kind {
+            Int | UnsignedInt => target::INT_SIZE,
+            Long | UnsignedLong => target::LONG_SIZE,
+            LongLong | UnsignedLongLong => target::LONG_LONG_SIZE,
+        };
+        match kind.is_unsigned() {
+            true => value <= target::unsigned_max(size),
+            false => value <= target::signed_max(size),
+        }
+    })
+    .unwrap_or(UnsignedLongLong)
+}
+
+// Hexadecimal digits with an optional point, then a binary exponent
+// that can't be left out, like `1.8p3`
+fn convert_hex_float(digits: &str) -> Option<f64> {
+    let (mantissa, exponent) = digits.split_once('p')?;
+    let exponent: i32 = exponent.parse().ok()?;
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    let mut value = 0.0;
+    for c in whole.chars().chain(fraction.chars()) {
+        value = value * 16.0 + f64::from(c.to_digit(16)?);
+    }
+    Some(value * 2f64.powi(exponent - 4 * fraction.len() as i32))
+}
@@ -1,18 +1,26 @@
 #![allow(dead_code)]
 
-use lexer::Lexer;
+use driver::Options;
 
+mod ast;
 mod comp_error;
+mod depfile;
+mod driver;
+mod headers;
+#[cfg(feature = "json")]
+mod json;
 mod lang;
 mod lexer;
+mod parser;
+mod preprocessor;
+mod source;
+mod target;
+mod types;
 
 fn main() {
-    let test_src = include_str!("../tests/big_test.c");
-    let mut lexer = Lexer::new(test_src);
-    match lexer.tokenize() {
-        Ok(_) => println!("Success"),
-        Err(err) => print!("{}", err),
+    let result = Options::parse(std::env::args().skip(1)).and_then(|options| driver::run(&options));
+    if let Err(err) = result {
+        eprint!("{}", err);
+        std::process::exit(1);
     }
-
-    println!("{:#?}", lexer);
 }
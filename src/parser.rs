@@ -0,0 +1,2263 @@
+use std::ops::Range;
+
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::{self, Constant, Keyword, Operator, Punctuator, Standard};
+use crate::lexer::Token;
+use crate::source::{SourceManager, Span};
+use crate::target;
+use crate::types::{ArraySize, CType, TypeKind, TypeTable};
+
+mod check;
+mod constant;
+mod expression;
+mod initializer;
+mod lower;
+mod scope;
+mod statement;
+
+use scope::{Defined, Linkage, ScopeKind, SymbolTable};
+use statement::Enclosing;
+
+// What kind of declarator is expected. Parameters may or may not
+// have a name, type names never do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DeclaratorMode {
+    Named,
+    Abstract,
+    Either,
+}
+
+// What an ordinary identifier in scope was declared as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    // An object or function of the type
+    Object(CType),
+    // An object declared `register`, which has no address
+    Register(CType),
+    // An object declared `static` in a block, which lives as long as
+    // the program does
+    Static(CType),
+    Typedef(CType),
+    Enumerator(i64),
+    // A `constexpr` object of integer type, which can be used in
+    // integer constant expressions
+    Constexpr(CType, i64),
+}
+
+pub struct Parser<'a> {
+    sources: &'a SourceManager,
+    tokens: Vec<Token>,
+    index: usize,
+    standard: Standard,
+    // Whether GNU extensions are allowed, with `-std=gnu17` and so on
+    gnu: bool,
+    // The return type of the function whose body is being parsed.
+    // Statement expressions can only be in a function body.
+    returns: Option<CType>,
+    // The loops and `switch` statements around the statement being
+    // parsed, innermost last
+    enclosing: Vec<Enclosing>,
+    // Names of each scope that is open. An identifier that's a typedef
+    // name starts a declaration rather than an expression, unless an
+    // inner scope declares it as something else.
+    symbols: SymbolTable,
+    types: TypeTable,
+    errors: Vec<CompError>,
+    // Token the last error was reported at, so that one mistake isn't
+    // reported again by whatever tries to parse the same token next
+    last_error_index: Option<usize>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(
+        sources: &'a SourceManager,
+        tokens: Vec<Token>,
+        standard: Standard,
+        gnu: bool,
+    ) -> Self {
+        Self {
+            sources,
+            tokens,
+            index: 0,
+            standard,
+            gnu,
+            returns: None,
+            enclosing: Vec::new(),
+            symbols: SymbolTable::new(),
+            types: TypeTable::new(),
+            errors: Vec::new(),
+            last_error_index: None,
+        }
+    }
+
+    // Parses as much as it can, syntax errors are collected rather than
+    // stopping the parse and the tokens they cover become error nodes
+    pub fn parse_translation_unit(&mut self) -> TranslationUnit {
+        let start = self.current_span();
+        let mut declarations = Vec::new();
+        while self.peek().is_some() {
+            let item_start = self.index;
+            let declaration = match self.external_declaration() {
+                Ok(declaration) => declaration,
+                Err(error) => {
+                    let span = self.recover(*error, item_start);
+                    ExternalDeclaration::Error(span)
+                }
+            };
+            declarations.push(declaration);
+        }
+        self.finish_tentative_definitions();
+        TranslationUnit {
+            declarations,
+            span: self.span_from(start),
+        }
+    }
+
+    pub fn types(&self) -> &TypeTable {
+        &self.types
+    }
+
+    pub fn errors(&self) -> &[CompError] {
+        &self.errors
+    }
+
+    pub fn take_errors(&mut self) -> Vec<CompError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    fn external_declaration(&mut self) -> CompResult<ExternalDeclaration> {
+        let start = self.current_span();
+        if self.peek_keyword() == Some(Keyword::StaticAssert) {
+            return Ok(ExternalDeclaration::StaticAssert(self.static_assert()?));
+        }
+        // Old code leaves out the specifiers entirely, `main() { ... }`
+        let specifiers = if self.missing_specifiers_follow() {
+            self.implicit_int("The declaration", start);
+            DeclarationSpecifiers {
+                specifiers: Vec::new(),
+                ty: CType::INT,
+                span: Span {
+                    hi: start.lo,
+                    ..start
+                },
+            }
+        } else {
+            self.declaration_specifiers()?
+        };
+        self.check_external_storage(&specifiers);
+        if self.eat_punctuator(Punctuator::Semicolon) {
+            return Ok(ExternalDeclaration::Declaration(Declaration {
+                specifiers,
+                declarators: Vec::new(),
+                span: self.span_from(start),
+            }));
+        }
+
+        let declarator = self.declarator(DeclaratorMode::Named)?;
+        let is_function = matches!(
+            declarator.derivations().first(),
+            Some(Derivation::Function { .. })
+        );
+        // The parameter declarations of an old-style definition come
+        // before its body
+        let old_style = declarator.identifier_list().is_some() && self.declaration_follows();
+        if is_function && (self.peek_punctuator(Punctuator::LCurly) || old_style) {
+            return self.function_definition(start, specifiers, declarator);
+        }
+
+        let declaration = self.init_declarators(start, specifiers, declarator)?;
+        Ok(ExternalDeclaration::Declaration(declaration))
+    }
+
+    fn function_definition(
+        &mut self,
+        start: Span,
+        specifiers: DeclarationSpecifiers,
+        declarator: Declarator,
+    ) -> CompResult<ExternalDeclaration> {
+        self.check_function_specifiers(&specifiers, Some(&declarator));
+        let ty = self.declared_type(specifiers.ty, &declarator);
+        if let Some(name) = declarator.name() {
+            let linkage = self.linkage(&name.name, &specifiers, ty);
+            self.declare(name, Binding::Object(ty), linkage, Defined::Yes);
+        }
+        // The parameters are in the same scope as the outermost block
+        // of the body
+        self.push_scope(ScopeKind::Block);
+        let result = self.function_body(&declarator, ty);
+        self.pop_scope();
+        self.check_labels();
+        let (declarations, body) = result?;
+        Ok(ExternalDeclaration::Function(FunctionDefinition {
+            specifiers,
+            declarator,
+            declarations,
+            body,
+            span: self.span_from(start),
+        }))
+    }
+
+    // The parameters are in scope in the body, along with the function's
+    // name as `__func__` since C99
+    fn function_body(
+        &mut self,
+        declarator: &Declarator,
+        ty: CType,
+    ) -> CompResult<(Vec<Declaration>, CompoundStatement)> {
+        if let Some(Derivation::Function { parameters, .. }) = declarator.derivations().first() {
+            for parameter in parameters.iter() {
+                if let Some(name) = parameter.declarator.name() {
+                    let binding = self.object_binding(&parameter.specifiers, parameter.ty);
+                    self.declare(name, binding, Linkage::None, Defined::Yes);
+                }
+            }
+        }
+        let declarations = match declarator.identifier_list() {
+            Some(identifiers) => self.old_style_parameters(identifiers)?,
+            None => Vec::new(),
+        };
+        if self.standard >= Standard::C99 {
+            let name = declarator.name().map_or("", |name| &name.name);
+            let ty = self.func_type(name);
+            self.declare_implicit("__func__", Binding::Static(ty), declarator.span);
+        }
+        self.returns = match *self.types.kind(ty) {
+            TypeKind::Function { returns, .. } => Some(returns),
+            _ => Some(CType::ERROR),
+        };
+        let body = self.block();
+        self.returns = None;
+        Ok((declarations, body?))
+    }
+
+    // The declarations between an old-style declarator and the body can
+    // only declare the parameters, and the parameters they leave out are
+    // `int`
+    fn old_style_parameters(&mut self, identifiers: &[Identifier]) -> CompResult<Vec<Declaration>> {
+        let mut declarations = Vec::new();
+        // Declaring a parameter twice is reported by its scope
+        let mut declared: Vec<&str> = Vec::new();
+        while self.declaration_follows() {
+            let declaration = self.declaration()?;
+            for init in &declaration.declarators {
+                let Some(name) = init.declarator.name() else {
+                    continue;
+                };
+                let message = if !identifiers.iter().any(|id| id.name == name.name) {
+                    format!("`{}` is not a parameter", name.name)
+                } else if init.initializer.is_some() {
+                    format!("Parameter `{}` can't be initialized", name.name)
+                } else {
+                    let identifier = identifiers.iter().find(|id| id.name == name.name);
+                    declared.extend(identifier.map(|id| id.name.as_str()));
+                    continue;
+                };
+                let error = self
+                    .error_at_span(name.span, ErrorCode::InvalidDeclaration)
+                    .message(message)
+                    .build();
+                self.report(*error);
+            }
+            declarations.push(declaration);
+        }
+        for identifier in identifiers {
+            if !declared.contains(&identifier.name.as_str()) {
+                let what = format!("Parameter `{}`", identifier.name);
+                self.implicit_int(&what, identifier.span);
+                let binding = Binding::Object(CType::INT);
+                self.declare_implicit(&identifier.name, binding, identifier.span);
+            }
+        }
+        Ok(declarations)
+    }
+
+    // A declaration inside of a block
+    fn declaration(&mut self) -> CompResult<Declaration> {
+        let start = self.current_span();
+        let specifiers = self.declaration_specifiers()?;
+        // `_Thread_local` objects have static storage, so in a block they
+        // have to be declared `static` or `extern` as well
+        if let Some(spec) = specifiers.specifiers.iter().find(|spec| {
+            spec.kind == DeclarationSpecifierKind::StorageClass(StorageClass::ThreadLocal)
+        }) {
+            let has_linkage = specifiers
+                .storage_classes()
+                .any(|class| matches!(class, StorageClass::Static | StorageClass::Extern));
+            if !has_linkage {
+                let error = self
+                    .error_at_span(spec.span, ErrorCode::InvalidDeclarationSpecifiers)
+                    .message("`_Thread_local` in a block needs `static` or `extern`".into())
+                    .build();
+                self.report(*error);
+            }
+        }
+        if self.eat_punctuator(Punctuator::Semicolon) {
+            return Ok(Declaration {
+                specifiers,
+                declarators: Vec::new(),
+                span: self.span_from(start),
+            });
+        }
+        let declarator = self.declarator(DeclaratorMode::Named)?;
+        self.init_declarators(start, specifiers, declarator)
+    }
+
+    // The rest of a declaration after its first declarator
+    fn init_declarators(
+        &mut self,
+        start: Span,
+        specifiers: DeclarationSpecifiers,
+        first: Declarator,
+    ) -> CompResult<Declaration> {
+        let mut declarators = Vec::new();
+        let mut declarator = first;
+        let typedef = specifiers.is_typedef();
+        let constexpr = specifiers
+            .storage_classes()
+            .any(|class| class == StorageClass::Constexpr);
+        // C23 infers the type of `auto x = 1;` from the initializer
+        let inferred = self.standard >= Standard::C23
+            && !has_type_specifier(&specifiers.specifiers)
+            && specifiers
+                .storage_classes()
+                .any(|class| matches!(class, StorageClass::Auto | StorageClass::Constexpr));
+        loop {
+            self.check_function_specifiers(&specifiers, Some(&declarator));
+            self.check_identifier_list(&declarator);
+            // A name is in scope from the end of its declarator, so
+            // the initializer can already see it
+            let ty = match inferred {
+                true => CType::ERROR,
+                false => {
+                    let ty = self.declared_type(specifiers.ty, &declarator);
+                    let ty = self.aligned_declaration(&specifiers, &declarator, ty);
+                    self.check_declared_type(&specifiers, &declarator, ty)
+                }
+            };
+            if let Some(name) = declarator.name() {
+                let (binding, linkage) = match typedef {
+                    true => (Binding::Typedef(ty), Linkage::None),
+                    false => (
+                        self.object_binding(&specifiers, ty),
+                        self.linkage(&name.name, &specifiers, ty),
+                    ),
+                };
+                let defined = self.defined(&specifiers, ty);
+                self.declare(name, binding, linkage, defined);
+            }
+            let initializer = if self.eat_operator(Operator::Equals) {
+                Some(self.initializer()?)
+            } else {
+                None
+            };
+            let (ty, initializer) = match inferred {
+                true => {
+                    self.check_inferred(&declarator, initializer.as_ref(), declarators.is_empty());
+                    self.inferred_type(&declarator, initializer)
+                }
+                false => match initializer {
+                    Some(initializer) => self.initialized_type(&declarator, ty, initializer),
+                    None => (ty, None),
+                },
+            };
+            if constexpr {
+                let integer = self.types.is_integer(ty);
+                self.constexpr_object(&declarator, initializer.as_ref(), ty, integer);
+            } else if let (Some(initializer), false) = (&initializer, typedef) {
+                self.check_initialized(&specifiers, &declarator, initializer);
+            }
+            declarators.push(InitDeclarator {
+                span: self.span_from(declarator.span),
+                declarator,
+                initializer,
+            });
+
+            if !self.eat_operator(Operator::Comma) {
+                break;
+            }
+            declarator = self.declarator(DeclaratorMode::Named)?;
+        }
+        self.expect_punctuator(Punctuator::Semicolon)?;
+
+        Ok(Declaration {
+            specifiers,
+            declarators,
+            span: self.span_from(start),
+        })
+    }
+
+    // An inferred type comes from an expression, so there's one plain
+    // name and an initializer that isn't a list
+    fn check_inferred(
+        &mut self,
+        declarator: &Declarator,
+        initializer: Option<&Initializer>,
+        first: bool,
+    ) {
+        let message = if !first {
+            "A declaration with an inferred type can only declare one name"
+        } else if !declarator.derivations().is_empty() {
+            "A declaration with an inferred type can't have pointer, array or function declarators"
+        } else if !matches!(initializer, Some(Initializer::Expression(_))) {
+            "A declaration with an inferred type needs an expression to initialize it"
+        } else {
+            return;
+        };
+        let error = self
+            .error_at_span(declarator.span, ErrorCode::InvalidDeclaration)
+            .message(message.to_string())
+            .build();
+        self.report(*error);
+    }
+
+    // An array of unknown size is completed by its initializer, and the
+    // name is rebound to the array with the size
+    fn initialized_type(
+        &mut self,
+        declarator: &Declarator,
+        ty: CType,
+        initializer: Initializer,
+    ) -> (CType, Option<Initializer>) {
+        let (sized, initializer) = self.initialize(ty, initializer);
+        if let (Some(name), true) = (declarator.name(), sized != ty) {
+            let binding = match self.lookup(&name.name) {
+                Some(Binding::Register(_)) => Binding::Register(sized),
+                Some(Binding::Static(_)) => Binding::Static(sized),
+                _ => Binding::Object(sized),
+            };
+            self.rebind(name, binding);
+        }
+        (sized, Some(initializer))
+    }
+
+    // The name declared with an inferred type is rebound to the type of
+    // its initializer once that has been parsed
+    fn inferred_type(
+        &mut self,
+        declarator: &Declarator,
+        initializer: Option<Initializer>,
+    ) -> (CType, Option<Initializer>) {
+        let Some(Initializer::Expression(expression)) = initializer else {
+            return (CType::ERROR, initializer);
+        };
+        let expression = self.value(expression);
+        let ty = expression.ty;
+        if let Some(name) = declarator.name() {
+            self.rebind(name, Binding::Object(ty));
+        }
+        (ty, Some(Initializer::Expression(expression)))
+    }
+
+    // A name declared `extern` in a block refers to an object defined
+    // elsewhere, and an object with static storage is initialized before
+    // the program starts, so only with constants and addresses
+    fn check_initialized(
+        &mut self,
+        specifiers: &DeclarationSpecifiers,
+        declarator: &Declarator,
+        initializer: &Initializer,
+    ) {
+        let name = declarator.name().map_or("", |name| &name.name);
+        let file_scope = self.symbols.kind() == ScopeKind::File;
+        let mut classes = specifiers.storage_classes();
+        let message = match classes.find(|class| {
+            matches!(
+                class,
+                StorageClass::Extern | StorageClass::Static | StorageClass::ThreadLocal
+            )
+        }) {
+            Some(StorageClass::Extern) if !file_scope => format!(
+                "`{}` is declared `extern` in a block, so it can't be initialized",
+                name
+            ),
+            Some(_) if self.constant_initializer(initializer, true) => return,
+            None if file_scope && self.constant_initializer(initializer, true) => return,
+            None if !file_scope => return,
+            _ => format!(
+                "The initializer of `{}` isn't a constant, which an object with static storage needs",
+                name
+            ),
+        };
+        let error = self
+            .error_at_span(initializer.span(), ErrorCode::InvalidInitializer)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+    }
+
+    // A `constexpr` object has to be initialized with a constant that
+    // its type can hold exactly, and one of integer type is then an
+    // integer constant
+    fn constexpr_object(
+        &mut self,
+        declarator: &Declarator,
+        initializer: Option<&Initializer>,
+        ty: CType,
+        integer: bool,
+    ) {
+        let Some(initializer) = initializer else {
+            let name = declarator.name().map_or("", |name| &name.name);
+            let error = self
+                .error_at_span(declarator.span, ErrorCode::InvalidDeclaration)
+                .message(format!(
+                    "`constexpr` object `{}` needs an initializer",
+                    name
+                ))
+                .build();
+            self.report(*error);
+            return;
+        };
+        let name = declarator.name().map_or("", |name| &name.name);
+        let message = match *initializer {
+            Initializer::Expression(ref expression) if integer => {
+                match self.evaluate_constant(expression) {
+                    Some(value) if self.converts_exactly(expression, value) => {
+                        if let Some(name) = declarator.name() {
+                            self.rebind(name, Binding::Constexpr(ty, value));
+                        }
+                        return;
+                    }
+                    Some(_) => format!(
+                        "The initializer of `constexpr` object `{}` doesn't fit in `{}`",
+                        name,
+                        self.types.display(ty)
+                    ),
+                    None => format!(
+                        "The initializer of `constexpr` object `{}` isn't an integer constant expression",
+                        name
+                    ),
+                }
+            }
+            _ if self.constant_initializer(initializer, false) => return,
+            _ => format!(
+                "The initializer of `constexpr` object `{}` isn't a constant expression",
+                name
+            ),
+        };
+        let error = self
+            .error_at_span(initializer.span(), ErrorCode::InvalidConstantExpression)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+    }
+
+    fn declaration_specifiers(&mut self) -> CompResult<DeclarationSpecifiers> {
+        let start = self.current_span();
+        let mut specifiers: Vec<DeclarationSpecifier> = Vec::new();
+
+        while let Some(token) = self.peek() {
+            if self.attributes_follow() {
+                let attributes_start = self.current_span();
+                let attributes = self.attribute_specifiers()?;
+                self.check_fallthrough(&attributes, false);
+                if !attributes.is_empty() {
+                    specifiers.push(DeclarationSpecifier {
+                        kind: DeclarationSpecifierKind::Attributes(attributes),
+                        span: self.span_from(attributes_start),
+                    });
+                }
+                continue;
+            }
+            let span = token.span();
+            let kind = match *token {
+                Token::Keyword(keyword, _) => {
+                    // `_Atomic` followed by `(` is always a type specifier
+                    if keyword == Keyword::Atomic
+                        && self
+                            .peek_nth(1)
+                            .is_some_and(|token| token.is_operator(Operator::LParen))
+                    {
+                        let specifier = self.atomic_specifier()?;
+                        specifiers.push(DeclarationSpecifier {
+                            span: specifier.span,
+                            kind: DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Atomic(
+                                specifier,
+                            )),
+                        });
+                        continue;
+                    }
+                    if let Some(class) = storage_class(keyword) {
+                        DeclarationSpecifierKind::StorageClass(class)
+                    } else if let Some(qualifier) = type_qualifier(keyword) {
+                        DeclarationSpecifierKind::TypeQualifier(qualifier)
+                    } else if let Some(specifier) = basic_type_specifier(keyword) {
+                        DeclarationSpecifierKind::TypeSpecifier(specifier)
+                    } else if let Some(specifier) = function_specifier(keyword) {
+                        DeclarationSpecifierKind::FunctionSpecifier(specifier)
+                    } else if keyword == Keyword::Extension {
+                        // Only there to silence warnings about extensions
+                        self.index += 1;
+                        continue;
+                    } else if keyword == Keyword::Alignas {
+                        let specifier = self.alignment_specifier()?;
+                        specifiers.push(specifier);
+                        continue;
+                    } else if matches!(keyword, Keyword::Struct | Keyword::Union) {
+                        let specifier = self.struct_specifier()?;
+                        specifiers.push(DeclarationSpecifier {
+                            span: specifier.span,
+                            kind: DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Struct(
+                                specifier,
+                            )),
+                        });
+                        continue;
+                    } else if matches!(keyword, Keyword::Typeof | Keyword::TypeofUnqual) {
+                        let specifier = self.typeof_specifier()?;
+                        specifiers.push(DeclarationSpecifier {
+                            span: specifier.span,
+                            kind: DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Typeof(
+                                specifier,
+                            )),
+                        });
+                        continue;
+                    } else if keyword == Keyword::Enum {
+                        let specifier = self.enum_specifier()?;
+                        specifiers.push(DeclarationSpecifier {
+                            span: specifier.span,
+                            kind: DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Enum(
+                                specifier,
+                            )),
+                        });
+                        continue;
+                    } else {
+                        break;
+                    }
+                }
+                // A typedef name is only a type if there isn't one yet,
+                // otherwise it's the name being declared
+                Token::Identifier(ref name, _)
+                    if self.is_typedef_name(name) && !has_type_specifier(&specifiers) =>
+                {
+                    DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::TypedefName(
+                        name.clone(),
+                    ))
+                }
+                _ => break,
+            };
+            self.index += 1;
+            specifiers.push(DeclarationSpecifier { kind, span });
+        }
+
+        if specifiers.is_empty() {
+            return Err(self.error_expected("declaration specifiers"));
+        }
+        let mut specifiers = DeclarationSpecifiers {
+            specifiers,
+            ty: CType::ERROR,
+            span: self.span_from(start),
+        };
+        self.check_specifiers(&specifiers)?;
+        specifiers.ty = self.specified_type(&specifiers);
+        Ok(specifiers)
+    }
+
+    // `_Alignas(T)` or `_Alignas(N)`
+    fn alignment_specifier(&mut self) -> CompResult<DeclarationSpecifier> {
+        let start = self.current_span();
+        self.expect_keyword(Keyword::Alignas)?;
+        let specifier = if self.parenthesized_type_follows() {
+            self.index += 1;
+            AlignmentSpecifier::Type(self.type_name()?)
+        } else {
+            self.expect_operator(Operator::LParen)?;
+            AlignmentSpecifier::Expression(self.conditional_expression()?)
+        };
+        self.expect_operator(Operator::RParen)?;
+        Ok(DeclarationSpecifier {
+            kind: DeclarationSpecifierKind::AlignmentSpecifier(specifier),
+            span: self.span_from(start),
+        })
+    }
+
+    // `typeof(T)` or `typeof(expression)`, or `typeof_unqual`
+    fn typeof_specifier(&mut self) -> CompResult<TypeofSpecifier> {
+        let start = self.current_span();
+        let unqualified = self.peek_keyword() == Some(Keyword::TypeofUnqual);
+        self.index += 1;
+        let operand = if self.parenthesized_type_follows() {
+            self.index += 1;
+            TypeofOperand::Type(Box::new(self.type_name()?))
+        } else {
+            self.expect_operator(Operator::LParen)?;
+            TypeofOperand::Expression(Box::new(self.expression()?))
+        };
+        self.expect_operator(Operator::RParen)?;
+        Ok(TypeofSpecifier {
+            unqualified,
+            operand,
+            span: self.span_from(start),
+        })
+    }
+
+    // `_Atomic(T)`
+    fn atomic_specifier(&mut self) -> CompResult<AtomicSpecifier> {
+        let start = self.current_span();
+        self.expect_keyword(Keyword::Atomic)?;
+        self.expect_operator(Operator::LParen)?;
+        let type_name = Box::new(self.type_name()?);
+        self.expect_operator(Operator::RParen)?;
+        Ok(AtomicSpecifier {
+            type_name,
+            span: self.span_from(start),
+        })
+    }
+
+    // Whether `__attribute__` follows, or in C23 `[[`
+    fn attributes_follow(&self) -> bool {
+        self.peek_keyword() == Some(Keyword::Attribute) || self.standard_attributes_follow()
+    }
+
+    // `[[` always starts attributes in C23
+    fn standard_attributes_follow(&self) -> bool {
+        self.standard >= Standard::C23
+            && self.peek_operator(Operator::LBracket)
+            && self
+                .peek_nth(1)
+                .is_some_and(|token| token.is_operator(Operator::LBracket))
+    }
+
+    // Index of the first token after any attributes and `__extension__`
+    // starting at `index`, for looking past them to what they belong to
+    fn skip_attributes(&self, mut index: usize) -> usize {
+        let is_operator = |index: usize, operator| {
+            self.tokens
+                .get(index)
+                .is_some_and(|token| token.is_operator(operator))
+        };
+        loop {
+            let (open, close) = match self.tokens.get(index) {
+                Some(Token::Keyword(Keyword::Extension, _)) => {
+                    index += 1;
+                    continue;
+                }
+                Some(Token::Keyword(Keyword::Attribute, _)) => {
+                    index += 1;
+                    (Operator::LParen, Operator::RParen)
+                }
+                Some(_)
+                    if self.standard >= Standard::C23
+                        && is_operator(index, Operator::LBracket)
+                        && is_operator(index + 1, Operator::LBracket) =>
+                {
+                    (Operator::LBracket, Operator::RBracket)
+                }
+                _ => return index,
+            };
+            let mut depth = 0usize;
+            loop {
+                match self.tokens.get(index) {
+                    Some(token) if token.is_operator(open) => depth += 1,
+                    Some(token) if token.is_operator(close) => depth = depth.saturating_sub(1),
+                    Some(_) => (),
+                    None => return index,
+                }
+                index += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    // `[[a, prefix::b(arguments)]]` and `__attribute__((a, b(arguments)))`,
+    // any number of them in a row. Attributes in a list can be left out,
+    // `[[a,,b]]` has two.
+    fn attribute_specifiers(&mut self) -> CompResult<Vec<Attribute>> {
+        let mut attributes = Vec::new();
+        loop {
+            let (syntax, open, close) = if self.peek_keyword() == Some(Keyword::Attribute) {
+                self.index += 1;
+                (AttributeSyntax::Gnu, Operator::LParen, Operator::RParen)
+            } else if self.standard_attributes_follow() {
+                (
+                    AttributeSyntax::Standard,
+                    Operator::LBracket,
+                    Operator::RBracket,
+                )
+            } else {
+                return Ok(attributes);
+            };
+            self.expect_operator(open)?;
+            self.expect_operator(open)?;
+            loop {
+                if self.eat_operator(Operator::Comma) {
+                    continue;
+                }
+                if self.peek_operator(close) {
+                    break;
+                }
+                attributes.push(self.attribute(syntax)?);
+                if !self.eat_operator(Operator::Comma) {
+                    break;
+                }
+            }
+            self.expect_operator(close)?;
+            self.expect_operator(close)?;
+        }
+    }
+
+    fn attribute(&mut self, syntax: AttributeSyntax) -> CompResult<Attribute> {
+        let start = self.current_span();
+        let mut name = self.attribute_token()?;
+        let mut prefix = None;
+        if syntax == AttributeSyntax::Standard && self.eat_punctuator(Punctuator::DoubleColon) {
+            prefix = Some(name);
+            name = self.attribute_token()?;
+        }
+
+        // Any tokens can be arguments, as long as the brackets balance
+        let mut arguments = None;
+        let mut range = None;
+        if self.eat_operator(Operator::LParen) {
+            let arguments_start = self.index;
+            let mut depth = 0usize;
+            loop {
+                match self.peek() {
+                    None => return Err(self.error_expected("`)`")),
+                    Some(token) if token.is_operator(Operator::RParen) && depth == 0 => break,
+                    Some(
+                        Token::Operator(Operator::LParen | Operator::LBracket, _)
+                        | Token::Punctuator(Punctuator::LCurly, _),
+                    ) => depth += 1,
+                    Some(
+                        Token::Operator(Operator::RParen | Operator::RBracket, _)
+                        | Token::Punctuator(Punctuator::RCurly, _),
+                    ) => depth = depth.saturating_sub(1),
+                    Some(_) => (),
+                }
+                self.index += 1;
+            }
+            let tokens = &self.tokens[arguments_start..self.index];
+            let spelled: Vec<String> = tokens.iter().map(Token::spelling).collect();
+            arguments = Some(spelled.join(" "));
+            range = Some(arguments_start..self.index);
+            self.index += 1;
+        }
+
+        let mut attribute = Attribute {
+            syntax,
+            prefix,
+            name,
+            arguments,
+            alignment: None,
+            span: self.span_from(start),
+        };
+        attribute.alignment = self.check_attribute(&attribute, range);
+        Ok(attribute)
+    }
+
+    // Attribute names and prefixes can be keywords as well
+    fn attribute_token(&mut self) -> CompResult<Identifier> {
+        match self.peek() {
+            Some(token @ (Token::Identifier(..) | Token::Keyword(..))) => {
+                let name = Identifier {
+                    name: token.spelling(),
+                    span: token.span(),
+                };
+                self.index += 1;
+                Ok(name)
+            }
+            _ => Err(self.error_expected("attribute name")),
+        }
+    }
+
+    // Checks the arguments of the attributes zcc knows about, given as
+    // the range of tokens inside the parentheses, and gives the
+    // alignment `aligned` asks for. Other attributes are ignored,
+    // whatever their arguments, with a warning for GNU ones like gcc
+    // gives.
+    fn check_attribute(
+        &mut self,
+        attribute: &Attribute,
+        arguments: Option<Range<usize>>,
+    ) -> Option<u64> {
+        let standard = attribute.syntax == AttributeSyntax::Standard && attribute.prefix.is_none();
+        let mut alignment = None;
+        let problem = match attribute.gnu_name() {
+            Some("aligned") => match self.attribute_alignment(arguments) {
+                Ok(align) => {
+                    alignment = Some(align);
+                    None
+                }
+                Err(message) => Some(message),
+            },
+            Some(name) => self.gnu_attribute_problem(name, arguments),
+            None if standard => {
+                lang::standard_attribute(&attribute.name.name).and_then(|(name, _)| {
+                    let arguments = arguments.map(|range| &self.tokens[range]);
+                    standard_attribute_problem(name, arguments)
+                })
+            }
+            None if attribute.is_gnu() => {
+                let warning = self
+                    .error_at_span(attribute.name.span, ErrorCode::InvalidAttribute)
+                    .warning()
+                    .message(format!(
+                        "Attribute `{}` is unknown, so it's ignored",
+                        attribute.name.name
+                    ))
+                    .build();
+                self.diagnose(*warning);
+                None
+            }
+            None => None,
+        };
+        if let Some(message) = problem {
+            let error = self
+                .error_at_span(attribute.name.span, ErrorCode::InvalidAttribute)
+                .message(message)
+                .build();
+            self.report(*error);
+        }
+        alignment
+    }
+
+    // `format` says which parameters are a format string and what it
+    // formats, and `section` names where the object goes. The rest take
+    // nothing.
+    fn gnu_attribute_problem(
+        &mut self,
+        name: &str,
+        arguments: Option<Range<usize>>,
+    ) -> Option<String> {
+        let arguments = arguments.map(|range| &self.tokens[range]);
+        match (name, arguments) {
+            ("format", Some([Token::Identifier(archetype, _), comma, Token::Constant(Constant::Integer(format, _), _), second_comma, Token::Constant(Constant::Integer(first, _), _)]))
+                if comma.is_operator(Operator::Comma) && second_comma.is_operator(Operator::Comma) =>
+            {
+                let known = lang::FORMAT_ARCHETYPES
+                    .iter()
+                    .any(|known| archetype == known || *archetype == format!("__{}__", known));
+                if !known {
+                    Some(format!("Unknown `format` archetype `{}`", archetype))
+                } else if *format < 1 || (*first != 0 && first <= format) {
+                    Some("The arguments `format` checks have to come after the format string".into())
+                } else {
+                    None
+                }
+            }
+            ("format", _) => Some(
+                "`format` takes an archetype and two parameter numbers, as in `format(printf, 1, 2)`"
+                    .into(),
+            ),
+            ("section", Some([Token::StringLiteral(..)])) => None,
+            ("section", _) => Some("`section` takes a string literal as its argument".into()),
+            (_, Some(_)) => Some(format!("`{}` takes no arguments", name)),
+            (_, None) => None,
+        }
+    }
+
+    // The argument of `aligned(N)` is parsed like `_Alignas(N)`, from
+    // the tokens that were skipped over as its arguments. Plain `aligned`
+    // is the biggest alignment of any type.
+    fn attribute_alignment(&mut self, arguments: Option<Range<usize>>) -> Result<u64, String> {
+        let Some(arguments) = arguments else {
+            return Ok(target::BIGGEST_ALIGNMENT);
+        };
+        let resume = self.index;
+        self.index = arguments.start;
+        let expression = self.conditional_expression();
+        let complete = self.index == arguments.end;
+        self.index = resume;
+        let value = match expression {
+            Ok(ref expression) if complete => self.evaluate_constant(expression),
+            _ => None,
+        };
+        match value {
+            Some(value) if value > 0 && value & (value - 1) == 0 => Ok(value as u64),
+            Some(value) => Err(format!("Alignment {} is not a power of two", value)),
+            None => Err("Alignment could not be evaluated as an integer constant".into()),
+        }
+    }
+
+    // `[[fallthrough]]` only goes on a null statement, `[[fallthrough]];`
+    fn check_fallthrough(&mut self, attributes: &[Attribute], allowed: bool) {
+        if allowed {
+            return;
+        }
+        let fallthrough = attributes.iter().find(|attribute| {
+            attribute.syntax == AttributeSyntax::Standard
+                && attribute.prefix.is_none()
+                && lang::standard_attribute(&attribute.name.name)
+                    .is_some_and(|(name, _)| name == "fallthrough")
+        });
+        if let Some(attribute) = fallthrough {
+            let error = self
+                .error_at_span(attribute.span, ErrorCode::InvalidAttribute)
+                .message("`fallthrough` can only be used on a null statement".into())
+                .build();
+            self.report(*error);
+        }
+    }
+
+    // At most one storage class, other than `_Thread_local` with
+    // `static` or `extern`, `constexpr` with `auto`, `register` or
+    // `static`, and in C23 `auto` with another one. Type specifiers
+    // have to name a type together, so no `short long` or
+    // `unsigned float`.
+    fn check_specifiers(&mut self, specifiers: &DeclarationSpecifiers) -> CompResult<()> {
+        let classes: Vec<_> = specifiers
+            .specifiers
+            .iter()
+            .filter_map(|spec| match spec.kind {
+                DeclarationSpecifierKind::StorageClass(class) => Some((class, spec.span)),
+                _ => None,
+            })
+            .collect();
+        for (i, &(second, span)) in classes.iter().enumerate() {
+            let Some(&(first, _)) = classes[..i]
+                .iter()
+                .find(|&&(first, _)| !self.storage_classes_combine(first, second))
+            else {
+                continue;
+            };
+            let message = match (first, second) {
+                (StorageClass::ThreadLocal, _) | (_, StorageClass::ThreadLocal) => {
+                    "`_Thread_local` can only be combined with `static` or `extern`"
+                }
+                (StorageClass::Constexpr, _) | (_, StorageClass::Constexpr) => {
+                    "`constexpr` can only be combined with `auto`, `register` or `static`"
+                }
+                _ => "More than one storage class in declaration",
+            };
+            return Err(self
+                .error_at_span(span, ErrorCode::InvalidDeclarationSpecifiers)
+                .message(message.to_string())
+                .build());
+        }
+        self.check_alignment(specifiers)?;
+
+        // C23 infers the type of `auto x = 1;` instead
+        let inferred = self.standard >= Standard::C23
+            && specifiers
+                .storage_classes()
+                .any(|class| matches!(class, StorageClass::Auto | StorageClass::Constexpr));
+        if !has_type_specifier(&specifiers.specifiers) && !inferred {
+            self.implicit_int("The declaration", specifiers.span);
+        }
+
+        if let Err(invalid) = specifiers.base_type() {
+            let mut written = Vec::new();
+            for spec in &specifiers.specifiers {
+                if let DeclarationSpecifierKind::TypeSpecifier(ref specifier) = spec.kind {
+                    written.push(type_specifier_spelling(specifier));
+                }
+                if std::ptr::eq(spec, invalid) {
+                    break;
+                }
+            }
+            return Err(self
+                .error_at_span(invalid.span, ErrorCode::InvalidDeclarationSpecifiers)
+                .message(format!(
+                    "Invalid combination of type specifiers `{}`",
+                    written.join(" ")
+                ))
+                .build());
+        }
+
+        let base = specifiers.base_type().expect("Checked above");
+        if matches!(base, BaseType::LongLong | BaseType::UnsignedLongLong) {
+            self.require_standard(Standard::C99, "`long long`", specifiers.span);
+        }
+        // Only pointers can be restrict qualified, and the specifiers
+        // qualify the base type. A typedef name could be a pointer.
+        if let Some(restrict) = specifiers.specifiers.iter().find(|spec| {
+            spec.kind == DeclarationSpecifierKind::TypeQualifier(TypeQualifier::Restrict)
+        }) {
+            if !matches!(base, BaseType::TypedefName(_)) {
+                return Err(self
+                    .error_at_span(restrict.span, ErrorCode::InvalidDeclarationSpecifiers)
+                    .message(format!(
+                        "`restrict` used on `{}`, which is not a pointer",
+                        base
+                    ))
+                    .build());
+            }
+        }
+        Ok(())
+    }
+
+    fn storage_classes_combine(&self, first: StorageClass, second: StorageClass) -> bool {
+        use StorageClass::*;
+        let pair = |a, b| (first == a && second == b) || (first == b && second == a);
+        pair(ThreadLocal, Static)
+            || pair(ThreadLocal, Extern)
+            || pair(Constexpr, Auto)
+            || pair(Constexpr, Register)
+            || pair(Constexpr, Static)
+            // For type inference, `static auto x = 1;`
+            || (self.standard >= Standard::C23
+                && (pair(Auto, Static) || pair(Auto, Extern) || pair(Auto, ThreadLocal)))
+    }
+
+    // Nothing outside of a function is `auto` or `register`, other than
+    // C23 `auto` that infers the type
+    fn check_external_storage(&mut self, specifiers: &DeclarationSpecifiers) {
+        let inferred =
+            self.standard >= Standard::C23 && !has_type_specifier(&specifiers.specifiers);
+        for spec in &specifiers.specifiers {
+            let name = match spec.kind {
+                DeclarationSpecifierKind::StorageClass(StorageClass::Auto) if !inferred => "auto",
+                DeclarationSpecifierKind::StorageClass(StorageClass::Register) => "register",
+                _ => continue,
+            };
+            let error = self
+                .error_at_span(spec.span, ErrorCode::InvalidDeclarationSpecifiers)
+                .message(format!("`{}` can't be used at file scope", name))
+                .build();
+            self.diagnose(*error);
+        }
+    }
+
+    // A variably modified type only goes with a name in a block without
+    // linkage, and an object that lasts the whole program can't be a
+    // variable length array. No object is larger than the biggest size.
+    // The name is declared with an error type after any of these.
+    fn check_declared_type(
+        &mut self,
+        specifiers: &DeclarationSpecifiers,
+        declarator: &Declarator,
+        ty: CType,
+    ) -> CType {
+        let name = declarator.name().map_or("", |name| &name.name);
+        let class = specifiers.storage_classes().find(|class| {
+            matches!(
+                class,
+                StorageClass::Extern | StorageClass::Static | StorageClass::ThreadLocal
+            )
+        });
+        let variable_array = matches!(
+            self.types.kind(ty),
+            TypeKind::Array {
+                size: ArraySize::Variable,
+                ..
+            }
+        );
+        // A struct or union that's too large was reported where it's defined
+        let array = matches!(self.types.kind(ty), TypeKind::Array { .. });
+        let message = if array && self.types.is_too_large(ty) {
+            format!(
+                "`{}` has type `{}`, which is too large for an object",
+                name,
+                self.types.display(ty)
+            )
+        } else if !self.types.is_variably_modified(ty) {
+            return ty;
+        } else if self.symbols.kind() == ScopeKind::File {
+            format!(
+                "`{}` has variably modified type `{}` at file scope",
+                name,
+                self.types.display(ty)
+            )
+        } else if let Some(class) =
+            class.filter(|&class| class == StorageClass::Extern || variable_array)
+        {
+            format!(
+                "`{}` has variably modified type `{}`, so it can't be `{}`",
+                name,
+                self.types.display(ty),
+                storage_class_spelling(class)
+            )
+        } else {
+            return ty;
+        };
+        let error = self
+            .error_at_span(declarator.span, ErrorCode::InvalidDeclaration)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+        CType::ERROR
+    }
+
+    // `_Alignas` can't be used on typedefs or `register` objects, and
+    // an alignment is a power of two, or 0 which changes nothing
+    fn check_alignment(&mut self, specifiers: &DeclarationSpecifiers) -> CompResult<()> {
+        for spec in &specifiers.specifiers {
+            let DeclarationSpecifierKind::AlignmentSpecifier(ref alignment) = spec.kind else {
+                continue;
+            };
+            if let Some(class) = specifiers
+                .storage_classes()
+                .find(|class| matches!(class, StorageClass::Typedef | StorageClass::Register))
+            {
+                let name = match class {
+                    StorageClass::Typedef => "typedef",
+                    _ => "register",
+                };
+                return Err(self
+                    .error_at_span(spec.span, ErrorCode::InvalidDeclarationSpecifiers)
+                    .message(format!("`_Alignas` can't be used with `{}`", name))
+                    .build());
+            }
+            let AlignmentSpecifier::Expression(ref expression) = *alignment else {
+                continue;
+            };
+            match self.evaluate_constant(expression) {
+                Some(value) if value == 0 || (value > 0 && value & (value - 1) == 0) => (),
+                Some(value) => {
+                    return Err(self
+                        .error_at_span(expression.span, ErrorCode::InvalidDeclarationSpecifiers)
+                        .message(format!("Alignment {} is not a power of two", value))
+                        .build())
+                }
+                None => {
+                    return Err(self
+                        .error_at_span(expression.span, ErrorCode::InvalidConstantExpression)
+                        .message("Alignment could not be evaluated as an integer constant".into())
+                        .build())
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // `inline` and `_Noreturn` can only be used in declarations of
+    // functions, so not on objects, typedefs, parameters, members or in
+    // type names
+    fn check_function_specifiers(
+        &mut self,
+        specifiers: &DeclarationSpecifiers,
+        declarator: Option<&Declarator>,
+    ) {
+        let function = declarator.is_some_and(|declarator| {
+            matches!(
+                declarator.derivations().first(),
+                Some(Derivation::Function { .. })
+            )
+        });
+        if function && !specifiers.is_typedef() {
+            return;
+        }
+        for spec in &specifiers.specifiers {
+            let DeclarationSpecifierKind::FunctionSpecifier(specifier) = spec.kind else {
+                continue;
+            };
+            let name = match specifier {
+                FunctionSpecifier::Inline => "inline",
+                FunctionSpecifier::Noreturn => "_Noreturn",
+            };
+            let error = self
+                .error_at_span(spec.span, ErrorCode::InvalidDeclarationSpecifiers)
+                .message(format!("`{}` can only be used on functions", name))
+                .build();
+            self.report(*error);
+        }
+    }
+
+    // `_Static_assert(condition, "message");`, at file scope or in a
+    // block. The condition is evaluated right away.
+    fn static_assert(&mut self) -> CompResult<StaticAssert> {
+        let start = self.current_span();
+        self.expect_keyword(Keyword::StaticAssert)?;
+        self.expect_operator(Operator::LParen)?;
+        let condition = self.conditional_expression()?;
+        let message = if self.eat_operator(Operator::Comma) {
+            let message = match self.peek() {
+                Some(Token::StringLiteral(message, _)) => message.clone(),
+                _ => return Err(self.error_expected("string literal")),
+            };
+            self.index += 1;
+            Some(message)
+        } else {
+            let what = "A static assertion without a message";
+            self.require_standard(Standard::C23, what, self.span_from(start));
+            None
+        };
+        self.expect_operator(Operator::RParen)?;
+        self.expect_punctuator(Punctuator::Semicolon)?;
+
+        match self.evaluate_constant(&condition) {
+            Some(0) => {
+                let error = self
+                    .error_at_span(condition.span, ErrorCode::StaticAssertionFailed)
+                    .message(match message {
+                        Some(ref message) => format!("\"{}\"", message),
+                        None => "The condition is false".to_string(),
+                    })
+                    .build();
+                self.report(*error);
+            }
+            Some(_) => (),
+            None => {
+                let error = self
+                    .error_at_span(condition.span, ErrorCode::InvalidConstantExpression)
+                    .message(
+                        "Static assertion could not be evaluated as an integer constant".into(),
+                    )
+                    .build();
+                self.report(*error);
+            }
+        }
+        Ok(StaticAssert {
+            condition,
+            message,
+            span: self.span_from(start),
+        })
+    }
+
+    fn initializer(&mut self) -> CompResult<Initializer> {
+        if self.peek_punctuator(Punctuator::LCurly) {
+            Ok(Initializer::List(self.initializer_list()?))
+        } else {
+            Ok(Initializer::Expression(self.assignment_expression()?))
+        }
+    }
+
+    // The list can end with a comma, but it can't be empty
+    fn initializer_list(&mut self) -> CompResult<InitializerList> {
+        let start = self.current_span();
+        self.expect_punctuator(Punctuator::LCurly)?;
+
+        let mut items = Vec::new();
+        loop {
+            let item_start = self.current_span();
+            let designators = self.designators()?;
+            if !designators.is_empty() {
+                self.expect_operator(Operator::Equals)?;
+            }
+            let initializer = self.initializer()?;
+            items.push(InitializerItem {
+                designators,
+                initializer,
+                span: self.span_from(item_start),
+            });
+
+            if !self.eat_operator(Operator::Comma) || self.peek_punctuator(Punctuator::RCurly) {
+                break;
+            }
+        }
+        self.expect_punctuator(Punctuator::RCurly)?;
+
+        Ok(InitializerList {
+            items,
+            span: self.span_from(start),
+        })
+    }
+
+    fn designators(&mut self) -> CompResult<Vec<Designator>> {
+        let mut designators = Vec::new();
+        loop {
+            let start = self.current_span();
+            let kind = if self.eat_operator(Operator::LBracket) {
+                let index = self.conditional_expression()?;
+                self.expect_operator(Operator::RBracket)?;
+                DesignatorKind::Index(index)
+            } else if self.eat_operator(Operator::Period) {
+                DesignatorKind::Member(self.expect_identifier()?)
+            } else {
+                return Ok(designators);
+            };
+            self.require_standard(
+                Standard::C99,
+                "A designated initializer",
+                self.span_from(start),
+            );
+            designators.push(Designator {
+                kind,
+                span: self.span_from(start),
+            });
+        }
+    }
+
+    fn struct_specifier(&mut self) -> CompResult<StructSpecifier> {
+        let start = self.current_span();
+        let kind = match self.next() {
+            Some(Token::Keyword(Keyword::Struct, _)) => StructKind::Struct,
+            Some(Token::Keyword(Keyword::Union, _)) => StructKind::Union,
+            _ => unreachable!("Only called at `struct` or `union`"),
+        };
+
+        let attributes = self.attribute_specifiers()?;
+        let tag = self.eat_identifier();
+        let members = if self.eat_punctuator(Punctuator::LCurly) {
+            let mut members = Vec::new();
+            while !self.eat_punctuator(Punctuator::RCurly) {
+                if self.peek().is_none() {
+                    return Err(self.error_expected("`}`"));
+                }
+                let member_start = self.index;
+                match self.struct_declaration() {
+                    Ok(member) => members.push(member),
+                    Err(error) => {
+                        self.recover(*error, member_start);
+                    }
+                }
+            }
+            self.check_flexible_array_members(kind, &members);
+            Some(members)
+        } else if tag.is_none() {
+            return Err(self.error_expected("tag name or `{`"));
+        } else {
+            None
+        };
+
+        Ok(StructSpecifier {
+            kind,
+            attributes,
+            tag,
+            members,
+            span: self.span_from(start),
+        })
+    }
+
+    // A C99 flexible array member is an array of unknown size that
+    // comes last in a struct with other named members
+    fn check_flexible_array_members(&mut self, kind: StructKind, members: &[StructDeclaration]) {
+        let declarators: Vec<_> = members
+            .iter()
+            .flat_map(|member| &member.declarators)
+            .collect();
+        for (i, member) in declarators.iter().enumerate() {
+            let flexible = matches!(
+                member.declarator.derivations().first(),
+                Some(Derivation::Array(None))
+            );
+            if !flexible {
+                continue;
+            }
+            let name = member.declarator.name().map_or("", |name| &name.name);
+            let problem = if kind == StructKind::Union {
+                "can't be in a union"
+            } else if i + 1 != declarators.len() {
+                "must be the last member"
+            } else if i == 0 {
+                "needs another named member before it"
+            } else {
+                self.require_standard(Standard::C99, "A flexible array member", member.span);
+                continue;
+            };
+            let error = self
+                .error_at_span(member.span, ErrorCode::InvalidDeclaration)
+                .message(format!("Flexible array member `{}` {}", name, problem))
+                .build();
+            self.report(*error);
+        }
+    }
+
+    fn struct_declaration(&mut self) -> CompResult<StructDeclaration> {
+        let start = self.current_span();
+        let specifiers = self.declaration_specifiers()?;
+        self.check_function_specifiers(&specifiers, None);
+
+        let mut declarators = Vec::new();
+        if !(is_anonymous_struct(&specifiers) && self.eat_punctuator(Punctuator::Semicolon)) {
+            loop {
+                declarators.push(self.struct_declarator()?);
+                if !self.eat_operator(Operator::Comma) {
+                    break;
+                }
+            }
+            self.expect_punctuator(Punctuator::Semicolon)?;
+        }
+        Ok(StructDeclaration {
+            specifiers,
+            declarators,
+            span: self.span_from(start),
+        })
+    }
+
+    fn struct_declarator(&mut self) -> CompResult<StructDeclarator> {
+        let start = self.current_span();
+        let declarator = if self.peek_operator(Operator::Colon) {
+            self.declarator(DeclaratorMode::Abstract)?
+        } else {
+            self.declarator(DeclaratorMode::Named)?
+        };
+        let width = if self.eat_operator(Operator::Colon) {
+            Some(self.conditional_expression()?)
+        } else {
+            None
+        };
+        Ok(StructDeclarator {
+            declarator,
+            width,
+            span: self.span_from(start),
+        })
+    }
+
+    fn enum_specifier(&mut self) -> CompResult<EnumSpecifier> {
+        let start = self.current_span();
+        self.next();
+
+        let attributes = self.attribute_specifiers()?;
+        let tag = self.eat_identifier();
+        let enumerators = if self.eat_punctuator(Punctuator::LCurly) {
+            let mut enumerators = Vec::new();
+            let mut next = 0;
+            // A trailing comma is allowed after the last enumerator
+            while !self.eat_punctuator(Punctuator::RCurly) {
+                let name = self.expect_identifier()?;
+                let value = if self.eat_operator(Operator::Equals) {
+                    Some(self.conditional_expression()?)
+                } else {
+                    None
+                };
+                let constant = self.enumerator_value(&name, value.as_ref(), next);
+                // In scope from the end of the enumerator, so the next
+                // value can use it
+                let binding = Binding::Enumerator(constant);
+                self.declare(&name, binding, Linkage::None, Defined::Yes);
+                enumerators.push(Enumerator {
+                    span: self.span_from(name.span),
+                    name,
+                    value,
+                    constant: Constant::Enumeration(constant),
+                });
+                next = constant + 1;
+                if !self.eat_operator(Operator::Comma) {
+                    self.expect_punctuator(Punctuator::RCurly)?;
+                    break;
+                }
+                if self.peek_punctuator(Punctuator::RCurly) {
+                    let comma = self.previous_span();
+                    self.require_standard(
+                        Standard::C99,
+                        "A comma after the last enumerator",
+                        comma,
+                    );
+                }
+            }
+            Some(enumerators)
+        } else if tag.is_none() {
+            return Err(self.error_expected("tag name or `{`"));
+        } else {
+            None
+        };
+
+        Ok(EnumSpecifier {
+            attributes,
+            tag,
+            enumerators,
+            span: self.span_from(start),
+        })
+    }
+
+    fn declarator(&mut self, mode: DeclaratorMode) -> CompResult<Declarator> {
+        let start = self.current_span();
+        if !self.eat_operator(Operator::Asterisk) {
+            return self.direct_declarator(mode);
+        }
+
+        let mut qualifiers = Vec::new();
+        while let Some(qualifier) = self.peek_keyword().and_then(type_qualifier) {
+            self.index += 1;
+            qualifiers.push(qualifier);
+        }
+        let inner = self.declarator(mode)?;
+        Ok(Declarator {
+            kind: DeclaratorKind::Pointer {
+                qualifiers,
+                inner: Box::new(inner),
+            },
+            span: self.span_from(start),
+        })
+    }
+
+    fn direct_declarator(&mut self, mode: DeclaratorMode) -> CompResult<Declarator> {
+        let start = self.current_span();
+        let mut declarator = match self.peek() {
+            Some(Token::Identifier(..)) if mode != DeclaratorMode::Abstract => {
+                let name = self.expect_identifier()?;
+                Declarator {
+                    span: name.span,
+                    kind: DeclaratorKind::Identifier(name),
+                }
+            }
+            Some(token)
+                if token.is_operator(Operator::LParen) && self.nested_declarator_follows(mode) =>
+            {
+                self.index += 1;
+                let inner = self.declarator(mode)?;
+                self.expect_operator(Operator::RParen)?;
+                inner
+            }
+            _ if mode != DeclaratorMode::Named => Declarator {
+                kind: DeclaratorKind::Abstract,
+                span: Span {
+                    hi: start.lo,
+                    ..start
+                },
+            },
+            _ => return Err(self.error_expected("identifier")),
+        };
+
+        loop {
+            if self.eat_operator(Operator::LBracket) {
+                let size = if self.peek_operator(Operator::RBracket) {
+                    None
+                } else {
+                    Some(Box::new(self.assignment_expression()?))
+                };
+                self.expect_operator(Operator::RBracket)?;
+                if let Some(ref size) = size {
+                    self.check_array_size(size);
+                }
+                declarator = Declarator {
+                    kind: DeclaratorKind::Array {
+                        inner: Box::new(declarator),
+                        size,
+                    },
+                    span: self.span_from(start),
+                };
+            } else if self.eat_operator(Operator::LParen) {
+                let kind = if self.identifier_list_follows() {
+                    DeclaratorKind::OldStyleFunction {
+                        inner: Box::new(declarator),
+                        identifiers: self.identifier_list()?,
+                    }
+                } else {
+                    let (parameters, variadic) = self.parameter_list()?;
+                    DeclaratorKind::Function {
+                        inner: Box::new(declarator),
+                        parameters,
+                        variadic,
+                    }
+                };
+                declarator = Declarator {
+                    kind,
+                    span: self.span_from(start),
+                };
+            } else if self.peek_keyword() == Some(Keyword::Attribute) {
+                let attributes = self.attribute_specifiers()?;
+                if attributes.is_empty() {
+                    return Ok(declarator);
+                }
+                return Ok(Declarator {
+                    kind: DeclaratorKind::Attributed {
+                        inner: Box::new(declarator),
+                        attributes,
+                    },
+                    span: self.span_from(start),
+                });
+            } else {
+                return Ok(declarator);
+            }
+        }
+    }
+
+    // An array has at least one element, unless GNU extensions allow
+    // the zero-length arrays that came before flexible array members
+    fn check_array_size(&mut self, size: &Expression) {
+        match self.evaluate_constant(size) {
+            Some(0) => self.require_gnu("A zero-length array", size.span),
+            Some(value) if value < 0 => {
+                let error = self
+                    .error_at_span(size.span, ErrorCode::InvalidDeclaration)
+                    .message(format!("Array size {} is negative", value))
+                    .build();
+                self.report(*error);
+            }
+            _ => (),
+        }
+    }
+
+    // Whether the `(` about to be read groups a declarator rather than
+    // starting the parameter list of an abstract function declarator,
+    // as in `int (*)(int)` compared to `int (int)`
+    fn nested_declarator_follows(&self, mode: DeclaratorMode) -> bool {
+        if mode == DeclaratorMode::Named {
+            return true;
+        }
+        match self.peek_nth(1) {
+            Some(Token::Operator(
+                Operator::Asterisk | Operator::LParen | Operator::LBracket,
+                _,
+            )) => true,
+            Some(Token::Identifier(name, _)) => {
+                mode == DeclaratorMode::Either && !self.is_typedef_name(name)
+            }
+            _ => false,
+        }
+    }
+
+    // After the opening parenthesis, returns the parameters and
+    // whether they end in `...`. Parameter names are only in scope
+    // until the end of the list, a definition declares them again
+    // for its body.
+    // A name that isn't a type starts the identifier list of an
+    // old-style declarator, `f(a, b)`
+    fn identifier_list_follows(&self) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(name, _)) if !self.is_typedef_name(name))
+    }
+
+    fn identifier_list(&mut self) -> CompResult<Vec<Identifier>> {
+        let start = self.current_span();
+        let mut identifiers = vec![self.expect_identifier()?];
+        while self.eat_operator(Operator::Comma) {
+            identifiers.push(self.expect_identifier()?);
+        }
+        self.expect_operator(Operator::RParen)?;
+        if self.standard >= Standard::C23 {
+            let error = self
+                .error_at_span(self.span_from(start), ErrorCode::InvalidDeclaration)
+                .message("Parameters without types were removed in C23".into())
+                .build();
+            self.report(*error);
+        }
+        Ok(identifiers)
+    }
+
+    // Only a function definition can name its parameters without
+    // giving their types, `int f(a, b);` isn't a prototype
+    fn check_identifier_list(&mut self, declarator: &Declarator) {
+        if declarator.identifier_list().is_some() {
+            let error = self
+                .error_at_span(declarator.span, ErrorCode::InvalidDeclaration)
+                .message(
+                    "Parameters without types are only allowed in a function definition".into(),
+                )
+                .build();
+            self.report(*error);
+        }
+    }
+
+    fn parameter_list(&mut self) -> CompResult<(Vec<ParameterDeclaration>, bool)> {
+        self.push_scope(ScopeKind::Prototype);
+        let parameters = self.parameters();
+        self.pop_scope();
+        parameters
+    }
+
+    fn parameters(&mut self) -> CompResult<(Vec<ParameterDeclaration>, bool)> {
+        let mut parameters = Vec::new();
+        if self.eat_operator(Operator::RParen) {
+            return Ok((parameters, false));
+        }
+
+        loop {
+            if self.eat_punctuator(Punctuator::TripleDot) {
+                self.expect_operator(Operator::RParen)?;
+                return Ok((parameters, true));
+            }
+
+            let start = self.current_span();
+            let specifiers = self.declaration_specifiers()?;
+            self.check_function_specifiers(&specifiers, None);
+            let declarator = self.declarator(DeclaratorMode::Either)?;
+            let ty = self.parameter_type(specifiers.ty, &declarator);
+            let parameter = ParameterDeclaration {
+                specifiers,
+                declarator,
+                ty,
+                span: self.span_from(start),
+            };
+            self.check_alignas(&parameter.specifiers, ty, Some("a parameter"));
+            if let Some(name) = parameter.declarator.name() {
+                self.declare(name, Binding::Object(ty), Linkage::None, Defined::Yes);
+            }
+            parameters.push(parameter);
+
+            if !self.eat_operator(Operator::Comma) {
+                self.expect_operator(Operator::RParen)?;
+                return Ok((parameters, false));
+            }
+        }
+    }
+
+    fn type_name(&mut self) -> CompResult<TypeName> {
+        let start = self.current_span();
+        let specifiers = self.declaration_specifiers()?;
+        self.check_function_specifiers(&specifiers, None);
+        let declarator = self.declarator(DeclaratorMode::Abstract)?;
+        Ok(TypeName {
+            ty: self.declared_type(specifiers.ty, &declarator),
+            specifiers,
+            declarator,
+            span: self.span_from(start),
+        })
+    }
+
+    // Whether the next token begins declaration specifiers, looking
+    // past any attributes
+    fn declaration_follows(&self) -> bool {
+        self.tokens
+            .get(self.skip_attributes(self.index))
+            .is_some_and(|token| self.starts_declaration(token))
+    }
+
+    fn starts_declaration(&self, token: &Token) -> bool {
+        match *token {
+            Token::Keyword(keyword, _) if storage_class(keyword).is_some() => true,
+            Token::Keyword(keyword, _) if function_specifier(keyword).is_some() => true,
+            Token::Keyword(Keyword::Alignas | Keyword::StaticAssert, _) => true,
+            _ => self.starts_type_name(token),
+        }
+    }
+
+    // Type names are declaration specifiers without a storage class
+    fn starts_type_name(&self, token: &Token) -> bool {
+        match *token {
+            Token::Keyword(keyword, _) => {
+                type_qualifier(keyword).is_some()
+                    || basic_type_specifier(keyword).is_some()
+                    || matches!(
+                        keyword,
+                        Keyword::Struct
+                            | Keyword::Union
+                            | Keyword::Enum
+                            | Keyword::Typeof
+                            | Keyword::TypeofUnqual
+                    )
+            }
+            Token::Identifier(ref name, _) => self.is_typedef_name(name),
+            _ => false,
+        }
+    }
+
+    // Whether the next two tokens are `(` and the start of a type name,
+    // which makes them a cast or `sizeof` of a type rather than an
+    // expression in parentheses
+    fn parenthesized_type_follows(&self) -> bool {
+        self.peek_operator(Operator::LParen)
+            && self
+                .peek_nth(1)
+                .is_some_and(|token| self.starts_type_name(token))
+    }
+
+    // Something from a later edition of C than the one selected is
+    // reported, but otherwise parsed as usual
+    fn require_standard(&mut self, standard: Standard, what: &str, span: Span) {
+        if self.standard < standard {
+            let error = self
+                .error_at_span(span, ErrorCode::NewerStandardRequired)
+                .message(format!("{} requires {} or later", what, standard.name()))
+                .build();
+            self.report(*error);
+        }
+    }
+
+    // Same for GNU extensions without `-std=gnu17` or the like
+    fn require_gnu(&mut self, what: &str, span: Span) {
+        if !self.gnu {
+            let error = self
+                .error_at_span(span, ErrorCode::GnuExtensionRequired)
+                .message(format!(
+                    "{} is a GNU extension, which needs `-std=gnu{}`",
+                    what,
+                    &self.standard.name()[1..]
+                ))
+                .build();
+            self.report(*error);
+        }
+    }
+
+    // A declaration without a type specifier declares an `int`. C99
+    // removed that, but old code still has it, so only the strict
+    // modes reject it.
+    fn implicit_int(&mut self, what: &str, span: Span) {
+        let builder = self.error_at_span(span, ErrorCode::MissingTypeSpecifier);
+        if self.standard >= Standard::C99 && !self.gnu {
+            let error = builder
+                .message(format!(
+                    "{} has no type specifier, which {} doesn't allow",
+                    what,
+                    self.standard.name()
+                ))
+                .build();
+            self.report(*error);
+        } else {
+            let warning = builder
+                .warning()
+                .message(format!("{} has no type specifier, so it's an `int`", what))
+                .build();
+            self.diagnose(*warning);
+        }
+    }
+
+    // Whether the specifiers of a declaration at file scope are left
+    // out, which needs the name it declares to be followed by what can
+    // come after a declarator
+    fn missing_specifiers_follow(&self) -> bool {
+        let Some(Token::Identifier(ref name, _)) = self.peek() else {
+            return false;
+        };
+        !self.is_typedef_name(name)
+            && self.peek_nth(1).is_some_and(|token| {
+                token.is_operator(Operator::LParen)
+                    || token.is_operator(Operator::LBracket)
+                    || token.is_operator(Operator::Comma)
+                    || token.is_operator(Operator::Equals)
+                    || token.is_punctuator(Punctuator::Semicolon)
+            })
+    }
+
+    // Warnings, and errors about what the code means rather than how
+    // it's written, don't come from the parser being thrown off by an
+    // earlier mistake, so they're all kept
+    fn diagnose(&mut self, diagnostic: CompError) {
+        self.errors.push(diagnostic);
+    }
+
+    fn report(&mut self, error: CompError) {
+        if self.last_error_index != Some(self.index) {
+            self.errors.push(error);
+        }
+        self.last_error_index = Some(self.index);
+    }
+
+    // Reports an error in the item that started at token `start`, then
+    // skips to the end of it. Returns the span of what was skipped.
+    fn recover(&mut self, error: CompError, start: usize) -> Span {
+        self.report(error);
+        let span = self
+            .tokens
+            .get(start)
+            .map_or(self.previous_span(), Token::span);
+        self.synchronize(start);
+        self.span_from(span)
+    }
+
+    // Panic mode recovery, skipping tokens up to and including a `;` or
+    // a block, or up to a `}` that closes the enclosing block or the
+    // start of a declaration. At least one token is skipped when the
+    // item failed on its first token, so parsing always moves forward.
+    fn synchronize(&mut self, start: usize) {
+        let mut braces = 0usize;
+        let mut parentheses = 0usize;
+        while let Some(token) = self.peek() {
+            let moved = self.index > start;
+            if braces == 0 && moved {
+                if token.is_punctuator(Punctuator::RCurly) {
+                    return;
+                }
+                if parentheses == 0 && self.starts_declaration(token) {
+                    return;
+                }
+            }
+
+            match *token {
+                Token::Punctuator(Punctuator::LCurly, _) => braces += 1,
+                Token::Punctuator(Punctuator::RCurly, _) => {
+                    braces = braces.saturating_sub(1);
+                    if braces == 0 {
+                        self.index += 1;
+                        return;
+                    }
+                }
+                Token::Punctuator(Punctuator::Semicolon, _) if braces == 0 => {
+                    self.index += 1;
+                    return;
+                }
+                Token::Operator(Operator::LParen | Operator::LBracket, _) => parentheses += 1,
+                Token::Operator(Operator::RParen | Operator::RBracket, _) => {
+                    parentheses = parentheses.saturating_sub(1)
+                }
+                _ => (),
+            }
+            self.index += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.index + n)
+    }
+
+    fn peek_keyword(&self) -> Option<Keyword> {
+        match self.peek()? {
+            Token::Keyword(keyword, _) => Some(*keyword),
+            _ => None,
+        }
+    }
+
+    fn peek_operator(&self, operator: Operator) -> bool {
+        self.peek().is_some_and(|token| token.is_operator(operator))
+    }
+
+    fn peek_punctuator(&self, punctuator: Punctuator) -> bool {
+        self.peek()
+            .is_some_and(|token| token.is_punctuator(punctuator))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.index).cloned();
+        if token.is_some() {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: Keyword) -> bool {
+        let found = self.peek().is_some_and(|token| token.is_keyword(keyword));
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn eat_operator(&mut self, operator: Operator) -> bool {
+        let found = self.peek_operator(operator);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn eat_punctuator(&mut self, punctuator: Punctuator) -> bool {
+        let found = self.peek_punctuator(punctuator);
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn eat_identifier(&mut self) -> Option<Identifier> {
+        match self.peek()? {
+            Token::Identifier(name, meta) => {
+                let identifier = Identifier {
+                    name: name.clone(),
+                    span: meta.span,
+                };
+                self.index += 1;
+                Some(identifier)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> CompResult<()> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        Err(self.error_expected(&format!("`{}`", lang::keyword_spelling(keyword))))
+    }
+
+    fn expect_operator(&mut self, operator: Operator) -> CompResult<()> {
+        if self.eat_operator(operator) {
+            return Ok(());
+        }
+        Err(self.error_expected(&format!("`{}`", lang::operator_spelling(operator))))
+    }
+
+    fn expect_punctuator(&mut self, punctuator: Punctuator) -> CompResult<()> {
+        if self.eat_punctuator(punctuator) {
+            return Ok(());
+        }
+        Err(self.error_expected(&format!("`{}`", lang::punctuator_spelling(punctuator))))
+    }
+
+    fn expect_identifier(&mut self) -> CompResult<Identifier> {
+        self.eat_identifier()
+            .ok_or_else(|| self.error_expected("identifier"))
+    }
+
+    // Points at the next token, or the last one when there is nothing
+    // left to read
+    fn error_expected(&self, expected: &str) -> Box<CompError> {
+        match self.peek() {
+            Some(token) => self
+                .sources
+                .error_at(token.metadata(), ErrorCode::UnexpectedToken)
+                .message(format!(
+                    "Expected {}, found `{}`",
+                    expected,
+                    token.spelling()
+                ))
+                .build(),
+            None => {
+                let error = match self.tokens.last() {
+                    Some(token) => self
+                        .sources
+                        .error_at(token.metadata(), ErrorCode::UnexpectedToken),
+                    None => CompErrorBuilder::new().code(ErrorCode::UnexpectedToken),
+                };
+                error
+                    .message(format!("Expected {}, found end of file", expected))
+                    .build()
+            }
+        }
+    }
+
+    // For errors about nodes rather than single tokens
+    // Whether a declaration of an object or function defines it, which
+    // for an object is up to whether it's initialized
+    fn defined(&self, specifiers: &DeclarationSpecifiers, ty: CType) -> Defined {
+        let function = matches!(self.types.kind(ty), TypeKind::Function { .. });
+        let external = specifiers
+            .storage_classes()
+            .any(|class| class == StorageClass::Extern);
+        if self.peek_operator(Operator::Equals) {
+            Defined::Yes
+        } else if function || external {
+            Defined::No
+        } else if self.symbols.kind() == ScopeKind::File {
+            Defined::Tentatively
+        } else {
+            Defined::Yes
+        }
+    }
+
+    fn error_at_span(&self, span: Span, code: ErrorCode) -> CompErrorBuilder {
+        CompErrorBuilder::new()
+            .code(code)
+            .span(self.sources.buffer(span.file_id), span)
+    }
+
+    // Span of the next token, which is where a node that starts
+    // here begins
+    fn current_span(&self) -> Span {
+        match self.peek() {
+            Some(token) => token.span(),
+            None => self.previous_span(),
+        }
+    }
+
+    fn previous_span(&self) -> Span {
+        match self.index.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(token) => token.span(),
+            None => Span::default(),
+        }
+    }
+
+    // From `start` up to the end of the last token read
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span())
+    }
+}
+
+fn storage_class_spelling(class: StorageClass) -> &'static str {
+    let keyword = match class {
+        StorageClass::Typedef => Keyword::Typedef,
+        StorageClass::Extern => Keyword::Extern,
+        StorageClass::Static => Keyword::Static,
+        StorageClass::ThreadLocal => Keyword::ThreadLocal,
+        StorageClass::Auto => Keyword::Auto,
+        StorageClass::Register => Keyword::Register,
+        StorageClass::Constexpr => Keyword::Constexpr,
+    };
+    lang::keyword_spelling(keyword)
+}
+
+fn storage_class(keyword: Keyword) -> Option<StorageClass> {
+    match keyword {
+        Keyword::Typedef => Some(StorageClass::Typedef),
+        Keyword::Extern => Some(StorageClass::Extern),
+        Keyword::Static => Some(StorageClass::Static),
+        Keyword::Auto => Some(StorageClass::Auto),
+        Keyword::Register => Some(StorageClass::Register),
+        Keyword::ThreadLocal => Some(StorageClass::ThreadLocal),
+        Keyword::Constexpr => Some(StorageClass::Constexpr),
+        _ => None,
+    }
+}
+
+fn function_specifier(keyword: Keyword) -> Option<FunctionSpecifier> {
+    match keyword {
+        Keyword::Inline => Some(FunctionSpecifier::Inline),
+        Keyword::Noreturn => Some(FunctionSpecifier::Noreturn),
+        _ => None,
+    }
+}
+
+fn type_qualifier(keyword: Keyword) -> Option<TypeQualifier> {
+    match keyword {
+        Keyword::Const => Some(TypeQualifier::Const),
+        Keyword::Volatile => Some(TypeQualifier::Volatile),
+        Keyword::Restrict => Some(TypeQualifier::Restrict),
+        Keyword::Atomic => Some(TypeQualifier::Atomic),
+        _ => None,
+    }
+}
+
+// Type specifiers that are a single keyword
+fn basic_type_specifier(keyword: Keyword) -> Option<TypeSpecifier> {
+    match keyword {
+        Keyword::Void => Some(TypeSpecifier::Void),
+        Keyword::Char => Some(TypeSpecifier::Char),
+        Keyword::Short => Some(TypeSpecifier::Short),
+        Keyword::Int => Some(TypeSpecifier::Int),
+        Keyword::Long => Some(TypeSpecifier::Long),
+        Keyword::Float => Some(TypeSpecifier::Float),
+        Keyword::Double => Some(TypeSpecifier::Double),
+        Keyword::Signed => Some(TypeSpecifier::Signed),
+        Keyword::Unsigned => Some(TypeSpecifier::Unsigned),
+        Keyword::Bool => Some(TypeSpecifier::Bool),
+        Keyword::BuiltinVaList => Some(TypeSpecifier::VaList),
+        _ => None,
+    }
+}
+
+fn type_specifier_spelling(specifier: &TypeSpecifier) -> String {
+    let keyword = match *specifier {
+        TypeSpecifier::Void => Keyword::Void,
+        TypeSpecifier::Char => Keyword::Char,
+        TypeSpecifier::Short => Keyword::Short,
+        TypeSpecifier::Int => Keyword::Int,
+        TypeSpecifier::Long => Keyword::Long,
+        TypeSpecifier::Float => Keyword::Float,
+        TypeSpecifier::Double => Keyword::Double,
+        TypeSpecifier::Signed => Keyword::Signed,
+        TypeSpecifier::Unsigned => Keyword::Unsigned,
+        TypeSpecifier::Bool => Keyword::Bool,
+        TypeSpecifier::VaList => Keyword::BuiltinVaList,
+        TypeSpecifier::Struct(ref specifier) => {
+            let keyword = match specifier.kind {
+                StructKind::Struct => "struct",
+                StructKind::Union => "union",
+            };
+            return match specifier.tag {
+                Some(ref tag) => format!("{} {}", keyword, tag.name),
+                None => keyword.to_string(),
+            };
+        }
+        TypeSpecifier::Enum(ref specifier) => {
+            return match specifier.tag {
+                Some(ref tag) => format!("enum {}", tag.name),
+                None => "enum".to_string(),
+            };
+        }
+        TypeSpecifier::TypedefName(ref name) => return name.clone(),
+        TypeSpecifier::Typeof(ref specifier) => {
+            return match specifier.unqualified {
+                true => "typeof_unqual(...)".to_string(),
+                false => "typeof(...)".to_string(),
+            };
+        }
+        TypeSpecifier::Atomic(_) => return "_Atomic(...)".to_string(),
+    };
+    lang::keyword_spelling(keyword).to_string()
+}
+
+// `deprecated` and `nodiscard` can be given a message, the other
+// standard attributes take no arguments
+fn standard_attribute_problem(name: &str, arguments: Option<&[Token]>) -> Option<String> {
+    match (name, arguments) {
+        (_, None) | ("deprecated" | "nodiscard", Some([Token::StringLiteral(..)])) => None,
+        ("deprecated" | "nodiscard", Some(_)) => {
+            Some(format!("`{}` takes a string literal as its argument", name))
+        }
+        (_, Some(_)) => Some(format!("`{}` takes no arguments", name)),
+    }
+}
+
+// `struct { ... }` or `union { ... }` without a tag
+fn is_anonymous_struct(specifiers: &DeclarationSpecifiers) -> bool {
+    let mut type_specifiers = specifiers.type_specifiers();
+    matches!(
+        (type_specifiers.next(), type_specifiers.next()),
+        (
+            Some(TypeSpecifier::Struct(StructSpecifier {
+                tag: None,
+                members: Some(_),
+                ..
+            })),
+            None
+        )
+    )
+}
+
+// The `void` of `f(void)`
+fn is_void_parameter(parameter: &ParameterDeclaration) -> bool {
+    parameter.declarator.kind == DeclaratorKind::Abstract
+        && parameter.specifiers.base_type() == Ok(BaseType::Void)
+}
+
+fn has_type_specifier(specifiers: &[DeclarationSpecifier]) -> bool {
+    specifiers
+        .iter()
+        .any(|spec| matches!(spec.kind, DeclarationSpecifierKind::TypeSpecifier(_)))
+}
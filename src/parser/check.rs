@@ -0,0 +1,1388 @@
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::{self, Constant, FloatingKind, IntegerKind, Operator, Standard};
+use crate::lexer;
+use crate::source::Span;
+use crate::types::*;
+
+use super::scope::{Defined, Linkage};
+use super::{Binding, Parser};
+
+// Where a value is converted as if by assignment, for the messages
+// about it
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Assigning {
+    Assignment,
+    // Counting from 1
+    Argument(usize),
+    Return,
+    Initializer,
+}
+
+impl std::fmt::Display for Assigning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Assignment => write!(f, "in an assignment"),
+            Self::Argument(number) => write!(f, "in argument {}", number),
+            Self::Return => write!(f, "in a return statement"),
+            Self::Initializer => write!(f, "in an initializer"),
+        }
+    }
+}
+
+// Each expression is typed as it's built, when the names in it are in
+// scope and its operands already have their types. Operands are
+// converted to what the operator works on with `ImplicitCast` nodes. An
+// operand with the error type makes the whole expression one, without
+// another error about it.
+impl Parser<'_> {
+    pub(super) fn typed(&mut self, kind: ExpressionKind, span: Span) -> Expression {
+        use ValueCategory::*;
+        match kind {
+            ExpressionKind::Error => node(kind, CType::ERROR, Value, span),
+            ExpressionKind::Identifier(ref name) => {
+                let (ty, category) = self.identifier_type(name, span);
+                node(kind, ty, category, span)
+            }
+            ExpressionKind::Constant(ref constant) => {
+                let ty = self.constant_type(constant);
+                node(kind, ty, Value, span)
+            }
+            // The terminating null is part of the array
+            ExpressionKind::StringLiteral(ref literal) => {
+                let length = lexer::string_length(literal) + 1;
+                let ty = self.types.array(CType::CHAR, ArraySize::Fixed(length));
+                node(kind, ty, Lvalue, span)
+            }
+            ExpressionKind::Call {
+                function,
+                arguments,
+            } => self.call(*function, arguments, span),
+            ExpressionKind::Member {
+                object,
+                operator,
+                member,
+            } => self.member(*object, operator, member, span),
+            ExpressionKind::Subscript { array, index } => self.subscript(*array, *index, span),
+            ExpressionKind::Postfix { operator, operand } => {
+                let ty = self.increment_type(operator, &operand);
+                let kind = ExpressionKind::Postfix { operator, operand };
+                node(kind, ty, Value, span)
+            }
+            ExpressionKind::Unary { operator, operand } => self.unary(operator, *operand, span),
+            ExpressionKind::SizeofExpression(ref operand) => {
+                match self.bit_field(operand) {
+                    Some(name) => {
+                        let error = self
+                            .error_at_span(span, ErrorCode::InvalidExpression)
+                            .message(format!("`sizeof` can't be applied to bit-field `{}`", name))
+                            .build();
+                        self.diagnose(*error);
+                    }
+                    None => self.check_sized("sizeof", operand.ty, span),
+                }
+                node(kind, CType::UNSIGNED_LONG, Value, span)
+            }
+            ExpressionKind::SizeofType(ref type_name) => {
+                self.check_sized("sizeof", type_name.ty, span);
+                node(kind, CType::UNSIGNED_LONG, Value, span)
+            }
+            ExpressionKind::AlignofType(ref type_name) => {
+                self.check_sized("_Alignof", type_name.ty, span);
+                node(kind, CType::UNSIGNED_LONG, Value, span)
+            }
+            ExpressionKind::Cast { type_name, operand } => self.cast(type_name, *operand, span),
+            ExpressionKind::ImplicitCast { .. } => {
+                unreachable!("Implicit casts are made with their type")
+            }
+            ExpressionKind::CompoundLiteral { ref type_name, .. } => {
+                let ty = type_name.ty;
+                node(kind, ty, Lvalue, span)
+            }
+            ExpressionKind::Binary { operator, lhs, rhs } => {
+                self.binary(operator, *lhs, *rhs, span)
+            }
+            ExpressionKind::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => self.conditional(*condition, then.map(|then| *then), *otherwise, span),
+            ExpressionKind::Assignment { operator, lhs, rhs } => {
+                self.assignment(operator, *lhs, *rhs, span)
+            }
+            // The selected expression is the result, lvalue or not
+            ExpressionKind::Generic {
+                ref controlling,
+                ref associations,
+            } => {
+                let (ty, category) = match self.select_association(controlling, associations) {
+                    Some(selected) => (selected.expression.ty, selected.expression.category),
+                    None => (CType::ERROR, Value),
+                };
+                node(kind, ty, category, span)
+            }
+            // The value of the last statement, if it's an expression
+            ExpressionKind::Statement(ref body) => {
+                let ty = match body.items.last() {
+                    Some(BlockItem::Statement(Statement {
+                        kind: StatementKind::Expression(ref last),
+                        ..
+                    })) => {
+                        let ty = self.types.decay(last.ty);
+                        self.types.unqualified(ty)
+                    }
+                    _ => CType::VOID,
+                };
+                node(kind, ty, Value, span)
+            }
+            ExpressionKind::Offsetof {
+                ref type_name,
+                ref designators,
+            } => {
+                if let Err((message, span)) = self.offset_of(type_name.ty, designators) {
+                    let error = self
+                        .error_at_span(span, ErrorCode::InvalidExpression)
+                        .message(message)
+                        .build();
+                    self.diagnose(*error);
+                }
+                node(kind, CType::UNSIGNED_LONG, Value, span)
+            }
+            ExpressionKind::VaArg { list, type_name } => {
+                let list = self.value(*list);
+                let ty = self.types.unqualified(type_name.ty);
+                let kind = ExpressionKind::VaArg {
+                    list: Box::new(list),
+                    type_name,
+                };
+                node(kind, ty, Value, span)
+            }
+        }
+    }
+
+    fn identifier_type(&mut self, name: &str, span: Span) -> (CType, ValueCategory) {
+        let ty = match self.lookup(name) {
+            Some(
+                Binding::Object(ty)
+                | Binding::Register(ty)
+                | Binding::Static(ty)
+                | Binding::Constexpr(ty, _),
+            ) => ty,
+            Some(Binding::Enumerator(_)) => return (CType::INT, ValueCategory::Value),
+            binding => {
+                let message = match binding {
+                    Some(_) => format!("`{}` is a type, not a value", name),
+                    None => format!("`{}` is not declared", name),
+                };
+                let error = self
+                    .error_at_span(span, ErrorCode::UndeclaredIdentifier)
+                    .message(message)
+                    .build();
+                self.diagnose(*error);
+                return (CType::ERROR, ValueCategory::Value);
+            }
+        };
+        match *self.types.kind(ty) {
+            TypeKind::Function { .. } => (ty, ValueCategory::Function),
+            _ => (ty, ValueCategory::Lvalue),
+        }
+    }
+
+    // Calling a function that isn't declared declares it as a function
+    // returning `int` without a prototype, which C99 removed
+    pub(super) fn implicit_function(&mut self, name: &Identifier) {
+        // The builtins are there without a declaration, and don't
+        // return anything
+        if lang::BUILTIN_FUNCTIONS.contains(&name.name.as_str()) {
+            let ty = self.types.function(CType::VOID, None, false);
+            self.declare(name, Binding::Object(ty), Linkage::External, Defined::No);
+            return;
+        }
+        if self.standard >= Standard::C99 {
+            let error = self
+                .error_at_span(name.span, ErrorCode::UndeclaredIdentifier)
+                .warning()
+                .message(format!(
+                    "Function `{}` is declared implicitly, which C99 doesn't allow",
+                    name.name
+                ))
+                .build();
+            self.diagnose(*error);
+        }
+        let ty = self.types.function(CType::INT, None, false);
+        self.declare(name, Binding::Object(ty), Linkage::External, Defined::No);
+    }
+
+    fn constant_type(&mut self, constant: &Constant) -> CType {
+        match *constant {
+            Constant::Integer(_, kind) => match kind {
+                IntegerKind::Int => CType::INT,
+                IntegerKind::UnsignedInt => CType::UNSIGNED_INT,
+                IntegerKind::Long => CType::LONG,
+                IntegerKind::UnsignedLong => CType::UNSIGNED_LONG,
+                IntegerKind::LongLong => CType::LONG_LONG,
+                IntegerKind::UnsignedLongLong => CType::UNSIGNED_LONG_LONG,
+            },
+            Constant::Floating(_, kind) => match kind {
+                FloatingKind::Float => CType::FLOAT,
+                FloatingKind::Double => CType::DOUBLE,
+                FloatingKind::LongDouble => CType::LONG_DOUBLE,
+            },
+            // Character constants are `int`s in C
+            Constant::Enumeration(_) | Constant::Character(_) => CType::INT,
+            Constant::Bool(_) => CType::BOOL,
+            // `nullptr_t` isn't a type of its own here, `nullptr` is a
+            // null pointer constant like `(void *)0` is
+            Constant::Nullptr => self.types.pointer(CType::VOID),
+        }
+    }
+
+    // An operand whose value is used. An array becomes a pointer to its
+    // first element and a function a pointer to itself, and anything
+    // else that's an lvalue is read, which drops its qualifiers.
+    pub(super) fn value(&mut self, expression: Expression) -> Expression {
+        let (conversion, ty) = match *self.types.kind(expression.ty) {
+            TypeKind::Array { element, .. } => {
+                (Conversion::ArrayToPointer, self.types.pointer(element))
+            }
+            TypeKind::Function { .. } => (
+                Conversion::FunctionToPointer,
+                self.types.pointer(expression.ty),
+            ),
+            _ if expression.category == ValueCategory::Lvalue => (
+                Conversion::LvalueToValue,
+                self.types.unqualified(expression.ty),
+            ),
+            _ => return expression,
+        };
+        implicit_cast(conversion, ty, expression)
+    }
+
+    // Converts a value to another type, which does nothing when it
+    // already has that type
+    pub(super) fn convert(&mut self, expression: Expression, to: CType) -> Expression {
+        let to = self.types.unqualified(to);
+        let from = expression.ty;
+        if from == to || self.types.is_error(from) || self.types.is_error(to) {
+            return expression;
+        }
+        let types = &self.types;
+        let conversion = if *types.kind(to) == TypeKind::Bool && types.is_scalar(from) {
+            Conversion::ToBool
+        } else if types.is_integer(to) && types.is_integer(from) {
+            Conversion::Integer
+        } else if types.is_floating(to) && types.is_integer(from) {
+            Conversion::IntegerToFloating
+        } else if types.is_integer(to) && types.is_floating(from) {
+            Conversion::FloatingToInteger
+        } else if types.is_floating(to) && types.is_floating(from) {
+            Conversion::Floating
+        } else if types.is_pointer(to) && types.is_pointer(from) {
+            Conversion::Pointer
+        } else if types.is_pointer(to) && self.is_null_pointer_constant(&expression) {
+            Conversion::NullToPointer
+        } else if types.is_pointer(to) && types.is_integer(from) {
+            Conversion::IntegerToPointer
+        } else if types.is_integer(to) && types.is_pointer(from) {
+            Conversion::PointerToInteger
+        } else {
+            return expression;
+        };
+        implicit_cast(conversion, to, expression)
+    }
+
+    // An integer constant expression that's 0, or one cast to `void *`,
+    // or `nullptr`
+    pub(super) fn is_null_pointer_constant(&self, expression: &Expression) -> bool {
+        match expression.kind {
+            ExpressionKind::Constant(Constant::Nullptr) => true,
+            ExpressionKind::Cast { ref operand, .. }
+                if self.types.pointee(expression.ty) == Some(CType::VOID) =>
+            {
+                self.is_null_pointer_constant(operand)
+            }
+            _ => {
+                self.types.is_integer(expression.ty)
+                    && self.evaluate_constant(expression) == Some(0)
+            }
+        }
+    }
+
+    // Converts a value as if it were assigned to something of type `to`.
+    // Pointers that are converted to pointers to an incompatible type,
+    // or to one without the qualifiers they point to, and conversions
+    // between pointers and integers are allowed with a warning.
+    pub(super) fn assign(
+        &mut self,
+        expression: Expression,
+        to: CType,
+        assigning: Assigning,
+    ) -> Expression {
+        let to = self.types.unqualified(to);
+        let from = expression.ty;
+        if self.types.is_error(from) || self.types.is_error(to) {
+            return expression;
+        }
+        let (from_pointer, to_pointer) = (self.types.is_pointer(from), self.types.is_pointer(to));
+        let warning = if self.types.is_arithmetic(to) && self.types.is_arithmetic(from)
+            || self.types.is_record(to) && self.types.compatible(to, from)
+            || to_pointer && self.is_null_pointer_constant(&expression)
+            || to == CType::BOOL && from_pointer
+        {
+            None
+        } else if to_pointer && from_pointer {
+            self.pointer_assignment_warning(from, to, assigning)
+        } else if to_pointer && self.types.is_integer(from)
+            || self.types.is_integer(to) && from_pointer
+        {
+            Some(format!(
+                "`{}` is converted to `{}` {} without a cast",
+                self.types.display(from),
+                self.types.display(to),
+                assigning
+            ))
+        } else {
+            let error = self
+                .error_at_span(expression.span, ErrorCode::IncompatibleTypes)
+                .message(format!(
+                    "Can't convert `{}` to `{}` {}",
+                    self.types.display(from),
+                    self.types.display(to),
+                    assigning
+                ))
+                .build();
+            self.diagnose(*error);
+            return expression;
+        };
+        if let Some(message) = warning {
+            let warning = self
+                .error_at_span(expression.span, ErrorCode::IncompatibleTypes)
+                .warning()
+                .message(message)
+                .build();
+            self.diagnose(*warning);
+        }
+        self.convert(expression, to)
+    }
+
+    // Pointers to `void` convert to and from pointers to any object
+    // type, and otherwise what they point to has to be compatible. The
+    // pointer it's converted to has to point to a type with at least the
+    // same qualifiers.
+    fn pointer_assignment_warning(
+        &mut self,
+        from: CType,
+        to: CType,
+        assigning: Assigning,
+    ) -> Option<String> {
+        let (from_target, to_target) = (self.types.pointee(from)?, self.types.pointee(to)?);
+        let void = self.types.is_void(from_target) || self.types.is_void(to_target);
+        let (from_unqualified, to_unqualified) = (
+            self.types.unqualified(from_target),
+            self.types.unqualified(to_target),
+        );
+        if !void && !self.types.compatible(from_unqualified, to_unqualified) {
+            return Some(format!(
+                "`{}` is converted to the incompatible pointer type `{}` {}",
+                self.types.display(from),
+                self.types.display(to),
+                assigning
+            ));
+        }
+        let (from_qualifiers, to_qualifiers) = (
+            self.types.qualifiers(from_target),
+            self.types.qualifiers(to_target),
+        );
+        let mut dropped = Qualifiers::NONE;
+        for qualifier in [
+            Qualifiers::CONST,
+            Qualifiers::VOLATILE,
+            Qualifiers::RESTRICT,
+            Qualifiers::ATOMIC,
+        ] {
+            if from_qualifiers.contains(qualifier) && !to_qualifiers.contains(qualifier) {
+                dropped |= qualifier;
+            }
+        }
+        if dropped.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "`{}` is converted to `{}` {}, which drops `{}` from what it points to",
+            self.types.display(from),
+            self.types.display(to),
+            assigning,
+            dropped.to_string().trim_end()
+        ))
+    }
+
+    // Arguments are converted to the types of the parameters of a
+    // prototype, and the rest get the default argument promotions
+    fn call(&mut self, function: Expression, arguments: Vec<Expression>, span: Span) -> Expression {
+        let function = self.value(function);
+        let arguments: Vec<Expression> = arguments
+            .into_iter()
+            .map(|argument| self.value(argument))
+            .collect();
+        let signature = match self
+            .types
+            .pointee(function.ty)
+            .map(|ty| self.types.kind(ty))
+        {
+            Some(&TypeKind::Function {
+                returns,
+                ref parameters,
+                variadic,
+            }) => Some((returns, parameters.clone(), variadic)),
+            _ => None,
+        };
+        let Some((returns, parameters, variadic)) = signature else {
+            if !self.types.is_error(function.ty) {
+                let error = self
+                    .error_at_span(function.span, ErrorCode::InvalidExpression)
+                    .message(format!(
+                        "The called expression has type `{}`, which isn't a function",
+                        self.types.display(function.ty)
+                    ))
+                    .build();
+                self.diagnose(*error);
+            }
+            let kind = ExpressionKind::Call {
+                function: Box::new(function),
+                arguments,
+            };
+            return node(kind, CType::ERROR, ValueCategory::Value, span);
+        };
+
+        // Without a prototype the number of arguments isn't known
+        let count = arguments.len();
+        let expected = parameters.as_ref().map_or(count, Vec::len);
+        if count < expected || count > expected && !variadic {
+            let error = self
+                .error_at_span(span, ErrorCode::InvalidExpression)
+                .message(format!(
+                    "The function takes {}{} argument{}, but is given {}",
+                    if variadic { "at least " } else { "" },
+                    expected,
+                    if expected == 1 { "" } else { "s" },
+                    count
+                ))
+                .build();
+            self.diagnose(*error);
+        }
+        let parameters = parameters.unwrap_or_default();
+        let arguments = arguments
+            .into_iter()
+            .enumerate()
+            .map(|(i, argument)| match parameters.get(i) {
+                Some(&parameter) => self.assign(argument, parameter, Assigning::Argument(i + 1)),
+                None => {
+                    let promoted = self.types.promote_argument(argument.ty);
+                    self.convert(argument, promoted)
+                }
+            })
+            .collect();
+        let kind = ExpressionKind::Call {
+            function: Box::new(function),
+            arguments,
+        };
+        let returns = self.types.unqualified(returns);
+        node(kind, returns, ValueCategory::Value, span)
+    }
+
+    // `.` gives an lvalue when the struct or union is one, and `->`
+    // always does. The member has the qualifiers of the object it's in.
+    fn member(
+        &mut self,
+        object: Expression,
+        operator: Operator,
+        member: Identifier,
+        span: Span,
+    ) -> Expression {
+        let object = match operator {
+            Operator::Arrow => self.value(object),
+            _ => object,
+        };
+        let record = match operator {
+            Operator::Arrow => self.types.pointee(object.ty),
+            _ => Some(object.ty),
+        };
+        let found = record
+            .filter(|&record| self.types.is_record(record))
+            .map(|record| {
+                (
+                    record,
+                    self.types.member(record, &member.name).map(|m| m.ty),
+                )
+            });
+        let message = match found {
+            _ if self.types.is_error(object.ty) => None,
+            None => Some(format!(
+                "The left operand of `{}` has type `{}`, which isn't {}a struct or union",
+                lang::operator_spelling(operator),
+                self.types.display(object.ty),
+                if operator == Operator::Arrow {
+                    "a pointer to "
+                } else {
+                    ""
+                }
+            )),
+            Some((record, None)) if !self.types.is_complete(record) => Some(format!(
+                "`{}` is incomplete, so it has no member `{}`",
+                self.types.display(record),
+                member.name
+            )),
+            Some((record, None)) => Some(format!(
+                "`{}` has no member named `{}`",
+                self.types.display(record),
+                member.name
+            )),
+            Some((_, Some(_))) => None,
+        };
+        if let Some(message) = message {
+            let error = self
+                .error_at_span(member.span, ErrorCode::InvalidExpression)
+                .message(message)
+                .build();
+            self.diagnose(*error);
+        }
+        let (ty, category) = match found {
+            Some((record, Some(ty))) => {
+                let qualifiers = self.types.qualifiers(record);
+                let category = match operator {
+                    Operator::Arrow => ValueCategory::Lvalue,
+                    _ => object.category,
+                };
+                (self.types.qualified(ty, qualifiers), category)
+            }
+            _ => (CType::ERROR, ValueCategory::Value),
+        };
+        let kind = ExpressionKind::Member {
+            object: Box::new(object),
+            operator,
+            member,
+        };
+        node(kind, ty, category, span)
+    }
+
+    // `a[i]` is `*(a + i)`, so either one can be the pointer
+    fn subscript(&mut self, array: Expression, index: Expression, span: Span) -> Expression {
+        let (array, index) = (self.value(array), self.value(index));
+        let pointer = match (self.types.pointee(array.ty), self.types.pointee(index.ty)) {
+            (Some(_), _) => array.ty,
+            (None, Some(_)) => index.ty,
+            (None, None) => array.ty,
+        };
+        let other = if pointer == array.ty {
+            index.ty
+        } else {
+            array.ty
+        };
+        let message = if self.types.is_error(array.ty) || self.types.is_error(index.ty) {
+            None
+        } else if !self.types.is_pointer(pointer) {
+            Some(format!(
+                "The subscripted value has type `{}`, which isn't an array or a pointer",
+                self.types.display(pointer)
+            ))
+        } else if !self.types.is_integer(other) {
+            Some(format!(
+                "The subscript has type `{}`, which isn't an integer type",
+                self.types.display(other)
+            ))
+        } else if !self.is_sized_pointer(pointer) {
+            Some(format!(
+                "Can't subscript `{}`, as what it points to has no size",
+                self.types.display(pointer)
+            ))
+        } else {
+            None
+        };
+        let ty = match message {
+            Some(message) => {
+                let error = self
+                    .error_at_span(span, ErrorCode::InvalidExpression)
+                    .message(message)
+                    .build();
+                self.diagnose(*error);
+                CType::ERROR
+            }
+            None => self.types.pointee(pointer).unwrap_or(CType::ERROR),
+        };
+        let kind = ExpressionKind::Subscript {
+            array: Box::new(array),
+            index: Box::new(index),
+        };
+        node(kind, ty, ValueCategory::Lvalue, span)
+    }
+
+    // Pointer arithmetic moves by the size of what's pointed to, which
+    // GNU C takes to be 1 for `void` and functions
+    fn is_sized_pointer(&self, ty: CType) -> bool {
+        let Some(target) = self.types.pointee(ty) else {
+            return false;
+        };
+        let unsized_gnu = self.types.is_void(target)
+            || matches!(self.types.kind(target), TypeKind::Function { .. });
+        self.types.is_complete(target)
+            || self.gnu && unsized_gnu
+            || matches!(
+                self.types.kind(target),
+                TypeKind::Array {
+                    size: ArraySize::Variable,
+                    ..
+                }
+            )
+    }
+
+    // `++` and `--` change a modifiable lvalue of arithmetic or pointer
+    // type, and give a value of its type
+    fn increment_type(&mut self, operator: Operator, operand: &Expression) -> CType {
+        if self.types.is_error(operand.ty) {
+            return CType::ERROR;
+        }
+        let what = format!("The operand of `{}`", lang::operator_spelling(operator));
+        if !self.check_modifiable(operand, &what) {
+            return CType::ERROR;
+        }
+        let ty = self.types.unqualified(operand.ty);
+        if self.types.is_arithmetic(ty) || self.is_sized_pointer(ty) {
+            return ty;
+        }
+        self.invalid_operand(operator, operand);
+        CType::ERROR
+    }
+
+    // Reports `what` if it can't be assigned to
+    fn check_modifiable(&mut self, operand: &Expression, what: &str) -> bool {
+        let message = if operand.category != ValueCategory::Lvalue {
+            format!("{} isn't an lvalue", what)
+        } else if !self.types.is_modifiable(operand.ty) {
+            format!(
+                "{} can't be modified, as it has type `{}`",
+                what,
+                self.types.display(operand.ty)
+            )
+        } else {
+            return true;
+        };
+        let error = self
+            .error_at_span(operand.span, ErrorCode::InvalidExpression)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+        false
+    }
+
+    fn invalid_operand(&mut self, operator: Operator, operand: &Expression) {
+        let error = self
+            .error_at_span(operand.span, ErrorCode::InvalidExpression)
+            .message(format!(
+                "Invalid operand to `{}`, `{}`",
+                lang::operator_spelling(operator),
+                self.types.display(operand.ty)
+            ))
+            .build();
+        self.diagnose(*error);
+    }
+
+    fn invalid_operands(&mut self, operator: Operator, lhs: &Expression, rhs: &Expression) {
+        let error = self
+            .error_at_span(lhs.span.to(rhs.span), ErrorCode::InvalidExpression)
+            .message(format!(
+                "Invalid operands to `{}`, `{}` and `{}`",
+                lang::operator_spelling(operator),
+                self.types.display(lhs.ty),
+                self.types.display(rhs.ty)
+            ))
+            .build();
+        self.diagnose(*error);
+    }
+
+    fn unary(&mut self, operator: Operator, operand: Expression, span: Span) -> Expression {
+        let (operand, ty, category) = match operator {
+            Operator::DoublePlus | Operator::DoubleMinus => {
+                let ty = self.increment_type(operator, &operand);
+                (operand, ty, ValueCategory::Value)
+            }
+            Operator::Ampersand => {
+                let ty = self.address_type(&operand);
+                (operand, ty, ValueCategory::Value)
+            }
+            Operator::Asterisk => {
+                let operand = self.value(operand);
+                let (ty, category) = match self.types.pointee(operand.ty) {
+                    Some(target) => match *self.types.kind(target) {
+                        TypeKind::Function { .. } => (target, ValueCategory::Function),
+                        _ => (target, ValueCategory::Lvalue),
+                    },
+                    None => {
+                        if !self.types.is_error(operand.ty) {
+                            self.invalid_operand(operator, &operand);
+                        }
+                        (CType::ERROR, ValueCategory::Value)
+                    }
+                };
+                (operand, ty, category)
+            }
+            // `!` compares with 0 and the rest work on the promoted value
+            _ => {
+                let operand = self.value(operand);
+                let allowed = match operator {
+                    Operator::Tilde => self.types.is_integer(operand.ty),
+                    Operator::Exclaimation => self.types.is_scalar(operand.ty),
+                    _ => self.types.is_arithmetic(operand.ty),
+                };
+                if !allowed {
+                    if !self.types.is_error(operand.ty) {
+                        self.invalid_operand(operator, &operand);
+                    }
+                    (operand, CType::ERROR, ValueCategory::Value)
+                } else if operator == Operator::Exclaimation {
+                    (operand, CType::INT, ValueCategory::Value)
+                } else {
+                    let ty = self.types.promote(operand.ty);
+                    let operand = self.convert(operand, ty);
+                    (operand, ty, ValueCategory::Value)
+                }
+            }
+        };
+        let kind = ExpressionKind::Unary {
+            operator,
+            operand: Box::new(operand),
+        };
+        node(kind, ty, category, span)
+    }
+
+    // Only lvalues and functions have an address, and not bit-fields or
+    // objects declared `register`
+    fn address_type(&mut self, operand: &Expression) -> CType {
+        if self.types.is_error(operand.ty) {
+            return CType::ERROR;
+        }
+        let message = if operand.category == ValueCategory::Value {
+            "The operand of `&` isn't an lvalue or a function".to_string()
+        } else if let Some(name) = self.bit_field(operand) {
+            format!("Can't take the address of bit-field `{}`", name)
+        } else if let Some(name) = self.register(operand) {
+            format!(
+                "Can't take the address of `{}`, which is declared `register`",
+                name
+            )
+        } else {
+            return self.types.pointer(operand.ty);
+        };
+        let error = self
+            .error_at_span(operand.span, ErrorCode::InvalidExpression)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+        CType::ERROR
+    }
+
+    fn register<'e>(&self, expression: &'e Expression) -> Option<&'e str> {
+        match expression.kind {
+            ExpressionKind::Identifier(ref name)
+                if matches!(self.lookup(name), Some(Binding::Register(_))) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    // The name of the bit-field a member expression designates
+    fn bit_field<'e>(&self, expression: &'e Expression) -> Option<&'e str> {
+        let ExpressionKind::Member {
+            ref object,
+            operator,
+            ref member,
+        } = expression.kind
+        else {
+            return None;
+        };
+        let record = match operator {
+            Operator::Arrow => self.types.pointee(object.ty)?,
+            _ => object.ty,
+        };
+        let width = self.types.member(record, &member.name)?.width;
+        width.map(|_| member.name.as_str())
+    }
+
+    // What `sizeof` and `_Alignof` are applied to needs a size, though
+    // a variable length array's is only known at run time
+    fn check_sized(&mut self, operator: &str, ty: CType, span: Span) {
+        let sized = self.types.is_complete(ty)
+            || self.types.is_error(ty)
+            || self.gnu
+                && (self.types.is_void(ty)
+                    || matches!(self.types.kind(ty), TypeKind::Function { .. }))
+            || matches!(
+                self.types.kind(ty),
+                TypeKind::Array {
+                    size: ArraySize::Variable,
+                    ..
+                }
+            );
+        if !sized {
+            let error = self
+                .error_at_span(span, ErrorCode::InvalidExpression)
+                .message(format!(
+                    "`{}` can't be applied to `{}`, which has no size",
+                    operator,
+                    self.types.display(ty)
+                ))
+                .build();
+            self.diagnose(*error);
+        }
+    }
+
+    // Anything can be cast to `void`, and otherwise only scalars can be
+    // cast to scalars. Pointers and floating types don't mix.
+    fn cast(&mut self, type_name: Box<TypeName>, operand: Expression, span: Span) -> Expression {
+        let operand = self.value(operand);
+        let (to, from) = (type_name.ty, operand.ty);
+        let types = &self.types;
+        let message = if types.is_error(to) || types.is_error(from) || types.is_void(to) {
+            None
+        } else if !types.is_scalar(to) {
+            Some(format!(
+                "Can't cast to `{}`, which isn't a scalar type",
+                types.display(to)
+            ))
+        } else if !types.is_scalar(from) {
+            Some(format!(
+                "Can't cast `{}`, which isn't a scalar type",
+                types.display(from)
+            ))
+        } else if types.is_pointer(to) && types.is_floating(from)
+            || types.is_floating(to) && types.is_pointer(from)
+        {
+            Some(format!(
+                "Can't cast `{}` to `{}`",
+                types.display(from),
+                types.display(to)
+            ))
+        } else {
+            None
+        };
+        let ty = match message {
+            Some(message) => {
+                let error = self
+                    .error_at_span(span, ErrorCode::InvalidExpression)
+                    .message(message)
+                    .build();
+                self.diagnose(*error);
+                CType::ERROR
+            }
+            None => self.types.unqualified(to),
+        };
+        let kind = ExpressionKind::Cast {
+            type_name,
+            operand: Box::new(operand),
+        };
+        node(kind, ty, ValueCategory::Value, span)
+    }
+
+    fn binary(
+        &mut self,
+        operator: Operator,
+        lhs: Expression,
+        rhs: Expression,
+        span: Span,
+    ) -> Expression {
+        // The left operand of `,` is only evaluated for its side effects
+        if operator == Operator::Comma {
+            let rhs = self.value(rhs);
+            let ty = rhs.ty;
+            let kind = ExpressionKind::Binary {
+                operator,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+            return node(kind, ty, ValueCategory::Value, span);
+        }
+        let (lhs, rhs) = (self.value(lhs), self.value(rhs));
+        let (lhs, rhs, ty) = if self.types.is_error(lhs.ty) || self.types.is_error(rhs.ty) {
+            (lhs, rhs, CType::ERROR)
+        } else {
+            self.binary_operands(operator, lhs, rhs)
+        };
+        let kind = ExpressionKind::Binary {
+            operator,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        node(kind, ty, ValueCategory::Value, span)
+    }
+
+    // The operands converted to what the operator works on, and the type
+    // of the result
+    fn binary_operands(
+        &mut self,
+        operator: Operator,
+        lhs: Expression,
+        rhs: Expression,
+    ) -> (Expression, Expression, CType) {
+        let types = &self.types;
+        let (a, b) = (lhs.ty, rhs.ty);
+        let arithmetic = types.is_arithmetic(a) && types.is_arithmetic(b);
+        let integer = types.is_integer(a) && types.is_integer(b);
+        match operator {
+            Operator::Asterisk | Operator::ForwardSlash if arithmetic => {
+                self.arithmetic_conversions(lhs, rhs)
+            }
+            Operator::Percent
+            | Operator::Ampersand
+            | Operator::BitwiseXor
+            | Operator::BitwiseOr
+                if integer =>
+            {
+                self.arithmetic_conversions(lhs, rhs)
+            }
+            Operator::Plus | Operator::Minus if arithmetic => self.arithmetic_conversions(lhs, rhs),
+            Operator::Plus | Operator::Minus if types.is_pointer(a) && types.is_integer(b) => {
+                self.pointer_arithmetic(operator, lhs, rhs, a)
+            }
+            Operator::Plus if types.is_integer(a) && types.is_pointer(b) => {
+                self.pointer_arithmetic(operator, lhs, rhs, b)
+            }
+            // The difference is a `ptrdiff_t`
+            Operator::Minus if types.is_pointer(a) && types.is_pointer(b) => {
+                let (a_target, b_target) = (types.pointee(a), types.pointee(b));
+                let (a_target, b_target) = (
+                    a_target.map(|ty| self.types.unqualified(ty)),
+                    b_target.map(|ty| self.types.unqualified(ty)),
+                );
+                let compatible = match (a_target, b_target) {
+                    (Some(a_target), Some(b_target)) => self.types.compatible(a_target, b_target),
+                    _ => false,
+                };
+                if !compatible || !self.is_sized_pointer(a) {
+                    self.invalid_operands(operator, &lhs, &rhs);
+                    return (lhs, rhs, CType::ERROR);
+                }
+                (lhs, rhs, CType::LONG)
+            }
+            // Each operand is promoted on its own
+            Operator::BitwiseLeft | Operator::BitwiseRight if integer => {
+                let (a, b) = (types.promote(a), types.promote(b));
+                let (lhs, rhs) = (self.convert(lhs, a), self.convert(rhs, b));
+                (lhs, rhs, a)
+            }
+            Operator::LessThan
+            | Operator::GreaterThan
+            | Operator::LessThanEquals
+            | Operator::GreaterThanEquals
+            | Operator::Equality
+            | Operator::NotEquality => self.comparison(operator, lhs, rhs),
+            Operator::BooleanAnd | Operator::BooleanOr
+                if types.is_scalar(a) && types.is_scalar(b) =>
+            {
+                (lhs, rhs, CType::INT)
+            }
+            _ => {
+                self.invalid_operands(operator, &lhs, &rhs);
+                (lhs, rhs, CType::ERROR)
+            }
+        }
+    }
+
+    // The usual arithmetic conversions, which bring both operands to a
+    // common type that's also the type of the result
+    fn arithmetic_conversions(
+        &mut self,
+        lhs: Expression,
+        rhs: Expression,
+    ) -> (Expression, Expression, CType) {
+        let ty = self.types.common_type(lhs.ty, rhs.ty);
+        let (lhs, rhs) = (self.convert(lhs, ty), self.convert(rhs, ty));
+        (lhs, rhs, ty)
+    }
+
+    fn pointer_arithmetic(
+        &mut self,
+        operator: Operator,
+        lhs: Expression,
+        rhs: Expression,
+        pointer: CType,
+    ) -> (Expression, Expression, CType) {
+        if !self.is_sized_pointer(pointer) {
+            self.invalid_operands(operator, &lhs, &rhs);
+            return (lhs, rhs, CType::ERROR);
+        }
+        (lhs, rhs, pointer)
+    }
+
+    // Comparisons give an `int`. Arithmetic operands are brought to a
+    // common type, and pointers should point to compatible types. Only
+    // `==` and `!=` can compare a pointer with `void *` or a null
+    // pointer constant.
+    fn comparison(
+        &mut self,
+        operator: Operator,
+        lhs: Expression,
+        rhs: Expression,
+    ) -> (Expression, Expression, CType) {
+        let (a, b) = (lhs.ty, rhs.ty);
+        let equality = matches!(operator, Operator::Equality | Operator::NotEquality);
+        if self.types.is_arithmetic(a) && self.types.is_arithmetic(b) {
+            let (lhs, rhs, _) = self.arithmetic_conversions(lhs, rhs);
+            return (lhs, rhs, CType::INT);
+        }
+        let (a_pointer, b_pointer) = (self.types.is_pointer(a), self.types.is_pointer(b));
+        if a_pointer && b_pointer {
+            let a_target = self.types.pointee(a).unwrap_or(CType::ERROR);
+            let b_target = self.types.pointee(b).unwrap_or(CType::ERROR);
+            let void = self.types.is_void(a_target) || self.types.is_void(b_target);
+            let (a_target, b_target) = (
+                self.types.unqualified(a_target),
+                self.types.unqualified(b_target),
+            );
+            if !(self.types.compatible(a_target, b_target) || void && equality) {
+                self.comparison_warning(
+                    format!(
+                        "Comparison of pointers to different types, `{}` and `{}`",
+                        self.types.display(a),
+                        self.types.display(b)
+                    ),
+                    &lhs,
+                    &rhs,
+                );
+            }
+            return (lhs, rhs, CType::INT);
+        }
+        if a_pointer && self.types.is_integer(b) || self.types.is_integer(a) && b_pointer {
+            let null = match a_pointer {
+                true => self.is_null_pointer_constant(&rhs),
+                false => self.is_null_pointer_constant(&lhs),
+            };
+            if !null || !equality {
+                self.comparison_warning(
+                    format!(
+                        "Comparison between `{}` and `{}`, a pointer and an integer",
+                        self.types.display(a),
+                        self.types.display(b)
+                    ),
+                    &lhs,
+                    &rhs,
+                );
+            }
+            let (lhs, rhs) = match a_pointer {
+                true => (lhs, self.convert(rhs, a)),
+                false => (self.convert(lhs, b), rhs),
+            };
+            return (lhs, rhs, CType::INT);
+        }
+        self.invalid_operands(operator, &lhs, &rhs);
+        (lhs, rhs, CType::ERROR)
+    }
+
+    fn comparison_warning(&mut self, message: String, lhs: &Expression, rhs: &Expression) {
+        let warning = self
+            .error_at_span(lhs.span.to(rhs.span), ErrorCode::IncompatibleTypes)
+            .warning()
+            .message(message)
+            .build();
+        self.diagnose(*warning);
+    }
+
+    // The result has the common type of the last two operands. GNU `a ?: b`
+    // has `a` as its middle operand, which keeps its own type.
+    fn conditional(
+        &mut self,
+        condition: Expression,
+        then: Option<Expression>,
+        otherwise: Expression,
+        span: Span,
+    ) -> Expression {
+        let condition = self.condition(condition, "?:");
+        let then = then.map(|then| self.value(then));
+        let otherwise = self.value(otherwise);
+        let then_ty = then.as_ref().map_or(condition.ty, |then| then.ty);
+        let (then, otherwise, ty) = self.conditional_operands(then, then_ty, otherwise);
+        let kind = ExpressionKind::Conditional {
+            condition: Box::new(condition),
+            then: then.map(Box::new),
+            otherwise: Box::new(otherwise),
+        };
+        node(kind, ty, ValueCategory::Value, span)
+    }
+
+    fn conditional_operands(
+        &mut self,
+        then: Option<Expression>,
+        a: CType,
+        otherwise: Expression,
+    ) -> (Option<Expression>, Expression, CType) {
+        let b = otherwise.ty;
+        let types = &self.types;
+        if types.is_error(a) || types.is_error(b) {
+            return (then, otherwise, CType::ERROR);
+        }
+        if types.is_arithmetic(a) && types.is_arithmetic(b) {
+            let ty = types.common_type(a, b);
+            let then = then.map(|then| self.convert(then, ty));
+            let otherwise = self.convert(otherwise, ty);
+            return (then, otherwise, ty);
+        }
+        if types.is_void(a) && types.is_void(b) || types.is_record(a) && types.compatible(a, b) {
+            return (then, otherwise, a);
+        }
+        let (a_pointer, b_pointer) = (types.is_pointer(a), types.is_pointer(b));
+        if a_pointer && b_pointer {
+            let ty = self.conditional_pointer(a, b, &otherwise);
+            return (then, otherwise, ty);
+        }
+        // One of them being a null pointer constant is fine, and another
+        // integer only gets a warning
+        let then_null = then
+            .as_ref()
+            .is_some_and(|then| self.is_null_pointer_constant(then));
+        if a_pointer && self.types.is_integer(b) || self.types.is_integer(a) && b_pointer {
+            let null = match a_pointer {
+                true => self.is_null_pointer_constant(&otherwise),
+                false => then_null,
+            };
+            if !null {
+                self.comparison_warning(
+                    format!(
+                        "`?:` has a pointer and an integer as its operands, `{}` and `{}`",
+                        self.types.display(a),
+                        self.types.display(b)
+                    ),
+                    &otherwise,
+                    &otherwise,
+                );
+            }
+            return match a_pointer {
+                true => {
+                    let otherwise = self.convert(otherwise, a);
+                    (then, otherwise, a)
+                }
+                false => {
+                    let then = then.map(|then| self.convert(then, b));
+                    (then, otherwise, b)
+                }
+            };
+        }
+        let error = self
+            .error_at_span(otherwise.span, ErrorCode::InvalidExpression)
+            .message(format!(
+                "Invalid operands to `?:`, `{}` and `{}`",
+                self.types.display(a),
+                self.types.display(b)
+            ))
+            .build();
+        self.diagnose(*error);
+        (then, otherwise, CType::ERROR)
+    }
+
+    // Two pointers give a pointer to what they both point to, with the
+    // qualifiers of both. A pointer to `void` and another pointer give a
+    // pointer to `void`.
+    fn conditional_pointer(&mut self, a: CType, b: CType, otherwise: &Expression) -> CType {
+        let a_target = self.types.pointee(a).unwrap_or(CType::ERROR);
+        let b_target = self.types.pointee(b).unwrap_or(CType::ERROR);
+        let qualifiers = self.types.qualifiers(a_target) | self.types.qualifiers(b_target);
+        let (a_unqualified, b_unqualified) = (
+            self.types.unqualified(a_target),
+            self.types.unqualified(b_target),
+        );
+        let target = if self.types.is_void(a_target) || self.types.is_void(b_target) {
+            CType::VOID
+        } else if self.types.compatible(a_unqualified, b_unqualified) {
+            self.types.composite(a_unqualified, b_unqualified)
+        } else {
+            self.comparison_warning(
+                format!(
+                    "`?:` has pointers to different types as its operands, `{}` and `{}`",
+                    self.types.display(a),
+                    self.types.display(b)
+                ),
+                otherwise,
+                otherwise,
+            );
+            CType::VOID
+        };
+        let target = self.types.qualified(target, qualifiers);
+        self.types.pointer(target)
+    }
+
+    // The value of an assignment is the new value of the left operand,
+    // which isn't an lvalue. `a += b` works out `a + b` in the common
+    // type of the two, and the right operand is converted to that type.
+    fn assignment(
+        &mut self,
+        operator: Operator,
+        lhs: Expression,
+        rhs: Expression,
+        span: Span,
+    ) -> Expression {
+        let rhs = self.value(rhs);
+        let (rhs, ty) = if self.types.is_error(lhs.ty) || self.types.is_error(rhs.ty) {
+            (rhs, CType::ERROR)
+        } else {
+            let what = format!(
+                "The left operand of `{}`",
+                lang::operator_spelling(operator)
+            );
+            self.check_modifiable(&lhs, &what);
+            let ty = self.types.unqualified(lhs.ty);
+            (self.assigned_operand(operator, &lhs, rhs, ty), ty)
+        };
+        let kind = ExpressionKind::Assignment {
+            operator,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        node(kind, ty, ValueCategory::Value, span)
+    }
+
+    fn assigned_operand(
+        &mut self,
+        operator: Operator,
+        lhs: &Expression,
+        rhs: Expression,
+        ty: CType,
+    ) -> Expression {
+        let types = &self.types;
+        let arithmetic = types.is_arithmetic(ty) && types.is_arithmetic(rhs.ty);
+        let integer = types.is_integer(ty) && types.is_integer(rhs.ty);
+        match operator {
+            Operator::Equals => self.assign(rhs, ty, Assigning::Assignment),
+            Operator::PlusEquals | Operator::MinusEquals
+                if types.is_pointer(ty) && types.is_integer(rhs.ty) =>
+            {
+                if !self.is_sized_pointer(ty) {
+                    self.invalid_operands(operator, lhs, &rhs);
+                }
+                rhs
+            }
+            Operator::BitwiseLeftEquals | Operator::BitwiseRightEquals if integer => {
+                let promoted = types.promote(rhs.ty);
+                self.convert(rhs, promoted)
+            }
+            Operator::MultiplyEquals
+            | Operator::DivideEquals
+            | Operator::PlusEquals
+            | Operator::MinusEquals
+                if arithmetic =>
+            {
+                let common = types.common_type(ty, rhs.ty);
+                self.convert(rhs, common)
+            }
+            Operator::ModulusEquals
+            | Operator::BitwiseAndEquals
+            | Operator::BitwiseXorEquals
+            | Operator::BitwiseOrEquals
+                if integer =>
+            {
+                let common = types.common_type(ty, rhs.ty);
+                self.convert(rhs, common)
+            }
+            _ => {
+                self.invalid_operands(operator, lhs, &rhs);
+                rhs
+            }
+        }
+    }
+
+    // The controlling expression of `if`, the loops and `?:`, which is
+    // compared with 0
+    pub(super) fn condition(&mut self, condition: Expression, statement: &str) -> Expression {
+        let condition = self.value(condition);
+        if !self.types.is_scalar(condition.ty) && !self.types.is_error(condition.ty) {
+            let error = self
+                .error_at_span(condition.span, ErrorCode::InvalidExpression)
+                .message(format!(
+                    "The condition of `{}` has type `{}`, which isn't a scalar type",
+                    statement,
+                    self.types.display(condition.ty)
+                ))
+                .build();
+            self.diagnose(*error);
+        }
+        condition
+    }
+
+    // `switch` compares the promoted value with each `case`
+    pub(super) fn switch_condition(&mut self, condition: Expression) -> Expression {
+        let condition = self.value(condition);
+        if self.types.is_integer(condition.ty) {
+            let promoted = self.types.promote(condition.ty);
+            return self.convert(condition, promoted);
+        }
+        if !self.types.is_error(condition.ty) {
+            let error = self
+                .error_at_span(condition.span, ErrorCode::InvalidExpression)
+                .message(format!(
+                    "The condition of `switch` has type `{}`, which isn't an integer type",
+                    self.types.display(condition.ty)
+                ))
+                .build();
+            self.diagnose(*error);
+        }
+        condition
+    }
+
+    // A function returning `void` can't return a value, and others
+    // should
+    pub(super) fn return_value(
+        &mut self,
+        value: Option<Expression>,
+        span: Span,
+    ) -> Option<Expression> {
+        let returns = self.returns.unwrap_or(CType::ERROR);
+        let value = value.map(|value| self.value(value));
+        let void = self.types.is_void(returns);
+        let (message, value) = match value {
+            _ if self.types.is_error(returns) => return value,
+            Some(value) if !void => return Some(self.assign(value, returns, Assigning::Return)),
+            None if !void => (
+                format!(
+                    "`return` without a value in a function returning `{}`",
+                    self.types.display(returns)
+                ),
+                None,
+            ),
+            // GNU C lets a `void` function return a `void` expression
+            Some(value) if self.gnu && self.types.is_void(value.ty) => return Some(value),
+            Some(value) => (
+                "A function returning `void` can't return a value".to_string(),
+                Some(value),
+            ),
+            None => return None,
+        };
+        let builder = self.error_at_span(span, ErrorCode::InvalidExpression);
+        let builder = match value {
+            None => builder.warning(),
+            Some(_) => builder,
+        };
+        let error = builder.message(message).build();
+        self.diagnose(*error);
+        value
+    }
+}
+
+fn node(kind: ExpressionKind, ty: CType, category: ValueCategory, span: Span) -> Expression {
+    Expression {
+        kind,
+        ty,
+        category,
+        span,
+    }
+}
+
+fn implicit_cast(conversion: Conversion, ty: CType, operand: Expression) -> Expression {
+    let span = operand.span;
+    Expression {
+        kind: ExpressionKind::ImplicitCast {
+            conversion,
+            operand: Box::new(operand),
+        },
+        ty,
+        category: ValueCategory::Value,
+        span,
+    }
+}
@@ -0,0 +1,462 @@
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::{Constant, Operator};
+use crate::source::Span;
+use crate::target;
+use crate::types::{CType, TypeKind};
+
+use super::scope::ScopeKind;
+use super::{Binding, Parser};
+
+impl Parser<'_> {
+    // The value of an enumerator, either the one it was given or one
+    // more than the enumerator before. It has to fit in an `int`. After
+    // an error the enumerator gets a value anyway, so the ones after it
+    // and their uses aren't reported too.
+    pub(super) fn enumerator_value(
+        &mut self,
+        name: &Identifier,
+        value: Option<&Expression>,
+        next: i64,
+    ) -> i64 {
+        let (constant, span) = match value {
+            Some(value) => match self.evaluate_constant(value) {
+                Some(constant) => (constant, value.span),
+                None => {
+                    let error = self
+                        .error_at_span(value.span, ErrorCode::InvalidConstantExpression)
+                        .message(format!(
+                        "Value of enumerator `{}` could not be evaluated as an integer constant",
+                        name.name
+                    ))
+                        .build();
+                    self.diagnose(*error);
+                    (next, name.span)
+                }
+            },
+            None => (next, name.span),
+        };
+
+        if i32::try_from(constant).is_err() {
+            let error = self
+                .error_at_span(span, ErrorCode::InvalidConstantExpression)
+                .message(format!(
+                    "Value of enumerator `{}` is {}, which doesn't fit in `int`",
+                    name.name, constant
+                ))
+                .build();
+            self.diagnose(*error);
+            return constant as i32 as i64;
+        }
+        constant
+    }
+
+    // Evaluates an integer constant expression, or returns `None` when
+    // it isn't one. The value wraps to the type of the expression, and
+    // comparisons of unsigned operands compare them as unsigned.
+    pub(super) fn evaluate_constant(&self, expression: &Expression) -> Option<i64> {
+        let value = match expression.kind {
+            ExpressionKind::Constant(Constant::Integer(value, _))
+            | ExpressionKind::Constant(Constant::Enumeration(value)) => value,
+            ExpressionKind::Constant(Constant::Character(c)) => target::character_value(c),
+            ExpressionKind::Constant(Constant::Bool(value)) => value as i64,
+            ExpressionKind::Identifier(ref name) => match self.lookup(name)? {
+                Binding::Enumerator(value) | Binding::Constexpr(_, value) => value,
+                _ => return None,
+            },
+            ExpressionKind::SizeofType(ref type_name) => {
+                self.types.size_of(type_name.ty)?.try_into().ok()?
+            }
+            ExpressionKind::SizeofExpression(ref operand) => {
+                self.types.size_of(operand.ty)?.try_into().ok()?
+            }
+            ExpressionKind::AlignofType(ref type_name) => {
+                self.types.align_of(type_name.ty)?.try_into().ok()?
+            }
+            ExpressionKind::Offsetof {
+                ref type_name,
+                ref designators,
+            } => self.offset_of(type_name.ty, designators).ok()??,
+            ExpressionKind::Generic {
+                ref controlling,
+                ref associations,
+            } => {
+                let selected = self.select_association(controlling, associations)?;
+                self.evaluate_constant(&selected.expression)?
+            }
+            // Casts to integer types keep the value as it fits the type.
+            // A floating constant can be cast too, and loses its fraction.
+            // Anything but zero is 1 as a `_Bool`.
+            ExpressionKind::Cast { ref operand, .. }
+            | ExpressionKind::ImplicitCast { ref operand, .. }
+                if self.types.is_integer(expression.ty) =>
+            {
+                let bool = *self.types.kind(expression.ty) == TypeKind::Bool;
+                match operand.kind {
+                    ExpressionKind::Constant(Constant::Floating(value, _)) if bool => {
+                        (value != 0.0) as i64
+                    }
+                    ExpressionKind::Constant(Constant::Floating(value, _)) => {
+                        self.truncate(value, expression.ty)?
+                    }
+                    _ if bool => (self.evaluate_constant(operand)? != 0) as i64,
+                    _ => self.evaluate_constant(operand)?,
+                }
+            }
+            ExpressionKind::Unary {
+                operator,
+                ref operand,
+            } => {
+                let operand = self.evaluate_constant(operand)?;
+                match operator {
+                    Operator::Plus => operand,
+                    Operator::Minus => operand.wrapping_neg(),
+                    Operator::Tilde => !operand,
+                    Operator::Exclaimation => (operand == 0) as i64,
+                    _ => return None,
+                }
+            }
+            ExpressionKind::Binary {
+                operator,
+                ref lhs,
+                ref rhs,
+            } => {
+                let unsigned = self.types.is_unsigned(lhs.ty);
+                let lhs = self.evaluate_constant(lhs)?;
+                // Only the side that decides the result is evaluated
+                match operator {
+                    Operator::BooleanAnd if lhs == 0 => return Some(0),
+                    Operator::BooleanOr if lhs != 0 => return Some(1),
+                    _ => (),
+                }
+                let rhs = self.evaluate_constant(rhs)?;
+                binary(operator, lhs, rhs, unsigned)?
+            }
+            ExpressionKind::Conditional {
+                ref condition,
+                ref then,
+                ref otherwise,
+            } => {
+                let condition = self.evaluate_constant(condition)?;
+                if condition != 0 {
+                    match then {
+                        Some(then) => self.evaluate_constant(then)?,
+                        // `a ?: b`
+                        None => condition,
+                    }
+                } else {
+                    self.evaluate_constant(otherwise)?
+                }
+            }
+            _ => return None,
+        };
+        Some(self.wrap(value, expression.ty))
+    }
+
+    // Where the designated member of `ty` starts, or `None` when an
+    // array subscript isn't an integer constant. The member has to be
+    // one that `&` could be applied to.
+    pub(super) fn offset_of(
+        &self,
+        ty: CType,
+        designators: &[Designator],
+    ) -> Result<Option<i64>, (String, Span)> {
+        let mut ty = ty;
+        let mut offset = Some(0i64);
+        for designator in designators {
+            match designator.kind {
+                DesignatorKind::Member(ref name) => {
+                    let found = match *self.types.kind(ty) {
+                        TypeKind::Struct(_) | TypeKind::Union(_) if self.types.is_complete(ty) => {
+                            self.types.member_offset(ty, &name.name)
+                        }
+                        TypeKind::Error => return Ok(None),
+                        _ => {
+                            return Err((
+                                format!(
+                                    "`offsetof` needs a complete struct or union, not `{}`",
+                                    self.types.display(ty)
+                                ),
+                                designator.span,
+                            ))
+                        }
+                    };
+                    let Some((member, member_offset)) = found else {
+                        return Err((
+                            format!(
+                                "`{}` has no member named `{}`",
+                                self.types.display(ty),
+                                name.name
+                            ),
+                            name.span,
+                        ));
+                    };
+                    if member.width.is_some() {
+                        return Err((
+                            format!("`offsetof` can't be applied to bit-field `{}`", name.name),
+                            name.span,
+                        ));
+                    }
+                    ty = member.ty;
+                    offset = offset.map(|offset| offset + member_offset as i64);
+                }
+                DesignatorKind::Index(ref index) => {
+                    let TypeKind::Array { element, .. } = *self.types.kind(ty) else {
+                        return Err((
+                            format!(
+                                "`offsetof` can only subscript arrays, not `{}`",
+                                self.types.display(ty)
+                            ),
+                            designator.span,
+                        ));
+                    };
+                    let size = self.types.size_of(element).unwrap_or(0) as i64;
+                    offset = offset
+                        .zip(self.evaluate_constant(index))
+                        .map(|(offset, index)| offset.wrapping_add(index.wrapping_mul(size)));
+                    ty = element;
+                }
+            }
+        }
+        Ok(offset)
+    }
+
+    // Whether converting an integer constant expression to the type it
+    // initializes keeps its value, given the value it has after
+    pub(super) fn converts_exactly(&self, expression: &Expression, value: i64) -> bool {
+        let ExpressionKind::ImplicitCast { ref operand, .. } = expression.kind else {
+            return true;
+        };
+        if let ExpressionKind::Constant(Constant::Floating(original, _)) = operand.kind {
+            return original.fract() == 0.0;
+        }
+        match self.evaluate_constant(operand) {
+            Some(original) if self.types.is_integer(operand.ty) => {
+                self.exact(original, operand.ty) == self.exact(value, expression.ty)
+            }
+            _ => true,
+        }
+    }
+
+    // The value an integer of the type stands for
+    fn exact(&self, value: i64, ty: CType) -> i128 {
+        match self.types.is_unsigned(ty) {
+            true => value as u64 as i128,
+            false => value as i128,
+        }
+    }
+
+    // Whether an initializer only has constants, which is all that's
+    // checked of what `constexpr` objects other than integers are
+    // initialized with. An object with static storage can also be
+    // initialized with `addresses`.
+    pub(super) fn constant_initializer(&self, initializer: &Initializer, addresses: bool) -> bool {
+        match *initializer {
+            Initializer::Expression(ref expression) => {
+                self.is_constant(expression) || addresses && self.is_address_constant(expression)
+            }
+            Initializer::List(ref list) => list
+                .items
+                .iter()
+                .all(|item| self.constant_initializer(&item.initializer, addresses)),
+        }
+    }
+
+    // The address of an object with static storage or of a function,
+    // give or take an integer constant
+    fn is_address_constant(&self, expression: &Expression) -> bool {
+        match expression.kind {
+            ExpressionKind::Unary {
+                operator: Operator::Ampersand,
+                ref operand,
+            }
+            | ExpressionKind::ImplicitCast {
+                conversion: Conversion::ArrayToPointer | Conversion::FunctionToPointer,
+                ref operand,
+            } => self.is_static_lvalue(operand),
+            ExpressionKind::Cast { ref operand, .. }
+            | ExpressionKind::ImplicitCast { ref operand, .. } => self.is_address_constant(operand),
+            ExpressionKind::Binary {
+                operator: operator @ (Operator::Plus | Operator::Minus),
+                ref lhs,
+                ref rhs,
+            } => {
+                self.is_address_constant(lhs) && self.is_constant(rhs)
+                    || operator == Operator::Plus
+                        && self.is_constant(lhs)
+                        && self.is_address_constant(rhs)
+            }
+            ExpressionKind::Conditional {
+                ref condition,
+                ref then,
+                ref otherwise,
+            } => {
+                let either = |expression| {
+                    self.is_constant(expression) || self.is_address_constant(expression)
+                };
+                self.is_constant(condition)
+                    && then.as_deref().is_none_or(either)
+                    && either(otherwise)
+            }
+            _ => false,
+        }
+    }
+
+    // An lvalue that designates an object with static storage or a
+    // function
+    fn is_static_lvalue(&self, expression: &Expression) -> bool {
+        match expression.kind {
+            ExpressionKind::Identifier(ref name) => self.has_static_storage(name),
+            ExpressionKind::StringLiteral(_) => true,
+            ExpressionKind::CompoundLiteral { .. } => self.symbols.kind() == ScopeKind::File,
+            ExpressionKind::Member {
+                ref object,
+                operator: Operator::Period,
+                ..
+            } => self.is_static_lvalue(object),
+            ExpressionKind::Member { ref object, .. }
+            | ExpressionKind::Unary {
+                operator: Operator::Asterisk,
+                operand: ref object,
+            } => self.is_address_constant(object),
+            ExpressionKind::Subscript {
+                ref array,
+                ref index,
+            } => {
+                self.is_address_constant(array) && self.is_constant(index)
+                    || self.is_constant(array) && self.is_address_constant(index)
+            }
+            _ => false,
+        }
+    }
+
+    fn is_constant(&self, expression: &Expression) -> bool {
+        match expression.kind {
+            ExpressionKind::Error
+            | ExpressionKind::Constant(_)
+            | ExpressionKind::StringLiteral(_)
+            | ExpressionKind::SizeofExpression(_)
+            | ExpressionKind::SizeofType(_)
+            | ExpressionKind::AlignofType(_) => true,
+            ExpressionKind::Identifier(ref name) => matches!(
+                self.lookup(name),
+                Some(Binding::Enumerator(_) | Binding::Constexpr(..))
+            ),
+            ExpressionKind::Unary {
+                operator:
+                    Operator::Plus | Operator::Minus | Operator::Tilde | Operator::Exclaimation,
+                ref operand,
+            }
+            | ExpressionKind::Cast { ref operand, .. }
+            | ExpressionKind::ImplicitCast { ref operand, .. } => self.is_constant(operand),
+            ExpressionKind::Binary {
+                operator,
+                ref lhs,
+                ref rhs,
+            } => operator != Operator::Comma && self.is_constant(lhs) && self.is_constant(rhs),
+            ExpressionKind::Conditional {
+                ref condition,
+                ref then,
+                ref otherwise,
+            } => {
+                self.is_constant(condition)
+                    && then.as_ref().is_none_or(|then| self.is_constant(then))
+                    && self.is_constant(otherwise)
+            }
+            ExpressionKind::Generic {
+                ref controlling,
+                ref associations,
+            } => self
+                .select_association(controlling, associations)
+                .is_some_and(|selected| self.is_constant(&selected.expression)),
+            _ => false,
+        }
+    }
+
+    // A floating value converted to an integer type, which it has to
+    // fit once its fraction is dropped
+    fn truncate(&self, value: f64, ty: CType) -> Option<i64> {
+        let bits = self.types.size_of(ty)? as i32 * 8;
+        let value = value.trunc();
+        let (min, max) = match self.types.is_unsigned(ty) {
+            true => (0.0, 2f64.powi(bits)),
+            false => (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1)),
+        };
+        match (value >= min && value < max, value < 0.0) {
+            (true, true) => Some(value as i64),
+            (true, false) => Some(value as u64 as i64),
+            (false, _) => None,
+        }
+    }
+
+    // The value as the integer type has it, cut down to its size
+    fn wrap(&self, value: i64, ty: CType) -> i64 {
+        let bits = match self.types.size_of(ty) {
+            Some(size @ 1..=7) if self.types.is_integer(ty) => size * 8,
+            _ => return value,
+        };
+        let shift = 64 - bits;
+        match self.types.is_unsigned(ty) {
+            true => ((value as u64) << shift >> shift) as i64,
+            false => value << shift >> shift,
+        }
+    }
+
+    // The association of a `_Generic` selection that the type of the
+    // controlling expression picks, or the default one
+    pub(super) fn select_association<'e>(
+        &self,
+        controlling: &Expression,
+        associations: &'e [GenericAssociation],
+    ) -> Option<&'e GenericAssociation> {
+        if self.types.is_error(controlling.ty) {
+            return None;
+        }
+        associations
+            .iter()
+            .find(|association| {
+                association
+                    .type_name
+                    .as_ref()
+                    .is_some_and(|type_name| self.types.compatible(controlling.ty, type_name.ty))
+            })
+            .or_else(|| {
+                associations
+                    .iter()
+                    .find(|association| association.type_name.is_none())
+            })
+    }
+}
+
+fn binary(operator: Operator, lhs: i64, rhs: i64, unsigned: bool) -> Option<i64> {
+    let (a, b) = (lhs as u64, rhs as u64);
+    let value = match operator {
+        Operator::ForwardSlash if unsigned => a.checked_div(b)? as i64,
+        Operator::Percent if unsigned => a.checked_rem(b)? as i64,
+        Operator::BitwiseRight if unsigned => a.checked_shr(u32::try_from(rhs).ok()?)? as i64,
+        Operator::LessThan if unsigned => (a < b) as i64,
+        Operator::GreaterThan if unsigned => (a > b) as i64,
+        Operator::LessThanEquals if unsigned => (a <= b) as i64,
+        Operator::GreaterThanEquals if unsigned => (a >= b) as i64,
+        Operator::Asterisk => lhs.wrapping_mul(rhs),
+        Operator::ForwardSlash => lhs.checked_div(rhs)?,
+        Operator::Percent => lhs.checked_rem(rhs)?,
+        Operator::Plus => lhs.wrapping_add(rhs),
+        Operator::Minus => lhs.wrapping_sub(rhs),
+        Operator::BitwiseLeft => lhs.checked_shl(u32::try_from(rhs).ok()?)?,
+        Operator::BitwiseRight => lhs.checked_shr(u32::try_from(rhs).ok()?)?,
+        Operator::LessThan => (lhs < rhs) as i64,
+        Operator::GreaterThan => (lhs > rhs) as i64,
+        Operator::LessThanEquals => (lhs <= rhs) as i64,
+        Operator::GreaterThanEquals => (lhs >= rhs) as i64,
+        Operator::Equality => (lhs == rhs) as i64,
+        Operator::NotEquality => (lhs != rhs) as i64,
+        Operator::Ampersand => lhs & rhs,
+        Operator::BitwiseXor => lhs ^ rhs,
+        Operator::BitwiseOr => lhs | rhs,
+        Operator::BooleanAnd => (lhs != 0 && rhs != 0) as i64,
+        Operator::BooleanOr => (lhs != 0 || rhs != 0) as i64,
+        _ => return None,
+    };
+    Some(value)
+}
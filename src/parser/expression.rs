@@ -0,0 +1,553 @@
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::{self, Constant, Keyword, Operator, Punctuator, Standard};
+use crate::lexer::Token;
+use crate::source::Span;
+
+use super::Parser;
+
+// The 15 levels of C precedence, from tightest to loosest:
+//
+//    1  postfix `[] () . -> ++ --`     left    `postfix_expression`
+//    2  prefix, `sizeof` and casts     right   `unary_expression`,
+//                                              `cast_expression`
+//    3  `* / %`                        left    `binary_expression`
+//    4  `+ -`                          left
+//    5  `<< >>`                        left
+//    6  `< > <= >=`                    left
+//    7  `== !=`                        left
+//    8  `&`                            left
+//    9  `^`                            left
+//   10  `|`                            left
+//   11  `&&`                           left
+//   12  `||`                           left
+//   13  `?:`                           right   `conditional_expression`
+//   14  assignment                     right   `assignment_expression`
+//   15  `,`                            left    `expression`
+impl Parser<'_> {
+    pub(super) fn expression(&mut self) -> CompResult<Expression> {
+        let mut lhs = self.assignment_expression()?;
+        while self.eat_operator(Operator::Comma) {
+            let rhs = self.assignment_expression()?;
+            let span = lhs.span.to(rhs.span);
+            let kind = ExpressionKind::Binary {
+                operator: Operator::Comma,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+            lhs = self.typed(kind, span);
+        }
+        Ok(lhs)
+    }
+
+    // Assignment is right associative, `a = b = c` assigns `c` to `b`
+    // and then `b` to `a`
+    pub(super) fn assignment_expression(&mut self) -> CompResult<Expression> {
+        let lhs = self.conditional_expression()?;
+        let Some(operator) = self.peek_assignment_operator() else {
+            return Ok(lhs);
+        };
+        self.index += 1;
+
+        let rhs = self.assignment_expression()?;
+        let span = lhs.span.to(rhs.span);
+        let kind = ExpressionKind::Assignment {
+            operator,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        Ok(self.typed(kind, span))
+    }
+
+    // The middle operand can be any expression, even one with commas,
+    // and the last is another conditional so `?:` nests to the right.
+    // GNU lets the middle be left out, `a ?: b` is `a` unless it's zero.
+    pub(super) fn conditional_expression(&mut self) -> CompResult<Expression> {
+        let condition = self.binary_expression(0)?;
+        let question = self.current_span();
+        if !self.eat_operator(Operator::QuestionMark) {
+            return Ok(condition);
+        }
+
+        let then = if self.peek_operator(Operator::Colon) {
+            self.require_gnu("`?:` without a middle operand", self.span_from(question));
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        self.expect_operator(Operator::Colon)?;
+        let otherwise = self.conditional_expression()?;
+        let span = condition.span.to(otherwise.span);
+        let kind = ExpressionKind::Conditional {
+            condition: Box::new(condition),
+            then,
+            otherwise: Box::new(otherwise),
+        };
+        Ok(self.typed(kind, span))
+    }
+
+    // Binary operators by precedence climbing, all of them are left
+    // associative
+    fn binary_expression(&mut self, min_precedence: u8) -> CompResult<Expression> {
+        let mut lhs = self.cast_expression()?;
+
+        while let Some(Token::Operator(operator, _)) = self.peek() {
+            let operator = *operator;
+            let Some(precedence) = lang::binary_precedence(operator) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.index += 1;
+
+            let rhs = self.binary_expression(precedence + 1)?;
+            let span = lhs.span.to(rhs.span);
+            let kind = ExpressionKind::Binary {
+                operator,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+            lhs = self.typed(kind, span);
+        }
+
+        Ok(lhs)
+    }
+
+    // A parenthesis followed by a type name is a cast, anything else
+    // in parentheses is an expression. Which one `(T)` is depends on
+    // whether `T` is a typedef name at this point.
+    fn cast_expression(&mut self) -> CompResult<Expression> {
+        if !self.parenthesized_type_follows() {
+            return self.unary_expression();
+        }
+
+        let start = self.current_span();
+        self.index += 1;
+        let type_name = self.type_name()?;
+        self.expect_operator(Operator::RParen)?;
+        if self.peek_punctuator(Punctuator::LCurly) {
+            return self.compound_literal(start, type_name);
+        }
+        let operand = self.cast_expression()?;
+        let span = start.to(operand.span);
+        let kind = ExpressionKind::Cast {
+            type_name: Box::new(type_name),
+            operand: Box::new(operand),
+        };
+        Ok(self.typed(kind, span))
+    }
+
+    fn unary_expression(&mut self) -> CompResult<Expression> {
+        let start = self.current_span();
+        if self.eat_keyword(Keyword::Sizeof) {
+            return self.sizeof_expression(start);
+        }
+        // Unlike `sizeof`, `_Alignof` only takes a type name
+        if self.eat_keyword(Keyword::Alignof) {
+            self.expect_operator(Operator::LParen)?;
+            let type_name = self.type_name()?;
+            self.expect_operator(Operator::RParen)?;
+            let span = self.span_from(start);
+            return Ok(self.typed(ExpressionKind::AlignofType(Box::new(type_name)), span));
+        }
+        // Only there to silence warnings about extensions
+        if self.eat_keyword(Keyword::Extension) {
+            return self.cast_expression();
+        }
+
+        let operator = match self.peek() {
+            // `++(int)x` isn't valid, so these take a unary expression
+            Some(Token::Operator(op @ (Operator::DoublePlus | Operator::DoubleMinus), _)) => {
+                let operator = *op;
+                self.index += 1;
+                let operand = self.unary_expression()?;
+                let span = start.to(operand.span);
+                let kind = ExpressionKind::Unary {
+                    operator,
+                    operand: Box::new(operand),
+                };
+                return Ok(self.typed(kind, span));
+            }
+            Some(Token::Operator(
+                op @ (Operator::Ampersand
+                | Operator::Asterisk
+                | Operator::Plus
+                | Operator::Minus
+                | Operator::Tilde
+                | Operator::Exclaimation),
+                _,
+            )) => *op,
+            _ => return self.postfix_expression(),
+        };
+        self.index += 1;
+
+        let operand = self.cast_expression()?;
+        let span = start.to(operand.span);
+        let kind = ExpressionKind::Unary {
+            operator,
+            operand: Box::new(operand),
+        };
+        Ok(self.typed(kind, span))
+    }
+
+    // `sizeof (T)` takes a type name, while `sizeof (x)` is the size
+    // of a parenthesized expression
+    fn sizeof_expression(&mut self, start: Span) -> CompResult<Expression> {
+        let kind = if self.parenthesized_type_follows() {
+            let type_start = self.current_span();
+            self.index += 1;
+            let type_name = self.type_name()?;
+            self.expect_operator(Operator::RParen)?;
+            if self.peek_punctuator(Punctuator::LCurly) {
+                let literal = self.compound_literal(type_start, type_name)?;
+                ExpressionKind::SizeofExpression(Box::new(literal))
+            } else {
+                ExpressionKind::SizeofType(Box::new(type_name))
+            }
+        } else {
+            ExpressionKind::SizeofExpression(Box::new(self.unary_expression()?))
+        };
+        let span = self.span_from(start);
+        Ok(self.typed(kind, span))
+    }
+
+    // After `(T)`, where the braces make it a compound literal rather
+    // than a cast. It's a postfix expression, so `(T){...}.x` works.
+    fn compound_literal(&mut self, start: Span, mut type_name: TypeName) -> CompResult<Expression> {
+        let initializers = self.initializer_list()?;
+        self.require_standard(Standard::C99, "A compound literal", self.span_from(start));
+        let (ty, initializers) = self.initialize_literal(type_name.ty, initializers);
+        type_name.ty = ty;
+        let kind = ExpressionKind::CompoundLiteral {
+            type_name: Box::new(type_name),
+            initializers,
+        };
+        let span = self.span_from(start);
+        let literal = self.typed(kind, span);
+        self.postfix_operators(literal)
+    }
+
+    fn postfix_expression(&mut self) -> CompResult<Expression> {
+        let expression = self.primary_expression()?;
+        self.postfix_operators(expression)
+    }
+
+    fn postfix_operators(&mut self, mut expression: Expression) -> CompResult<Expression> {
+        loop {
+            let kind = if self.eat_operator(Operator::LParen) {
+                let mut arguments = Vec::new();
+                if !self.eat_operator(Operator::RParen) {
+                    loop {
+                        arguments.push(self.assignment_expression()?);
+                        if !self.eat_operator(Operator::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect_operator(Operator::RParen)?;
+                }
+                ExpressionKind::Call {
+                    function: Box::new(expression),
+                    arguments,
+                }
+            } else if self.eat_operator(Operator::LBracket) {
+                let index = self.expression()?;
+                self.expect_operator(Operator::RBracket)?;
+                ExpressionKind::Subscript {
+                    array: Box::new(expression),
+                    index: Box::new(index),
+                }
+            } else if let Some(Token::Operator(
+                operator @ (Operator::Period | Operator::Arrow),
+                _,
+            )) = self.peek()
+            {
+                let operator = *operator;
+                self.index += 1;
+                let member = self.expect_identifier()?;
+                ExpressionKind::Member {
+                    object: Box::new(expression),
+                    operator,
+                    member,
+                }
+            } else if let Some(Token::Operator(
+                operator @ (Operator::DoublePlus | Operator::DoubleMinus),
+                _,
+            )) = self.peek()
+            {
+                let operator = *operator;
+                self.index += 1;
+                ExpressionKind::Postfix {
+                    operator,
+                    operand: Box::new(expression),
+                }
+            } else {
+                return Ok(expression);
+            };
+
+            let span = match kind {
+                ExpressionKind::Call { ref function, .. } => self.span_from(function.span),
+                ExpressionKind::Subscript { ref array, .. } => self.span_from(array.span),
+                ExpressionKind::Member { ref object, .. } => self.span_from(object.span),
+                ExpressionKind::Postfix { ref operand, .. } => self.span_from(operand.span),
+                _ => unreachable!("Only postfix expressions are built here"),
+            };
+            expression = self.typed(kind, span);
+        }
+    }
+
+    fn primary_expression(&mut self) -> CompResult<Expression> {
+        let start = self.current_span();
+        // Every function body declares `__func__`
+        let is_func = matches!(self.peek(), Some(Token::Identifier(name, _)) if name == "__func__");
+        if is_func && self.standard >= Standard::C99 && self.lookup("__func__").is_none() {
+            let error = self
+                .error_at_span(start, ErrorCode::InvalidDeclaration)
+                .message("`__func__` can only be used inside of a function".into())
+                .build();
+            self.report(*error);
+        }
+        let kind = match self.peek() {
+            Some(Token::Identifier(name, _)) => ExpressionKind::Identifier(name.clone()),
+            Some(Token::Constant(constant, _)) => ExpressionKind::Constant(constant.clone()),
+            Some(Token::StringLiteral(literal, _)) => {
+                ExpressionKind::StringLiteral(literal.clone())
+            }
+            Some(Token::Keyword(Keyword::Generic, _)) => return self.generic_selection(),
+            Some(Token::Keyword(Keyword::BuiltinVaArg, _)) => return self.va_arg(),
+            Some(Token::Keyword(Keyword::BuiltinOffsetof, _)) => return self.offsetof(),
+            Some(Token::Keyword(Keyword::True, _)) => {
+                ExpressionKind::Constant(Constant::Bool(true))
+            }
+            Some(Token::Keyword(Keyword::False, _)) => {
+                ExpressionKind::Constant(Constant::Bool(false))
+            }
+            Some(Token::Keyword(Keyword::Nullptr, _)) => {
+                ExpressionKind::Constant(Constant::Nullptr)
+            }
+            Some(token)
+                if token.is_operator(Operator::LParen)
+                    && self
+                        .peek_nth(1)
+                        .is_some_and(|token| token.is_punctuator(Punctuator::LCurly)) =>
+            {
+                return self.statement_expression();
+            }
+            Some(token) if token.is_operator(Operator::LParen) => {
+                self.index += 1;
+                let mut inner = self.expression()?;
+                self.expect_operator(Operator::RParen)?;
+                // The parentheses are part of the expression as written
+                inner.span = self.span_from(start);
+                return Ok(inner);
+            }
+            // Reported here so the rest of the statement can still be
+            // parsed, `x = ;` only has the one mistake
+            _ => {
+                let error = self.error_expected("expression");
+                self.report(*error);
+                ExpressionKind::Error
+            }
+        };
+        if kind == ExpressionKind::Error {
+            let span = Span {
+                hi: start.lo,
+                ..start
+            };
+            return Ok(self.typed(kind, span));
+        }
+        self.index += 1;
+        // Calling a function that isn't declared declares it, before C99
+        if let ExpressionKind::Identifier(ref name) = kind {
+            if self.lookup(name).is_none() && self.peek_operator(Operator::LParen) {
+                let name = Identifier {
+                    name: name.clone(),
+                    span: start,
+                };
+                self.implicit_function(&name);
+            }
+        }
+        Ok(self.typed(kind, start))
+    }
+
+    // GNU `({ int y = f(x); y * y; })`, whose value is that of the last
+    // statement in the block. It needs a function to run in.
+    fn statement_expression(&mut self) -> CompResult<Expression> {
+        let start = self.current_span();
+        self.expect_operator(Operator::LParen)?;
+        let body = self.compound_statement()?;
+        self.expect_operator(Operator::RParen)?;
+        let span = self.span_from(start);
+        self.require_gnu("A statement expression", span);
+        if self.returns.is_none() {
+            let error = self
+                .error_at_span(span, ErrorCode::InvalidExpression)
+                .message("A statement expression can only be used inside of a function".into())
+                .build();
+            self.report(*error);
+        }
+        Ok(self.typed(ExpressionKind::Statement(body), span))
+    }
+
+    // `__builtin_va_arg(list, T)`, which `va_arg` from `<stdarg.h>`
+    // expands to
+    fn va_arg(&mut self) -> CompResult<Expression> {
+        let start = self.current_span();
+        self.expect_keyword(Keyword::BuiltinVaArg)?;
+        self.expect_operator(Operator::LParen)?;
+        let list = self.assignment_expression()?;
+        self.expect_operator(Operator::Comma)?;
+        let type_name = self.type_name()?;
+        self.expect_operator(Operator::RParen)?;
+        let kind = ExpressionKind::VaArg {
+            list: Box::new(list),
+            type_name: Box::new(type_name),
+        };
+        let span = self.span_from(start);
+        Ok(self.typed(kind, span))
+    }
+
+    // `__builtin_offsetof(T, member)`, where the member can be followed
+    // by more members and array subscripts
+    fn offsetof(&mut self) -> CompResult<Expression> {
+        let start = self.current_span();
+        self.expect_keyword(Keyword::BuiltinOffsetof)?;
+        self.expect_operator(Operator::LParen)?;
+        let type_name = self.type_name()?;
+        self.expect_operator(Operator::Comma)?;
+        let member = self.expect_identifier()?;
+        let mut designators = vec![Designator {
+            span: member.span,
+            kind: DesignatorKind::Member(member),
+        }];
+        loop {
+            let designator_start = self.current_span();
+            let kind = if self.eat_operator(Operator::Period) {
+                DesignatorKind::Member(self.expect_identifier()?)
+            } else if self.eat_operator(Operator::LBracket) {
+                let index = self.expression()?;
+                self.expect_operator(Operator::RBracket)?;
+                DesignatorKind::Index(index)
+            } else {
+                break;
+            };
+            designators.push(Designator {
+                kind,
+                span: self.span_from(designator_start),
+            });
+        }
+        self.expect_operator(Operator::RParen)?;
+        let kind = ExpressionKind::Offsetof {
+            type_name: Box::new(type_name),
+            designators,
+        };
+        let span = self.span_from(start);
+        Ok(self.typed(kind, span))
+    }
+
+    // `_Generic(x, int: a, double: b, default: c)` is whichever of the
+    // expressions is associated with the type of `x`
+    fn generic_selection(&mut self) -> CompResult<Expression> {
+        let start = self.current_span();
+        self.expect_keyword(Keyword::Generic)?;
+        self.expect_operator(Operator::LParen)?;
+        // The controlling expression is converted as an operand would be,
+        // so arrays match pointers and qualifiers are dropped
+        let controlling = self.assignment_expression()?;
+        let controlling = self.value(controlling);
+        let mut associations = Vec::new();
+        while self.eat_operator(Operator::Comma) {
+            let association_start = self.current_span();
+            let type_name = if self.eat_keyword(Keyword::Default) {
+                None
+            } else {
+                Some(self.type_name()?)
+            };
+            self.expect_operator(Operator::Colon)?;
+            let expression = self.assignment_expression()?;
+            associations.push(GenericAssociation {
+                type_name,
+                expression,
+                span: self.span_from(association_start),
+            });
+        }
+        self.expect_operator(Operator::RParen)?;
+        if associations.is_empty() {
+            return Err(self.error_expected("`,`"));
+        }
+        self.check_generic_associations(&controlling, &associations);
+
+        let kind = ExpressionKind::Generic {
+            controlling: Box::new(controlling),
+            associations,
+        };
+        let span = self.span_from(start);
+        Ok(self.typed(kind, span))
+    }
+
+    // No two associations can have compatible types and there's at
+    // most one `default`, and something has to match
+    fn check_generic_associations(
+        &mut self,
+        controlling: &Expression,
+        associations: &[GenericAssociation],
+    ) {
+        for (i, association) in associations.iter().enumerate() {
+            let ty = association.type_name.as_ref().map(|type_name| type_name.ty);
+            let repeated =
+                associations[..i]
+                    .iter()
+                    .any(|earlier| match (ty, earlier.type_name.as_ref()) {
+                        (Some(ty), Some(earlier)) => self.types.compatible(ty, earlier.ty),
+                        (None, None) => true,
+                        _ => false,
+                    });
+            if repeated {
+                let message = match ty {
+                    Some(ty) => {
+                        format!("`{}` has more than one association", self.types.display(ty))
+                    }
+                    None => "More than one `default` association".to_string(),
+                };
+                let error = self
+                    .error_at_span(association.span, ErrorCode::InvalidGenericSelection)
+                    .message(message)
+                    .build();
+                self.report(*error);
+            }
+        }
+
+        if !self.types.is_error(controlling.ty)
+            && self.select_association(controlling, associations).is_none()
+        {
+            let error = self
+                .error_at_span(controlling.span, ErrorCode::InvalidGenericSelection)
+                .message(format!(
+                    "No association matches `{}` and there is no `default`",
+                    self.types.display(controlling.ty)
+                ))
+                .build();
+            self.report(*error);
+        }
+    }
+
+    fn peek_assignment_operator(&self) -> Option<Operator> {
+        match self.peek()? {
+            Token::Operator(
+                op @ (Operator::Equals
+                | Operator::MultiplyEquals
+                | Operator::DivideEquals
+                | Operator::ModulusEquals
+                | Operator::PlusEquals
+                | Operator::MinusEquals
+                | Operator::BitwiseLeftEquals
+                | Operator::BitwiseRightEquals
+                | Operator::BitwiseAndEquals
+                | Operator::BitwiseXorEquals
+                | Operator::BitwiseOrEquals),
+                _,
+            ) => Some(*op),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::source::Span;
+use crate::types::{ArraySize, CType, TypeKind};
+
+use super::check::Assigning;
+use super::Parser;
+
+// An item of a brace list on its way to the part of the object it
+// initializes, along with how many of its designators were followed
+struct Pending {
+    item: InitializerItem,
+    designator: usize,
+}
+
+impl Parser<'_> {
+    // Checks an initializer against the type it initializes, converting
+    // every expression in it as if by assignment. An array of unknown
+    // size gets its size from the initializer, so the type is given back
+    // along with it.
+    pub(super) fn initialize(
+        &mut self,
+        ty: CType,
+        initializer: Initializer,
+    ) -> (CType, Initializer) {
+        let (length, initializer) = match initializer {
+            Initializer::Expression(expression) => {
+                let (length, expression) = self.initialize_expression(ty, expression);
+                (length, Initializer::Expression(expression))
+            }
+            Initializer::List(list) => {
+                let (length, list) = self.initialize_list(ty, list);
+                (length, Initializer::List(list))
+            }
+        };
+        (self.sized(ty, length), initializer)
+    }
+
+    // A compound literal is initialized like an object of its type name
+    pub(super) fn initialize_literal(
+        &mut self,
+        ty: CType,
+        list: InitializerList,
+    ) -> (CType, InitializerList) {
+        let (length, list) = self.initialize_list(ty, list);
+        (self.sized(ty, length), list)
+    }
+
+    fn sized(&mut self, ty: CType, length: Option<u64>) -> CType {
+        match (self.types.kind(ty), length) {
+            (
+                &TypeKind::Array {
+                    element,
+                    size: ArraySize::Incomplete,
+                },
+                Some(length),
+            ) => self.types.array(element, ArraySize::Fixed(length)),
+            _ => ty,
+        }
+    }
+
+    // An array can only be initialized by an expression if it's an array
+    // of characters and the expression a string literal
+    fn initialize_expression(
+        &mut self,
+        ty: CType,
+        expression: Expression,
+    ) -> (Option<u64>, Expression) {
+        if self.is_string_initializer(ty, &expression) {
+            return (Some(self.string_length(ty, &expression)), expression);
+        }
+        if let TypeKind::Array { .. } = *self.types.kind(ty) {
+            let error = self
+                .error_at_span(expression.span, ErrorCode::InvalidInitializer)
+                .message(format!(
+                    "`{}` has to be initialized with a list in braces",
+                    self.types.display(ty)
+                ))
+                .build();
+            self.diagnose(*error);
+            return (None, expression);
+        }
+        if !self.types.is_scalar(ty) && !self.types.is_record(ty) {
+            return (None, expression);
+        }
+        let expression = self.value(expression);
+        (None, self.assign(expression, ty, Assigning::Initializer))
+    }
+
+    fn initialize_list(
+        &mut self,
+        ty: CType,
+        mut list: InitializerList,
+    ) -> (Option<u64>, InitializerList) {
+        // `char s[] = {"abc"}` is the same as without the braces
+        if let [InitializerItem {
+            ref designators,
+            initializer: Initializer::Expression(ref expression),
+            ..
+        }] = *list.items
+        {
+            if designators.is_empty() && self.is_string_initializer(ty, expression) {
+                return (Some(self.string_length(ty, expression)), list);
+            }
+        }
+        if self.is_aggregate(ty) {
+            let mut pending = list
+                .items
+                .into_iter()
+                .map(|item| Pending {
+                    item,
+                    designator: 0,
+                })
+                .collect();
+            let mut items = Vec::new();
+            let length = self.initialize_elements(ty, &mut pending, &mut items, true, false);
+            list.items = items;
+            return (Some(length), list);
+        }
+        if !self.types.is_scalar(ty) {
+            return (None, list);
+        }
+        // A scalar can have braces around the one expression it's
+        // initialized with
+        if let Some(designator) = list.items.first().and_then(|item| item.designators.first()) {
+            let error = self.not_designatable(ty, designator.span);
+            self.diagnose(*error);
+        }
+        if let Some(excess) = list.items.get(1) {
+            let error = self.excess_elements(ty, excess.span);
+            self.diagnose(*error);
+        }
+        if let Some(item) = list.items.first_mut() {
+            let initializer = std::mem::replace(
+                &mut item.initializer,
+                Initializer::Expression(Expression {
+                    kind: ExpressionKind::Error,
+                    ty: CType::ERROR,
+                    category: ValueCategory::Value,
+                    span: item.span,
+                }),
+            );
+            item.initializer = self.initialize(ty, initializer).1;
+        }
+        (None, list)
+    }
+
+    // Initializes the elements or members of `ty` in order from where
+    // the last designator put it, taking items from `pending` until
+    // they run out. Without braces of its own it only takes as many as
+    // it has elements, and stops at a designator unless it was
+    // `designated` into. Gives how many elements were initialized,
+    // counting up to the highest one.
+    fn initialize_elements(
+        &mut self,
+        ty: CType,
+        pending: &mut VecDeque<Pending>,
+        items: &mut Vec<InitializerItem>,
+        braced: bool,
+        mut designated: bool,
+    ) -> u64 {
+        let (mut position, mut length) = (0, 0);
+        while let Some(next) = pending.front() {
+            if let Some(designator) = next.item.designators.get(next.designator) {
+                if !braced && !designated {
+                    break;
+                }
+                designated = false;
+                match self.designated(ty, designator) {
+                    Some((index, anonymous)) => {
+                        position = index;
+                        // A member of an anonymous struct or union is
+                        // designated again inside of it
+                        if !anonymous {
+                            pending[0].designator += 1;
+                        }
+                    }
+                    None => {
+                        items.extend(pending.pop_front().map(|next| next.item));
+                        continue;
+                    }
+                }
+            } else if self.is_full(ty, position) {
+                if braced {
+                    let error = self.excess_elements(ty, next.item.span);
+                    self.diagnose(*error);
+                    items.extend(pending.drain(..).map(|next| next.item));
+                }
+                break;
+            }
+            let element = self.element_type(ty, position);
+            self.initialize_element(element, pending, items);
+            position += 1;
+            length = length.max(position);
+        }
+        length
+    }
+
+    fn initialize_element(
+        &mut self,
+        ty: CType,
+        pending: &mut VecDeque<Pending>,
+        items: &mut Vec<InitializerItem>,
+    ) {
+        let next = &pending[0];
+        // The rest of the designators are for inside of the element
+        if let Some(designator) = next.item.designators.get(next.designator) {
+            if self.is_aggregate(ty) {
+                self.initialize_elements(ty, pending, items, false, true);
+            } else {
+                let error = self.not_designatable(ty, designator.span);
+                self.diagnose(*error);
+                items.extend(pending.pop_front().map(|next| next.item));
+            }
+            return;
+        }
+        // Without braces, an aggregate takes as many items as it needs
+        // unless the expression is the whole of it
+        if let Initializer::Expression(ref expression) = next.item.initializer {
+            if self.is_aggregate(ty) && !self.initializes_whole(ty, expression) {
+                self.initialize_elements(ty, pending, items, false, false);
+                return;
+            }
+        }
+        let Some(Pending { mut item, .. }) = pending.pop_front() else {
+            return;
+        };
+        item.initializer = match item.initializer {
+            Initializer::Expression(expression) => {
+                Initializer::Expression(self.initialize_expression(ty, expression).1)
+            }
+            Initializer::List(list) => Initializer::List(self.initialize_list(ty, list).1),
+        };
+        items.push(item);
+    }
+
+    // Where a designator puts the initialization in `ty`, and whether
+    // that's an anonymous member with the designated member inside
+    fn designated(&mut self, ty: CType, designator: &Designator) -> Option<(u64, bool)> {
+        let message = match designator.kind {
+            DesignatorKind::Index(ref index) => match *self.types.kind(ty) {
+                TypeKind::Array { size, .. } => match self.evaluate_constant(index) {
+                    Some(value) if value < 0 => {
+                        format!("Array index {} in an initializer is negative", value)
+                    }
+                    Some(value) if matches!(size, ArraySize::Fixed(length) if value as u64 >= length) =>
+                    {
+                        format!(
+                            "Array index {} in an initializer is past the end of `{}`",
+                            value,
+                            self.types.display(ty)
+                        )
+                    }
+                    Some(value) => return Some((value as u64, false)),
+                    None => {
+                        "An array index in an initializer has to be an integer constant expression"
+                            .to_string()
+                    }
+                },
+                _ => format!(
+                    "An array index can't be used to initialize `{}`",
+                    self.types.display(ty)
+                ),
+            },
+            DesignatorKind::Member(ref name) if self.types.is_record(ty) => {
+                let members = self.initialized_members(ty);
+                if let Some(index) = members
+                    .iter()
+                    .position(|(member, _)| member.as_deref() == Some(&name.name))
+                {
+                    return Some((index as u64, false));
+                }
+                if let Some(index) = members.iter().position(|&(ref member, member_ty)| {
+                    member.is_none() && self.types.member(member_ty, &name.name).is_some()
+                }) {
+                    return Some((index as u64, true));
+                }
+                format!(
+                    "`{}` has no member named `{}`",
+                    self.types.display(ty),
+                    name.name
+                )
+            }
+            DesignatorKind::Member(ref name) => format!(
+                "A member designator `.{}` can't be used to initialize `{}`",
+                name.name,
+                self.types.display(ty)
+            ),
+        };
+        let error = self
+            .error_at_span(designator.span, ErrorCode::InvalidInitializer)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+        None
+    }
+
+    // Unnamed bit-fields are skipped over, they're only padding
+    fn initialized_members(&self, ty: CType) -> Vec<(Option<String>, CType)> {
+        let Some(id) = self.types.tag_id(ty) else {
+            return Vec::new();
+        };
+        self.types
+            .tag(id)
+            .members
+            .iter()
+            .filter(|member| member.name.is_some() || member.width.is_none())
+            .map(|member| (member.name.clone(), member.ty))
+            .collect()
+    }
+
+    fn is_aggregate(&self, ty: CType) -> bool {
+        match *self.types.kind(ty) {
+            TypeKind::Array { .. } => true,
+            TypeKind::Struct(_) | TypeKind::Union(_) => self.types.is_complete(ty),
+            _ => false,
+        }
+    }
+
+    // Only the first member of a union is initialized without a
+    // designator
+    fn is_full(&self, ty: CType, position: u64) -> bool {
+        match *self.types.kind(ty) {
+            TypeKind::Array {
+                size: ArraySize::Fixed(length),
+                ..
+            } => position >= length,
+            TypeKind::Array { .. } => false,
+            TypeKind::Union(_) => position >= 1,
+            _ => position >= self.initialized_members(ty).len() as u64,
+        }
+    }
+
+    fn element_type(&self, ty: CType, position: u64) -> CType {
+        match *self.types.kind(ty) {
+            TypeKind::Array { element, .. } => element,
+            _ => self
+                .initialized_members(ty)
+                .get(position as usize)
+                .map_or(CType::ERROR, |&(_, member)| member),
+        }
+    }
+
+    // A struct or union can be initialized by an expression of its own
+    // type, and an array of characters by a string literal
+    fn initializes_whole(&mut self, ty: CType, expression: &Expression) -> bool {
+        if self.types.is_record(ty) {
+            let (ty, from) = (
+                self.types.unqualified(ty),
+                self.types.unqualified(expression.ty),
+            );
+            return self.types.compatible(ty, from);
+        }
+        self.is_string_initializer(ty, expression)
+    }
+
+    fn is_string_initializer(&self, ty: CType, expression: &Expression) -> bool {
+        let TypeKind::Array { element, .. } = *self.types.kind(ty) else {
+            return false;
+        };
+        matches!(expression.kind, ExpressionKind::StringLiteral(_))
+            && matches!(
+                *self.types.kind(element),
+                TypeKind::Char | TypeKind::SignedChar | TypeKind::UnsignedChar
+            )
+    }
+
+    // The length of the string with its null, which is left out if the
+    // array is just long enough for the characters
+    fn string_length(&mut self, ty: CType, string: &Expression) -> u64 {
+        let length = match *self.types.kind(string.ty) {
+            TypeKind::Array {
+                size: ArraySize::Fixed(length),
+                ..
+            } => length,
+            _ => return 0,
+        };
+        if let TypeKind::Array {
+            size: ArraySize::Fixed(size),
+            ..
+        } = *self.types.kind(ty)
+        {
+            if length - 1 > size {
+                let error = self
+                    .error_at_span(string.span, ErrorCode::InvalidInitializer)
+                    .message(format!(
+                        "The string has {} characters, which is too many for `{}`",
+                        length - 1,
+                        self.types.display(ty)
+                    ))
+                    .build();
+                self.diagnose(*error);
+            }
+        }
+        length
+    }
+
+    fn excess_elements(&self, ty: CType, span: Span) -> Box<CompError> {
+        self.error_at_span(span, ErrorCode::InvalidInitializer)
+            .message(format!(
+                "Excess elements in the initializer of `{}`",
+                self.types.display(ty)
+            ))
+            .build()
+    }
+
+    fn not_designatable(&self, ty: CType, span: Span) -> Box<CompError> {
+        self.error_at_span(span, ErrorCode::InvalidInitializer)
+            .message(format!(
+                "A designator can't be used to initialize `{}`, which isn't an array, struct or union",
+                self.types.display(ty)
+            ))
+            .build()
+    }
+}
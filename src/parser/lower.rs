@@ -0,0 +1,616 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::Standard;
+use crate::source::Span;
+use crate::target;
+use crate::types::*;
+
+use super::scope::TagSymbol;
+use super::{is_void_parameter, Binding, Parser};
+
+impl Parser<'_> {
+    // The type that declaration specifiers name. This is worked out once
+    // as the specifiers are parsed, since each struct, union or enum with
+    // a body is a new type.
+    pub(super) fn specified_type(&mut self, specifiers: &DeclarationSpecifiers) -> CType {
+        let Ok(base) = specifiers.base_type() else {
+            return CType::ERROR;
+        };
+        let ty = match base {
+            BaseType::Void => CType::VOID,
+            BaseType::Bool => CType::BOOL,
+            BaseType::Char => CType::CHAR,
+            BaseType::SignedChar => CType::SIGNED_CHAR,
+            BaseType::UnsignedChar => CType::UNSIGNED_CHAR,
+            BaseType::Short => CType::SHORT,
+            BaseType::UnsignedShort => CType::UNSIGNED_SHORT,
+            BaseType::Int => CType::INT,
+            BaseType::UnsignedInt => CType::UNSIGNED_INT,
+            BaseType::Long => CType::LONG,
+            BaseType::UnsignedLong => CType::UNSIGNED_LONG,
+            BaseType::LongLong => CType::LONG_LONG,
+            BaseType::UnsignedLongLong => CType::UNSIGNED_LONG_LONG,
+            BaseType::Float => CType::FLOAT,
+            BaseType::Double => CType::DOUBLE,
+            BaseType::LongDouble => CType::LONG_DOUBLE,
+            BaseType::VaList => CType::VA_LIST,
+            BaseType::Struct(specifier) => {
+                let (attributes, _) = split_attributes(specifiers);
+                self.record_type(specifier, &attributes)
+            }
+            BaseType::Enum(specifier) => self.enum_type(specifier),
+            BaseType::TypedefName(name) => match self.lookup(name) {
+                Some(Binding::Typedef(ty)) => ty,
+                _ => CType::ERROR,
+            },
+            BaseType::Atomic(specifier) => self.atomic_type(specifier),
+            BaseType::Typeof(specifier) => {
+                let ty = match specifier.operand {
+                    TypeofOperand::Type(ref type_name) => type_name.ty,
+                    TypeofOperand::Expression(ref expression) => expression.ty,
+                };
+                match specifier.unqualified {
+                    true => self.types.unqualified(ty),
+                    false => ty,
+                }
+            }
+        };
+        self.types
+            .qualified(ty, qualifiers(specifiers.qualifiers()))
+    }
+
+    // `_Atomic(T)` can't name an atomic array, function or a type that's
+    // already qualified
+    fn atomic_type(&mut self, specifier: &AtomicSpecifier) -> CType {
+        let ty = specifier.type_name.ty;
+        let what = match *self.types.kind(ty) {
+            TypeKind::Error => return CType::ERROR,
+            TypeKind::Array { .. } => "an array type",
+            TypeKind::Function { .. } => "a function type",
+            _ if !self.types.qualifiers(ty).is_empty() => "a qualified type",
+            _ => return self.types.qualified(ty, Qualifiers::ATOMIC),
+        };
+        let error = self
+            .error_at_span(specifier.span, ErrorCode::InvalidDeclarationSpecifiers)
+            .message(format!(
+                "`_Atomic(...)` can't be applied to {}, `{}`",
+                what,
+                self.types.display(ty)
+            ))
+            .build();
+        self.diagnose(*error);
+        CType::ERROR
+    }
+
+    // The type of what a declarator declares, given the type named by
+    // its specifiers. The derivations are applied to it from the
+    // outside in.
+    pub(super) fn declared_type(&mut self, base: CType, declarator: &Declarator) -> CType {
+        let mut ty = base;
+        for derivation in declarator.derivations().iter().rev() {
+            ty = match *derivation {
+                Derivation::Pointer(pointer_qualifiers) => {
+                    let pointer = self.types.pointer(ty);
+                    self.types
+                        .qualified(pointer, qualifiers(pointer_qualifiers.iter().copied()))
+                }
+                Derivation::Array(size) => {
+                    let size = match size.map(|size| self.evaluate_constant(size)) {
+                        None => ArraySize::Incomplete,
+                        Some(Some(length)) => match u64::try_from(length) {
+                            Ok(length) => ArraySize::Fixed(length),
+                            // A negative size was already reported
+                            Err(_) => return CType::ERROR,
+                        },
+                        Some(None) => ArraySize::Variable,
+                    };
+                    if self.types.has_flexible_array(ty) {
+                        let error = self
+                            .error_at_span(declarator.span, ErrorCode::InvalidDeclaration)
+                            .message(format!(
+                                "An array can't have elements of type `{}`, which has a flexible array member",
+                                self.types.display(ty)
+                            ))
+                            .build();
+                        self.diagnose(*error);
+                        return CType::ERROR;
+                    }
+                    self.types.array(ty, size)
+                }
+                Derivation::Function {
+                    parameters,
+                    variadic,
+                } => {
+                    let parameters = self.parameter_types(parameters, variadic);
+                    if !self.check_returned(ty, declarator.span) {
+                        return CType::ERROR;
+                    }
+                    self.types.function(ty, parameters, variadic)
+                }
+            };
+        }
+        ty
+    }
+
+    // A function can return a pointer to an array or a function, but
+    // not one itself
+    fn check_returned(&mut self, ty: CType, span: Span) -> bool {
+        let what = match *self.types.kind(ty) {
+            TypeKind::Array { .. } => "an array",
+            TypeKind::Function { .. } => "a function",
+            _ => return true,
+        };
+        let error = self
+            .error_at_span(span, ErrorCode::InvalidDeclaration)
+            .message(format!(
+                "A function can't return {}, `{}`",
+                what,
+                self.types.display(ty)
+            ))
+            .build();
+        self.diagnose(*error);
+        false
+    }
+
+    // A parameter declared as an array is a pointer to its first element,
+    // and one declared as a function is a pointer to the function
+    pub(super) fn parameter_type(&mut self, base: CType, declarator: &Declarator) -> CType {
+        let ty = self.declared_type(base, declarator);
+        self.types.decay(ty)
+    }
+
+    // Before C23 `f()` has no prototype, and `f(void)` is the one with
+    // no parameters. The qualifiers of a parameter don't change the type
+    // of the function.
+    fn parameter_types(
+        &mut self,
+        parameters: &[ParameterDeclaration],
+        variadic: bool,
+    ) -> Option<Vec<CType>> {
+        match parameters {
+            [] if !variadic && self.standard < Standard::C23 => return None,
+            [parameter] if is_void_parameter(parameter) => return Some(Vec::new()),
+            _ => (),
+        }
+        let types = parameters
+            .iter()
+            .map(|parameter| self.types.unqualified(parameter.ty))
+            .collect();
+        Some(types)
+    }
+
+    // The largest alignment the `_Alignas` specifiers and GNU `aligned`
+    // attributes of a declaration ask for, and whether it's `packed`.
+    // An alignment of 0 asks for nothing.
+    pub(super) fn requested_alignment(
+        &self,
+        specifiers: &DeclarationSpecifiers,
+        declarator: Option<&Declarator>,
+    ) -> Alignment {
+        let (_, mut attributes) = split_attributes(specifiers);
+        attributes.extend(declarator.map(Declarator::attributes).unwrap_or_default());
+        let alignment = attribute_alignment(&attributes);
+        Alignment {
+            packed: alignment.packed,
+            requested: self.alignas(specifiers).max(alignment.requested),
+        }
+    }
+
+    // The largest alignment the `_Alignas` specifiers ask for
+    fn alignas(&self, specifiers: &DeclarationSpecifiers) -> Option<u64> {
+        specifiers
+            .specifiers
+            .iter()
+            .filter_map(|spec| match spec.kind {
+                DeclarationSpecifierKind::AlignmentSpecifier(AlignmentSpecifier::Type(
+                    ref type_name,
+                )) => self.types.align_of(type_name.ty),
+                DeclarationSpecifierKind::AlignmentSpecifier(AlignmentSpecifier::Expression(
+                    ref expression,
+                )) => self
+                    .evaluate_constant(expression)
+                    .and_then(|value| u64::try_from(value).ok()),
+                _ => None,
+            })
+            .filter(|&align| align > 0)
+            .max()
+    }
+
+    // `_Alignas` can't make an object or member less aligned than its
+    // type would be, and can't be used on `misplaced`, a function,
+    // parameter or bit-field
+    pub(super) fn check_alignas(
+        &mut self,
+        specifiers: &DeclarationSpecifiers,
+        ty: CType,
+        misplaced: Option<&str>,
+    ) {
+        let Some(span) = specifiers.specifiers.iter().find_map(|spec| {
+            matches!(spec.kind, DeclarationSpecifierKind::AlignmentSpecifier(_))
+                .then_some(spec.span)
+        }) else {
+            return;
+        };
+        let message = match (misplaced, self.alignas(specifiers), self.types.align_of(ty)) {
+            (Some(what), _, _) => format!("`_Alignas` can't be used on {}", what),
+            (None, Some(requested), Some(natural)) if requested < natural => format!(
+                "`_Alignas` asks for an alignment of {}, less than the {} of `{}`",
+                requested,
+                natural,
+                self.types.display(ty)
+            ),
+            _ => return,
+        };
+        let error = self
+            .error_at_span(span, ErrorCode::InvalidDeclarationSpecifiers)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+    }
+
+    // A typedef with `aligned` names a type with that alignment. Other
+    // declarations can't be `packed`, which is only for the layout of
+    // structs and unions.
+    pub(super) fn aligned_declaration(
+        &mut self,
+        specifiers: &DeclarationSpecifiers,
+        declarator: &Declarator,
+        ty: CType,
+    ) -> CType {
+        // `_Alignas` with `typedef` was already reported
+        if !specifiers.is_typedef() {
+            let function = matches!(self.types.kind(ty), TypeKind::Function { .. });
+            self.check_alignas(specifiers, ty, function.then_some("a function"));
+        }
+        let alignment = self.requested_alignment(specifiers, Some(declarator));
+        if alignment.packed {
+            let warning = self
+                .error_at_span(declarator.span, ErrorCode::InvalidAttribute)
+                .warning()
+                .message("`packed` only applies to structs, unions and their members".into())
+                .build();
+            self.diagnose(*warning);
+        }
+        match (specifiers.is_typedef(), alignment.requested) {
+            (true, Some(_)) => match self.types.align_of(ty) {
+                Some(natural) => self.types.aligned(ty, alignment.apply(natural)),
+                None => ty,
+            },
+            _ => ty,
+        }
+    }
+
+    // `__func__` is a `static const char[]` holding the function's name
+    pub(super) fn func_type(&mut self, name: &str) -> CType {
+        let element = self.types.qualified(CType::CHAR, Qualifiers::CONST);
+        let length = name.len() as u64 + 1;
+        self.types.array(element, ArraySize::Fixed(length))
+    }
+
+    // Attributes right after `struct` or after the body change the
+    // layout of the type
+    fn record_type(&mut self, specifier: &StructSpecifier, trailing: &[&Attribute]) -> CType {
+        let kind = match specifier.kind {
+            StructKind::Struct => TagKind::Struct,
+            StructKind::Union => TagKind::Union,
+        };
+        let defining = specifier.members.is_some();
+        let ty = self.tag_type(kind, specifier.tag.as_ref(), defining);
+        if let (Some(ref members), Some(id)) = (&specifier.members, self.types.tag_id(ty)) {
+            self.check_member_names(members);
+            let members = self.members(kind, members);
+            let attributes: Vec<&Attribute> = specifier
+                .attributes
+                .iter()
+                .chain(trailing.iter().copied())
+                .collect();
+            let alignment = attribute_alignment(&attributes);
+            self.types.complete_record(id, members, alignment);
+            if self.types.is_too_large(ty) {
+                let error = self
+                    .error_at_span(specifier.span, ErrorCode::InvalidDeclaration)
+                    .message(format!(
+                        "`{}` is too large for an object",
+                        self.types.display(ty)
+                    ))
+                    .build();
+                self.diagnose(*error);
+            }
+        }
+        ty
+    }
+
+    fn enum_type(&mut self, specifier: &EnumSpecifier) -> CType {
+        let defining = specifier.enumerators.is_some();
+        let ty = self.tag_type(TagKind::Enum, specifier.tag.as_ref(), defining);
+        if let (true, Some(id)) = (defining, self.types.tag_id(ty)) {
+            self.types.complete_enum(id);
+        }
+        ty
+    }
+
+    // A tag with a body is a new type, unless it completes one that was
+    // declared without a body in the same scope. A tag without one is
+    // the type it names in the nearest scope, or a new incomplete type
+    // when it isn't declared yet.
+    fn tag_type(&mut self, kind: TagKind, tag: Option<&Identifier>, defining: bool) -> CType {
+        let Some(tag) = tag else {
+            return self.types.new_tag(kind, None);
+        };
+        let found = match defining {
+            true => self.symbols.current_tag(&tag.name),
+            false => self.symbols.lookup_tag(&tag.name),
+        };
+        let Some(&TagSymbol { ty, span }) = found else {
+            let ty = self.types.new_tag(kind, Some(&tag.name));
+            self.symbols
+                .insert_tag(&tag.name, TagSymbol { ty, span: tag.span });
+            return ty;
+        };
+
+        let existing = self
+            .types
+            .tag_id(ty)
+            .map(|id| self.types.tag(id))
+            .expect("Tags name struct, union or enum types");
+        let (message, note) = if existing.kind != kind {
+            (
+                format!(
+                    "`{}` was declared as a `{}`, not a `{}`",
+                    tag.name, existing.kind, kind
+                ),
+                "Previous declaration is here",
+            )
+        } else if defining && existing.complete {
+            (
+                format!("`{} {}` is defined more than once", kind, tag.name),
+                "Previous definition is here",
+            )
+        } else {
+            // Notes about it point at the definition from now on
+            if defining {
+                self.symbols
+                    .insert_tag(&tag.name, TagSymbol { ty, span: tag.span });
+            }
+            return ty;
+        };
+        let error = self
+            .error_at_span(tag.span, ErrorCode::InvalidDeclaration)
+            .message(message)
+            .note_at(note.into(), self.sources.buffer(span.file_id), span)
+            .build();
+        self.diagnose(*error);
+        // A new type that isn't in scope, so the body is still checked
+        self.types.new_tag(kind, Some(&tag.name))
+    }
+
+    // Members need a complete type, other than a flexible array member,
+    // and anonymous structs and unions are members without a name
+    fn members(&mut self, kind: TagKind, declarations: &[StructDeclaration]) -> Vec<Member> {
+        let mut members = Vec::new();
+        let count: usize = declarations
+            .iter()
+            .map(|declaration| declaration.declarators.len().max(1))
+            .sum();
+        let mut position = 0;
+        for declaration in declarations {
+            let base = declaration.specifiers.ty;
+            if declaration.declarators.is_empty() {
+                position += 1;
+                if kind == TagKind::Struct
+                    && position != count
+                    && self.types.has_flexible_array(base)
+                {
+                    let error = self
+                        .error_at_span(declaration.span, ErrorCode::InvalidDeclaration)
+                        .message(format!(
+                            "Anonymous member of struct comes before other members, but has a flexible array member in `{}`",
+                            self.types.display(base)
+                        ))
+                        .build();
+                    self.diagnose(*error);
+                    continue;
+                }
+                self.check_alignas(&declaration.specifiers, base, None);
+                let alignment = self.requested_alignment(&declaration.specifiers, None);
+                members.push(Member::new(None, base, None, alignment));
+                continue;
+            }
+            for member in &declaration.declarators {
+                position += 1;
+                let ty = self.declared_type(base, &member.declarator);
+                let name = member.declarator.name().map(|name| name.name.clone());
+                let flexible = matches!(
+                    self.types.kind(ty),
+                    TypeKind::Array {
+                        size: ArraySize::Incomplete,
+                        ..
+                    }
+                );
+                let error = *self.types.kind(ty) == TypeKind::Error;
+                let before = kind == TagKind::Struct && position != count;
+                let problem = if before && self.types.has_flexible_array(ty) {
+                    Some("comes before other members, but has a flexible array member in")
+                } else if self.types.is_variably_modified(ty) {
+                    Some("has variably modified type")
+                } else if self.types.is_too_large(ty) {
+                    Some("is too large for an object, with type")
+                } else if !self.types.is_complete(ty) && !flexible && !error {
+                    Some("has incomplete type")
+                } else {
+                    None
+                };
+                if let Some(problem) = problem {
+                    let error = self
+                        .error_at_span(member.span, ErrorCode::InvalidDeclaration)
+                        .message(format!(
+                            "Member `{}` of {} {} `{}`",
+                            name.as_deref().unwrap_or(""),
+                            kind,
+                            problem,
+                            self.types.display(ty)
+                        ))
+                        .build();
+                    self.diagnose(*error);
+                    continue;
+                }
+                let width = match member.width {
+                    Some(ref width) => match self.bit_field_width(member, ty, width) {
+                        Some(width) => Some(width),
+                        None => continue,
+                    },
+                    None => None,
+                };
+                let misplaced = width.is_some().then_some("a bit-field");
+                self.check_alignas(&declaration.specifiers, ty, misplaced);
+                let alignment =
+                    self.requested_alignment(&declaration.specifiers, Some(&member.declarator));
+                members.push(Member::new(name, ty, width, alignment));
+            }
+        }
+        members
+    }
+
+    // Each struct or union is a namespace for its members, which
+    // includes the members of the anonymous structs and unions in it
+    fn check_member_names(&mut self, declarations: &[StructDeclaration]) {
+        let mut seen: HashMap<String, Span> = HashMap::new();
+        for declaration in declarations {
+            let names: Vec<(String, Span)> = match declaration.declarators.is_empty() {
+                true => self
+                    .types
+                    .member_names(declaration.specifiers.ty)
+                    .into_iter()
+                    .map(|name| (name.to_string(), declaration.span))
+                    .collect(),
+                false => declaration
+                    .declarators
+                    .iter()
+                    .filter_map(|member| member.declarator.name())
+                    .map(|name| (name.name.clone(), name.span))
+                    .collect(),
+            };
+            for (name, span) in names {
+                let Some(&previous) = seen.get(&name) else {
+                    seen.insert(name, span);
+                    continue;
+                };
+                let error = self
+                    .error_at_span(span, ErrorCode::InvalidDeclaration)
+                    .message(format!("Member `{}` is declared more than once", name))
+                    .note_at(
+                        "Previous declaration is here".into(),
+                        self.sources.buffer(previous.file_id),
+                        previous,
+                    )
+                    .build();
+                self.diagnose(*error);
+            }
+        }
+    }
+
+    // A bit-field has an integer type and a width that's a constant no
+    // wider than that type. Only an unnamed one can have width 0.
+    fn bit_field_width(
+        &mut self,
+        member: &StructDeclarator,
+        ty: CType,
+        width: &Expression,
+    ) -> Option<u64> {
+        let name = member.declarator.name().map_or("", |name| &name.name);
+        let bits = self.types.size_of(ty).unwrap_or(0) * target::CHAR_BIT;
+        let (message, span) = match self.evaluate_constant(width) {
+            _ if !self.types.is_integer(ty) => (
+                format!(
+                    "Bit-field `{}` has type `{}`, which is not an integer type",
+                    name,
+                    self.types.display(ty)
+                ),
+                member.span,
+            ),
+            None => (
+                format!(
+                    "Width of bit-field `{}` could not be evaluated as an integer constant",
+                    name
+                ),
+                width.span,
+            ),
+            Some(value) if value < 0 => (
+                format!("Bit-field `{}` has negative width {}", name, value),
+                width.span,
+            ),
+            Some(value) if value as u64 > bits => (
+                format!(
+                    "Bit-field `{}` is {} bits wide, which is wider than its type `{}`",
+                    name,
+                    value,
+                    self.types.display(ty)
+                ),
+                width.span,
+            ),
+            Some(0) if member.declarator.name().is_some() => (
+                format!(
+                    "Bit-field `{}` has width 0, which only an unnamed one can have",
+                    name
+                ),
+                width.span,
+            ),
+            Some(value) => return Some(value as u64),
+        };
+        let error = self
+            .error_at_span(span, ErrorCode::InvalidDeclaration)
+            .message(message)
+            .build();
+        self.diagnose(*error);
+        None
+    }
+}
+
+fn qualifiers(written: impl Iterator<Item = TypeQualifier>) -> Qualifiers {
+    let mut qualifiers = Qualifiers::NONE;
+    for qualifier in written {
+        qualifiers |= match qualifier {
+            TypeQualifier::Const => Qualifiers::CONST,
+            TypeQualifier::Volatile => Qualifiers::VOLATILE,
+            TypeQualifier::Restrict => Qualifiers::RESTRICT,
+            TypeQualifier::Atomic => Qualifiers::ATOMIC,
+        };
+    }
+    qualifiers
+}
+
+// The attributes in declaration specifiers that come after a struct or
+// union body belong to that type, and the rest to what's declared
+fn split_attributes(specifiers: &DeclarationSpecifiers) -> (Vec<&Attribute>, Vec<&Attribute>) {
+    let mut record = Vec::new();
+    let mut declaration = Vec::new();
+    let mut after_body = false;
+    for specifier in &specifiers.specifiers {
+        match specifier.kind {
+            DeclarationSpecifierKind::TypeSpecifier(TypeSpecifier::Struct(ref specifier)) => {
+                after_body = specifier.members.is_some();
+            }
+            DeclarationSpecifierKind::Attributes(ref attributes) => match after_body {
+                true => record.extend(attributes),
+                false => declaration.extend(attributes),
+            },
+            _ => (),
+        }
+    }
+    (record, declaration)
+}
+
+// What GNU `aligned` and `packed` attributes ask for
+fn attribute_alignment(attributes: &[&Attribute]) -> Alignment {
+    Alignment {
+        packed: attributes
+            .iter()
+            .any(|attribute| attribute.gnu_name() == Some("packed")),
+        requested: attributes
+            .iter()
+            .filter_map(|attribute| attribute.alignment)
+            .max(),
+    }
+}
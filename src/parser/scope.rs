@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::Standard;
+use crate::source::Span;
+use crate::types::{ArraySize, CType, TypeKind};
+
+use super::{Binding, Parser};
+
+// Where a name is declared decides how long it's in scope and what
+// linkage it gets. Labels have function scope, so they're kept apart
+// from these for the function being parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ScopeKind {
+    File,
+    Block,
+    // The parameters of a function declarator that isn't a definition
+    Prototype,
+}
+
+// Declarations of a name with external linkage anywhere in the program,
+// or internal linkage anywhere in the file, are all of the same thing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Linkage {
+    External,
+    Internal,
+    None,
+}
+
+// Whether a declaration defines what it declares. A file scope object
+// without an initializer is a tentative definition, which becomes a
+// definition at the end of the file unless something else defines it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum Defined {
+    No,
+    Tentatively,
+    Yes,
+}
+
+// An ordinary identifier: an object, function, typedef name or
+// enumerator
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Symbol {
+    pub binding: Binding,
+    pub linkage: Linkage,
+    pub defined: Defined,
+    // Where it was last declared, or defined once it is
+    pub span: Span,
+}
+
+// A struct, union or enum tag
+#[derive(Debug, Clone, Copy)]
+pub(super) struct TagSymbol {
+    pub ty: CType,
+    pub span: Span,
+}
+
+// A label has to be defined once in its function, and the first
+// `goto` to it is kept until it is
+#[derive(Debug, Clone, Copy, Default)]
+struct Label {
+    defined: Option<Span>,
+    used: Option<Span>,
+}
+
+// Tags and ordinary identifiers are separate namespaces, `struct s` and
+// `s` can both be declared in one scope. Members are a namespace of each
+// struct or union, and are kept with its type.
+struct Scope {
+    kind: ScopeKind,
+    ordinary: HashMap<String, Symbol>,
+    tags: HashMap<String, TagSymbol>,
+}
+
+pub(super) struct SymbolTable {
+    // Innermost last, the file scope is never closed
+    scopes: Vec<Scope>,
+    // Every name declared with linkage, even when the declaration is in
+    // a block that has been closed, as later ones have to agree with it
+    linked: HashMap<String, Symbol>,
+    labels: HashMap<String, Label>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        let mut table = Self {
+            scopes: Vec::new(),
+            linked: HashMap::new(),
+            labels: HashMap::new(),
+        };
+        table.push(ScopeKind::File);
+        table
+    }
+
+    pub fn push(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope {
+            kind,
+            ordinary: HashMap::new(),
+            tags: HashMap::new(),
+        });
+    }
+
+    pub fn pop(&mut self) {
+        assert!(self.scopes.len() > 1, "File scope is never closed");
+        self.scopes.pop();
+    }
+
+    pub fn kind(&self) -> ScopeKind {
+        self.innermost().kind
+    }
+
+    fn innermost(&self) -> &Scope {
+        self.scopes.last().expect("File scope is never closed")
+    }
+
+    fn innermost_mut(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("File scope is never closed")
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.ordinary.get(name))
+    }
+
+    // A declaration of the name in the innermost scope
+    pub fn current(&self, name: &str) -> Option<&Symbol> {
+        self.innermost().ordinary.get(name)
+    }
+
+    pub fn linked(&self, name: &str) -> Option<&Symbol> {
+        self.linked.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, symbol: Symbol) {
+        if symbol.linkage != Linkage::None {
+            self.linked.insert(name.to_string(), symbol);
+        }
+        self.innermost_mut()
+            .ordinary
+            .insert(name.to_string(), symbol);
+    }
+
+    // The file scope objects, to finish their tentative definitions
+    pub fn file_scope(&self) -> impl Iterator<Item = (&String, &Symbol)> {
+        self.scopes[0].ordinary.iter()
+    }
+
+    pub fn lookup_tag(&self, name: &str) -> Option<&TagSymbol> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.tags.get(name))
+    }
+
+    pub fn current_tag(&self, name: &str) -> Option<&TagSymbol> {
+        self.innermost().tags.get(name)
+    }
+
+    pub fn insert_tag(&mut self, name: &str, tag: TagSymbol) {
+        self.innermost_mut().tags.insert(name.to_string(), tag);
+    }
+
+    // The span of the label's earlier definition if it has one
+    pub fn define_label(&mut self, name: &str, span: Span) -> Option<Span> {
+        let label = self.labels.entry(name.to_string()).or_default();
+        match label.defined {
+            Some(previous) => Some(previous),
+            None => {
+                label.defined = Some(span);
+                None
+            }
+        }
+    }
+
+    pub fn use_label(&mut self, name: &str, span: Span) {
+        let label = self.labels.entry(name.to_string()).or_default();
+        label.used.get_or_insert(span);
+    }
+
+    // The labels a function went to without defining, at its end
+    pub fn take_undefined_labels(&mut self) -> Vec<(String, Span)> {
+        let mut undefined: Vec<_> = std::mem::take(&mut self.labels)
+            .into_iter()
+            .filter_map(|(name, label)| match label {
+                Label {
+                    defined: None,
+                    used: Some(span),
+                } => Some((name, span)),
+                _ => None,
+            })
+            .collect();
+        undefined.sort_by_key(|&(_, span)| span.lo);
+        undefined
+    }
+}
+
+impl Parser<'_> {
+    pub(super) fn push_scope(&mut self, kind: ScopeKind) {
+        self.symbols.push(kind);
+    }
+
+    pub(super) fn pop_scope(&mut self) {
+        self.symbols.pop();
+    }
+
+    pub(super) fn lookup(&self, name: &str) -> Option<Binding> {
+        self.symbols.lookup(name).map(|symbol| symbol.binding)
+    }
+
+    pub(super) fn is_typedef_name(&self, name: &str) -> bool {
+        matches!(self.lookup(name), Some(Binding::Typedef(_)))
+    }
+
+    // The linkage of an object or function declared with these
+    // specifiers. At file scope a name has external linkage unless it's
+    // `static`. `extern`, and a function without a storage class, takes
+    // the linkage of a declaration that's visible, if there is one.
+    // Anything else in a block has no linkage.
+    pub(super) fn linkage(
+        &self,
+        name: &str,
+        specifiers: &DeclarationSpecifiers,
+        ty: CType,
+    ) -> Linkage {
+        let file_scope = self.symbols.kind() == ScopeKind::File;
+        let function = matches!(self.types.kind(ty), TypeKind::Function { .. });
+        let mut classes = specifiers.storage_classes();
+        let storage =
+            classes.find(|class| matches!(class, StorageClass::Static | StorageClass::Extern));
+        match storage {
+            Some(StorageClass::Static) if file_scope => Linkage::Internal,
+            Some(StorageClass::Extern) => self.visible_linkage(name),
+            None if function => self.visible_linkage(name),
+            None if file_scope => Linkage::External,
+            _ => Linkage::None,
+        }
+    }
+
+    // What an object or function declared with these specifiers is
+    // bound to, which keeps track of whether its address can be taken
+    // or is a constant
+    pub(super) fn object_binding(&self, specifiers: &DeclarationSpecifiers, ty: CType) -> Binding {
+        let block = self.symbols.kind() != ScopeKind::File;
+        let mut classes = specifiers.storage_classes();
+        match classes.find(|class| matches!(class, StorageClass::Register | StorageClass::Static)) {
+            Some(StorageClass::Register) => Binding::Register(ty),
+            Some(StorageClass::Static) if block => Binding::Static(ty),
+            _ => Binding::Object(ty),
+        }
+    }
+
+    // Objects with linkage, and ones declared `static` in a block, last
+    // as long as the program does
+    pub(super) fn has_static_storage(&self, name: &str) -> bool {
+        match self.symbols.lookup(name) {
+            Some(&Symbol {
+                binding: Binding::Static(_),
+                ..
+            }) => true,
+            Some(&Symbol {
+                binding: Binding::Object(_),
+                linkage,
+                ..
+            }) => linkage != Linkage::None,
+            _ => false,
+        }
+    }
+
+    fn visible_linkage(&self, name: &str) -> Linkage {
+        match self.symbols.lookup(name) {
+            Some(&Symbol {
+                linkage: linkage @ Linkage::Internal,
+                ..
+            }) => linkage,
+            _ => Linkage::External,
+        }
+    }
+
+    // Declares a name in the innermost scope. Declaring it again in the
+    // same scope is only allowed when both declarations have linkage, or
+    // for typedefs of the same type since C11. Every declaration of a
+    // name with linkage needs a compatible type, and the name then has
+    // the composite of the types.
+    pub(super) fn declare(
+        &mut self,
+        name: &Identifier,
+        binding: Binding,
+        linkage: Linkage,
+        defined: Defined,
+    ) {
+        let mut symbol = Symbol {
+            binding,
+            linkage,
+            defined,
+            span: name.span,
+        };
+        let previous = self.symbols.current(&name.name).copied();
+        let previous = previous.or_else(|| match linkage {
+            Linkage::None => None,
+            _ => self.symbols.linked(&name.name).copied(),
+        });
+        if let Some(previous) = previous {
+            if !self.redeclare(name, &previous, &mut symbol) {
+                return;
+            }
+        }
+        self.symbols.insert(&name.name, symbol);
+    }
+
+    // Merges a declaration with the one before it, or reports why they
+    // can't both be declared. `false` keeps the one before.
+    fn redeclare(&mut self, name: &Identifier, previous: &Symbol, symbol: &mut Symbol) -> bool {
+        let same_scope = self.symbols.current(&name.name).is_some();
+        let conflict = |parser: &Self, old, new| {
+            format!(
+                "Conflicting types for `{}`, `{}` and `{}`",
+                name.name,
+                parser.types.display(old),
+                parser.types.display(new)
+            )
+        };
+        let (code, message, note) = match (previous.binding, symbol.binding) {
+            (
+                Binding::Object(old) | Binding::Register(old) | Binding::Static(old),
+                Binding::Object(new) | Binding::Register(new) | Binding::Static(new),
+            ) => {
+                let linked = previous.linkage != Linkage::None && symbol.linkage != Linkage::None;
+                if same_scope && !linked {
+                    (
+                        ErrorCode::InvalidDeclaration,
+                        format!("`{}` is declared more than once", name.name),
+                        "Previous declaration is here",
+                    )
+                } else if previous.linkage == Linkage::External
+                    && symbol.linkage == Linkage::Internal
+                {
+                    (
+                        ErrorCode::InvalidDeclaration,
+                        format!(
+                            "`{}` is declared `static` after a declaration that isn't",
+                            name.name
+                        ),
+                        "Previous declaration is here",
+                    )
+                } else if previous.linkage == Linkage::Internal
+                    && symbol.linkage == Linkage::External
+                {
+                    (
+                        ErrorCode::InvalidDeclaration,
+                        format!(
+                            "`{}` isn't declared `static` after a declaration that is",
+                            name.name
+                        ),
+                        "Previous declaration is here",
+                    )
+                } else if !self.types.compatible(old, new) {
+                    (
+                        ErrorCode::IncompatibleTypes,
+                        conflict(self, old, new),
+                        "Previous declaration is here",
+                    )
+                } else if previous.defined == Defined::Yes && symbol.defined == Defined::Yes {
+                    (
+                        ErrorCode::InvalidDeclaration,
+                        format!("`{}` is defined more than once", name.name),
+                        "Previous definition is here",
+                    )
+                } else {
+                    // Notes point at the definition, or else the first
+                    // declaration
+                    if symbol.defined != Defined::Yes {
+                        symbol.span = previous.span;
+                    }
+                    symbol.binding = Binding::Object(self.types.composite(old, new));
+                    symbol.defined = symbol.defined.max(previous.defined);
+                    return true;
+                }
+            }
+            (Binding::Typedef(old), Binding::Typedef(new)) if same_scope => {
+                if self.types.compatible(old, new) {
+                    self.require_standard(Standard::C11, "Declaring a typedef again", name.span);
+                    return true;
+                }
+                (
+                    ErrorCode::IncompatibleTypes,
+                    conflict(self, old, new),
+                    "Previous declaration is here",
+                )
+            }
+            // A name with linkage declared in a block that's been closed
+            _ if !same_scope => return true,
+            _ => (
+                ErrorCode::InvalidDeclaration,
+                format!("`{}` is declared again as something different", name.name),
+                "Previous declaration is here",
+            ),
+        };
+        let error = self
+            .error_at_span(name.span, code)
+            .message(message)
+            .note_at(
+                note.into(),
+                self.sources.buffer(previous.span.file_id),
+                previous.span,
+            )
+            .build();
+        self.diagnose(*error);
+        false
+    }
+
+    // Declarations without linkage can't be merged, so this replaces
+    // what a name in the innermost scope is bound to
+    pub(super) fn rebind(&mut self, name: &Identifier, binding: Binding) {
+        if let Some(&symbol) = self.symbols.current(&name.name) {
+            self.symbols
+                .insert(&name.name, Symbol { binding, ..symbol });
+        }
+    }
+
+    // Something the compiler declares, like `__func__` or a parameter
+    // that's an `int` because an old-style definition didn't say
+    pub(super) fn declare_implicit(&mut self, name: &str, binding: Binding, span: Span) {
+        let symbol = Symbol {
+            binding,
+            linkage: Linkage::None,
+            defined: Defined::Yes,
+            span,
+        };
+        self.symbols.insert(name, symbol);
+    }
+
+    // At the end of the file, tentative definitions that weren't
+    // defined become definitions. An array of unknown size then has one
+    // element, and anything else has to be complete by now.
+    pub(super) fn finish_tentative_definitions(&mut self) {
+        let mut tentative: Vec<(String, Symbol)> = self
+            .symbols
+            .file_scope()
+            .filter(|(_, symbol)| symbol.defined == Defined::Tentatively)
+            .map(|(name, symbol)| (name.clone(), *symbol))
+            .collect();
+        tentative.sort_by_key(|(_, symbol)| symbol.span.lo);
+        for (name, symbol) in tentative {
+            let Binding::Object(ty) = symbol.binding else {
+                continue;
+            };
+            if self.types.is_complete(ty) || *self.types.kind(ty) == TypeKind::Error {
+                continue;
+            }
+            let error = match *self.types.kind(ty) {
+                TypeKind::Array {
+                    element,
+                    size: ArraySize::Incomplete,
+                } => {
+                    let one = self.types.array(element, ArraySize::Fixed(1));
+                    self.symbols.insert(
+                        &name,
+                        Symbol {
+                            binding: Binding::Object(one),
+                            ..symbol
+                        },
+                    );
+                    self.error_at_span(symbol.span, ErrorCode::InvalidDeclaration)
+                        .warning()
+                        .message(format!("Array `{}` is assumed to have one element", name))
+                }
+                _ => self
+                    .error_at_span(symbol.span, ErrorCode::InvalidDeclaration)
+                    .message(format!(
+                        "`{}` is defined with incomplete type `{}`",
+                        name,
+                        self.types.display(ty)
+                    )),
+            };
+            self.diagnose(*error.build());
+        }
+    }
+
+    // `goto` can jump to a label anywhere in the function, so labels are
+    // checked once the whole body has been seen
+    pub(super) fn define_label(&mut self, label: &Identifier) {
+        if let Some(previous) = self.symbols.define_label(&label.name, label.span) {
+            let error = self
+                .error_at_span(label.span, ErrorCode::InvalidDeclaration)
+                .message(format!("Label `{}` is defined more than once", label.name))
+                .note_at(
+                    "Previous definition is here".into(),
+                    self.sources.buffer(previous.file_id),
+                    previous,
+                )
+                .build();
+            self.diagnose(*error);
+        }
+    }
+
+    pub(super) fn use_label(&mut self, label: &Identifier) {
+        self.symbols.use_label(&label.name, label.span);
+    }
+
+    pub(super) fn check_labels(&mut self) {
+        for (name, span) in self.symbols.take_undefined_labels() {
+            let error = self
+                .error_at_span(span, ErrorCode::InvalidDeclaration)
+                .message(format!("Label `{}` is used but never defined", name))
+                .build();
+            self.diagnose(*error);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+
+use crate::ast::*;
+use crate::comp_error::*;
+use crate::lang::{Keyword, Operator, Punctuator, Standard};
+use crate::lexer::Token;
+use crate::source::Span;
+use crate::types::CType;
+
+use super::{Parser, ScopeKind};
+
+// A statement that `break` leaves
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Enclosing {
+    Loop,
+    Switch(SwitchLabels),
+}
+
+// The labels of a `switch` so far. Cases are keyed by their lowest
+// value, with their highest, and never overlap.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct SwitchLabels {
+    ty: CType,
+    default: Option<Span>,
+    cases: BTreeMap<i64, (i64, Span)>,
+}
+
+impl SwitchLabels {
+    fn new(ty: CType) -> Self {
+        Self {
+            ty,
+            default: None,
+            cases: BTreeMap::new(),
+        }
+    }
+
+    // Only the case starting closest below `hi` can reach `lo`
+    fn overlapping(&self, lo: i64, hi: i64) -> Option<Span> {
+        let (_, &(high, span)) = self.cases.range(..=hi).next_back()?;
+        (high >= lo).then_some(span)
+    }
+}
+
+impl Parser<'_> {
+    // Each block is a new scope for names, which closes even if parsing
+    // the block fails
+    pub(super) fn compound_statement(&mut self) -> CompResult<CompoundStatement> {
+        self.push_scope(ScopeKind::Block);
+        let block = self.block();
+        self.pop_scope();
+        block
+    }
+
+    pub(super) fn block(&mut self) -> CompResult<CompoundStatement> {
+        let start = self.current_span();
+        self.expect_punctuator(Punctuator::LCurly)?;
+
+        let mut items = Vec::new();
+        let mut seen_statement = false;
+        while !self.eat_punctuator(Punctuator::RCurly) {
+            if self.peek().is_none() {
+                return Err(self.error_expected("`}`"));
+            }
+            let item_start = self.index;
+            // A typedef name can also be a label
+            let item = if self.peek_keyword() == Some(Keyword::StaticAssert) {
+                self.static_assert().map(BlockItem::StaticAssert)
+            } else if self.declaration_follows() && !self.label_follows() {
+                if seen_statement {
+                    let what = "A declaration after a statement";
+                    self.require_standard(Standard::C99, what, self.current_span());
+                }
+                self.declaration().map(BlockItem::Declaration)
+            } else {
+                seen_statement = true;
+                self.statement().map(BlockItem::Statement)
+            };
+            let item = item.unwrap_or_else(|error| {
+                let span = self.recover(*error, item_start);
+                BlockItem::Statement(Statement {
+                    kind: StatementKind::Error,
+                    span,
+                })
+            });
+            items.push(item);
+        }
+
+        Ok(CompoundStatement {
+            items,
+            span: self.span_from(start),
+        })
+    }
+
+    fn statement(&mut self) -> CompResult<Statement> {
+        let start = self.current_span();
+        if self.attributes_follow() {
+            let attributes = self.attribute_specifiers()?;
+            let statement = self.statement()?;
+            let null = statement.kind == StatementKind::Null;
+            self.check_fallthrough(&attributes, null);
+            if attributes.is_empty() {
+                return Ok(statement);
+            }
+            return Ok(Statement {
+                kind: StatementKind::Attributed {
+                    attributes,
+                    statement: Box::new(statement),
+                },
+                span: self.span_from(start),
+            });
+        }
+        let kind = match self.peek() {
+            Some(token) if token.is_punctuator(Punctuator::LCurly) => {
+                StatementKind::Compound(self.compound_statement()?)
+            }
+            Some(token) if token.is_punctuator(Punctuator::Semicolon) => {
+                self.index += 1;
+                StatementKind::Null
+            }
+            Some(Token::Keyword(Keyword::If, _)) => self.if_statement()?,
+            Some(Token::Keyword(Keyword::Switch, _)) => {
+                self.index += 1;
+                let condition = self.parenthesized_expression()?;
+                let condition = self.switch_condition(condition);
+                let labels = SwitchLabels::new(self.case_type(&condition));
+                let body = self.enclosed(Enclosing::Switch(labels), Self::statement)?;
+                StatementKind::Switch {
+                    condition,
+                    body: Box::new(body),
+                }
+            }
+            Some(Token::Keyword(Keyword::While, _)) => {
+                self.index += 1;
+                let condition = self.parenthesized_expression()?;
+                let condition = self.condition(condition, "while");
+                let body = self.enclosed(Enclosing::Loop, Self::statement)?;
+                StatementKind::While {
+                    condition,
+                    body: Box::new(body),
+                }
+            }
+            Some(Token::Keyword(Keyword::Do, _)) => {
+                self.index += 1;
+                let body = self.enclosed(Enclosing::Loop, Self::statement)?;
+                self.expect_keyword(Keyword::While)?;
+                let condition = self.parenthesized_expression()?;
+                let condition = self.condition(condition, "do");
+                self.expect_punctuator(Punctuator::Semicolon)?;
+                StatementKind::DoWhile {
+                    body: Box::new(body),
+                    condition,
+                }
+            }
+            Some(Token::Keyword(Keyword::For, _)) => {
+                // A declaration in the first clause is only in scope
+                // for the loop
+                self.push_scope(ScopeKind::Block);
+                let statement = self.for_statement();
+                self.pop_scope();
+                statement?
+            }
+            Some(Token::Keyword(Keyword::Case, _)) => {
+                let keyword = self.current_span();
+                self.index += 1;
+                let value = self.conditional_expression()?;
+                if self.eat_punctuator(Punctuator::TripleDot) {
+                    let high = self.conditional_expression()?;
+                    let span = value.span.to(high.span);
+                    self.require_gnu("A case range", span);
+                    let (low, lo) = self.case_value(value);
+                    let (high, hi) = self.case_value(high);
+                    let range = lo
+                        .zip(hi)
+                        .filter(|&range| self.check_case_range(range, span));
+                    self.add_case(keyword, range, span);
+                    self.expect_operator(Operator::Colon)?;
+                    let body = self.statement()?;
+                    StatementKind::CaseRange {
+                        low,
+                        high,
+                        body: Box::new(body),
+                    }
+                } else {
+                    let span = value.span;
+                    let (value, constant) = self.case_value(value);
+                    self.add_case(keyword, constant.map(|value| (value, value)), span);
+                    self.expect_operator(Operator::Colon)?;
+                    let body = self.statement()?;
+                    StatementKind::Case {
+                        value,
+                        body: Box::new(body),
+                    }
+                }
+            }
+            Some(Token::Keyword(Keyword::Default, _)) => {
+                let keyword = self.current_span();
+                self.index += 1;
+                self.add_default(keyword);
+                self.expect_operator(Operator::Colon)?;
+                StatementKind::Default(Box::new(self.statement()?))
+            }
+            Some(Token::Keyword(Keyword::Goto, _)) => {
+                self.index += 1;
+                let label = self.expect_identifier()?;
+                self.expect_punctuator(Punctuator::Semicolon)?;
+                self.use_label(&label);
+                StatementKind::Goto(label)
+            }
+            Some(Token::Keyword(Keyword::Continue, _)) => {
+                let keyword = self.current_span();
+                self.index += 1;
+                self.expect_punctuator(Punctuator::Semicolon)?;
+                if !self.enclosing.contains(&Enclosing::Loop) {
+                    self.misplaced(keyword, "`continue` can only be used in a loop");
+                }
+                StatementKind::Continue
+            }
+            Some(Token::Keyword(Keyword::Break, _)) => {
+                let keyword = self.current_span();
+                self.index += 1;
+                self.expect_punctuator(Punctuator::Semicolon)?;
+                if self.enclosing.is_empty() {
+                    self.misplaced(keyword, "`break` can only be used in a loop or `switch`");
+                }
+                StatementKind::Break
+            }
+            Some(Token::Keyword(Keyword::Return, _)) => {
+                let start = self.current_span();
+                self.index += 1;
+                let value = if self.peek_punctuator(Punctuator::Semicolon) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.expect_punctuator(Punctuator::Semicolon)?;
+                let value = self.return_value(value, self.span_from(start));
+                StatementKind::Return(value)
+            }
+            Some(Token::Identifier(..)) if self.label_follows() => {
+                let label = self.expect_identifier()?;
+                self.index += 1;
+                self.define_label(&label);
+                let body = self.statement()?;
+                StatementKind::Labeled {
+                    label,
+                    body: Box::new(body),
+                }
+            }
+            _ => {
+                let expression = self.expression()?;
+                self.expect_punctuator(Punctuator::Semicolon)?;
+                StatementKind::Expression(expression)
+            }
+        };
+
+        Ok(Statement {
+            kind,
+            span: self.span_from(start),
+        })
+    }
+
+    // The body of a loop or `switch` is where `break` and the labels of
+    // a `switch` can be, even if parsing it fails
+    fn enclosed(
+        &mut self,
+        enclosing: Enclosing,
+        parse: impl FnOnce(&mut Self) -> CompResult<Statement>,
+    ) -> CompResult<Statement> {
+        self.enclosing.push(enclosing);
+        let result = parse(self);
+        self.enclosing.pop();
+        result
+    }
+
+    fn switch_labels(&mut self) -> Option<&mut SwitchLabels> {
+        self.enclosing
+            .iter_mut()
+            .rev()
+            .find_map(|enclosing| match enclosing {
+                Enclosing::Switch(labels) => Some(labels),
+                Enclosing::Loop => None,
+            })
+    }
+
+    // Case values are compared as the promoted condition, so they are
+    // converted to it
+    fn case_type(&self, condition: &Expression) -> CType {
+        if self.types.is_integer(condition.ty) {
+            condition.ty
+        } else {
+            CType::ERROR
+        }
+    }
+
+    fn case_value(&mut self, value: Expression) -> (Expression, Option<i64>) {
+        let value = self.value(value);
+        if self.types.is_error(value.ty) {
+            return (value, None);
+        }
+        let value = match self.switch_labels().map(|labels| labels.ty) {
+            Some(ty) if self.types.is_integer(value.ty) => self.convert(value, ty),
+            _ => value,
+        };
+        let constant = self.evaluate_constant(&value);
+        if constant.is_none() || !self.types.is_integer(value.ty) {
+            let error = self
+                .error_at_span(value.span, ErrorCode::InvalidConstantExpression)
+                .message("A `case` value has to be an integer constant expression".into())
+                .build();
+            self.diagnose(*error);
+            return (value, None);
+        }
+        (value, constant)
+    }
+
+    // GNU `case 1 ... 5:` covers both ends, so it has to go upwards
+    fn check_case_range(&mut self, (lo, hi): (i64, i64), span: Span) -> bool {
+        if lo > hi {
+            let error = self
+                .error_at_span(span, ErrorCode::InvalidConstantExpression)
+                .message(format!("Case range `{} ... {}` is empty", lo, hi))
+                .build();
+            self.report(*error);
+        }
+        lo <= hi
+    }
+
+    // Each value can only have one `case` in a `switch`, which a range
+    // counts as for all of its values
+    fn add_case(&mut self, keyword: Span, range: Option<(i64, i64)>, span: Span) {
+        let Some(labels) = self.switch_labels() else {
+            self.misplaced(keyword, "`case` can only be used in a `switch`");
+            return;
+        };
+        let Some((lo, hi)) = range else {
+            return;
+        };
+        let Some(previous) = labels.overlapping(lo, hi) else {
+            labels.cases.insert(lo, (hi, span));
+            return;
+        };
+        let message = if lo == hi {
+            format!("Case value {} is already used in the `switch`", lo)
+        } else {
+            format!(
+                "Case range `{} ... {}` overlaps a case already in the `switch`",
+                lo, hi
+            )
+        };
+        let error = self
+            .error_at_span(span, ErrorCode::InvalidStatement)
+            .message(message)
+            .note_at(
+                "Previous case is here".into(),
+                self.sources.buffer(previous.file_id),
+                previous,
+            )
+            .build();
+        self.diagnose(*error);
+    }
+
+    fn add_default(&mut self, keyword: Span) {
+        let Some(labels) = self.switch_labels() else {
+            self.misplaced(keyword, "`default` can only be used in a `switch`");
+            return;
+        };
+        let Some(previous) = labels.default.replace(keyword) else {
+            return;
+        };
+        labels.default = Some(previous);
+        let error = self
+            .error_at_span(keyword, ErrorCode::InvalidStatement)
+            .message("A `switch` can only have one `default`".into())
+            .note_at(
+                "Previous `default` is here".into(),
+                self.sources.buffer(previous.file_id),
+                previous,
+            )
+            .build();
+        self.diagnose(*error);
+    }
+
+    fn misplaced(&mut self, keyword: Span, message: &str) {
+        let error = self
+            .error_at_span(keyword, ErrorCode::InvalidStatement)
+            .message(message.into())
+            .build();
+        self.diagnose(*error);
+    }
+
+    // An `else` belongs to the closest `if` that doesn't have one yet
+    fn if_statement(&mut self) -> CompResult<StatementKind> {
+        self.expect_keyword(Keyword::If)?;
+        let condition = self.parenthesized_expression()?;
+        let condition = self.condition(condition, "if");
+        let then = self.statement()?;
+        let otherwise = if self.eat_keyword(Keyword::Else) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(StatementKind::If {
+            condition,
+            then: Box::new(then),
+            otherwise,
+        })
+    }
+
+    // The first clause can also be a declaration, which ends with its
+    // own semicolon
+    fn for_statement(&mut self) -> CompResult<StatementKind> {
+        self.expect_keyword(Keyword::For)?;
+        self.expect_operator(Operator::LParen)?;
+
+        let init = if self.eat_punctuator(Punctuator::Semicolon) {
+            None
+        } else if self.declaration_follows() {
+            let what = "A declaration in a `for` loop";
+            self.require_standard(Standard::C99, what, self.current_span());
+            Some(Box::new(ForInit::Declaration(self.declaration()?)))
+        } else {
+            let expression = self.expression()?;
+            self.expect_punctuator(Punctuator::Semicolon)?;
+            Some(Box::new(ForInit::Expression(expression)))
+        };
+        let condition = if self.peek_punctuator(Punctuator::Semicolon) {
+            None
+        } else {
+            let condition = self.expression()?;
+            Some(self.condition(condition, "for"))
+        };
+        self.expect_punctuator(Punctuator::Semicolon)?;
+        let step = if self.peek_operator(Operator::RParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.expect_operator(Operator::RParen)?;
+        let body = self.enclosed(Enclosing::Loop, Self::statement)?;
+
+        Ok(StatementKind::For {
+            init,
+            condition,
+            step,
+            body: Box::new(body),
+        })
+    }
+
+    fn label_follows(&self) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(..)))
+            && self
+                .peek_nth(1)
+                .is_some_and(|token| token.is_operator(Operator::Colon))
+    }
+
+    fn parenthesized_expression(&mut self) -> CompResult<Expression> {
+        self.expect_operator(Operator::LParen)?;
+        let expression = self.expression()?;
+        self.expect_operator(Operator::RParen)?;
+        Ok(expression)
+    }
+}
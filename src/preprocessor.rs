@@ -0,0 +1,1354 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::comp_error::*;
+use crate::headers::{self, BUNDLED_INCLUDE_DIR};
+use crate::lang;
+use crate::lexer::*;
+use crate::source::{MacroExpansion, SourceBuffer, SourceManager};
+use crate::target;
+
+mod condition;
+mod embed;
+
+use condition::ConditionEvaluator;
+
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+// Name of the buffers token pastes are relexed in
+const SCRATCH_BUFFER: &str = "<scratch space>";
+
+// Macros every translation unit starts out with, processed
+// as the `<built-in>` buffer before anything else. Like GCC,
+// `__STRICT_ANSI__` says GNU extensions are off.
+pub fn predefined_macros(standard: lang::Standard, gnu: bool) -> String {
+    let mut macros = String::from("#define __STDC__ 1\n");
+    if let Some(version) = standard.version() {
+        macros.push_str(&format!("#define __STDC_VERSION__ {}\n", version));
+    }
+    if !gnu {
+        macros.push_str("#define __STRICT_ANSI__ 1\n");
+    }
+    macros.push_str(&target::predefined_macros());
+    macros
+}
+
+// Macros whose expansion depends on where they are used, so
+// they can't live in the macro table
+const DYNAMIC_MACROS: [&str; 2] = ["__FILE__", "__LINE__"];
+
+// Function-like operators that only exist inside of `#if`
+const CONDITION_BUILTINS: [&str; 6] = [
+    "__has_include",
+    "__has_include_next",
+    "__has_attribute",
+    "__has_builtin",
+    "__has_feature",
+    "__has_extension",
+];
+
+// The ones C23 added
+const C23_CONDITION_BUILTINS: [&str; 2] = ["__has_c_attribute", "__has_embed"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MacroKind {
+    Object,
+    Function { params: Vec<String>, variadic: bool },
+}
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub name: String,
+    pub kind: MacroKind,
+    pub body: Vec<PreprocessToken>,
+}
+
+impl Macro {
+    // Index of the argument a body token refers to, the variadic
+    // arguments come after all of the named ones
+    fn param_index(&self, token: &PreprocessToken) -> Option<usize> {
+        let MacroKind::Function { params, variadic } = &self.kind else {
+            return None;
+        };
+        let PreprocessToken::Identifier(name, _) = token else {
+            return None;
+        };
+        if *variadic && name == "__VA_ARGS__" {
+            return Some(params.len());
+        }
+        params.iter().position(|param| param == name)
+    }
+}
+
+// A buffer that is being preprocessed, kept around so that
+// relative includes can refer back to it
+#[derive(Debug)]
+pub(crate) struct SourceFile {
+    buffer: Rc<SourceBuffer>,
+    dir: PathBuf,
+    path: Option<PathBuf>,
+    // Found through a system include directory
+    system: bool,
+    // Position in the search path the file was found at, which is
+    // where `#include_next` continues from
+    search_index: Option<usize>,
+}
+
+// Where `find_include` found a header
+#[derive(Debug)]
+struct FoundInclude {
+    path: PathBuf,
+    system: bool,
+    search_index: Option<usize>,
+}
+
+// A token in the middle of macro expansion along with the names
+// of the macros that are no longer allowed to expand it
+#[derive(Debug, Clone)]
+struct ExpansionToken {
+    token: PreprocessToken,
+    hide_set: Rc<HashSet<String>>,
+}
+
+impl ExpansionToken {
+    fn new(token: PreprocessToken) -> Self {
+        Self {
+            token,
+            hide_set: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Conditional {
+    directive: PreprocessToken,
+    parent_active: bool,
+    active: bool,
+    taken: bool,
+    seen_else: bool,
+}
+
+// A file that was opened while preprocessing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub path: PathBuf,
+    pub system: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Preprocessor {
+    include_dirs: Vec<PathBuf>,
+    system_dirs: Vec<PathBuf>,
+    macros: HashMap<String, Rc<Macro>>,
+    // Everything processed so far, which tokens point into
+    sources: SourceManager,
+    once_files: HashSet<PathBuf>,
+    include_depth: usize,
+    dependencies: Vec<Dependency>,
+    output: Vec<PreprocessToken>,
+    standard: lang::Standard,
+}
+
+impl Preprocessor {
+    pub fn new(
+        include_dirs: Vec<PathBuf>,
+        system_dirs: Vec<PathBuf>,
+        standard: lang::Standard,
+    ) -> Self {
+        Self {
+            include_dirs,
+            system_dirs,
+            standard,
+            ..Default::default()
+        }
+    }
+
+    pub fn output(&self) -> &[PreprocessToken] {
+        &self.output
+    }
+
+    pub fn take_output(&mut self) -> Vec<PreprocessToken> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn macros(&self) -> &HashMap<String, Rc<Macro>> {
+        &self.macros
+    }
+
+    pub fn sources(&self) -> &SourceManager {
+        &self.sources
+    }
+
+    pub(crate) fn error_at(&self, token: &PreprocessToken, code: ErrorCode) -> CompErrorBuilder {
+        self.sources.error_at(token.metadata(), code)
+    }
+
+    // Every file that has been opened, in the order they were first
+    // opened and without duplicates
+    pub fn dependencies(&self) -> &[Dependency] {
+        &self.dependencies
+    }
+
+    pub fn process_file(&mut self, path: &Path) -> CompResult<()> {
+        self.open_file(FoundInclude {
+            path: path.to_path_buf(),
+            system: false,
+            search_index: None,
+        })
+    }
+
+    fn open_file(&mut self, found: FoundInclude) -> CompResult<()> {
+        let FoundInclude {
+            ref path,
+            system,
+            search_index,
+        } = found;
+        let source = match headers::bundled_header(path) {
+            Some(contents) => contents.to_string(),
+            None => std::fs::read_to_string(path).map_err(|err| {
+                CompErrorBuilder::new()
+                    .code(ErrorCode::InputFileUnreadable)
+                    .message(format!("Failed to read `{}`: {}", path.display(), err))
+                    .build()
+            })?,
+        };
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        self.add_dependency(path, system);
+
+        let buffer = self.sources.add(&path.display().to_string(), source);
+        self.process_source(SourceFile {
+            buffer,
+            dir,
+            path: Some(path.to_path_buf()),
+            system,
+            search_index,
+        })
+    }
+
+    // Bundled headers are part of zcc rather than files Make could
+    // track, so they aren't dependencies
+    fn add_dependency(&mut self, path: &Path, system: bool) {
+        let bundled = headers::is_bundled(path);
+        if !bundled && !self.dependencies.iter().any(|dep| dep.path == *path) {
+            self.dependencies.push(Dependency {
+                path: path.to_path_buf(),
+                system,
+            });
+        }
+    }
+
+    // Processes text that doesn't come from a file on disk, such as
+    // the command line definitions. Relative includes are looked up
+    // from `dir`.
+    pub fn process_buffer(&mut self, name: &str, dir: &Path, source: &str) -> CompResult<()> {
+        let buffer = self.sources.add(name, source.into());
+        self.process_source(SourceFile {
+            buffer,
+            dir: dir.to_path_buf(),
+            path: None,
+            system: false,
+            search_index: None,
+        })
+    }
+
+    fn process_source(&mut self, file: SourceFile) -> CompResult<()> {
+        let mut lexer = Lexer::new(file.buffer.clone(), self.standard);
+        lexer.tokenize()?;
+        let tokens = lexer.take_pp_tokens();
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut text: Vec<PreprocessToken> = Vec::new();
+
+        for line in tokens.split(|token| token.is_newline()) {
+            let Some(first) = line.first() else {
+                continue;
+            };
+
+            if let PreprocessToken::Punctuator(hash, _) = first {
+                if hash == "#" {
+                    // Text before a directive has to be expanded first,
+                    // the directive might change the macros it uses
+                    if !text.is_empty() {
+                        let expanded = self.expand_tokens(&file, std::mem::take(&mut text))?;
+                        self.output.extend(expanded);
+                    }
+                    self.directive(&file, line, &mut conditionals)?;
+                    continue;
+                }
+            }
+
+            if conditionals.last().is_none_or(|cond| cond.active) {
+                text.extend(line.iter().cloned());
+            }
+        }
+
+        if !text.is_empty() {
+            let expanded = self.expand_tokens(&file, text)?;
+            self.output.extend(expanded);
+        }
+
+        if let Some(cond) = conditionals.last() {
+            return Err(self
+                .error_at(&cond.directive, ErrorCode::UnterminatedConditional)
+                .message("Expected `#endif`, found end of file".into())
+                .highlight_message("Conditional started here".into())
+                .build());
+        }
+
+        Ok(())
+    }
+
+    fn directive(
+        &mut self,
+        file: &SourceFile,
+        line: &[PreprocessToken],
+        conditionals: &mut Vec<Conditional>,
+    ) -> CompResult<()> {
+        let active = conditionals.last().is_none_or(|cond| cond.active);
+
+        // A lone `#` is the null directive
+        let Some(directive) = line.get(1) else {
+            return Ok(());
+        };
+        let args = &line[2..];
+
+        let name = match directive {
+            PreprocessToken::Identifier(name, _) => name.as_str(),
+            _ if !active => return Ok(()),
+            _ => {
+                return Err(self
+                    .error_at(directive, ErrorCode::InvalidDirective)
+                    .message(format!(
+                        "Expected directive name, found `{}`",
+                        directive.spelling()
+                    ))
+                    .build())
+            }
+        };
+
+        match name {
+            "if" | "ifdef" | "ifndef" => {
+                let value = active
+                    && match name {
+                        "if" => self.evaluate_condition(file, directive, args)?,
+                        "ifdef" => self.evaluate_defined(directive, args)?,
+                        _ => !self.evaluate_defined(directive, args)?,
+                    };
+                conditionals.push(Conditional {
+                    directive: directive.clone(),
+                    parent_active: active,
+                    active: value,
+                    taken: value,
+                    seen_else: false,
+                });
+            }
+            "elif" | "else" | "endif" | "elifdef" | "elifndef"
+                if self.standard >= lang::Standard::C23
+                    || !name.starts_with("elif")
+                    || name == "elif" =>
+            {
+                let Some(cond) = conditionals.last_mut() else {
+                    return Err(self
+                        .error_at(directive, ErrorCode::UnmatchedConditional)
+                        .message(format!("Found `#{}` without a matching `#if`", name))
+                        .build());
+                };
+                if name != "endif" && cond.seen_else {
+                    return Err(self
+                        .error_at(directive, ErrorCode::InvalidDirective)
+                        .message(format!("Found `#{}` after `#else`", name))
+                        .build());
+                }
+
+                match name {
+                    "elif" | "elifdef" | "elifndef" => {
+                        if cond.parent_active && !cond.taken {
+                            cond.active = match name {
+                                "elif" => self.evaluate_condition(file, directive, args)?,
+                                "elifdef" => self.evaluate_defined(directive, args)?,
+                                _ => !self.evaluate_defined(directive, args)?,
+                            };
+                            cond.taken = cond.active;
+                        } else {
+                            cond.active = false;
+                        }
+                    }
+                    "else" => {
+                        cond.active = cond.parent_active && !cond.taken;
+                        cond.taken = true;
+                        cond.seen_else = true;
+                    }
+                    _ => {
+                        conditionals.pop();
+                    }
+                }
+            }
+            _ if !active => (),
+            "define" => self.define(directive, args)?,
+            "undef" => {
+                let name = self.macro_name(directive, args)?;
+                self.macros.remove(&name);
+            }
+            "include" => self.include(file, directive, args, false)?,
+            "include_next" => self.include(file, directive, args, true)?,
+            "embed" if self.standard >= lang::Standard::C23 => self.embed(file, directive, args)?,
+            "error" => {
+                let message = args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, token)| match i {
+                        0 => token.spelling(),
+                        _ if token.metadata().leading_space => format!(" {}", token.spelling()),
+                        _ => token.spelling(),
+                    })
+                    .collect::<String>();
+                return Err(self
+                    .error_at(directive, ErrorCode::ErrorDirective)
+                    .message(message)
+                    .build());
+            }
+            "pragma" => {
+                if args
+                    .first()
+                    .is_some_and(|token| token.is_identifier("once"))
+                {
+                    if let Some(ref path) = file.path {
+                        self.once_files.insert(canonical(path));
+                    }
+                }
+            }
+            // Line markers don't affect anything we track yet
+            "line" => (),
+            _ => {
+                return Err(self
+                    .error_at(directive, ErrorCode::InvalidDirective)
+                    .message(format!("Unknown directive `#{}`", name))
+                    .build())
+            }
+        }
+
+        Ok(())
+    }
+
+    fn macro_name(
+        &self,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<String> {
+        match args.first() {
+            Some(PreprocessToken::Identifier(name, _)) if name == "defined" => Err(self
+                .error_at(&args[0], ErrorCode::InvalidMacroName)
+                .message("`defined` cannot be used as a macro name".into())
+                .build()),
+            Some(PreprocessToken::Identifier(name, _)) => Ok(name.clone()),
+            Some(token) => Err(self
+                .error_at(token, ErrorCode::InvalidMacroName)
+                .message(format!(
+                    "Expected identifier as macro name, found `{}`",
+                    token.spelling()
+                ))
+                .build()),
+            None => Err(self
+                .error_at(directive, ErrorCode::InvalidMacroName)
+                .message("Expected macro name, found end of line".into())
+                .build()),
+        }
+    }
+
+    fn define(&mut self, directive: &PreprocessToken, args: &[PreprocessToken]) -> CompResult<()> {
+        let name = self.macro_name(directive, args)?;
+        let rest = &args[1..];
+
+        // Only a parenthesis directly after the name makes the
+        // macro function-like
+        let function_like = rest
+            .first()
+            .is_some_and(|token| token.is_punctuation("(") && !token.metadata().leading_space);
+
+        let (kind, body) = if function_like {
+            let mut params = Vec::new();
+            let mut variadic = false;
+            let mut index = 1;
+            let mut closed = rest.get(index).is_some_and(|t| t.is_punctuation(")"));
+            if closed {
+                index += 1;
+            }
+
+            while !closed {
+                let token = self.parameter_token(rest, index)?;
+                match token {
+                    PreprocessToken::Identifier(param, _) if param != "__VA_ARGS__" => {
+                        if params.contains(param) {
+                            return Err(self
+                                .error_at(token, ErrorCode::InvalidMacroDefinition)
+                                .message(format!("Duplicate macro parameter `{}`", param))
+                                .build());
+                        }
+                        params.push(param.clone());
+                    }
+                    _ if token.is_punctuation("...") => variadic = true,
+                    _ => {
+                        return Err(self
+                            .error_at(token, ErrorCode::InvalidMacroDefinition)
+                            .message(format!(
+                                "Expected macro parameter name, found `{}`",
+                                token.spelling()
+                            ))
+                            .build())
+                    }
+                }
+
+                // `...` has to be the last parameter
+                let separator = self.parameter_token(rest, index + 1)?;
+                closed = separator.is_punctuation(")");
+                if !closed && (variadic || !separator.is_punctuation(",")) {
+                    return Err(self
+                        .error_at(separator, ErrorCode::InvalidMacroDefinition)
+                        .message(format!(
+                            "Expected {} in macro parameter list, found `{}`",
+                            if variadic { "`)`" } else { "`,` or `)`" },
+                            separator.spelling()
+                        ))
+                        .build());
+                }
+                index += 2;
+            }
+            (MacroKind::Function { params, variadic }, &rest[index..])
+        } else {
+            (MacroKind::Object, rest)
+        };
+
+        let mac = Macro {
+            name: name.clone(),
+            kind,
+            body: body.to_vec(),
+        };
+        self.validate_body(&mac)?;
+        self.macros.insert(name, Rc::new(mac));
+
+        Ok(())
+    }
+
+    fn parameter_token<'t>(
+        &self,
+        rest: &'t [PreprocessToken],
+        index: usize,
+    ) -> CompResult<&'t PreprocessToken> {
+        rest.get(index).ok_or_else(|| {
+            self.error_at(&rest[index - 1], ErrorCode::InvalidMacroDefinition)
+                .message("Expected `)` in macro parameter list, found end of line".into())
+                .build()
+        })
+    }
+
+    fn validate_body(&self, mac: &Macro) -> CompResult<()> {
+        if let (Some(first), Some(last)) = (mac.body.first(), mac.body.last()) {
+            for token in [first, last] {
+                if token.is_punctuation("##") {
+                    return Err(self
+                        .error_at(token, ErrorCode::InvalidMacroDefinition)
+                        .message("`##` cannot appear at either end of a macro body".into())
+                        .build());
+                }
+            }
+        }
+
+        let variadic = matches!(mac.kind, MacroKind::Function { variadic: true, .. });
+        for (i, token) in mac.body.iter().enumerate() {
+            if !token.is_identifier("__VA_OPT__") {
+                continue;
+            }
+            let problem = match va_opt_end(&mac.body, i) {
+                _ if !variadic => "`__VA_OPT__` can only be used in a variadic macro",
+                None => "`__VA_OPT__` must be followed by tokens in parentheses",
+                Some(end)
+                    if mac.body[i + 1..end]
+                        .iter()
+                        .any(|t| t.is_identifier("__VA_OPT__")) =>
+                {
+                    "`__VA_OPT__` can't be nested"
+                }
+                Some(_) => continue,
+            };
+            return Err(self
+                .error_at(token, ErrorCode::InvalidMacroDefinition)
+                .message(problem.into())
+                .build());
+        }
+
+        if matches!(mac.kind, MacroKind::Function { .. }) {
+            for (i, token) in mac.body.iter().enumerate() {
+                let operand = mac.body.get(i + 1);
+                if token.is_punctuation("#") && operand.and_then(|t| mac.param_index(t)).is_none() {
+                    return Err(self
+                        .error_at(token, ErrorCode::InvalidMacroDefinition)
+                        .message("`#` must be followed by a macro parameter".into())
+                        .build());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn include(
+        &mut self,
+        file: &SourceFile,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+        next: bool,
+    ) -> CompResult<()> {
+        let (name, header_type, token) = self.header_name(file, directive, args)?;
+
+        let Some(found) = self.find_include(file, &name, header_type, next) else {
+            return Err(self
+                .error_at(&token, ErrorCode::IncludeNotFound)
+                .message(format!("Could not find `{}`", name))
+                .highlight_message("Included here".into())
+                .build());
+        };
+
+        if self.once_files.contains(&canonical(&found.path)) {
+            return Ok(());
+        }
+
+        if self.include_depth >= MAX_INCLUDE_DEPTH {
+            return Err(self
+                .error_at(&token, ErrorCode::IncludeDepthExceeded)
+                .message(format!(
+                    "Exceeded the maximum include depth of {}",
+                    MAX_INCLUDE_DEPTH
+                ))
+                .build());
+        }
+
+        self.include_depth += 1;
+        let result = self.open_file(found);
+        self.include_depth -= 1;
+        result
+    }
+
+    fn header_name(
+        &mut self,
+        file: &SourceFile,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<(String, HeaderNameType, PreprocessToken)> {
+        match args.first() {
+            Some(token @ PreprocessToken::HeaderName(name, header_type, _)) => {
+                Ok((name.clone(), *header_type, token.clone()))
+            }
+            Some(_) => self.computed_header_name(file, directive, args),
+            None => Err(self
+                .error_at(directive, ErrorCode::InvalidDirective)
+                .message("Expected header name, found end of line".into())
+                .build()),
+        }
+    }
+
+    // `#include` followed by something other than a header name gets
+    // macro expanded and then has to look like one
+    fn computed_header_name(
+        &mut self,
+        file: &SourceFile,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<(String, HeaderNameType, PreprocessToken)> {
+        let expanded = self.expand_tokens(file, args.to_vec())?;
+        match expanded.first() {
+            Some(token @ PreprocessToken::StringLiteral(name, _)) if expanded.len() == 1 => {
+                Ok((name.clone(), HeaderNameType::Local, token.clone()))
+            }
+            Some(token) if token.is_punctuation("<") && expanded.len() > 2 => {
+                let end = expanded.len() - 1;
+                if !expanded[end].is_punctuation(">") {
+                    return Err(self
+                        .error_at(&expanded[end], ErrorCode::InvalidDirective)
+                        .message(format!(
+                            "Expected `>` at end of header name, found `{}`",
+                            expanded[end].spelling()
+                        ))
+                        .build());
+                }
+                let mut name = String::new();
+                for (i, part) in expanded[1..end].iter().enumerate() {
+                    if i > 0 && part.metadata().leading_space {
+                        name.push(' ');
+                    }
+                    name.push_str(&part.spelling());
+                }
+                Ok((name, HeaderNameType::Included, token.clone()))
+            }
+            Some(token) => Err(self
+                .error_at(token, ErrorCode::InvalidDirective)
+                .message(format!(
+                    "Expected header name, found `{}`",
+                    token.spelling()
+                ))
+                .build()),
+            None => Err(self
+                .error_at(directive, ErrorCode::InvalidDirective)
+                .message("Expected header name, found end of line".into())
+                .build()),
+        }
+    }
+
+    // Looks for a header the way `#include` does, returning where it
+    // was found and whether that makes it a system header. Headers
+    // included relative to a system header are system headers too.
+    // `next` skips everything up to and including the directory the
+    // current file was found in, for `#include_next`.
+    fn find_include(
+        &self,
+        file: &SourceFile,
+        name: &str,
+        header_type: HeaderNameType,
+        next: bool,
+    ) -> Option<FoundInclude> {
+        let name = Path::new(name);
+        if name.is_absolute() {
+            return name.is_file().then(|| FoundInclude {
+                path: name.to_path_buf(),
+                system: false,
+                search_index: None,
+            });
+        }
+
+        // `#include_next` from a file that wasn't found through the
+        // search path acts like `#include`
+        let start = match file.search_index {
+            Some(index) if next => index + 1,
+            _ => 0,
+        };
+        let local = match header_type {
+            HeaderNameType::Local if start == 0 => Some(FoundInclude {
+                path: file.dir.join(name),
+                system: file.system,
+                search_index: None,
+            }),
+            _ => None,
+        };
+
+        let searched =
+            self.search_path()
+                .into_iter()
+                .enumerate()
+                .skip(start)
+                .map(|(index, (dir, system))| FoundInclude {
+                    path: dir.join(name),
+                    system,
+                    search_index: Some(index),
+                });
+
+        local
+            .into_iter()
+            .chain(searched)
+            .find(|found| match headers::is_bundled(&found.path) {
+                true => headers::bundled_header(&found.path).is_some(),
+                false => found.path.is_file(),
+            })
+    }
+
+    // Directories searched for headers after the including file's own,
+    // and whether they hold system headers
+    fn search_path(&self) -> Vec<(PathBuf, bool)> {
+        let user = self.include_dirs.iter().map(|dir| (dir.clone(), false));
+        let system = self.system_dirs.iter().map(|dir| (dir.clone(), true));
+        let bundled = std::iter::once((PathBuf::from(BUNDLED_INCLUDE_DIR), true));
+        user.chain(system).chain(bundled).collect()
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+            || DYNAMIC_MACROS.contains(&name)
+            || self.is_condition_builtin(name)
+    }
+
+    fn is_condition_builtin(&self, name: &str) -> bool {
+        CONDITION_BUILTINS.contains(&name)
+            || (self.standard >= lang::Standard::C23 && C23_CONDITION_BUILTINS.contains(&name))
+    }
+
+    fn evaluate_defined(
+        &self,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<bool> {
+        let name = self.macro_name(directive, args)?;
+        Ok(self.is_defined(&name))
+    }
+
+    fn evaluate_condition(
+        &mut self,
+        file: &SourceFile,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<bool> {
+        // `defined` and the `__has_*` operators have to be resolved
+        // before expansion, otherwise the names they refer to would be
+        // replaced
+        let mut tokens = Vec::new();
+        let mut index = 0;
+        while index < args.len() {
+            let token = &args[index];
+            index += 1;
+
+            if let PreprocessToken::Identifier(name, meta) = token {
+                if self.is_condition_builtin(name) {
+                    let (value, end) = self.condition_builtin(file, token, &args[index..])?;
+                    index += end;
+                    tokens.push(PreprocessToken::Number(value.to_string(), *meta));
+                    continue;
+                }
+            }
+
+            if !token.is_identifier("defined") {
+                tokens.push(token.clone());
+                continue;
+            }
+
+            let parenthesized = args.get(index).is_some_and(|t| t.is_punctuation("("));
+            if parenthesized {
+                index += 1;
+            }
+            let name = match args.get(index) {
+                Some(PreprocessToken::Identifier(name, _)) => name,
+                other => {
+                    return Err(self
+                        .error_at(
+                            other.unwrap_or(token),
+                            ErrorCode::InvalidConditionalExpression,
+                        )
+                        .message("Expected macro name after `defined`".into())
+                        .build())
+                }
+            };
+            index += 1;
+            if parenthesized {
+                match args.get(index) {
+                    Some(t) if t.is_punctuation(")") => index += 1,
+                    other => {
+                        return Err(self
+                            .error_at(
+                                other.unwrap_or(&args[index - 1]),
+                                ErrorCode::InvalidConditionalExpression,
+                            )
+                            .message("Expected `)` after macro name in `defined`".into())
+                            .build())
+                    }
+                }
+            }
+
+            let value = if self.is_defined(name) { "1" } else { "0" };
+            tokens.push(PreprocessToken::Number(value.into(), *token.metadata()));
+        }
+
+        let expanded = self.expand_tokens(file, tokens)?;
+        ConditionEvaluator::new(self, directive, &expanded).evaluate()
+    }
+
+    // Evaluates one of the `__has_*` operators, `args` starts after the
+    // operator name. Returns the result and how many tokens it used.
+    fn condition_builtin(
+        &mut self,
+        file: &SourceFile,
+        operator: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<(u64, usize)> {
+        let PreprocessToken::Identifier(ref name, _) = *operator else {
+            unreachable!("Only called with identifiers");
+        };
+
+        if !args.first().is_some_and(|t| t.is_punctuation("(")) {
+            return Err(self
+                .error_at(
+                    args.first().unwrap_or(operator),
+                    ErrorCode::InvalidConditionalExpression,
+                )
+                .message(format!("Expected `(` after `{}`", name))
+                .build());
+        }
+
+        // Everything up to the matching parenthesis is the operand
+        let mut depth = 0;
+        let Some(end) = args.iter().position(|token| {
+            if token.is_punctuation("(") {
+                depth += 1;
+            } else if token.is_punctuation(")") {
+                depth -= 1;
+            }
+            depth == 0
+        }) else {
+            return Err(self
+                .error_at(operator, ErrorCode::InvalidConditionalExpression)
+                .message(format!("Expected `)` to end `{}`, found end of line", name))
+                .build());
+        };
+        let operand = &args[1..end];
+
+        let value = match name.as_str() {
+            "__has_include" | "__has_include_next" => {
+                let (header, header_type, _) = self.header_name(file, operator, operand)?;
+                let next = name == "__has_include_next";
+                self.find_include(file, &header, header_type, next)
+                    .is_some() as u64
+            }
+            "__has_embed" => self.has_embed(file, operator, operand)?,
+            // `__has_c_attribute(gnu::packed)` asks about `__attribute__`
+            "__has_c_attribute" => match operand {
+                [PreprocessToken::Identifier(attribute, _)] => {
+                    lang::standard_attribute(attribute).map_or(0, |(_, version)| version)
+                }
+                [PreprocessToken::Identifier(prefix, _), colons, PreprocessToken::Identifier(attribute, _)]
+                    if colons.is_punctuation("::") =>
+                {
+                    (matches!(prefix.as_str(), "gnu" | "__gnu__") && lang::is_attribute(attribute))
+                        as u64
+                }
+                _ => {
+                    return Err(self
+                        .error_at(
+                            operand.first().unwrap_or(&args[end]),
+                            ErrorCode::InvalidConditionalExpression,
+                        )
+                        .message(format!("Expected attribute as the operand of `{}`", name))
+                        .build())
+                }
+            },
+            _ => {
+                let feature = match operand {
+                    [PreprocessToken::Identifier(feature, _)] => feature,
+                    _ => {
+                        return Err(self
+                            .error_at(
+                                operand.first().unwrap_or(&args[end]),
+                                ErrorCode::InvalidConditionalExpression,
+                            )
+                            .message(format!("Expected identifier as the operand of `{}`", name))
+                            .build())
+                    }
+                };
+                let value = match name.as_str() {
+                    "__has_attribute" => lang::is_attribute(feature),
+                    "__has_builtin" => lang::BUILTIN_FUNCTIONS.contains(&feature.as_str()),
+                    "__has_feature" => {
+                        lang::feature(feature).is_some_and(|standard| self.standard >= standard)
+                    }
+                    _ => lang::feature(feature).is_some(),
+                };
+                value as u64
+            }
+        };
+
+        Ok((value, end + 1))
+    }
+
+    fn expand_tokens(
+        &mut self,
+        file: &SourceFile,
+        tokens: Vec<PreprocessToken>,
+    ) -> CompResult<Vec<PreprocessToken>> {
+        let tokens = tokens.into_iter().map(ExpansionToken::new).collect();
+        Ok(self
+            .expand(file, tokens)?
+            .into_iter()
+            .map(|token| token.token)
+            .collect())
+    }
+
+    // Macro expansion using hide sets, a token that came out of
+    // expanding a macro can never expand that macro again
+    fn expand(
+        &mut self,
+        file: &SourceFile,
+        tokens: Vec<ExpansionToken>,
+    ) -> CompResult<Vec<ExpansionToken>> {
+        let mut input: VecDeque<ExpansionToken> = tokens.into();
+        let mut output = Vec::new();
+
+        while let Some(current) = input.pop_front() {
+            let name = match current.token {
+                PreprocessToken::Identifier(ref name, _) if !current.hide_set.contains(name) => {
+                    name.clone()
+                }
+                _ => {
+                    output.push(current);
+                    continue;
+                }
+            };
+
+            if let Some(token) = self.dynamic_macro(file, &name, current.token.metadata()) {
+                output.push(ExpansionToken::new(token));
+                continue;
+            }
+
+            let Some(mac) = self.macros.get(&name).cloned() else {
+                output.push(current);
+                continue;
+            };
+
+            let replacement = match mac.kind {
+                MacroKind::Object => {
+                    let hide_set = add_to_hide_set(&current.hide_set, &name);
+                    self.substitute(file, &mac, &[], &current, hide_set)?
+                }
+                MacroKind::Function { .. } => {
+                    // A function-like macro name without arguments is
+                    // just an identifier
+                    if !input.front().is_some_and(|t| t.token.is_punctuation("(")) {
+                        output.push(current);
+                        continue;
+                    }
+                    let (args, rparen) = self.collect_arguments(&mac, &current, &mut input)?;
+                    let hide_set = current
+                        .hide_set
+                        .intersection(&rparen.hide_set)
+                        .cloned()
+                        .collect::<HashSet<_>>();
+                    let hide_set = add_to_hide_set(&Rc::new(hide_set), &name);
+                    self.substitute(file, &mac, &args, &current, hide_set)?
+                }
+            };
+
+            for token in replacement.into_iter().rev() {
+                input.push_front(token);
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn dynamic_macro(
+        &self,
+        file: &SourceFile,
+        name: &str,
+        meta: &PreprocessMetadata,
+    ) -> Option<PreprocessToken> {
+        match name {
+            "__FILE__" => {
+                let name = file.buffer.name();
+                let escaped = name.chars().map(|c| escape_char(c, '"')).collect();
+                Some(PreprocessToken::StringLiteral(escaped, *meta))
+            }
+            "__LINE__" => {
+                let (line, _) = self.sources.line_col(meta.span);
+                Some(PreprocessToken::Number(line.to_string(), *meta))
+            }
+            _ => None,
+        }
+    }
+
+    fn collect_arguments(
+        &mut self,
+        mac: &Macro,
+        invocation: &ExpansionToken,
+        input: &mut VecDeque<ExpansionToken>,
+    ) -> CompResult<(Vec<Vec<ExpansionToken>>, ExpansionToken)> {
+        let MacroKind::Function {
+            ref params,
+            variadic,
+        } = mac.kind
+        else {
+            unreachable!("Only function-like macros take arguments");
+        };
+
+        let lparen = input.pop_front().expect("Precondition");
+        assert!(lparen.token.is_punctuation("("));
+
+        let mut args: Vec<Vec<ExpansionToken>> = vec![Vec::new()];
+        let mut depth = 0;
+        let rparen = loop {
+            let Some(token) = input.pop_front() else {
+                return Err(self
+                    .error_at(&invocation.token, ErrorCode::UnterminatedMacroInvocation)
+                    .message(format!(
+                        "Expected `)` to end invocation of `{}`, found end of file",
+                        mac.name
+                    ))
+                    .highlight_message("Invoked here".into())
+                    .build());
+            };
+
+            if token.token.is_punctuation("(") {
+                depth += 1;
+            } else if token.token.is_punctuation(")") {
+                if depth == 0 {
+                    break token;
+                }
+                depth -= 1;
+            } else if token.token.is_punctuation(",")
+                && depth == 0
+                && !(variadic && args.len() > params.len())
+            {
+                args.push(Vec::new());
+                continue;
+            }
+
+            args.last_mut().expect("Never empty").push(token);
+        };
+
+        // `F()` passes one empty argument, which is no arguments at all
+        // if the macro doesn't take any
+        if params.is_empty() && args.len() == 1 && args[0].is_empty() && !variadic {
+            args.clear();
+        }
+        // The variadic arguments are allowed to be left out entirely
+        if variadic && args.len() == params.len() {
+            args.push(Vec::new());
+        }
+
+        let expected = params.len() + usize::from(variadic);
+        if args.len() != expected {
+            return Err(self
+                .error_at(&invocation.token, ErrorCode::MacroArgumentCount)
+                .message(format!(
+                    "`{}` expects {}{} argument{}, but {} {} given",
+                    mac.name,
+                    if variadic { "at least " } else { "" },
+                    params.len(),
+                    if params.len() == 1 { "" } else { "s" },
+                    args.len(),
+                    if args.len() == 1 { "was" } else { "were" },
+                ))
+                .highlight_message("Invoked here".into())
+                .build());
+        }
+
+        Ok((args, rparen))
+    }
+
+    fn substitute(
+        &mut self,
+        file: &SourceFile,
+        mac: &Macro,
+        args: &[Vec<ExpansionToken>],
+        invocation: &ExpansionToken,
+        hide_set: Rc<HashSet<String>>,
+    ) -> CompResult<Vec<ExpansionToken>> {
+        let location = *invocation.token.metadata();
+        let function_like = matches!(mac.kind, MacroKind::Function { .. });
+
+        // Tokens from the body appear where the macro was used, while
+        // remembering where they were written
+        let body: Vec<PreprocessToken> = mac
+            .body
+            .iter()
+            .map(|token| {
+                let mut token = token.clone();
+                let meta = token.metadata_mut();
+                let expansion = self.sources.add_expansion(MacroExpansion {
+                    name: mac.name.clone(),
+                    spelling: meta.span,
+                    parent: location.expansion,
+                });
+                *meta = PreprocessMetadata {
+                    leading_space: meta.leading_space,
+                    expansion: Some(expansion),
+                    ..location
+                };
+                token
+            })
+            .collect();
+        let body = match mac.kind {
+            MacroKind::Function {
+                ref params,
+                variadic: true,
+            } if body.iter().any(|t| t.is_identifier("__VA_OPT__")) => {
+                let present = !self.expand(file, args[params.len()].clone())?.is_empty();
+                va_opt(body, present)
+            }
+            _ => body,
+        };
+
+        let mut output: Vec<ExpansionToken> = Vec::new();
+        // Whether the left operand of an upcoming `##` was an empty argument
+        let mut paste_lhs_empty = false;
+        let mut index = 0;
+        while index < body.len() {
+            let token = &body[index];
+            let next = body.get(index + 1);
+            let next_is_paste = next.is_some_and(|t| t.is_punctuation("##"));
+
+            if function_like && token.is_punctuation("#") {
+                if let Some(param) = next.and_then(|t| mac.param_index(t)) {
+                    output.push(ExpansionToken::new(stringify(&args[param], token)));
+                    paste_lhs_empty = false;
+                    index += 2;
+                    continue;
+                }
+            }
+
+            if token.is_punctuation("##") {
+                let rhs_token = next.expect("Validated when defined");
+                let rhs = match mac.param_index(rhs_token) {
+                    Some(param) => args[param].clone(),
+                    None => vec![ExpansionToken::new(rhs_token.clone())],
+                };
+
+                let gnu_comma = rhs_token.is_identifier("__VA_ARGS__")
+                    && output.last().is_some_and(|t| t.token.is_punctuation(","));
+                if paste_lhs_empty {
+                    output.extend(rhs);
+                } else if gnu_comma {
+                    // `, ## __VA_ARGS__` drops the comma when there are
+                    // no variadic arguments
+                    if rhs.is_empty() {
+                        output.pop();
+                    } else {
+                        output.extend(rhs);
+                    }
+                } else if let Some((first, rest)) = rhs.split_first() {
+                    let lhs = output.pop().expect("Validated when defined");
+                    output.push(self.paste(token, &lhs, first)?);
+                    output.extend(rest.iter().cloned());
+                }
+
+                paste_lhs_empty = paste_lhs_empty
+                    && mac
+                        .param_index(rhs_token)
+                        .is_some_and(|p| args[p].is_empty());
+                index += 2;
+                continue;
+            }
+
+            paste_lhs_empty = false;
+            if let Some(param) = mac.param_index(token) {
+                // Operands of `##` are used as written, everything
+                // else is fully expanded first
+                let mut arg = if next_is_paste {
+                    paste_lhs_empty = args[param].is_empty();
+                    args[param].clone()
+                } else {
+                    self.expand(file, args[param].clone())?
+                };
+                if let Some(first) = arg.first_mut() {
+                    first.token.metadata_mut().leading_space = token.metadata().leading_space;
+                }
+                output.extend(arg);
+            } else {
+                output.push(ExpansionToken::new(token.clone()));
+            }
+            index += 1;
+        }
+
+        // Arguments keep their own locations, they were written at the
+        // invocation already
+        for token in output.iter_mut() {
+            token.hide_set = Rc::new(token.hide_set.union(&hide_set).cloned().collect());
+        }
+        if let Some(first) = output.first_mut() {
+            first.token.metadata_mut().leading_space = location.leading_space;
+        }
+
+        Ok(output)
+    }
+
+    fn paste(
+        &mut self,
+        operator: &PreprocessToken,
+        lhs: &ExpansionToken,
+        rhs: &ExpansionToken,
+    ) -> CompResult<ExpansionToken> {
+        // The pasted text is lexed from a buffer of its own, the same
+        // way it would be if it had been written out
+        let text = format!("{}{}", lhs.token.spelling(), rhs.token.spelling());
+        let mut lexer = Lexer::new(self.sources.add(SCRATCH_BUFFER, text), self.standard);
+        let valid = lexer.tokenize().is_ok() && lexer.pp_tokens().len() == 1;
+        if !valid {
+            return Err(self
+                .error_at(operator, ErrorCode::InvalidTokenPaste)
+                .message(format!(
+                    "Pasting `{}` and `{}` does not give a valid preprocessing token",
+                    lhs.token.spelling(),
+                    rhs.token.spelling()
+                ))
+                .build());
+        }
+
+        let mut token = lexer.take_pp_tokens().remove(0);
+        *token.metadata_mut() = *lhs.token.metadata();
+        Ok(ExpansionToken {
+            token,
+            hide_set: lhs.hide_set.clone(),
+        })
+    }
+}
+
+// The index of the `)` that ends `__VA_OPT__(...)` at `start`
+fn va_opt_end(body: &[PreprocessToken], start: usize) -> Option<usize> {
+    if !body.get(start + 1)?.is_punctuation("(") {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, token) in body.iter().enumerate().skip(start + 2) {
+        if token.is_punctuation("(") {
+            depth += 1;
+        } else if token.is_punctuation(")") {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+// `__VA_OPT__(...)` is the tokens in the parentheses when there are
+// variadic arguments, and nothing when there aren't. A `##` next to
+// nothing has nothing to paste, so it goes too.
+fn va_opt(body: Vec<PreprocessToken>, present: bool) -> Vec<PreprocessToken> {
+    let mut output: Vec<PreprocessToken> = Vec::new();
+    let mut index = 0;
+    while index < body.len() {
+        if !body[index].is_identifier("__VA_OPT__") {
+            output.push(body[index].clone());
+            index += 1;
+            continue;
+        }
+        let end = va_opt_end(&body, index).expect("Validated when defined");
+        if present && end > index + 2 {
+            output.extend_from_slice(&body[index + 2..end]);
+        } else if output.last().is_some_and(|t| t.is_punctuation("##")) {
+            output.pop();
+        } else if body.get(end + 1).is_some_and(|t| t.is_punctuation("##")) {
+            index = end + 2;
+            continue;
+        }
+        index = end + 1;
+    }
+    output
+}
+
+fn add_to_hide_set(hide_set: &Rc<HashSet<String>>, name: &str) -> Rc<HashSet<String>> {
+    let mut hide_set = (**hide_set).clone();
+    hide_set.insert(name.into());
+    Rc::new(hide_set)
+}
+
+fn stringify(arg: &[ExpansionToken], hash: &PreprocessToken) -> PreprocessToken {
+    let mut literal = String::new();
+    for (i, token) in arg.iter().enumerate() {
+        if i > 0 && token.token.metadata().leading_space {
+            literal.push(' ');
+        }
+        let spelling = token.token.spelling();
+        match token.token {
+            PreprocessToken::StringLiteral(..) | PreprocessToken::CharacterConstant(..) => {
+                for c in spelling.chars() {
+                    if c == '"' || c == '\\' {
+                        literal.push('\\');
+                    }
+                    literal.push(c);
+                }
+            }
+            _ => literal.push_str(&spelling),
+        }
+    }
+    PreprocessToken::StringLiteral(literal, *hash.metadata())
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
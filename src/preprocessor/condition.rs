@@ -0,0 +1,390 @@
+use crate::comp_error::*;
+use crate::lang::Standard;
+use crate::lexer::*;
+use crate::target;
+
+use super::Preprocessor;
+
+// Preprocessor arithmetic is done in the widest integer types,
+// keeping track of whether the value is unsigned
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    bits: u64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn signed(value: i64) -> Self {
+        Self {
+            bits: value as u64,
+            unsigned: false,
+        }
+    }
+
+    fn boolean(value: bool) -> Self {
+        Self::signed(value as i64)
+    }
+
+    fn is_true(&self) -> bool {
+        self.bits != 0
+    }
+}
+
+// Evaluates the already macro expanded tokens of an `#if` or `#elif`
+pub(super) struct ConditionEvaluator<'a> {
+    preprocessor: &'a Preprocessor,
+    directive: &'a PreprocessToken,
+    tokens: &'a [PreprocessToken],
+    index: usize,
+    // Greater than zero while inside an operand that isn't evaluated,
+    // like the right side of `0 && x`
+    unevaluated: usize,
+}
+
+impl<'a> ConditionEvaluator<'a> {
+    pub(super) fn new(
+        preprocessor: &'a Preprocessor,
+        directive: &'a PreprocessToken,
+        tokens: &'a [PreprocessToken],
+    ) -> Self {
+        Self {
+            preprocessor,
+            directive,
+            tokens,
+            index: 0,
+            unevaluated: 0,
+        }
+    }
+
+    pub(super) fn evaluate(self) -> CompResult<bool> {
+        Ok(self.value()?.is_true())
+    }
+
+    // The value as a count, `None` if it's negative
+    pub(super) fn evaluate_count(self) -> CompResult<Option<u64>> {
+        let value = self.value()?;
+        Ok((value.unsigned || (value.bits as i64) >= 0).then_some(value.bits))
+    }
+
+    fn value(mut self) -> CompResult<Value> {
+        if self.tokens.is_empty() {
+            return Err(self.error_at_end("Expected expression"));
+        }
+
+        let value = self.expression()?;
+        if let Some(token) = self.tokens.get(self.index) {
+            return Err(self
+                .preprocessor
+                .error_at(token, ErrorCode::InvalidConditionalExpression)
+                .message(format!(
+                    "Expected end of expression, found `{}`",
+                    token.spelling()
+                ))
+                .build());
+        }
+
+        Ok(value)
+    }
+
+    fn expression(&mut self) -> CompResult<Value> {
+        let mut value = self.conditional()?;
+        while self.eat(",") {
+            value = self.conditional()?;
+        }
+        Ok(value)
+    }
+
+    fn conditional(&mut self) -> CompResult<Value> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+
+        let taken = condition.is_true();
+        let lhs = self.evaluated_if(taken, |this| this.expression())?;
+        self.expect(":")?;
+        let rhs = self.evaluated_if(!taken, |this| this.conditional())?;
+
+        let unsigned = lhs.unsigned || rhs.unsigned;
+        let bits = if taken { lhs.bits } else { rhs.bits };
+        Ok(Value { bits, unsigned })
+    }
+
+    fn evaluated_if<F>(&mut self, evaluated: bool, parse: F) -> CompResult<Value>
+    where
+        F: FnOnce(&mut Self) -> CompResult<Value>,
+    {
+        if !evaluated {
+            self.unevaluated += 1;
+        }
+        let value = parse(self);
+        if !evaluated {
+            self.unevaluated -= 1;
+        }
+        value
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> CompResult<Value> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.peek_punctuation() {
+            let Some(precedence) = binary_precedence(&op) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            let tokens = self.tokens;
+            let op_token = &tokens[self.index];
+            self.index += 1;
+
+            lhs = match op.as_str() {
+                "&&" => {
+                    let rhs =
+                        self.evaluated_if(lhs.is_true(), |this| this.binary(precedence + 1))?;
+                    Value::boolean(lhs.is_true() && rhs.is_true())
+                }
+                "||" => {
+                    let rhs =
+                        self.evaluated_if(!lhs.is_true(), |this| this.binary(precedence + 1))?;
+                    Value::boolean(lhs.is_true() || rhs.is_true())
+                }
+                _ => {
+                    let rhs = self.binary(precedence + 1)?;
+                    self.apply(op_token, &op, lhs, rhs)?
+                }
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    fn apply(
+        &self,
+        op_token: &PreprocessToken,
+        op: &str,
+        lhs: Value,
+        rhs: Value,
+    ) -> CompResult<Value> {
+        // The usual arithmetic conversions, if either side is unsigned
+        // both are
+        let unsigned = lhs.unsigned || rhs.unsigned;
+        let (a, b) = (lhs.bits, rhs.bits);
+        let (sa, sb) = (a as i64, b as i64);
+
+        if matches!(op, "/" | "%") && b == 0 {
+            if self.unevaluated > 0 {
+                return Ok(Value { bits: 0, unsigned });
+            }
+            return Err(self
+                .preprocessor
+                .error_at(op_token, ErrorCode::InvalidConditionalExpression)
+                .message("Division by zero in preprocessor expression".into())
+                .build());
+        }
+
+        let bits = match op {
+            "*" => a.wrapping_mul(b),
+            "/" if unsigned => a / b,
+            "/" => sa.wrapping_div(sb) as u64,
+            "%" if unsigned => a % b,
+            "%" => sa.wrapping_rem(sb) as u64,
+            "+" => a.wrapping_add(b),
+            "-" => a.wrapping_sub(b),
+            // Shifts take the type of the left operand
+            "<<" => a.wrapping_shl(b as u32),
+            ">>" if lhs.unsigned => a.wrapping_shr(b as u32),
+            ">>" => sa.wrapping_shr(b as u32) as u64,
+            "&" => a & b,
+            "^" => a ^ b,
+            "|" => a | b,
+            _ => {
+                let result = match op {
+                    "<" if unsigned => a < b,
+                    "<" => sa < sb,
+                    ">" if unsigned => a > b,
+                    ">" => sa > sb,
+                    "<=" if unsigned => a <= b,
+                    "<=" => sa <= sb,
+                    ">=" if unsigned => a >= b,
+                    ">=" => sa >= sb,
+                    "==" => a == b,
+                    "!=" => a != b,
+                    _ => unreachable!("Not a binary operator: {}", op),
+                };
+                return Ok(Value::boolean(result));
+            }
+        };
+
+        let unsigned = match op {
+            "<<" | ">>" => lhs.unsigned,
+            _ => unsigned,
+        };
+        Ok(Value { bits, unsigned })
+    }
+
+    fn unary(&mut self) -> CompResult<Value> {
+        let Some(token) = self.tokens.get(self.index) else {
+            return Err(self.error_at_end("Expected operand"));
+        };
+        self.index += 1;
+
+        match token {
+            PreprocessToken::Number(number, _) => self.number(token, number),
+            PreprocessToken::CharacterConstant(c, _, _) => {
+                Ok(Value::signed(target::character_value(*c)))
+            }
+            // C23 made `true` a keyword, so it stays true here
+            PreprocessToken::Identifier(name, _)
+                if name == "true" && self.preprocessor.standard >= Standard::C23 =>
+            {
+                Ok(Value::signed(1))
+            }
+            // Identifiers left after expansion aren't macros, which
+            // evaluate to zero
+            PreprocessToken::Identifier(..) => Ok(Value::signed(0)),
+            _ if token.is_punctuation("(") => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            _ if token.is_punctuation("+") => self.unary(),
+            _ if token.is_punctuation("-") => {
+                let value = self.unary()?;
+                Ok(Value {
+                    bits: value.bits.wrapping_neg(),
+                    ..value
+                })
+            }
+            _ if token.is_punctuation("~") => {
+                let value = self.unary()?;
+                Ok(Value {
+                    bits: !value.bits,
+                    ..value
+                })
+            }
+            _ if token.is_punctuation("!") => {
+                let value = self.unary()?;
+                Ok(Value::boolean(!value.is_true()))
+            }
+            _ => Err(self
+                .preprocessor
+                .error_at(token, ErrorCode::InvalidConditionalExpression)
+                .message(format!("Expected operand, found `{}`", token.spelling()))
+                .build()),
+        }
+    }
+
+    fn number(&self, token: &PreprocessToken, number: &str) -> CompResult<Value> {
+        let invalid = || {
+            self.preprocessor
+                .error_at(token, ErrorCode::InvalidConditionalExpression)
+                .message(format!(
+                    "`{}` is not a valid integer constant in a preprocessor expression",
+                    number
+                ))
+                .build()
+        };
+
+        let c23 = self.preprocessor.standard >= Standard::C23;
+        if number.contains("''") || number.ends_with('\'') {
+            return Err(invalid());
+        }
+        let number = number.replace('\'', "");
+        let digits_end = number.find(['u', 'U', 'l', 'L']).unwrap_or(number.len());
+        let (digits, suffix) = number.split_at(digits_end);
+        let suffix = suffix.to_ascii_lowercase();
+        if !matches!(
+            suffix.as_str(),
+            "" | "u" | "l" | "ul" | "lu" | "ll" | "ull" | "llu"
+        ) {
+            return Err(invalid());
+        }
+
+        let (radix, digits) = if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            (16, hex)
+        } else if let Some(binary) = digits
+            .strip_prefix("0b")
+            .or_else(|| digits.strip_prefix("0B"))
+            .filter(|_| c23)
+        {
+            (2, binary)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            (8, &digits[1..])
+        } else {
+            (10, digits)
+        };
+
+        let bits = u64::from_str_radix(digits, radix).map_err(|_| invalid())?;
+        // Decimal constants without a suffix are always signed, others
+        // become unsigned when they don't fit
+        let unsigned = suffix.contains('u') || (radix != 10 && bits > i64::MAX as u64);
+        Ok(Value { bits, unsigned })
+    }
+
+    fn peek_punctuation(&self) -> Option<String> {
+        match self.tokens.get(self.index)? {
+            PreprocessToken::Operator(op, _) | PreprocessToken::Punctuator(op, _) => {
+                Some(op.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, punctuation: &str) -> bool {
+        let found = self
+            .tokens
+            .get(self.index)
+            .is_some_and(|token| token.is_punctuation(punctuation));
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punctuation: &str) -> CompResult<()> {
+        if self.eat(punctuation) {
+            return Ok(());
+        }
+        match self.tokens.get(self.index) {
+            Some(token) => Err(self
+                .preprocessor
+                .error_at(token, ErrorCode::InvalidConditionalExpression)
+                .message(format!(
+                    "Expected `{}`, found `{}`",
+                    punctuation,
+                    token.spelling()
+                ))
+                .build()),
+            None => Err(self.error_at_end(&format!("Expected `{}`", punctuation))),
+        }
+    }
+
+    fn error_at_end(&self, expected: &str) -> Box<CompError> {
+        let token = self.tokens.last().unwrap_or(self.directive);
+        self.preprocessor
+            .error_at(token, ErrorCode::InvalidConditionalExpression)
+            .message(format!("{}, found end of line", expected))
+            .build()
+    }
+}
+
+fn binary_precedence(op: &str) -> Option<u8> {
+    let precedence = match op {
+        "*" | "/" | "%" => 10,
+        "+" | "-" => 9,
+        "<<" | ">>" => 8,
+        "<" | ">" | "<=" | ">=" => 7,
+        "==" | "!=" => 6,
+        "&" => 5,
+        "^" => 4,
+        "|" => 3,
+        "&&" => 2,
+        "||" => 1,
+        _ => return None,
+    };
+    Some(precedence)
+}
@@ -0,0 +1,219 @@
+use crate::comp_error::*;
+use crate::headers;
+use crate::lexer::*;
+
+use super::condition::ConditionEvaluator;
+use super::{FoundInclude, Preprocessor, SourceFile};
+
+// What the parameters of an `#embed` or `__has_embed` ask for
+#[derive(Debug, Default)]
+struct EmbedParameters {
+    limit: Option<u64>,
+    prefix: Vec<PreprocessToken>,
+    suffix: Vec<PreprocessToken>,
+    if_empty: Vec<PreprocessToken>,
+}
+
+impl Preprocessor {
+    // `#embed "file" limit(n) prefix(...) suffix(...) if_empty(...)`
+    // becomes the bytes of the file as integer constants separated by
+    // commas. An empty file becomes the `if_empty` tokens instead.
+    pub(super) fn embed(
+        &mut self,
+        file: &SourceFile,
+        directive: &PreprocessToken,
+        args: &[PreprocessToken],
+    ) -> CompResult<()> {
+        let (name, header_type, token, params) = self.embed_resource(file, directive, args)?;
+        let parameters = match self.embed_parameters(file, params)? {
+            Ok(parameters) => parameters,
+            Err(unknown) => {
+                return Err(self
+                    .error_at(&unknown, ErrorCode::InvalidDirective)
+                    .message(format!(
+                        "Unknown `#embed` parameter `{}`",
+                        unknown.spelling()
+                    ))
+                    .build())
+            }
+        };
+
+        let Some(found) = self.find_include(file, &name, header_type, false) else {
+            return Err(self
+                .error_at(&token, ErrorCode::IncludeNotFound)
+                .message(format!("Could not find `{}`", name))
+                .highlight_message("Embedded here".into())
+                .build());
+        };
+        let bytes = self.read_resource(&found)?;
+        self.add_dependency(&found.path, found.system);
+
+        let limit = parameters
+            .limit
+            .map_or(bytes.len(), |limit| bytes.len().min(limit as usize));
+        let bytes = &bytes[..limit];
+        if bytes.is_empty() {
+            self.output.extend(parameters.if_empty);
+            return Ok(());
+        }
+
+        let prefixed = !parameters.prefix.is_empty();
+        self.output.extend(parameters.prefix);
+        let meta = *token.metadata();
+        for (i, byte) in bytes.iter().enumerate() {
+            if i > 0 {
+                let comma = PreprocessMetadata {
+                    leading_space: false,
+                    ..meta
+                };
+                self.output
+                    .push(PreprocessToken::Punctuator(",".into(), comma));
+            }
+            let number = PreprocessMetadata {
+                leading_space: i > 0 || prefixed,
+                ..meta
+            };
+            self.output
+                .push(PreprocessToken::Number(byte.to_string(), number));
+        }
+        self.output.extend(parameters.suffix);
+        Ok(())
+    }
+
+    // `__has_embed` is 0 when the resource can't be found or one of
+    // the parameters isn't supported, 2 when it's empty and 1 otherwise
+    pub(super) fn has_embed(
+        &mut self,
+        file: &SourceFile,
+        operator: &PreprocessToken,
+        operand: &[PreprocessToken],
+    ) -> CompResult<u64> {
+        let (name, header_type, _, params) = self.embed_resource(file, operator, operand)?;
+        let Ok(parameters) = self.embed_parameters(file, params)? else {
+            return Ok(0);
+        };
+        let Some(found) = self.find_include(file, &name, header_type, false) else {
+            return Ok(0);
+        };
+        let Ok(bytes) = self.read_resource(&found) else {
+            return Ok(0);
+        };
+        let empty = bytes.is_empty() || parameters.limit == Some(0);
+        Ok(if empty { 2 } else { 1 })
+    }
+
+    // The resource is named like a header, and the parameters follow it.
+    // A name that has to be macro expanded takes up the whole line.
+    fn embed_resource<'t>(
+        &mut self,
+        file: &SourceFile,
+        directive: &PreprocessToken,
+        args: &'t [PreprocessToken],
+    ) -> CompResult<(
+        String,
+        HeaderNameType,
+        PreprocessToken,
+        &'t [PreprocessToken],
+    )> {
+        let end = match args.first() {
+            Some(PreprocessToken::HeaderName(..)) => 1,
+            _ => args.len(),
+        };
+        let (name, header_type, token) = self.header_name(file, directive, &args[..end])?;
+        Ok((name, header_type, token, &args[end..]))
+    }
+
+    // Each parameter is a name, possibly with a prefix like `gnu::`,
+    // and arguments in parentheses. The first one that isn't supported
+    // is given back rather than the parameters.
+    fn embed_parameters(
+        &mut self,
+        file: &SourceFile,
+        params: &[PreprocessToken],
+    ) -> CompResult<Result<EmbedParameters, PreprocessToken>> {
+        let mut parameters = EmbedParameters::default();
+        let mut index = 0;
+        while let Some(token) = params.get(index) {
+            let PreprocessToken::Identifier(ref name, _) = *token else {
+                return Err(self
+                    .error_at(token, ErrorCode::InvalidDirective)
+                    .message(format!(
+                        "Expected `#embed` parameter, found `{}`",
+                        token.spelling()
+                    ))
+                    .build());
+            };
+            index += 1;
+            let prefixed = params.get(index).is_some_and(|t| t.is_punctuation("::"));
+            if prefixed {
+                index += 2;
+            }
+
+            let mut arguments = None;
+            if params.get(index).is_some_and(|t| t.is_punctuation("(")) {
+                let mut depth = 0;
+                let Some(end) = params[index..].iter().position(|token| {
+                    if token.is_punctuation("(") {
+                        depth += 1;
+                    } else if token.is_punctuation(")") {
+                        depth -= 1;
+                    }
+                    depth == 0
+                }) else {
+                    return Err(self
+                        .error_at(token, ErrorCode::InvalidDirective)
+                        .message(format!("Expected `)` to end `{}`, found end of line", name))
+                        .build());
+                };
+                arguments = Some(&params[index + 1..index + end]);
+                index += end + 1;
+            }
+
+            // Like attributes, `__limit__` is the same as `limit`
+            let name = name
+                .strip_prefix("__")
+                .and_then(|name| name.strip_suffix("__"))
+                .unwrap_or(name);
+            match (prefixed, name, arguments) {
+                (false, "limit", Some(arguments)) => {
+                    let expanded = self.expand_tokens(file, arguments.to_vec())?;
+                    let evaluator = ConditionEvaluator::new(self, token, &expanded);
+                    let Some(limit) = evaluator.evaluate_count()? else {
+                        return Err(self
+                            .error_at(token, ErrorCode::InvalidDirective)
+                            .message("The `limit` of `#embed` can't be negative".into())
+                            .build());
+                    };
+                    parameters.limit = Some(limit);
+                }
+                (false, "prefix", Some(arguments)) => parameters.prefix = arguments.to_vec(),
+                (false, "suffix", Some(arguments)) => parameters.suffix = arguments.to_vec(),
+                (false, "if_empty", Some(arguments)) => parameters.if_empty = arguments.to_vec(),
+                (false, "limit" | "prefix" | "suffix" | "if_empty", None) => {
+                    return Err(self
+                        .error_at(token, ErrorCode::InvalidDirective)
+                        .message(format!("`{}` needs an argument in parentheses", name))
+                        .build())
+                }
+                _ => return Ok(Err(token.clone())),
+            }
+        }
+        Ok(Ok(parameters))
+    }
+
+    fn read_resource(&self, found: &FoundInclude) -> CompResult<Vec<u8>> {
+        if let Some(contents) = headers::bundled_header(&found.path) {
+            return Ok(contents.as_bytes().to_vec());
+        }
+        std::fs::read(&found.path).map_err(|err| {
+            CompErrorBuilder::new()
+                .code(ErrorCode::InputFileUnreadable)
+                .message(format!(
+                    "Failed to read `{}`: {}",
+                    found.path.display(),
+                    err
+                ))
+                .build()
+        })
+    }
+}
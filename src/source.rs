@@ -0,0 +1,178 @@
+use std::rc::Rc;
+
+use crate::comp_error::*;
+use crate::lexer::PreprocessMetadata;
+
+// Index of a buffer in the `SourceManager`
+pub type FileId = usize;
+
+// Index of the macro expansion a token was produced by
+pub type ExpansionId = usize;
+
+// A range of bytes in one buffer, `lo` inclusive and `hi` exclusive.
+// Offsets are into the text as it was loaded, before trigraphs are
+// replaced or lines spliced, so they always match what the user wrote.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct Span {
+    pub file_id: FileId,
+    pub lo: usize,
+    pub hi: usize,
+}
+
+impl Span {
+    pub fn new(file_id: FileId, lo: usize, hi: usize) -> Self {
+        Self { file_id, lo, hi }
+    }
+
+    // The smallest span covering both, which only makes sense when
+    // they are in the same buffer
+    pub fn to(self, end: Span) -> Self {
+        if self.file_id != end.file_id {
+            return self;
+        }
+        Self {
+            lo: self.lo.min(end.lo),
+            hi: self.hi.max(end.hi),
+            ..self
+        }
+    }
+}
+
+// Text of a file, or of something that acts like one such as the
+// command line definitions or the result of a token paste
+#[derive(Debug)]
+pub struct SourceBuffer {
+    id: FileId,
+    name: String,
+    text: String,
+    // Byte offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+impl SourceBuffer {
+    fn new(id: FileId, name: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self {
+            id,
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Line and column of a byte offset, both starting at 1. Columns
+    // count characters rather than bytes.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        let col = self.text[start..offset].chars().count() + 1;
+        (line, col)
+    }
+
+    // Text of a line without its newline
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.text.len(), |next| next - 1);
+        &self.text[start..end]
+    }
+}
+
+// A token that came out of a macro body. The token itself is located
+// at the outermost invocation, this records where it was spelled in
+// the macro definition and the expansion the invocation came from.
+#[derive(Debug, Clone)]
+pub struct MacroExpansion {
+    pub name: String,
+    pub spelling: Span,
+    pub parent: Option<ExpansionId>,
+}
+
+// Owns every buffer that tokens can point into, along with the macro
+// expansions they went through. Buffers are shared with errors, which
+// show lines from them long after lexing.
+#[derive(Debug, Default)]
+pub struct SourceManager {
+    buffers: Vec<Rc<SourceBuffer>>,
+    expansions: Vec<MacroExpansion>,
+}
+
+impl SourceManager {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add(&mut self, name: &str, text: String) -> Rc<SourceBuffer> {
+        let buffer = Rc::new(SourceBuffer::new(self.buffers.len(), name.into(), text));
+        self.buffers.push(buffer.clone());
+        buffer
+    }
+
+    pub fn buffer(&self, id: FileId) -> &Rc<SourceBuffer> {
+        &self.buffers[id]
+    }
+
+    pub fn name(&self, id: FileId) -> &str {
+        self.buffers[id].name()
+    }
+
+    pub fn line_col(&self, span: Span) -> (usize, usize) {
+        self.buffers[span.file_id].line_col(span.lo)
+    }
+
+    pub fn add_expansion(&mut self, expansion: MacroExpansion) -> ExpansionId {
+        self.expansions.push(expansion);
+        self.expansions.len() - 1
+    }
+
+    pub fn expansions(&self) -> &[MacroExpansion] {
+        &self.expansions
+    }
+
+    // Errors in tokens that came from macros point at the invocation,
+    // followed by a note for each macro definition they went through
+    pub fn error_at(&self, meta: &PreprocessMetadata, code: ErrorCode) -> CompErrorBuilder {
+        CompErrorBuilder::new()
+            .code(code)
+            .span(self.buffer(meta.span.file_id), meta.span)
+            .notes(self.expansion_notes(meta.expansion))
+    }
+
+    pub fn expansion_notes(&self, mut expansion: Option<ExpansionId>) -> Vec<CompNote> {
+        let mut notes = Vec::new();
+        while let Some(id) = expansion {
+            let MacroExpansion {
+                ref name,
+                spelling,
+                parent,
+            } = self.expansions[id];
+            notes.push(CompNote {
+                message: format!("Expanded from macro `{}`", name),
+                location: Some(ErrorLocation {
+                    buffer: self.buffer(spelling.file_id).clone(),
+                    span: spelling,
+                }),
+            });
+            expansion = parent;
+        }
+        notes
+    }
+}
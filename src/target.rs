@@ -0,0 +1,210 @@
+// Sizes and limits of the machine zcc compiles for. This is an LP64
+// target laid out like x86-64 System V: 32 bit `int`, 64 bit `long`
+// and pointers, signed `char` and 80 bit extended `long double`.
+
+pub const CHAR_BIT: u64 = 8;
+pub const CHAR_SIGNED: bool = true;
+
+pub const BOOL_SIZE: u64 = 1;
+pub const SHORT_SIZE: u64 = 2;
+pub const INT_SIZE: u64 = 4;
+pub const LONG_SIZE: u64 = 8;
+pub const LONG_LONG_SIZE: u64 = 8;
+pub const POINTER_SIZE: u64 = 8;
+pub const FLOAT_SIZE: u64 = 4;
+pub const DOUBLE_SIZE: u64 = 8;
+pub const LONG_DOUBLE_SIZE: u64 = 16;
+// `va_list` is an array of one struct with two `unsigned int` offsets
+// and two pointers into the saved arguments
+pub const VA_LIST_SIZE: u64 = 24;
+pub const BIGGEST_ALIGNMENT: u64 = 16;
+// Objects are no larger than `ptrdiff_t` can count
+pub const MAX_OBJECT_SIZE: u64 = i64::MAX as u64;
+
+// Characteristics of a floating type as `<float.h>` describes them
+pub struct FloatFormat {
+    pub prefix: &'static str,
+    pub mant_dig: u32,
+    pub dig: u32,
+    pub min_exp: i32,
+    pub min_10_exp: i32,
+    pub max_exp: i32,
+    pub max_10_exp: i32,
+    pub max: &'static str,
+    pub min: &'static str,
+    pub epsilon: &'static str,
+}
+
+pub const FLOAT_FORMATS: [FloatFormat; 3] = [
+    FloatFormat {
+        prefix: "FLT",
+        mant_dig: 24,
+        dig: 6,
+        min_exp: -125,
+        min_10_exp: -37,
+        max_exp: 128,
+        max_10_exp: 38,
+        max: "3.40282346638528859812e+38F",
+        min: "1.17549435082228750797e-38F",
+        epsilon: "1.19209289550781250000e-7F",
+    },
+    FloatFormat {
+        prefix: "DBL",
+        mant_dig: 53,
+        dig: 15,
+        min_exp: -1021,
+        min_10_exp: -307,
+        max_exp: 1024,
+        max_10_exp: 308,
+        max: "1.79769313486231570815e+308",
+        min: "2.22507385850720138309e-308",
+        epsilon: "2.22044604925031308085e-16",
+    },
+    FloatFormat {
+        prefix: "LDBL",
+        mant_dig: 64,
+        dig: 18,
+        min_exp: -16381,
+        min_10_exp: -4931,
+        max_exp: 16384,
+        max_10_exp: 4932,
+        max: "1.18973149535723176502e+4932L",
+        min: "3.36210314311209350626e-4932L",
+        epsilon: "1.08420217248550443401e-19L",
+    },
+];
+
+pub const DECIMAL_DIG: u32 = 21;
+
+// Integer types from smallest to largest, with the suffix a
+// constant of that type needs
+const INTEGER_TYPES: [(&str, u64, &str); 4] = [
+    ("char", 1, ""),
+    ("short", SHORT_SIZE, ""),
+    ("int", INT_SIZE, ""),
+    ("long", LONG_SIZE, "L"),
+];
+
+pub fn signed_max(size: u64) -> u64 {
+    (1u64 << (size * CHAR_BIT - 1)) - 1
+}
+
+pub fn unsigned_max(size: u64) -> u64 {
+    u64::MAX >> (64 - size * CHAR_BIT)
+}
+
+// A character constant is an `int` with the value the character has
+// as a `char`, so `'\xff'` is -1 when `char` is signed
+pub fn character_value(c: char) -> i64 {
+    match c as u64 >= 1 << CHAR_BIT {
+        false if CHAR_SIGNED => c as u8 as i8 as i64,
+        _ => c as i64,
+    }
+}
+
+// The smallest integer type with exactly `size` bytes
+fn exact_width_type(size: u64) -> (&'static str, &'static str) {
+    let (name, _, suffix) = INTEGER_TYPES
+        .iter()
+        .find(|(_, type_size, _)| *type_size == size)
+        .expect("Every exact width type exists on this target");
+    (name, suffix)
+}
+
+// Macros describing the target that the bundled headers are written
+// in terms of, following the names GCC and Clang use
+pub fn predefined_macros() -> String {
+    let mut defines: Vec<(String, String)> = vec![
+        ("__CHAR_BIT__".into(), CHAR_BIT.to_string()),
+        ("__LP64__".into(), "1".into()),
+        (
+            "__BIGGEST_ALIGNMENT__".into(),
+            BIGGEST_ALIGNMENT.to_string(),
+        ),
+        ("__SIZEOF_SHORT__".into(), SHORT_SIZE.to_string()),
+        ("__SIZEOF_INT__".into(), INT_SIZE.to_string()),
+        ("__SIZEOF_LONG__".into(), LONG_SIZE.to_string()),
+        ("__SIZEOF_LONG_LONG__".into(), LONG_LONG_SIZE.to_string()),
+        ("__SIZEOF_POINTER__".into(), POINTER_SIZE.to_string()),
+        ("__SIZEOF_FLOAT__".into(), FLOAT_SIZE.to_string()),
+        ("__SIZEOF_DOUBLE__".into(), DOUBLE_SIZE.to_string()),
+        (
+            "__SIZEOF_LONG_DOUBLE__".into(),
+            LONG_DOUBLE_SIZE.to_string(),
+        ),
+        ("__SIZEOF_SIZE_T__".into(), POINTER_SIZE.to_string()),
+        ("__SIZEOF_PTRDIFF_T__".into(), POINTER_SIZE.to_string()),
+        ("__SIZEOF_WCHAR_T__".into(), INT_SIZE.to_string()),
+        ("__SCHAR_MAX__".into(), signed_max(1).to_string()),
+        ("__SHRT_MAX__".into(), signed_max(SHORT_SIZE).to_string()),
+        ("__INT_MAX__".into(), signed_max(INT_SIZE).to_string()),
+        ("__LONG_MAX__".into(), format!("{}L", signed_max(LONG_SIZE))),
+        (
+            "__LONG_LONG_MAX__".into(),
+            format!("{}LL", signed_max(LONG_LONG_SIZE)),
+        ),
+        ("__SIZE_TYPE__".into(), "unsigned long".into()),
+        (
+            "__SIZE_MAX__".into(),
+            format!("{}UL", unsigned_max(POINTER_SIZE)),
+        ),
+        ("__PTRDIFF_TYPE__".into(), "long".into()),
+        (
+            "__PTRDIFF_MAX__".into(),
+            format!("{}L", signed_max(POINTER_SIZE)),
+        ),
+        ("__INTPTR_TYPE__".into(), "long".into()),
+        ("__UINTPTR_TYPE__".into(), "unsigned long".into()),
+        ("__INTMAX_TYPE__".into(), "long".into()),
+        ("__UINTMAX_TYPE__".into(), "unsigned long".into()),
+        ("__INTMAX_C_SUFFIX__".into(), "L".into()),
+        ("__UINTMAX_C_SUFFIX__".into(), "UL".into()),
+        ("__WCHAR_TYPE__".into(), "int".into()),
+        ("__WCHAR_MAX__".into(), "__INT_MAX__".into()),
+        ("__WCHAR_MIN__".into(), "(-__INT_MAX__ - 1)".into()),
+        ("__FLT_RADIX__".into(), "2".into()),
+        ("__FLT_EVAL_METHOD__".into(), "0".into()),
+        ("__DECIMAL_DIG__".into(), DECIMAL_DIG.to_string()),
+    ];
+
+    for bits in [8, 16, 32, 64] {
+        let (name, suffix) = exact_width_type(bits / CHAR_BIT);
+        let signed_name = match name {
+            "char" => "signed char",
+            name => name,
+        };
+        defines.push((format!("__INT{}_TYPE__", bits), signed_name.into()));
+        defines.push((
+            format!("__UINT{}_TYPE__", bits),
+            format!("unsigned {}", name),
+        ));
+        defines.push((format!("__INT{}_C_SUFFIX__", bits), suffix.into()));
+        defines.push((format!("__UINT{}_C_SUFFIX__", bits), format!("U{}", suffix)));
+    }
+
+    for format in &FLOAT_FORMATS {
+        let values = [
+            ("MANT_DIG", format.mant_dig.to_string()),
+            ("DIG", format.dig.to_string()),
+            ("MIN_EXP", format!("({})", format.min_exp)),
+            ("MIN_10_EXP", format!("({})", format.min_10_exp)),
+            ("MAX_EXP", format.max_exp.to_string()),
+            ("MAX_10_EXP", format.max_10_exp.to_string()),
+            ("MAX", format.max.to_string()),
+            ("MIN", format.min.to_string()),
+            ("EPSILON", format.epsilon.to_string()),
+        ];
+        for (name, value) in values {
+            defines.push((format!("__{}_{}__", format.prefix, name), value));
+        }
+    }
+
+    if !CHAR_SIGNED {
+        defines.push(("__CHAR_UNSIGNED__".into(), "1".into()));
+    }
+
+    defines
+        .into_iter()
+        .map(|(name, value)| format!("#define {} {}\n", name, value))
+        .collect()
+}
@@ -0,0 +1,1095 @@
+// Types as the checker sees them, after typedef names, `typeof` and
+// tags have been resolved. Each distinct type is interned once in a
+// `TypeTable` and referred to by a `CType` handle, so two handles are
+// equal exactly when the types are the same. Structs, unions and enums
+// are told apart by their tag, which is made once per definition.
+
+use std::collections::HashMap;
+
+use crate::target;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Qualifiers(u8);
+
+impl Qualifiers {
+    pub const NONE: Self = Self(0);
+    pub const CONST: Self = Self(1);
+    pub const VOLATILE: Self = Self(2);
+    pub const RESTRICT: Self = Self(4);
+    pub const ATOMIC: Self = Self(8);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl std::ops::BitOr for Qualifiers {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Qualifiers {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl std::fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = [
+            (Self::CONST, "const "),
+            (Self::VOLATILE, "volatile "),
+            (Self::RESTRICT, "restrict "),
+            (Self::ATOMIC, "_Atomic "),
+        ];
+        for (qualifier, name) in names {
+            if self.contains(qualifier) {
+                write!(f, "{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CType(u32);
+
+// A type is only an index without its table, so in JSON it's written
+// as one for `json::to_json` to replace with what the type is
+#[cfg(feature = "json")]
+impl serde::Serialize for CType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut ty = serializer.serialize_struct("CType", 1)?;
+        ty.serialize_field("type_id", &self.0)?;
+        ty.end()
+    }
+}
+
+// The unqualified types without parts, which every table interns first
+// in the order of `BASIC_KINDS`
+impl CType {
+    pub const ERROR: Self = Self(0);
+    pub const VOID: Self = Self(1);
+    pub const BOOL: Self = Self(2);
+    pub const CHAR: Self = Self(3);
+    pub const SIGNED_CHAR: Self = Self(4);
+    pub const UNSIGNED_CHAR: Self = Self(5);
+    pub const SHORT: Self = Self(6);
+    pub const UNSIGNED_SHORT: Self = Self(7);
+    pub const INT: Self = Self(8);
+    pub const UNSIGNED_INT: Self = Self(9);
+    pub const LONG: Self = Self(10);
+    pub const UNSIGNED_LONG: Self = Self(11);
+    pub const LONG_LONG: Self = Self(12);
+    pub const UNSIGNED_LONG_LONG: Self = Self(13);
+    pub const FLOAT: Self = Self(14);
+    pub const DOUBLE: Self = Self(15);
+    pub const LONG_DOUBLE: Self = Self(16);
+    pub const VA_LIST: Self = Self(17);
+}
+
+const BASIC_KINDS: [TypeKind; 18] = [
+    TypeKind::Error,
+    TypeKind::Void,
+    TypeKind::Bool,
+    TypeKind::Char,
+    TypeKind::SignedChar,
+    TypeKind::UnsignedChar,
+    TypeKind::Short,
+    TypeKind::UnsignedShort,
+    TypeKind::Int,
+    TypeKind::UnsignedInt,
+    TypeKind::Long,
+    TypeKind::UnsignedLong,
+    TypeKind::LongLong,
+    TypeKind::UnsignedLongLong,
+    TypeKind::Float,
+    TypeKind::Double,
+    TypeKind::LongDouble,
+    TypeKind::VaList,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    // The type of something that already had an error reported, or
+    // that can't be worked out yet. It's compatible with every type so
+    // that one mistake isn't reported again.
+    Error,
+    Void,
+    Bool,
+    Char,
+    SignedChar,
+    UnsignedChar,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
+    Float,
+    Double,
+    LongDouble,
+    // `__builtin_va_list`
+    VaList,
+    Pointer(CType),
+    Array {
+        element: CType,
+        size: ArraySize,
+    },
+    // `parameters` is `None` for a function without a prototype
+    Function {
+        returns: CType,
+        parameters: Option<Vec<CType>>,
+        variadic: bool,
+    },
+    Struct(TagId),
+    Union(TagId),
+    Enum(TagId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArraySize {
+    // `int a[]`
+    Incomplete,
+    Fixed(u64),
+    // A variable length array, whose size is only known at run time
+    Variable,
+}
+
+// The qualifiers of an array are those of its elements, so an array
+// type itself is never qualified. A typedef with the `aligned`
+// attribute gives its type an alignment other than the natural one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub qualifiers: Qualifiers,
+    pub align: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TagId(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    Struct,
+    Union,
+    Enum,
+}
+
+impl std::fmt::Display for TagKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Struct => "struct",
+            Self::Union => "union",
+            Self::Enum => "enum",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// What `_Alignas` and the `aligned` attribute ask for, which only ever
+// adds to the natural alignment, and whether `packed` takes that away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Alignment {
+    pub packed: bool,
+    pub requested: Option<u64>,
+}
+
+impl Alignment {
+    pub fn apply(self, natural: u64) -> u64 {
+        let natural = if self.packed { 1 } else { natural };
+        self.requested
+            .map_or(natural, |requested| requested.max(natural))
+    }
+}
+
+// A struct, union or enum. It's incomplete until its members or
+// enumerators are given, and then its size and alignment are known.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub kind: TagKind,
+    pub name: Option<String>,
+    pub complete: bool,
+    pub members: Vec<Member>,
+    pub size: u64,
+    pub align: u64,
+}
+
+// A member of a struct or union. A bit-field has a width, and starts
+// `bit_offset` bits into the storage unit at `offset`. A C11 anonymous
+// struct or union member has no name.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: Option<String>,
+    pub ty: CType,
+    pub width: Option<u64>,
+    pub alignment: Alignment,
+    pub offset: u64,
+    pub bit_offset: u64,
+}
+
+impl Member {
+    // A member before the layout of its struct is worked out
+    pub fn new(name: Option<String>, ty: CType, width: Option<u64>, alignment: Alignment) -> Self {
+        Self {
+            name,
+            ty,
+            width,
+            alignment,
+            offset: 0,
+            bit_offset: 0,
+        }
+    }
+}
+
+pub struct TypeTable {
+    types: Vec<Type>,
+    interned: HashMap<Type, CType>,
+    tags: Vec<Tag>,
+}
+
+impl TypeTable {
+    pub fn new() -> Self {
+        let mut table = Self {
+            types: Vec::new(),
+            interned: HashMap::new(),
+            tags: Vec::new(),
+        };
+        for kind in BASIC_KINDS {
+            table.intern(kind, Qualifiers::NONE);
+        }
+        table
+    }
+
+    // The type with the index `id`, if the table has one
+    pub fn by_id(&self, id: u64) -> Option<CType> {
+        (id < self.types.len() as u64).then_some(CType(id as u32))
+    }
+
+    pub fn get(&self, ty: CType) -> &Type {
+        &self.types[ty.0 as usize]
+    }
+
+    pub fn kind(&self, ty: CType) -> &TypeKind {
+        &self.get(ty).kind
+    }
+
+    pub fn qualifiers(&self, ty: CType) -> Qualifiers {
+        match *self.kind(ty) {
+            TypeKind::Array { element, .. } => self.qualifiers(element),
+            _ => self.get(ty).qualifiers,
+        }
+    }
+
+    fn intern(&mut self, kind: TypeKind, qualifiers: Qualifiers) -> CType {
+        self.intern_type(Type {
+            kind,
+            qualifiers,
+            align: None,
+        })
+    }
+
+    fn intern_type(&mut self, ty: Type) -> CType {
+        if let Some(&interned) = self.interned.get(&ty) {
+            return interned;
+        }
+        let handle = CType(self.types.len() as u32);
+        self.types.push(ty.clone());
+        self.interned.insert(ty, handle);
+        handle
+    }
+
+    pub fn pointer(&mut self, to: CType) -> CType {
+        self.intern(TypeKind::Pointer(to), Qualifiers::NONE)
+    }
+
+    pub fn array(&mut self, element: CType, size: ArraySize) -> CType {
+        self.intern(TypeKind::Array { element, size }, Qualifiers::NONE)
+    }
+
+    pub fn function(
+        &mut self,
+        returns: CType,
+        parameters: Option<Vec<CType>>,
+        variadic: bool,
+    ) -> CType {
+        let kind = TypeKind::Function {
+            returns,
+            parameters,
+            variadic,
+        };
+        self.intern(kind, Qualifiers::NONE)
+    }
+
+    // The type with `qualifiers` added to the ones it has
+    pub fn qualified(&mut self, ty: CType, qualifiers: Qualifiers) -> CType {
+        if qualifiers.is_empty() {
+            return ty;
+        }
+        let Type {
+            kind,
+            qualifiers: existing,
+            align,
+        } = self.get(ty).clone();
+        match kind {
+            TypeKind::Error => ty,
+            TypeKind::Array { element, size } => {
+                let element = self.qualified(element, qualifiers);
+                self.array(element, size)
+            }
+            kind => self.intern_type(Type {
+                kind,
+                qualifiers: existing | qualifiers,
+                align,
+            }),
+        }
+    }
+
+    pub fn unqualified(&mut self, ty: CType) -> CType {
+        let Type { kind, align, .. } = self.get(ty).clone();
+        match kind {
+            TypeKind::Array { element, size } => {
+                let element = self.unqualified(element);
+                self.array(element, size)
+            }
+            kind => self.intern_type(Type {
+                kind,
+                qualifiers: Qualifiers::NONE,
+                align,
+            }),
+        }
+    }
+
+    // The type as a typedef with `aligned(align)` has it. The elements
+    // of an array are what's aligned.
+    pub fn aligned(&mut self, ty: CType, align: u64) -> CType {
+        let Type {
+            kind, qualifiers, ..
+        } = self.get(ty).clone();
+        match kind {
+            TypeKind::Error | TypeKind::Function { .. } => ty,
+            TypeKind::Array { element, size } => {
+                let element = self.aligned(element, align);
+                self.array(element, size)
+            }
+            kind => self.intern_type(Type {
+                kind,
+                qualifiers,
+                align: Some(align),
+            }),
+        }
+    }
+
+    // Arrays become pointers to their first element and functions
+    // pointers to themselves, as they do for parameters and in most
+    // expressions
+    pub fn decay(&mut self, ty: CType) -> CType {
+        match *self.kind(ty) {
+            TypeKind::Array { element, .. } => self.pointer(element),
+            TypeKind::Function { .. } => self.pointer(ty),
+            _ => ty,
+        }
+    }
+
+    // A new incomplete struct, union or enum
+    pub fn new_tag(&mut self, kind: TagKind, name: Option<&str>) -> CType {
+        let id = TagId(self.tags.len() as u32);
+        self.tags.push(Tag {
+            kind,
+            name: name.map(str::to_string),
+            complete: false,
+            members: Vec::new(),
+            size: 0,
+            align: 1,
+        });
+        let kind = match kind {
+            TagKind::Struct => TypeKind::Struct(id),
+            TagKind::Union => TypeKind::Union(id),
+            TagKind::Enum => TypeKind::Enum(id),
+        };
+        self.intern(kind, Qualifiers::NONE)
+    }
+
+    pub fn tag_id(&self, ty: CType) -> Option<TagId> {
+        match *self.kind(ty) {
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn tag(&self, id: TagId) -> &Tag {
+        &self.tags[id.0 as usize]
+    }
+
+    // Gives a struct or union its members and lays them out the way
+    // System V does. Members go at the next offset aligned for their
+    // type, and a bit-field goes in the storage unit of its type that
+    // the one before it ended in, unless it doesn't fit. An unnamed
+    // bit-field doesn't affect the alignment of the struct, and one of
+    // width 0 moves on to the next unit. A packed bit-field goes right
+    // after the one before it.
+    pub fn complete_record(&mut self, id: TagId, mut members: Vec<Member>, alignment: Alignment) {
+        let union = self.tag(id).kind == TagKind::Union;
+        // Counted in bits wide enough that members too large to have an
+        // offset can't overflow it
+        let char_bit = target::CHAR_BIT as u128;
+        let mut bits: u128 = 0;
+        let mut size: u128 = 0;
+        let mut align = alignment.requested.unwrap_or(1);
+        for member in &mut members {
+            let member_size = self.size_of(member.ty).unwrap_or(0) as u128;
+            let natural = self.align_of(member.ty).unwrap_or(1);
+            let member_alignment = Alignment {
+                packed: alignment.packed || member.alignment.packed,
+                requested: member.alignment.requested,
+            };
+            let member_align = member_alignment.apply(natural);
+            if member.name.is_some() || member.width.is_none() {
+                align = align.max(member_align);
+            }
+            if union {
+                bits = 0;
+            }
+            match member.width {
+                Some(width) if member_alignment.packed && width > 0 => {
+                    bits = bits.next_multiple_of(member_align as u128 * char_bit);
+                    member.offset = byte_offset(bits / char_bit);
+                    member.bit_offset = (bits % char_bit) as u64;
+                    bits += width as u128;
+                }
+                Some(width) => {
+                    let width = width as u128;
+                    let unit = (member_size * char_bit).max(1);
+                    if width == 0 {
+                        bits = bits.next_multiple_of(unit);
+                        member.offset = byte_offset(bits / char_bit);
+                        continue;
+                    }
+                    if bits % unit + width > unit {
+                        bits = bits.next_multiple_of(unit);
+                    }
+                    member.offset = byte_offset(bits / unit * member_size);
+                    member.bit_offset = (bits % unit) as u64;
+                    bits += width;
+                }
+                None => {
+                    bits = bits.next_multiple_of(member_align as u128 * char_bit);
+                    member.offset = byte_offset(bits / char_bit);
+                    bits += member_size * char_bit;
+                }
+            }
+            size = size.max(bits.div_ceil(char_bit));
+        }
+
+        let tag = &mut self.tags[id.0 as usize];
+        tag.members = members;
+        // One that's too large has a size no object can have
+        tag.size = match u64::try_from(size.next_multiple_of(align as u128)) {
+            Ok(size) if size <= target::MAX_OBJECT_SIZE => size,
+            _ => u64::MAX,
+        };
+        tag.align = align;
+        tag.complete = true;
+    }
+
+    // An enum is complete once its enumerators are given. Its values
+    // all fit in an `int`, which is the type it's compatible with.
+    pub fn complete_enum(&mut self, id: TagId) {
+        let tag = &mut self.tags[id.0 as usize];
+        tag.size = target::INT_SIZE;
+        tag.align = target::INT_SIZE;
+        tag.complete = true;
+    }
+
+    // The names of the members of a struct or union, with the members
+    // of anonymous structs and unions in it as if they were its own
+    pub fn member_names(&self, ty: CType) -> Vec<&str> {
+        let Some(id) = self.tag_id(ty) else {
+            return Vec::new();
+        };
+        let mut names = Vec::new();
+        for member in &self.tag(id).members {
+            match member.name {
+                Some(ref name) => names.push(name.as_str()),
+                None if member.width.is_none() => names.extend(self.member_names(member.ty)),
+                None => (),
+            }
+        }
+        names
+    }
+
+    pub fn is_integer(&self, ty: CType) -> bool {
+        use TypeKind::*;
+        matches!(
+            self.kind(ty),
+            Bool | Char
+                | SignedChar
+                | UnsignedChar
+                | Short
+                | UnsignedShort
+                | Int
+                | UnsignedInt
+                | Long
+                | UnsignedLong
+                | LongLong
+                | UnsignedLongLong
+                | Enum(_)
+        )
+    }
+
+    pub fn is_arithmetic(&self, ty: CType) -> bool {
+        self.is_integer(ty)
+            || matches!(
+                self.kind(ty),
+                TypeKind::Float | TypeKind::Double | TypeKind::LongDouble
+            )
+    }
+
+    pub fn is_scalar(&self, ty: CType) -> bool {
+        self.is_arithmetic(ty) || self.is_pointer(ty)
+    }
+
+    pub fn is_floating(&self, ty: CType) -> bool {
+        matches!(
+            self.kind(ty),
+            TypeKind::Float | TypeKind::Double | TypeKind::LongDouble
+        )
+    }
+
+    pub fn is_pointer(&self, ty: CType) -> bool {
+        matches!(self.kind(ty), TypeKind::Pointer(_))
+    }
+
+    pub fn is_void(&self, ty: CType) -> bool {
+        *self.kind(ty) == TypeKind::Void
+    }
+
+    pub fn is_error(&self, ty: CType) -> bool {
+        *self.kind(ty) == TypeKind::Error
+    }
+
+    pub fn is_record(&self, ty: CType) -> bool {
+        matches!(self.kind(ty), TypeKind::Struct(_) | TypeKind::Union(_))
+    }
+
+    // What a pointer type points to
+    pub fn pointee(&self, ty: CType) -> Option<CType> {
+        match *self.kind(ty) {
+            TypeKind::Pointer(to) => Some(to),
+            _ => None,
+        }
+    }
+
+    // Enums are `int`s, since every enumerator has to fit in one
+    pub fn is_unsigned(&self, ty: CType) -> bool {
+        match self.kind(ty) {
+            TypeKind::Char => !target::CHAR_SIGNED,
+            kind => matches!(
+                kind,
+                TypeKind::Bool
+                    | TypeKind::UnsignedChar
+                    | TypeKind::UnsignedShort
+                    | TypeKind::UnsignedInt
+                    | TypeKind::UnsignedLong
+                    | TypeKind::UnsignedLongLong
+            ),
+        }
+    }
+
+    // Integer types are ranked by width, and a signed type and its
+    // unsigned counterpart have the same rank
+    fn rank(&self, ty: CType) -> u8 {
+        match self.kind(ty) {
+            TypeKind::Bool => 0,
+            TypeKind::Char | TypeKind::SignedChar | TypeKind::UnsignedChar => 1,
+            TypeKind::Short | TypeKind::UnsignedShort => 2,
+            TypeKind::Long | TypeKind::UnsignedLong => 4,
+            TypeKind::LongLong | TypeKind::UnsignedLongLong => 5,
+            _ => 3,
+        }
+    }
+
+    // The integer promotions. Every type ranked below `int` fits in it.
+    pub fn promote(&self, ty: CType) -> CType {
+        let narrow = self.is_integer(ty) && self.rank(ty) < 3;
+        match narrow || matches!(self.kind(ty), TypeKind::Enum(_)) {
+            true => CType::INT,
+            false => ty,
+        }
+    }
+
+    // The type the usual arithmetic conversions bring the operands of a
+    // binary operator to. The wider floating type wins, and otherwise
+    // both are promoted and the one of higher rank wins, unless only
+    // the other is unsigned. A signed type then only wins if it can
+    // hold every value of the unsigned one.
+    pub fn common_type(&self, a: CType, b: CType) -> CType {
+        for floating in [CType::LONG_DOUBLE, CType::DOUBLE, CType::FLOAT] {
+            if self.kind(a) == self.kind(floating) || self.kind(b) == self.kind(floating) {
+                return floating;
+            }
+        }
+        let (a, b) = (self.promote(a), self.promote(b));
+        let (a, b) = (self.unqualified_basic(a), self.unqualified_basic(b));
+        if a == b {
+            return a;
+        }
+        let (high, low) = match self.rank(a) >= self.rank(b) {
+            true => (a, b),
+            false => (b, a),
+        };
+        match (self.is_unsigned(high), self.is_unsigned(low)) {
+            (true, _) => high,
+            (false, false) => high,
+            (false, true) if self.size_of(high) > self.size_of(low) => high,
+            (false, true) => self.to_unsigned(high),
+        }
+    }
+
+    // The basic types are the first ones interned, without qualifiers
+    fn unqualified_basic(&self, ty: CType) -> CType {
+        let index = BASIC_KINDS
+            .iter()
+            .position(|kind| kind == self.kind(ty))
+            .expect("Arithmetic types are basic types");
+        CType(index as u32)
+    }
+
+    fn to_unsigned(&self, ty: CType) -> CType {
+        match self.kind(ty) {
+            TypeKind::Int => CType::UNSIGNED_INT,
+            TypeKind::Long => CType::UNSIGNED_LONG,
+            TypeKind::LongLong => CType::UNSIGNED_LONG_LONG,
+            _ => ty,
+        }
+    }
+
+    // A member of a struct or union by name, including the members of
+    // the anonymous structs and unions in it
+    pub fn member(&self, ty: CType, name: &str) -> Option<&Member> {
+        let id = self.tag_id(ty)?;
+        self.tag(id)
+            .members
+            .iter()
+            .find_map(|member| match member.name {
+                Some(ref member_name) if member_name == name => Some(member),
+                Some(_) => None,
+                None if member.width.is_none() => self.member(member.ty, name),
+                None => None,
+            })
+    }
+
+    // A struct that ends in a flexible array member, or a union with one
+    // of those in it, can't be an array element or come before other
+    // members of a struct
+    pub fn has_flexible_array(&self, ty: CType) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Struct(id) => self.tag(id).members.last().is_some_and(|member| {
+                matches!(
+                    self.kind(member.ty),
+                    TypeKind::Array {
+                        size: ArraySize::Incomplete,
+                        ..
+                    }
+                ) || self.has_flexible_array(member.ty)
+            }),
+            TypeKind::Union(id) => self
+                .tag(id)
+                .members
+                .iter()
+                .any(|member| self.has_flexible_array(member.ty)),
+            _ => false,
+        }
+    }
+
+    // A complete type too large for an object to have, which is counted
+    // once for the innermost type that is
+    pub fn is_too_large(&self, ty: CType) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Array {
+                element,
+                size: ArraySize::Fixed(length),
+            } => {
+                !self.is_too_large(element)
+                    && self.size_of(element).is_some_and(|size| {
+                        size.checked_mul(length)
+                            .is_none_or(|size| size > target::MAX_OBJECT_SIZE)
+                    })
+            }
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let tag = self.tag(id);
+                tag.complete && tag.size > target::MAX_OBJECT_SIZE
+            }
+            _ => false,
+        }
+    }
+
+    // A variable length array, or a type derived from one
+    pub fn is_variably_modified(&self, ty: CType) -> bool {
+        match *self.kind(ty) {
+            TypeKind::Array {
+                size: ArraySize::Variable,
+                ..
+            } => true,
+            TypeKind::Array { element, .. } => self.is_variably_modified(element),
+            TypeKind::Pointer(pointee) => self.is_variably_modified(pointee),
+            TypeKind::Function { returns, .. } => self.is_variably_modified(returns),
+            _ => false,
+        }
+    }
+
+    // A member along with where it starts in the struct or union, which
+    // for a member of an anonymous struct or union adds where that is
+    pub fn member_offset(&self, ty: CType, name: &str) -> Option<(&Member, u64)> {
+        let id = self.tag_id(ty)?;
+        self.tag(id)
+            .members
+            .iter()
+            .find_map(|member| match member.name {
+                Some(ref member_name) if member_name == name => Some((member, member.offset)),
+                Some(_) => None,
+                None if member.width.is_none() => self
+                    .member_offset(member.ty, name)
+                    .map(|(inner, offset)| (inner, member.offset + offset)),
+                None => None,
+            })
+    }
+
+    // An lvalue of this type can be assigned to. It can't be an array
+    // or incomplete, or const, or a struct or union with a const
+    // member anywhere in it.
+    pub fn is_modifiable(&self, ty: CType) -> bool {
+        if self.qualifiers(ty).contains(Qualifiers::CONST) {
+            return false;
+        }
+        match *self.kind(ty) {
+            TypeKind::Array { .. } => false,
+            TypeKind::Struct(id) | TypeKind::Union(id) => {
+                let tag = self.tag(id);
+                tag.complete
+                    && tag
+                        .members
+                        .iter()
+                        .all(|member| self.is_modifiable(member.ty))
+            }
+            _ => self.is_complete(ty),
+        }
+    }
+
+    pub fn is_complete(&self, ty: CType) -> bool {
+        self.size_of(ty).is_some()
+    }
+
+    // `None` for the types without a size: `void`, functions and
+    // incomplete types
+    pub fn size_of(&self, ty: CType) -> Option<u64> {
+        let size = match *self.kind(ty) {
+            TypeKind::Error | TypeKind::Void | TypeKind::Function { .. } => return None,
+            TypeKind::Bool => target::BOOL_SIZE,
+            TypeKind::Char | TypeKind::SignedChar | TypeKind::UnsignedChar => 1,
+            TypeKind::Short | TypeKind::UnsignedShort => target::SHORT_SIZE,
+            TypeKind::Int | TypeKind::UnsignedInt => target::INT_SIZE,
+            TypeKind::Long | TypeKind::UnsignedLong => target::LONG_SIZE,
+            TypeKind::LongLong | TypeKind::UnsignedLongLong => target::LONG_LONG_SIZE,
+            TypeKind::Float => target::FLOAT_SIZE,
+            TypeKind::Double => target::DOUBLE_SIZE,
+            TypeKind::LongDouble => target::LONG_DOUBLE_SIZE,
+            TypeKind::VaList => target::VA_LIST_SIZE,
+            TypeKind::Pointer(_) => target::POINTER_SIZE,
+            TypeKind::Array {
+                element,
+                size: ArraySize::Fixed(length),
+            } => self.size_of(element)?.checked_mul(length)?,
+            TypeKind::Array { .. } => return None,
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => {
+                let tag = self.tag(id);
+                if !tag.complete {
+                    return None;
+                }
+                tag.size
+            }
+        };
+        Some(size)
+    }
+
+    // Scalars are aligned to their size, except that `va_list` is
+    // aligned like the pointers in it. An array is aligned like its
+    // elements. An aligned typedef has the alignment it was given.
+    pub fn align_of(&self, ty: CType) -> Option<u64> {
+        if let Some(align) = self.get(ty).align {
+            return self.is_complete(ty).then_some(align);
+        }
+        match *self.kind(ty) {
+            TypeKind::Array { element, .. } => self.align_of(element),
+            TypeKind::VaList => Some(target::POINTER_SIZE),
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => {
+                let tag = self.tag(id);
+                tag.complete.then_some(tag.align)
+            }
+            _ => self.size_of(ty),
+        }
+    }
+
+    // What an argument without a parameter type to convert it to
+    // becomes. The integer types narrower than `int` are promoted to
+    // `int`, and `float` to `double`.
+    pub fn promote_argument(&self, ty: CType) -> CType {
+        match self.kind(ty) {
+            TypeKind::Float => CType::DOUBLE,
+            _ => self.promote(ty),
+        }
+    }
+
+    // Whether two declarations of the same thing can have these types.
+    // Qualifiers have to match, pointers and arrays need compatible
+    // element types, and arrays of known size need the same size. A
+    // function without a prototype is compatible with one with a
+    // prototype whose parameters aren't changed by the default
+    // argument promotions.
+    pub fn compatible(&self, a: CType, b: CType) -> bool {
+        if a == b {
+            return true;
+        }
+        let (a_type, b_type) = (self.get(a), self.get(b));
+        if a_type.kind == TypeKind::Error || b_type.kind == TypeKind::Error {
+            return true;
+        }
+        if a_type.qualifiers != b_type.qualifiers {
+            return false;
+        }
+
+        use TypeKind::*;
+        match (&a_type.kind, &b_type.kind) {
+            (&Pointer(a), &Pointer(b)) => self.compatible(a, b),
+            (
+                &Array {
+                    element: a,
+                    size: a_size,
+                },
+                &Array {
+                    element: b,
+                    size: b_size,
+                },
+            ) => {
+                let sizes_match = match (a_size, b_size) {
+                    (ArraySize::Fixed(a), ArraySize::Fixed(b)) => a == b,
+                    _ => true,
+                };
+                sizes_match && self.compatible(a, b)
+            }
+            (
+                &Function {
+                    returns: a_returns,
+                    parameters: ref a_parameters,
+                    variadic: a_variadic,
+                },
+                &Function {
+                    returns: b_returns,
+                    parameters: ref b_parameters,
+                    variadic: b_variadic,
+                },
+            ) => {
+                if !self.compatible(a_returns, b_returns) {
+                    return false;
+                }
+                match (a_parameters, b_parameters) {
+                    (Some(a), Some(b)) => {
+                        a_variadic == b_variadic
+                            && a.len() == b.len()
+                            && a.iter().zip(b).all(|(&a, &b)| self.compatible(a, b))
+                    }
+                    (Some(parameters), None) | (None, Some(parameters)) => {
+                        let variadic = a_variadic || b_variadic;
+                        !variadic
+                            && parameters
+                                .iter()
+                                .all(|&p| self.compatible(p, self.promote_argument(p)))
+                    }
+                    (None, None) => true,
+                }
+            }
+            // Each enum is its own type, compatible with `int`
+            (Enum(_), Enum(_)) => false,
+            (Enum(_), Int) | (Int, Enum(_)) => true,
+            // The same type, other than its alignment
+            (a, b) => a == b,
+        }
+    }
+
+    // The type of something declared again with a compatible type,
+    // which combines what each declaration says. An array takes the
+    // size that's known and a function the prototype that's given.
+    pub fn composite(&mut self, a: CType, b: CType) -> CType {
+        if a == b || *self.kind(b) == TypeKind::Error {
+            return a;
+        }
+        if *self.kind(a) == TypeKind::Error {
+            return b;
+        }
+
+        let Type {
+            qualifiers, align, ..
+        } = *self.get(a);
+        let kind = match (self.kind(a).clone(), self.kind(b).clone()) {
+            (TypeKind::Pointer(a), TypeKind::Pointer(b)) => TypeKind::Pointer(self.composite(a, b)),
+            (
+                TypeKind::Array {
+                    element: a,
+                    size: a_size,
+                },
+                TypeKind::Array {
+                    element: b,
+                    size: b_size,
+                },
+            ) => {
+                let size = match (a_size, b_size) {
+                    (ArraySize::Fixed(size), _) | (_, ArraySize::Fixed(size)) => {
+                        ArraySize::Fixed(size)
+                    }
+                    (ArraySize::Variable, _) | (_, ArraySize::Variable) => ArraySize::Variable,
+                    _ => ArraySize::Incomplete,
+                };
+                TypeKind::Array {
+                    element: self.composite(a, b),
+                    size,
+                }
+            }
+            (
+                TypeKind::Function {
+                    returns: a_returns,
+                    parameters: a_parameters,
+                    variadic: a_variadic,
+                },
+                TypeKind::Function {
+                    returns: b_returns,
+                    parameters: b_parameters,
+                    variadic: b_variadic,
+                },
+            ) => {
+                let returns = self.composite(a_returns, b_returns);
+                let (parameters, variadic) = match (a_parameters, b_parameters) {
+                    (Some(a), Some(b)) => {
+                        let parameters = a
+                            .into_iter()
+                            .zip(b)
+                            .map(|(a, b)| self.composite(a, b))
+                            .collect();
+                        (Some(parameters), a_variadic)
+                    }
+                    (Some(parameters), None) => (Some(parameters), a_variadic),
+                    (None, Some(parameters)) => (Some(parameters), b_variadic),
+                    (None, None) => (None, false),
+                };
+                TypeKind::Function {
+                    returns,
+                    parameters,
+                    variadic,
+                }
+            }
+            _ => return a,
+        };
+        self.intern_type(Type {
+            kind,
+            qualifiers,
+            align,
+        })
+    }
+
+    // Describes a type in words, `pointer to const char`
+    pub fn display(&self, ty: CType) -> TypeDisplay<'_> {
+        TypeDisplay { table: self, ty }
+    }
+}
+
+pub struct TypeDisplay<'a> {
+    table: &'a TypeTable,
+    ty: CType,
+}
+
+impl std::fmt::Display for TypeDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Type {
+            ref kind,
+            qualifiers,
+            ..
+        } = *self.table.get(self.ty);
+        let nested = |ty| self.table.display(ty);
+        write!(f, "{}", qualifiers)?;
+        let name = match *kind {
+            TypeKind::Error => "<error>",
+            TypeKind::Void => "void",
+            TypeKind::Bool => "_Bool",
+            TypeKind::Char => "char",
+            TypeKind::SignedChar => "signed char",
+            TypeKind::UnsignedChar => "unsigned char",
+            TypeKind::Short => "short",
+            TypeKind::UnsignedShort => "unsigned short",
+            TypeKind::Int => "int",
+            TypeKind::UnsignedInt => "unsigned int",
+            TypeKind::Long => "long",
+            TypeKind::UnsignedLong => "unsigned long",
+            TypeKind::LongLong => "long long",
+            TypeKind::UnsignedLongLong => "unsigned long long",
+            TypeKind::Float => "float",
+            TypeKind::Double => "double",
+            TypeKind::LongDouble => "long double",
+            TypeKind::VaList => "__builtin_va_list",
+            TypeKind::Pointer(to) => return write!(f, "pointer to {}", nested(to)),
+            TypeKind::Array { element, size } => {
+                return match size {
+                    ArraySize::Fixed(size) => write!(f, "array of {} {}", size, nested(element)),
+                    _ => write!(f, "array of {}", nested(element)),
+                }
+            }
+            TypeKind::Function {
+                returns,
+                ref parameters,
+                variadic,
+            } => {
+                write!(f, "function (")?;
+                match *parameters {
+                    Some(ref parameters) if parameters.is_empty() && !variadic => {
+                        write!(f, "void")?
+                    }
+                    Some(ref parameters) => {
+                        for (i, &parameter) in parameters.iter().enumerate() {
+                            if i > 0 {
+                                write!(f, ", ")?;
+                            }
+                            write!(f, "{}", nested(parameter))?;
+                        }
+                        if variadic {
+                            let separator = if parameters.is_empty() { "" } else { ", " };
+                            write!(f, "{}...", separator)?;
+                        }
+                    }
+                    None => (),
+                }
+                return write!(f, ") returning {}", nested(returns));
+            }
+            TypeKind::Struct(id) | TypeKind::Union(id) | TypeKind::Enum(id) => {
+                let tag = self.table.tag(id);
+                return match tag.name {
+                    Some(ref name) => write!(f, "{} {}", tag.kind, name),
+                    None => write!(f, "anonymous {}", tag.kind),
+                };
+            }
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// An offset in bytes, which a member past the largest object can't have
+fn byte_offset(offset: u128) -> u64 {
+    u64::try_from(offset).unwrap_or(u64::MAX)
+}
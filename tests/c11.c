@@ -0,0 +1,39 @@
+// Features added in C11
+
+_Static_assert(sizeof(int) == 4, "int is 32 bits");
+_Static_assert(_Alignof(double) == 8, "double is aligned to 8 bytes");
+_Static_assert(_Alignof(char[16]) == 1, "arrays align like their elements");
+
+_Thread_local int counter;
+static _Thread_local int per_thread = 1;
+extern _Thread_local long shared;
+
+_Alignas(16) char buffer[64];
+_Alignas(double) int aligned;
+_Alignas(0) int unchanged;
+
+_Atomic int flag;
+_Atomic const long *volatile ticks;
+
+_Noreturn void fail(const char *message);
+inline _Noreturn void stop(void) {
+    fail("stop");
+}
+
+enum { IS_INT = _Generic((char)1, char: 1, int: 2, default: 0) };
+_Static_assert(IS_INT == 1, "`char` selects `char`");
+_Static_assert(_Generic('a', int: 1, default: 0), "character constants are `int`");
+_Static_assert(_Generic(sizeof(int), unsigned long: 1, default: 0), "sizeof gives `size_t`");
+_Static_assert(_Generic((const int)0, int: 1, default: 0), "casts drop qualifiers");
+
+int classify(double x) {
+    _Static_assert(sizeof(double) == 8, "double is 64 bits");
+    static _Thread_local int calls;
+    calls++;
+    return _Generic(x, float: 1, double: 2, long double: 3, default: 0);
+}
+
+struct node {
+    _Alignas(8) char tag;
+    _Atomic int *next;
+};
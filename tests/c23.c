@@ -0,0 +1,47 @@
+// Features added in C23
+
+static_assert(sizeof(bool) == 1);
+static_assert(true && !false, "true and false are keywords");
+static_assert(0b1010 == 10, "binary constants");
+static_assert(1'000'000 == 1000000, "digit separators");
+static_assert(0x7f'ff == 32767);
+
+constexpr int buffer_size = 64;
+constexpr long kilo = 1'000;
+static_assert(buffer_size * 2 == 128, "constexpr objects are constants");
+char buffer[buffer_size];
+
+alignas(16) static thread_local int counter;
+static_assert(alignof(typeof(long)) == 8);
+typeof(buffer) *buffer_pointer;
+typeof_unqual(const int) mutable_int;
+static_assert(sizeof(typeof(char[3])) == 3);
+
+[[nodiscard("check the result")]] int parse(const char *text);
+[[deprecated, maybe_unused]] static int old_api(void);
+[[gnu::always_inline]] inline int fast(int x);
+int unused_parameter([[maybe_unused]] int x);
+
+struct pair {
+    [[deprecated("use second")]] int first;
+    int second;
+};
+
+int classify(int kind) {
+    auto limit = 10;
+    static auto calls = 0;
+    constexpr auto twice = buffer_size * 2;
+    static_assert(twice == 128);
+    int *nothing = nullptr;
+    [[maybe_unused]] int ignored = 0;
+    calls++;
+    switch (kind) {
+    case 0:
+        kind++;
+        [[fallthrough]];
+    case 1:
+        return limit;
+    default:
+        return nothing == nullptr;
+    }
+}
@@ -0,0 +1,44 @@
+/* Old-style C that C89 still allows */
+
+static count;
+extern total, *last;
+
+max(a, b)
+    int a, b;
+{
+    return a > b ? a : b;
+}
+
+char *copy(dest, src, n)
+    char *dest;
+    register char *src;
+{
+    while (n--)
+        *dest++ = *src++;
+    return dest;
+}
+
+double average(values, length)
+    float *values;
+    unsigned length;
+{
+    double sum;
+    unsigned i;
+
+    sum = 0;
+    for (i = 0; i < length; i++)
+        sum += values[i];
+    return sum / length;
+}
+
+double scale();
+long clamp(long value, long limit);
+
+report(level)
+    short level;
+{
+    register i;
+
+    i = max(level, 3);
+    return (int)scale((float)i, (char)'x', (short)level, clamp(i, 10));
+}
@@ -0,0 +1,29 @@
+// Features added in C99, which zcc accepts by default
+#include <stddef.h>
+
+struct buffer {
+    size_t length;
+    unsigned char data[];
+};
+
+static char header[offsetof(struct buffer, data)];
+
+static inline int square(int x) { return x * x; }
+
+void copy(int *restrict to, const int *restrict from, int count) {
+    for (int i = 0; i < count; i++)
+        to[i] = from[i];
+}
+
+int main(void) {
+    const char *name = __func__;
+    _Bool done = 0;
+    long long big = 1LL << 40;
+    double half = 0x1p-1;
+    int values[4] = { [2] = 1, [0] = square(3), };
+    int total = 0;
+    for (int i = 0; i < 4; i++)
+        total += values[i];
+    struct buffer *empty = &(struct buffer){ .length = 0 };
+    return total + done + (big > 0) + (half < 1) + (name != 0) + (int)empty->length + (int)sizeof header;
+}
@@ -0,0 +1,75 @@
+// Running zcc from the integration tests. Each test crate uses only
+// some of these.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+// Runs zcc with `args`, giving what it printed if it succeeds and the
+// errors if it fails
+pub fn zcc(args: &[&str]) -> Result<String, String> {
+    let output = Command::new(env!("CARGO_BIN_EXE_zcc"))
+        .args(args)
+        .output()
+        .expect("Failed to run zcc");
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+// A file in the temporary directory that's unique to the test crate
+// and `name`, and is removed when it's dropped
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(name: &str, contents: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "zcc_{}_{}_{}",
+            env!("CARGO_CRATE_NAME"),
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).expect("Failed to write temporary file");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().expect("Path should be UTF-8")
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+// Compiles `source` with `args` before the file name
+pub fn run(name: &str, source: &str, args: &[&str]) -> Result<String, String> {
+    let file = TempFile::new(&format!("{}.c", name), source);
+    zcc(&[args, &[file.path()]].concat())
+}
+
+// Compiles `source` as C17, giving the errors if it fails
+pub fn compile(name: &str, source: &str) -> Result<(), String> {
+    run(name, source, &["-std=c17"]).map(|_| ())
+}
+
+// Same with the given standard, `c99` or `gnu11` and so on
+pub fn compile_as(standard: &str, name: &str, source: &str) -> Result<(), String> {
+    run(name, source, &[&format!("-std={}", standard)]).map(|_| ())
+}
+
+// The warnings of a compilation that has to succeed
+pub fn warnings(name: &str, source: &str) -> String {
+    let file = TempFile::new(&format!("{}.c", name), source);
+    let output = Command::new(env!("CARGO_BIN_EXE_zcc"))
+        .args(["-std=c17", file.path()])
+        .output()
+        .expect("Failed to run zcc");
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "zcc failed:\n{}", stderr);
+    stderr
+}
@@ -0,0 +1,164 @@
+// Where errors point and what else they show
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{compile, zcc, TempFile};
+
+// Whether `text` has each of `parts`, one after the other
+fn in_order(text: &str, parts: &[&str]) -> bool {
+    let mut rest = text;
+    parts.iter().all(|part| match rest.find(part) {
+        Some(index) => {
+            rest = &rest[index + part.len()..];
+            true
+        }
+        None => false,
+    })
+}
+
+#[test]
+fn macro_expansion_backtraces() {
+    // The error is at the invocation, then each macro it went through
+    // from the innermost out
+    let source = "#define INNER(x) (x + )\n\
+                  #define OUTER(y) INNER(y)\n\
+                  int f(void) { return OUTER(1); }\n";
+    let errors = compile("backtrace", source).unwrap_err();
+    assert!(
+        in_order(
+            &errors,
+            &[
+                "Expected expression, found `)`",
+                "3 | int f(void) { return OUTER(1); }",
+                "= note: Expanded from macro `INNER`",
+                "1 | #define INNER(x) (x + )",
+                "= note: Expanded from macro `OUTER`",
+                "2 | #define OUTER(y) INNER(y)",
+            ]
+        ),
+        "{}",
+        errors
+    );
+
+    let source = "#define BAD 1.2.3\nint f(void) { return BAD; }\n";
+    let errors = compile("constant", source).unwrap_err();
+    assert!(
+        in_order(
+            &errors,
+            &[
+                "`1.2.3` is not a valid constant",
+                "2 | int f(void) { return BAD; }",
+                "= note: Expanded from macro `BAD`",
+                "1 | #define BAD 1.2.3",
+            ]
+        ),
+        "{}",
+        errors
+    );
+
+    // Tokens that weren't produced by a macro have no notes
+    let errors = compile("no_macro", "int f(void) { return 1 + ; }\n").unwrap_err();
+    assert!(!errors.contains("Expanded from macro"));
+}
+
+#[test]
+fn spans_are_in_the_buffer_they_came_from() {
+    let header = TempFile::new("spans.h", "int ok;\n\nint bad = ;\n");
+    let name = Path::new(header.path())
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let source = format!("#include \"{}\"\nint x;\n", name);
+    let errors = compile("include", &source).unwrap_err();
+    assert!(
+        errors.contains(&format!("--> {}:3", header.path())),
+        "{}",
+        errors
+    );
+    assert!(errors.contains("3 | int bad = ;"));
+
+    // Carets are indented with the same tabs as the line they point at
+    let errors = compile("tabs", "void f(void) {\n\tint\tb = \tzz;\n}\n").unwrap_err();
+    assert!(
+        errors.contains("2 | \tint\tb = \tzz;\n  | \t   \t    \t^^\n"),
+        "{}",
+        errors
+    );
+
+    // Lines and columns are counted in the text as it was written,
+    // before lines are spliced
+    let header = TempFile::new("dump.h", "int z;\n");
+    let name = Path::new(header.path())
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let source = format!("#include \"{}\"\nint *lo\\\nng_name = &z;\n", name);
+    let file = TempFile::new("dump.c", &source);
+    let dump = zcc(&["-ast-dump", file.path()]).unwrap();
+    let at = |node: &str, path: &str, location: &str| format!("{} <{}:{}>", node, path, location);
+    assert!(
+        dump.contains(&at("IdentifierDeclarator z", header.path(), "1:5, 1:6")),
+        "{}",
+        dump
+    );
+    assert!(dump.contains(&at(
+        "IdentifierDeclarator long_name",
+        file.path(),
+        "2:6, 3:8"
+    )));
+    assert!(dump.contains(&at("Identifier z 'int' lvalue", file.path(), "3:12, 3:13")));
+}
+
+#[test]
+fn syntax_errors_are_recovered_from() {
+    let source = "int a = 1\n\
+                  int b;\n\
+                  struct s { int m int n; };\n\
+                  int f(void) {\n\
+                  \x20   int x = ;\n\
+                  \x20   x = 2 +;\n\
+                  \x20   return x;\n\
+                  }\n\
+                  int g(void) { return 1 }\n\
+                  int h(void) { return undeclared; }\n";
+    let errors = compile("recovery", source).unwrap_err();
+    assert_eq!(errors.matches("Compilation Error").count(), 6, "{}", errors);
+    assert!(
+        in_order(
+            &errors,
+            &[
+                "Expected `;`, found `int`",
+                "2 | int b;",
+                "Expected `;`, found `int`",
+                "3 | struct s { int m int n; };",
+                "Expected expression, found `;`",
+                "5 |     int x = ;",
+                "Expected expression, found `;`",
+                "6 |     x = 2 +;",
+                "Expected `;`, found `}`",
+                "9 | int g(void) { return 1 }",
+                // Checks carry on after the errors
+                "`undeclared` is not declared",
+            ]
+        ),
+        "{}",
+        errors
+    );
+
+    // What couldn't be parsed is left in the tree as error nodes, and
+    // the tree is dumped even though the compilation fails
+    let file = TempFile::new("recovery_dump.c", source);
+    let output = Command::new(env!("CARGO_BIN_EXE_zcc"))
+        .args(["-ast-dump", file.path()])
+        .output()
+        .expect("Failed to run zcc");
+    assert!(!output.status.success());
+    let dump = String::from_utf8_lossy(&output.stdout);
+    assert!(dump.contains("Error '<error>'"), "{}", dump);
+    assert!(dump.contains("FunctionDefinition 'function (void) returning int'"));
+    assert!(dump.contains("Identifier undeclared"));
+}
@@ -0,0 +1,110 @@
+// Command line options that change what's preprocessed and what's
+// written besides the compiled file
+mod common;
+
+use std::path::Path;
+
+use common::{run, zcc, TempFile};
+
+#[test]
+fn command_line_macros_are_in_order() {
+    let source = "int a = A;\n\
+                  int b = B;\n\
+                  #ifdef C\n\
+                  int c;\n\
+                  #endif\n\
+                  int d = D;\n\
+                  int e = E;\n";
+    let args = [
+        "-E", "-DA", "-DB=2", "-UB", "-DB=3", "-DC", "-UC", "-D", "D=(4)",
+    ];
+    let output = run("macros", source, &args).unwrap();
+    assert!(output.contains("int a = 1;"));
+    assert!(output.contains("int b = 3;"));
+    assert!(!output.contains("int c;"));
+    assert!(output.contains("int d = (4);"));
+    assert!(output.contains("int e = E;"));
+
+    // Forced includes come after every `-D` and `-U`, wherever they
+    // are on the command line
+    let header = TempFile::new("forced.h", "#ifdef A\n#define E A + 1\n#endif\n");
+    let args = ["-E", "-include", header.path(), "-DA=5"];
+    let output = run("include", source, &args).unwrap();
+    assert!(output.contains("int e = 5 + 1;"));
+
+    let errors = run("bad_name", source, &["-E", "-D1X"]).unwrap_err();
+    assert!(errors.contains("--> <command line>:1"));
+    assert!(errors.contains("Expected identifier as macro name, found `1X`"));
+    let errors = run("missing", source, &["-E", "-include", "no_such_header.h"]).unwrap_err();
+    assert!(errors.contains("--> <command line>:1"));
+    assert!(errors.contains("Could not find `no_such_header.h`"));
+    let errors = zcc(&["-E", "-D"]).unwrap_err();
+    assert!(errors.contains("Missing argument to `-D`"));
+}
+
+// The name a temporary file is included by, since they're all in the
+// same directory
+fn file_name(file: &TempFile) -> &str {
+    Path::new(file.path())
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+}
+
+#[test]
+fn dependency_rules() {
+    let nested = TempFile::new("nested dep.h", "\n");
+    let user = TempFile::new(
+        "user_dep.h",
+        &format!("#include \"{}\"\n", file_name(&nested)),
+    );
+    let system = TempFile::new("system_dep.h", "\n");
+    let source = format!(
+        "#include \"{}\"\n#include <{}>\nint x;\n",
+        file_name(&user),
+        file_name(&system)
+    );
+    let temp = std::env::temp_dir();
+    let isystem = ["-isystem", temp.to_str().unwrap()];
+    let escaped = nested.path().replace(' ', "\\ ");
+
+    // `-M` lists every header and `-MM` leaves out system ones, both
+    // instead of compiling
+    let rule = run(
+        "deps",
+        &source,
+        &[&isystem[..], &["-M", "-MT", "deps.o"]].concat(),
+    )
+    .unwrap();
+    assert!(rule.starts_with("deps.o: "), "{}", rule);
+    assert!(rule.contains(user.path()));
+    assert!(rule.contains(&escaped));
+    assert!(rule.contains(system.path()));
+    assert!(!rule.contains("int x;"));
+    let rule = run(
+        "deps",
+        &source,
+        &[&isystem[..], &["-MM", "-MT", "deps.o"]].concat(),
+    )
+    .unwrap();
+    assert!(rule.contains(user.path()));
+    assert!(!rule.contains(system.path()));
+
+    // `-MQ` quotes the target for Make and `-MP` adds an empty rule
+    // for each header
+    let args = ["-MM", "-MT", "a$b.o", "-MQ", "c$d.o", "-MP"];
+    let rule = run("deps", &source, &[&isystem[..], &args].concat()).unwrap();
+    assert!(rule.starts_with("a$b.o c$$d.o: "), "{}", rule);
+    assert!(rule.contains(&format!("\n{}:\n", user.path())));
+    assert!(rule.contains(&format!("\n{}:\n", escaped)));
+
+    // `-MD` writes the rule to the `-MF` file and carries on
+    let depfile = TempFile::new("deps.d", "");
+    let args = ["-E", "-MD", "-MF", depfile.path(), "-MT", "deps.o"];
+    let output = run("deps", &source, &[&isystem[..], &args].concat()).unwrap();
+    assert!(output.contains("int x;"));
+    let rule = std::fs::read_to_string(depfile.path()).unwrap();
+    assert!(rule.starts_with("deps.o: "), "{}", rule);
+    assert!(rule.contains(system.path()));
+}
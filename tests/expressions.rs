@@ -0,0 +1,185 @@
+// What the types of expressions are and the conversions made to them
+mod common;
+
+use common::{compile, run, warnings};
+
+#[test]
+fn usual_arithmetic_conversions() {
+    let source = "_Static_assert(_Generic('a', int: 1), \"character constant\");\n\
+                  _Static_assert(_Generic(1u + -1, unsigned: 1), \"unsigned wins\");\n\
+                  _Static_assert(_Generic(1L + 1u, long: 1), \"wider signed wins\");\n\
+                  _Static_assert(_Generic(1 + 1.0f, float: 1), \"float\");\n\
+                  _Static_assert(_Generic(1.0f + 1.0, double: 1), \"double\");\n\
+                  _Static_assert(sizeof(1 + 1L) == 8, \"long\");\n\
+                  _Static_assert(sizeof(1 << 1L) == 4, \"shifts promote on their own\");\n\
+                  _Static_assert(_Generic(2147483648, long: 1), \"decimal constants\");\n\
+                  _Static_assert(_Generic(0xffffffff, unsigned: 1), \"hex constants\");\n\
+                  _Static_assert(-1 < 0u == 0, \"compared as unsigned\");\n\
+                  _Static_assert((unsigned char)300 == 44, \"wraps\");\n\
+                  _Static_assert((int)1.9 == 1 && (long)1e3 == 1000, \"truncated\");\n\
+                  _Static_assert((_Bool)0.5 && !(_Bool)0.0 && (_Bool)2 == 1, \"_Bool\");\n\
+                  _Static_assert((unsigned char)255.9 == 255, \"fits\");\n\
+                  char c;\n\
+                  const short s;\n\
+                  int a[4];\n\
+                  _Static_assert(_Generic(c + c, int: 1), \"promoted\");\n\
+                  _Static_assert(_Generic(s, short: 1), \"qualifiers are dropped\");\n\
+                  _Static_assert(_Generic(a, int *: 1), \"arrays decay\");\n\
+                  _Static_assert(_Generic(&a[1] - &a[0], long: 1), \"ptrdiff_t\");\n\
+                  _Static_assert(sizeof \"abc\\n\" == 5, \"string length\");\n";
+    assert_eq!(compile("conversions", source), Ok(()));
+}
+
+#[test]
+fn character_constants_have_the_value_of_a_char() {
+    let source = "_Static_assert('\\xff' == -1, \"signed char\");\n\
+                  _Static_assert('\\x7f' == 127 && '\\200' == -128, \"sign extended\");\n\
+                  _Static_assert((unsigned char)'\\xff' == 255, \"converted\");\n\
+                  #if '\\xff' != -1\n\
+                  #error \"The preprocessor agrees\"\n\
+                  #endif\n";
+    assert_eq!(compile("character_values", source), Ok(()));
+}
+
+#[test]
+fn floating_constants_that_do_not_fit() {
+    let source = "_Static_assert((unsigned char)256.0, \"too big\");\n";
+    let errors = compile("float_range", source).unwrap_err();
+    assert!(errors.contains("Static assertion could not be evaluated as an integer constant"));
+}
+
+#[test]
+fn conversions_are_in_the_ast() {
+    let source = "long f(long n, ...);\n\
+                  int g(char c, int a[]) {\n\
+                  \x20   return f(c, 1.0f) + a[0];\n\
+                  }\n";
+    let dump = run("dump", source, &["-std=c17", "-ast-dump"]).unwrap();
+    assert!(dump.contains("ImplicitCast <LvalueToValue> 'char'"));
+    assert!(dump.contains("ImplicitCast <Integer> 'long'"));
+    assert!(dump.contains("ImplicitCast <Floating> 'double'"));
+    assert!(dump.contains("ImplicitCast <FunctionToPointer>"));
+    assert!(dump.contains("Subscript 'int' lvalue"));
+    assert!(dump.contains("BinaryOperator '+' 'long'"));
+}
+
+#[test]
+fn lvalues_and_modifiable_lvalues() {
+    let errors = compile("const", "void f(void) { const int k = 1; k = 2; }\n").unwrap_err();
+    assert!(
+        errors.contains("The left operand of `=` can't be modified, as it has type `const int`")
+    );
+
+    let errors = compile("rvalue", "void f(int x) { x + 1 = 2; }\n").unwrap_err();
+    assert!(errors.contains("The left operand of `=` isn't an lvalue"));
+
+    let errors = compile("array", "int a[2], b[2];\nvoid f(void) { a = b; }\n").unwrap_err();
+    assert!(errors.contains("can't be modified, as it has type `array of 2 int`"));
+
+    let errors = compile("increment", "void f(void) { 1++; }\n").unwrap_err();
+    assert!(errors.contains("The operand of `++` isn't an lvalue"));
+
+    let errors = compile("address", "void f(int x) { &(x + 1); }\n").unwrap_err();
+    assert!(errors.contains("The operand of `&` isn't an lvalue or a function"));
+
+    let source = "struct s { int m; } const c;\n\
+                  void f(void) { c.m = 1; }\n";
+    let errors = compile("const_member", source).unwrap_err();
+    assert!(errors.contains("can't be modified, as it has type `const int`"));
+}
+
+#[test]
+fn bit_fields_and_registers_have_no_address() {
+    let source = "struct s { int b : 3; int m; } v, *p;
+";
+    let errors = compile("address_bit_field", &format!("{}int *q = &v.b;\n", source)).unwrap_err();
+    assert!(errors.contains("Can't take the address of bit-field `b`"));
+
+    let errors = compile("address_arrow", &format!("{}int *q = &p->b;\n", source)).unwrap_err();
+    assert!(errors.contains("Can't take the address of bit-field `b`"));
+
+    let errors = compile(
+        "sizeof_bit_field",
+        &format!("{}int n = sizeof v.b;\n", source),
+    )
+    .unwrap_err();
+    assert!(errors.contains("`sizeof` can't be applied to bit-field `b`"));
+
+    let errors = compile(
+        "address_register",
+        "void f(register int x) { register int r = 1; &r; &x; }\n",
+    )
+    .unwrap_err();
+    assert!(errors.contains("Can't take the address of `r`, which is declared `register`"));
+    assert!(errors.contains("Can't take the address of `x`, which is declared `register`"));
+
+    let source = format!("{}int *q = &v.m;\nint n = sizeof v.m;\n", source);
+    assert_eq!(compile("address_member", &source), Ok(()));
+}
+
+#[test]
+fn pointer_conversions_warn() {
+    // Warnings don't stop the compilation
+    let source = "void h(char *s);\n\
+                  void f(const char *c, int *ip) {\n\
+                  \x20   long *lp = ip;\n\
+                  \x20   h(c);\n\
+                  \x20   ip = 5;\n\
+                  \x20   (void)(ip == lp);\n\
+                  }\n";
+    let output = warnings("pointer_conversions", source);
+    assert!(output.contains(
+        "`pointer to int` is converted to the incompatible pointer type `pointer to long` in an initializer"
+    ));
+    assert!(output.contains("in argument 1, which drops `const` from what it points to"));
+    assert!(
+        output.contains("`int` is converted to `pointer to int` in an assignment without a cast")
+    );
+    assert!(output.contains("Comparison of pointers to different types"));
+}
+
+#[test]
+fn null_pointer_constants() {
+    let source = "void *v = 0;\n\
+                  int *p = (void *)0;\n\
+                  char *q = 1 - 1;\n\
+                  int f(int *p) {\n\
+                  \x20   p = v;\n\
+                  \x20   return p == 0 || p != (void *)0 ? 0 : *(p ? p : 0);\n\
+                  }\n";
+    let output = warnings("null", source);
+    assert!(!output.contains("Incompatible types"), "{}", output);
+}
+
+#[test]
+fn invalid_operands() {
+    let source = "struct s { int m; } v;\n\
+                  void f(int *p, double d) { v + 1; }\n";
+    let errors = compile("plus", source).unwrap_err();
+    assert!(errors.contains("Invalid operands to `+`, `struct s` and `int`"));
+
+    let errors = compile("modulus", "void f(double d) { d % 2; }\n").unwrap_err();
+    assert!(errors.contains("Invalid operands to `%`, `double` and `int`"));
+
+    let errors = compile("pointer_sum", "void f(int *p, int *q) { p + q; }\n").unwrap_err();
+    assert!(errors.contains("Invalid operands to `+`, `pointer to int` and `pointer to int`"));
+
+    let errors = compile(
+        "no_member",
+        "struct s { int m; };\nvoid f(struct s *p) { p->n; }\n",
+    )
+    .unwrap_err();
+    assert!(errors.contains("`struct s` has no member named `n`"));
+
+    let errors = compile("call", "int x;\nvoid f(void) { x(); }\n").unwrap_err();
+    assert!(errors.contains("The called expression has type `int`, which isn't a function"));
+
+    let errors = compile("arguments", "void g(int);\nvoid f(void) { g(1, 2); }\n").unwrap_err();
+    assert!(errors.contains("The function takes 1 argument, but is given 2"));
+
+    let errors = compile("undeclared", "int f(void) { return y; }\n").unwrap_err();
+    assert!(errors.contains("`y` is not declared"));
+
+    let errors = compile("void_return", "void f(void) { return 1; }\n").unwrap_err();
+    assert!(errors.contains("A function returning `void` can't return a value"));
+}
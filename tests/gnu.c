@@ -0,0 +1,53 @@
+// GNU extensions, compiled with `-std=gnu11`
+#include <stdarg.h>
+
+struct __attribute__((packed)) header {
+    char tag;
+    int length;
+} __attribute__((aligned(8)));
+
+struct message {
+    struct header header;
+    char data[0];
+};
+
+enum __attribute__((unused)) color { RED, GREEN, BLUE };
+
+__extension__ typedef long long wide;
+static int counter __attribute__((section(".counters"), unused));
+void fatal(const char *format, ...) __attribute__((noreturn, format(printf, 1, 2)));
+int (*handler)(int) __attribute__((weak));
+__attribute__((unused)) static int helper(int x __attribute__((unused)));
+
+typeof(counter) copy;
+__typeof__(int *) pointer;
+_Static_assert(sizeof(__typeof__(char[5])) == 5, "__typeof__ takes a type");
+_Static_assert(__alignof__(__builtin_va_list) == 8, "va_list is pointer aligned");
+_Static_assert((0 ?: 7) == 7 && (3 ?: 7) == 3, "?: gives the condition");
+
+int sum(int count, ...) {
+    va_list args;
+    va_start(args, count);
+    int total = 0;
+    for (int i = 0; i < count; i++) {
+        total += __builtin_va_arg(args, int);
+    }
+    va_end(args);
+    return total;
+}
+
+int grade(int score) {
+    int bonus = ({
+        int doubled = score * 2;
+        doubled > 100 ? 100 : doubled;
+    });
+    switch (bonus) {
+    case 0 ... 49:
+        return 'F';
+    case 50 ... 89:
+        return 'B';
+    default:
+        break;
+    }
+    return __extension__ (bonus ?: 1);
+}
@@ -0,0 +1,122 @@
+// `-emit-json` output read back the way a tool would read it
+#![cfg(feature = "json")]
+
+mod common;
+
+use serde_json::Value;
+
+fn emit_json(name: &str, output: &str, source: &str) -> Value {
+    let emitted = common::run(name, source, &[&format!("-emit-json={}", output)]);
+    let emitted = emitted.unwrap_or_else(|errors| panic!("zcc failed:\n{}", errors));
+    serde_json::from_str(&emitted).expect("Output should be JSON")
+}
+
+fn position(value: &Value) -> (u64, u64) {
+    (
+        value["line"].as_u64().unwrap(),
+        value["col"].as_u64().unwrap(),
+    )
+}
+
+#[test]
+fn tokens_have_locations() {
+    let tokens = emit_json("locations", "tokens", "#define N 10\nint a[N];\n");
+    let tokens = tokens.as_array().unwrap();
+    assert_eq!(tokens.len(), 6);
+
+    // `N` is located at its use, not at its definition
+    let [value, meta] = tokens[3]["Constant"].as_array().unwrap().as_slice() else {
+        panic!("Expected a constant, got {}", tokens[3]);
+    };
+    assert_eq!(value["Integer"], serde_json::json!([10, "Int"]));
+    assert!(meta["span"]["file"].as_str().unwrap().ends_with(".c"));
+    assert_eq!(position(&meta["span"]["start"]), (2, 7));
+    assert_eq!(position(&meta["span"]["end"]), (2, 8));
+}
+
+#[test]
+fn preprocessing_tokens_keep_spelling() {
+    let tokens = emit_json("spelling", "pp-tokens", "x = 0x1F;\n");
+    let number = &tokens[2]["Number"];
+    assert_eq!(number[0], "0x1F");
+    assert_eq!(position(&number[1]["span"]["start"]), (1, 5));
+}
+
+#[test]
+fn ast_has_locations() {
+    let unit = emit_json(
+        "ast_locations",
+        "ast",
+        "int main(void) {\n    return 1 + 2;\n}\n",
+    );
+    let function = &unit["declarations"][0]["Function"];
+    let items = function["body"]["items"].as_array().unwrap();
+    let statement = &items[0]["Statement"];
+    assert_eq!(position(&statement["span"]["start"]), (2, 5));
+    assert_eq!(position(&statement["span"]["end"]), (2, 18));
+
+    let sum = &statement["kind"]["Return"];
+    assert_eq!(sum["kind"]["Binary"]["operator"], "Plus");
+    assert_eq!(
+        sum["kind"]["Binary"]["rhs"]["kind"]["Constant"]["Integer"],
+        serde_json::json!([2, "Int"])
+    );
+}
+
+#[test]
+fn ast_has_types_and_conversions() {
+    let source = "unsigned long f(const char *s) {\n    return s[1] + 2.5f;\n}\n";
+    let unit = emit_json("ast_types", "ast", source);
+    let function = &unit["declarations"][0]["Function"];
+    assert_eq!(function["specifiers"]["ty"], "unsigned long");
+
+    // The float sum is converted to the return type, and its operands
+    // to `float`
+    let items = function["body"]["items"].as_array().unwrap();
+    let value = &items[0]["Statement"]["kind"]["Return"];
+    assert_eq!(value["ty"], "unsigned long");
+    assert_eq!(value["category"], "Value");
+    let cast = &value["kind"]["ImplicitCast"];
+    assert_eq!(cast["conversion"], "FloatingToInteger");
+    let sum = &cast["operand"];
+    assert_eq!(sum["ty"], "float");
+    let lhs = &sum["kind"]["Binary"]["lhs"];
+    assert_eq!(
+        lhs["kind"]["ImplicitCast"]["conversion"],
+        "IntegerToFloating"
+    );
+    let element = &lhs["kind"]["ImplicitCast"]["operand"]["kind"]["ImplicitCast"]["operand"];
+    assert_eq!(element["ty"], "const char");
+    assert_eq!(element["category"], "Lvalue");
+    let rhs = &sum["kind"]["Binary"]["rhs"];
+    assert_eq!(
+        rhs["kind"]["Constant"]["Floating"],
+        serde_json::json!([2.5, "Float"])
+    );
+}
+
+#[test]
+fn unsigned_constants_keep_their_value() {
+    let source = "unsigned long long x = 18446744073709551615ULL;\nlong y = -1L;\n";
+    let tokens = emit_json("unsigned_tokens", "tokens", source);
+    let constants: Vec<_> = tokens
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|token| token["Constant"][0].get("Integer"))
+        .collect();
+    assert_eq!(
+        constants,
+        [
+            &serde_json::json!([18446744073709551615u64, "UnsignedLongLong"]),
+            &serde_json::json!([1, "Long"]),
+        ]
+    );
+
+    let unit = emit_json("unsigned_ast", "ast", source);
+    let declarator = &unit["declarations"][0]["Declaration"]["declarators"][0];
+    assert_eq!(
+        declarator["initializer"]["Expression"]["kind"]["Constant"]["Integer"],
+        serde_json::json!([18446744073709551615u64, "UnsignedLongLong"])
+    );
+}
@@ -0,0 +1,134 @@
+// What the preprocessor gives for macros, includes and conditions
+mod common;
+
+use std::path::Path;
+
+use common::{run, TempFile};
+
+// The preprocessed source, as `-E` prints it
+fn preprocess(name: &str, source: &str) -> Result<String, String> {
+    run(name, source, &["-std=c23", "-E"])
+}
+
+#[test]
+fn va_opt() {
+    let source = "#define F(a, ...) f(a __VA_OPT__(,) __VA_ARGS__)\n\
+                  #define G(x, ...) x ## __VA_OPT__(suffix)\n\
+                  #define H(...) [__VA_OPT__(1 __VA_ARGS__ 2)]\n\
+                  #define EMPTY\n\
+                  F(1)\n\
+                  F(1, 2, 3)\n\
+                  F(1, EMPTY)\n\
+                  G(a)\n\
+                  G(a, 1)\n\
+                  H()\n\
+                  H(x)\n";
+    let output = preprocess("va_opt", source).unwrap();
+    let lines: Vec<&str> = output.lines().filter(|line| !line.is_empty()).collect();
+    assert_eq!(
+        lines,
+        [
+            "f(1)",
+            "f(1, 2, 3)",
+            "f(1)",
+            "a",
+            "asuffix",
+            "[]",
+            "[1 x 2]"
+        ]
+    );
+
+    let errors = preprocess("va_opt_fixed", "#define B(a) __VA_OPT__(a)\n").unwrap_err();
+    assert!(errors.contains("`__VA_OPT__` can only be used in a variadic macro"));
+    let errors = preprocess("va_opt_open", "#define B(...) __VA_OPT__(a\n").unwrap_err();
+    assert!(errors.contains("`__VA_OPT__` must be followed by tokens in parentheses"));
+    let source = "#define B(...) __VA_OPT__(__VA_OPT__(a))\n";
+    let errors = preprocess("va_opt_nested", source).unwrap_err();
+    assert!(errors.contains("`__VA_OPT__` can't be nested"));
+}
+
+#[test]
+fn has_include() {
+    let header = TempFile::new("has_include.h", "int x;\n");
+    let name = Path::new(header.path())
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let source = format!(
+        "#if __has_include(<stddef.h>) && __has_include(\"{}\")\n\
+         found\n\
+         #endif\n\
+         #if __has_include(<no_such_header.h>) || __has_include(\"no_such_header.h\")\n\
+         missing\n\
+         #endif\n",
+        name
+    );
+    let output = preprocess("has_include", &source).unwrap();
+    assert!(output.contains("found"));
+    assert!(!output.contains("missing"));
+}
+
+#[test]
+fn has_attribute() {
+    let source = "#if __has_attribute(packed) && __has_attribute(__aligned__)\n\
+                  gnu\n\
+                  #endif\n\
+                  #if __has_attribute(no_such_attribute)\n\
+                  unknown\n\
+                  #endif\n\
+                  #if __has_c_attribute(nodiscard) == 202003 && __has_c_attribute(gnu::packed)\n\
+                  standard\n\
+                  #endif\n";
+    let output = preprocess("has_attribute", source).unwrap();
+    assert!(output.contains("gnu"));
+    assert!(!output.contains("unknown"));
+    assert!(output.contains("standard"));
+
+    let errors = preprocess("has_attribute_operand", "#if __has_attribute(1)\n#endif\n");
+    assert!(errors
+        .unwrap_err()
+        .contains("Expected identifier as the operand of `__has_attribute`"));
+}
+
+#[test]
+fn has_feature() {
+    let source = "#if __has_feature(c_generic_selections) && __has_feature(c_static_assert)\n\
+                  feature\n\
+                  #endif\n\
+                  #if __has_extension(c_alignas)\n\
+                  extension\n\
+                  #endif\n\
+                  #if __has_feature(no_such_feature) || __has_extension(no_such_feature)\n\
+                  unknown\n\
+                  #endif\n";
+    let output = preprocess("has_feature", source).unwrap();
+    assert!(output.contains("feature"));
+    assert!(output.contains("extension"));
+    assert!(!output.contains("unknown"));
+
+    // C99 has them as extensions only
+    let output = run("has_feature_c99", source, &["-std=c99", "-E"]).unwrap();
+    assert!(!output.contains("feature"));
+    assert!(output.contains("extension"));
+}
+
+#[test]
+fn character_constants_keep_their_spelling() {
+    let source = "#define S(x) #x\n\
+                  char c = '\\4', d = '\\x41', e = 'a';\n\
+                  const char *s = S('\\4');\n\
+                  #if '\\4' == 4 && '\\x41' == 'A'\n\
+                  int same;\n\
+                  #endif\n";
+    let output = preprocess("character_spelling", source).unwrap();
+    let lines: Vec<&str> = output.lines().filter(|line| !line.is_empty()).collect();
+    assert_eq!(
+        lines,
+        [
+            "char c = '\\4', d = '\\x41', e = 'a';",
+            "const char *s = \"'\\\\4'\";",
+            "int same;"
+        ]
+    );
+}
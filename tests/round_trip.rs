@@ -0,0 +1,74 @@
+// Printing the AST of a file as C and parsing that again has to give
+// the same tree. The trees are compared through `-ast-dump` with the
+// spans left out, since the printed C is laid out differently.
+use std::path::Path;
+
+mod common;
+
+use common::{run, TempFile};
+
+fn zcc(args: &[&str]) -> String {
+    common::zcc(args).unwrap_or_else(|errors| panic!("zcc {:?} failed:\n{}", args, errors))
+}
+
+// Every node line ends with its span in angle brackets
+fn without_spans(dump: &str) -> String {
+    dump.lines()
+        .map(|line| line.rsplit_once(" <").map_or(line, |(node, _)| node))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// `flags` are passed to every run, such as the standard
+fn assert_round_trips(file: &str, flags: &[&str]) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(file);
+    let path = path.to_str().expect("Path should be UTF-8");
+    let run = |action: &str, path: &str| zcc(&[flags, &[action, path]].concat());
+    let printed = run("-ast-print", path);
+
+    let name = Path::new(file).file_name().unwrap().to_str().unwrap();
+    let printed_file = TempFile::new(name, &printed);
+    let reparsed = run("-ast-dump", printed_file.path());
+
+    let original = run("-ast-dump", path);
+    assert_eq!(without_spans(&original), without_spans(&reparsed));
+}
+
+#[test]
+fn big_test_round_trips() {
+    assert_round_trips("tests/big_test.c", &[]);
+}
+
+#[test]
+fn c99_round_trips() {
+    assert_round_trips("tests/c99.c", &[]);
+}
+
+#[test]
+fn c11_round_trips() {
+    assert_round_trips("tests/c11.c", &[]);
+}
+
+#[test]
+fn c23_round_trips() {
+    assert_round_trips("tests/c23.c", &["-std=c23"]);
+}
+
+#[test]
+fn gnu_round_trips() {
+    assert_round_trips("tests/gnu.c", &["-std=gnu11"]);
+}
+
+#[test]
+fn c89_round_trips() {
+    assert_round_trips("tests/c89.c", &["-std=c89"]);
+}
+
+#[test]
+fn implicit_conversions_are_not_printed() {
+    let source = "long f(char c, int a[]) { return c + 1.0f + a[0]; }\n";
+    let dump = run("conversions_dump", source, &["-ast-dump"]).unwrap();
+    assert!(dump.contains("ImplicitCast"));
+    let printed = run("conversions", source, &["-ast-print"]).unwrap();
+    assert!(printed.contains("return c + 1.0f + a[0];"), "{}", printed);
+}
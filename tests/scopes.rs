@@ -0,0 +1,262 @@
+// Which declarations of a name refer to the same thing
+mod common;
+
+use common::{compile, compile_as};
+
+#[test]
+fn namespaces_are_separate() {
+    let source = "struct s { int s; } s;\n\
+                  void f(void) { s: goto s; }\n\
+                  typedef struct node { struct node *node; } node;\n";
+    assert_eq!(compile("namespaces", source), Ok(()));
+}
+
+#[test]
+fn inner_scopes_hide_outer_ones() {
+    let source = "int x;\n\
+                  void f(int y) {\n\
+                      long x;\n\
+                      { char y; }\n\
+                      for (int x = 0; x < 3; x++) { float x; }\n\
+                  }\n\
+                  void g(int x, int y);\n";
+    assert_eq!(compile("hiding", source), Ok(()));
+}
+
+#[test]
+fn typedef_names_are_hidden_by_objects() {
+    // `(T)` is the object in parentheses, not a cast
+    let source = "typedef int T;\n\
+                  void f(void) { int T = 1; int y = (T) + 1; }\n\
+                  void g(void) { long T = 1; _Static_assert(sizeof((T) + 1) == 8, \"\"); }\n";
+    assert_eq!(compile("typedef_parenthesized", source), Ok(()));
+
+    // The declarator names an object of the type, which then hides it
+    let source = "typedef int T;\nvoid f(void) { T T; T = 2; }\n";
+    assert_eq!(compile("typedef_declarator", source), Ok(()));
+
+    // The typedef is visible again after the block that hid it
+    let source = "typedef int T;\nvoid f(void) { { int T; } T z; z = 1; }\n";
+    assert_eq!(compile("typedef_visible", source), Ok(()));
+
+    // A parameter hides the typedef for the rest of the prototype
+    let source = "typedef int T;\nvoid f(int T, T y);\n";
+    assert!(compile("typedef_parameter", source).is_err());
+}
+
+#[test]
+fn tentative_definitions() {
+    let source = "int x;\nint x;\nint x = 1;\nint x;\nstatic int y;\nstatic int y;\n";
+    assert_eq!(compile("tentative", source), Ok(()));
+
+    let errors = compile("defined_twice", "int x = 1;\nint x = 2;\n").unwrap_err();
+    assert!(errors.contains("`x` is defined more than once"));
+    assert!(errors.contains("note: Previous definition is here"));
+
+    let source = "struct s;\nstruct s x;\n";
+    let errors = compile("tentative_incomplete", source).unwrap_err();
+    assert!(errors.contains("`x` is defined with incomplete type `struct s`"));
+
+    // Only a warning, given at the end of the file. Until then the
+    // array is incomplete.
+    assert_eq!(compile("tentative_array", "int a[];\n"), Ok(()));
+    let source = "int a[];\nvoid f(void) { _Static_assert(sizeof a == 4, \"\"); }\n";
+    assert!(compile("tentative_array_size", source).is_err());
+}
+
+#[test]
+fn linkage_is_resolved() {
+    // `extern` takes the linkage of the declaration it can see
+    let source = "static int x;\nextern int x;\nstatic int f(void);\nint f(void) { return x; }\n";
+    assert_eq!(compile("linkage", source), Ok(()));
+
+    let errors = compile("linkage_static", "int x;\nstatic int x;\n").unwrap_err();
+    assert!(errors.contains("`x` is declared `static` after a declaration that isn't"));
+
+    let errors = compile("linkage_extern", "static int s;\nint s;\n").unwrap_err();
+    assert!(errors.contains("`s` isn't declared `static` after a declaration that is"));
+    let errors = compile("linkage_defined", "static int s;\nint s = 1;\n").unwrap_err();
+    assert!(errors.contains("`s` isn't declared `static` after a declaration that is"));
+
+    // Declarations in blocks refer to the same object as the ones outside
+    let source = "void f(void) { extern long x; }\nint x;\n";
+    let errors = compile("linkage_block", source).unwrap_err();
+    assert!(errors.contains("Conflicting types for `x`, `long` and `int`"));
+}
+
+#[test]
+fn redeclarations_without_linkage() {
+    let errors = compile("block", "void f(void) { int x; int x; }\n").unwrap_err();
+    assert!(errors.contains("`x` is declared more than once"));
+    assert!(errors.contains("note: Previous declaration is here"));
+
+    // The parameters are in the outermost block of the body
+    let errors = compile("parameter", "void f(int p) { int p; }\n").unwrap_err();
+    assert!(errors.contains("`p` is declared more than once"));
+
+    let errors = compile("prototype", "void f(int p, int p);\n").unwrap_err();
+    assert!(errors.contains("`p` is declared more than once"));
+
+    let errors = compile("kind", "typedef int t;\nint t;\n").unwrap_err();
+    assert!(errors.contains("`t` is declared again as something different"));
+
+    // C11 allows a typedef to be declared again with the same type
+    let source = "typedef int t;\ntypedef int t;\n";
+    assert_eq!(compile("typedef_again", source), Ok(()));
+
+    let errors = compile("member", "struct s { int a; struct { int a; }; };\n").unwrap_err();
+    assert!(errors.contains("Member `a` is declared more than once"));
+}
+
+#[test]
+fn labels_are_checked() {
+    let source = "void f(void) { goto end; end: ; }\n";
+    assert_eq!(compile("labels", source), Ok(()));
+
+    let source = "void f(void) { a: ; a: ; goto b; }\n";
+    let errors = compile("labels_bad", source).unwrap_err();
+    assert!(errors.contains("Label `a` is defined more than once"));
+    assert!(errors.contains("Label `b` is used but never defined"));
+}
+
+#[test]
+fn jumps_and_cases_are_checked() {
+    let source = "void f(int x) {\n\
+                  \x20   while (x) { if (x) break; continue; }\n\
+                  \x20   switch (x) {\n\
+                  \x20   case 1: for (;;) { case 2: break; }\n\
+                  \x20   case 3 ... 5: switch (x) { case 1: default: ; }\n\
+                  \x20   default: break;\n\
+                  \x20   }\n\
+                  }\n";
+    assert_eq!(compile_as("gnu17", "jumps", source), Ok(()));
+
+    let source = "void f(void) { break; continue; case 1: ; default: ; }\n";
+    let errors = compile("jumps_outside", source).unwrap_err();
+    assert!(errors.contains("`break` can only be used in a loop or `switch`"));
+    assert!(errors.contains("`continue` can only be used in a loop"));
+    assert!(errors.contains("`case` can only be used in a `switch`"));
+    assert!(errors.contains("`default` can only be used in a `switch`"));
+
+    let source = "void f(int x) { switch (x) { default: continue; } }\n";
+    let errors = compile("continue_in_switch", source).unwrap_err();
+    assert!(errors.contains("`continue` can only be used in a loop"));
+
+    let source = "void f(int x, unsigned u) {\n\
+                  \x20   switch (x) { case 1: case 2: case 1: default: default: ; }\n\
+                  \x20   switch (x) { case x: case 1.5: ; }\n\
+                  \x20   switch (u) { case -1: case 0xFFFFFFFFu: ; }\n\
+                  \x20   switch (x) { case 1 ... 4: case 3: ; }\n\
+                  }\n";
+    let errors = compile_as("gnu17", "cases", source).unwrap_err();
+    assert!(errors.contains("Case value 1 is already used in the `switch`"));
+    assert!(errors.contains("A `switch` can only have one `default`"));
+    assert_eq!(
+        errors
+            .matches("A `case` value has to be an integer constant expression")
+            .count(),
+        2
+    );
+    assert!(errors.contains("Case value 4294967295 is already used in the `switch`"));
+    assert!(errors.contains("Case value 3 is already used in the `switch`"));
+}
+
+#[test]
+fn variably_modified_types_are_block_scoped() {
+    let source = "void f(int n, int a[n]) {\n\
+                  \x20   int v[n];\n\
+                  \x20   int (*p)[n] = &v;\n\
+                  \x20   static int (*q)[n];\n\
+                  \x20   typedef int row[n];\n\
+                  }\n";
+    assert_eq!(compile("vla", source), Ok(()));
+
+    let errors = compile("vla_file", "int n;\nint a[n];\n").unwrap_err();
+    assert!(errors.contains("`a` has variably modified type `array of int` at file scope"));
+    assert!(!errors.contains("incomplete type"), "{}", errors);
+
+    let errors = compile("vla_pointer", "int n;\nint (*p)[n];\n").unwrap_err();
+    assert!(
+        errors.contains("`p` has variably modified type `pointer to array of int` at file scope")
+    );
+
+    let errors = compile("vla_static", "void f(int n) { static int a[n]; }\n").unwrap_err();
+    assert!(
+        errors.contains("`a` has variably modified type `array of int`, so it can't be `static`")
+    );
+
+    let errors = compile("vla_extern", "void f(int n) { extern int a[n]; }\n").unwrap_err();
+    assert!(errors.contains("so it can't be `extern`"));
+
+    let errors = compile("vla_member", "void f(int n) { struct { int a[n]; } s; }\n").unwrap_err();
+    assert!(errors.contains("Member `a` of struct has variably modified type `array of int`"));
+}
+
+#[test]
+fn objects_have_a_size() {
+    let errors = compile("too_large", "int big[1L << 62];\n").unwrap_err();
+    assert!(errors.contains(
+        "`big` has type `array of 4611686018427387904 int`, which is too large for an object"
+    ));
+    assert!(!errors.contains("incomplete type"), "{}", errors);
+
+    let source = "struct s { char c[1L << 62]; char d[1L << 62]; } x;\n";
+    let errors = compile("too_large_struct", source).unwrap_err();
+    assert!(errors.contains("`struct s` is too large for an object"));
+    assert_eq!(errors.matches("Compilation Error").count(), 1, "{}", errors);
+}
+
+#[test]
+fn file_scope_storage_classes() {
+    let errors = compile("auto", "auto int x;\n").unwrap_err();
+    assert!(errors.contains("`auto` can't be used at file scope"));
+
+    let errors = compile(
+        "register",
+        "register int y;\nregister int f(void) { return 0; }\n",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors
+            .matches("`register` can't be used at file scope")
+            .count(),
+        2
+    );
+
+    let source = "void f(register int x) { register int y = x; auto int z = y; }\n";
+    assert_eq!(compile("block_storage", source), Ok(()));
+}
+
+#[test]
+fn static_storage_is_initialized_with_constants() {
+    let source = "int y, a[3], f(void);\n\
+                  struct s { int m[2]; } v;\n\
+                  int *p = &y, *q = a + 1, *r = &a[2], *m = &v.m[1];\n\
+                  int (*fp)(void) = f;\n\
+                  const char *c = \"abc\" + 1;\n\
+                  void g(void) {\n\
+                  \x20   static int l;\n\
+                  \x20   static int *lp = &l;\n\
+                  \x20   static const char *name = __func__;\n\
+                  \x20   int automatic = y;\n\
+                  }\n";
+    assert_eq!(compile("static_initializers", source), Ok(()));
+
+    let errors = compile("static_object", "int y;\nint x = y;\n").unwrap_err();
+    assert!(errors.contains(
+        "The initializer of `x` isn't a constant, which an object with static storage needs"
+    ));
+
+    let errors = compile("static_call", "int f(void);\nint x = f();\n").unwrap_err();
+    assert!(errors.contains("The initializer of `x` isn't a constant"));
+
+    let errors = compile(
+        "static_automatic",
+        "void g(void) { int a; static int *p = &a; }\n",
+    )
+    .unwrap_err();
+    assert!(errors.contains("The initializer of `p` isn't a constant"));
+
+    let errors = compile("extern_block", "void g(void) { extern int e = 1; }\n").unwrap_err();
+    assert!(errors.contains("`e` is declared `extern` in a block, so it can't be initialized"));
+}
@@ -0,0 +1,258 @@
+// What `-std` turns on and off
+mod common;
+
+use common::{compile_as, warnings};
+
+#[test]
+fn line_comments_need_c99() {
+    let source = "int x; // comment\n";
+    assert!(compile_as("c99", "line_comment", source).is_ok());
+    assert!(compile_as("c89", "line_comment", source).is_err());
+
+    let source = "double d = 0x1.8p1;\n";
+    assert!(compile_as("c99", "hex_float", source).is_ok());
+    let errors = compile_as("c89", "hex_float", source).unwrap_err();
+    assert!(errors.contains("A hexadecimal floating constant requires C99 or later"));
+}
+
+#[test]
+fn c99_keywords_are_identifiers_in_c89() {
+    let source = "int inline, restrict;\n";
+    assert!(compile_as("c89", "keywords", source).is_ok());
+    assert!(compile_as("c99", "keywords", source).is_err());
+}
+
+#[test]
+fn c99_declarations_are_rejected_in_c89() {
+    let source = "void f(void) {\n    f();\n    int x;\n    for (int i = 0;;) {}\n}\n";
+    assert!(compile_as("c99", "declarations", source).is_ok());
+    let errors = compile_as("c89", "declarations", source).unwrap_err();
+    assert!(errors.contains("A declaration after a statement requires C99 or later"));
+    assert!(errors.contains("A declaration in a `for` loop requires C99 or later"));
+}
+
+#[test]
+fn c11_keywords_are_identifiers_before_c11() {
+    let source = "int _Alignas, _Generic, _Noreturn;\n";
+    assert!(compile_as("c99", "c11_keywords", source).is_ok());
+    assert!(compile_as("c11", "c11_keywords", source).is_err());
+}
+
+#[test]
+fn static_assertions_are_checked() {
+    let source = "_Static_assert(sizeof(long) == 8, \"LP64\");\n";
+    assert!(compile_as("c11", "static_assert", source).is_ok());
+    let source = "_Static_assert(_Alignof(int) == 2, \"int is 2 aligned\");\n";
+    let errors = compile_as("c11", "static_assert", source).unwrap_err();
+    assert!(errors.contains("\"int is 2 aligned\""));
+}
+
+#[test]
+fn generic_selects_by_type() {
+    let source = "_Static_assert(_Generic((short)0, short: 1, int: 2), \"short\");\n";
+    assert!(compile_as("c11", "generic", source).is_ok());
+    let source = "int x = _Generic((char)0, int: 1);\n";
+    let errors = compile_as("c11", "generic", source).unwrap_err();
+    assert!(errors.contains("No association matches `char` and there is no `default`"));
+}
+
+#[test]
+fn c23_keywords_are_identifiers_before_c23() {
+    let source = "int bool, true, nullptr, constexpr, typeof;\n";
+    assert!(compile_as("c17", "c23_keywords", source).is_ok());
+    assert!(compile_as("c23", "c23_keywords", source).is_err());
+}
+
+#[test]
+fn c23_constants() {
+    let source = "static_assert(0b1010 == 10 && 1'000'000 == 1000000 && 0x1'0 == 16);\n";
+    assert!(compile_as("c23", "c23_constants", source).is_ok());
+    assert!(compile_as("c17", "c23_constants", source).is_err());
+    assert!(compile_as("c23", "c23_constants", "int x = 1'';\n").is_err());
+}
+
+#[test]
+fn c23_declarations() {
+    let source = "constexpr int size = 4;\nint array[size];\nauto x = 1.0;\n\
+        typeof(int *) p;\n[[nodiscard(\"why\")]] int f(void);\n";
+    assert!(compile_as("c23", "c23_declarations", source).is_ok());
+    let source = "constexpr int x;\n";
+    let errors = compile_as("c23", "c23_declarations", source).unwrap_err();
+    assert!(errors.contains("`constexpr` object `x` needs an initializer"));
+    let source = "void f(int x) {\n    [[fallthrough]] x++;\n}\n";
+    let errors = compile_as("c23", "c23_declarations", source).unwrap_err();
+    assert!(errors.contains("`fallthrough` can only be used on a null statement"));
+}
+
+#[test]
+fn constexpr_initializers_are_constants() {
+    let source = "enum { E = 4 };\n\
+        constexpr unsigned char c = 255;\n\
+        constexpr int i = 2.0;\n\
+        constexpr double d = E * 2.0;\n\
+        constexpr int a[] = { 1, E, c };\n";
+    assert!(compile_as("c23", "constexpr", source).is_ok());
+    let source = "int i;\nconstexpr int m = i;\n";
+    let errors = compile_as("c23", "constexpr", source).unwrap_err();
+    assert!(errors.contains(
+        "The initializer of `constexpr` object `m` isn't an integer constant expression"
+    ));
+    let source = "int i;\nconstexpr double d = i;\n";
+    let errors = compile_as("c23", "constexpr", source).unwrap_err();
+    assert!(
+        errors.contains("The initializer of `constexpr` object `d` isn't a constant expression")
+    );
+    let source = "constexpr unsigned char c = 300;\n";
+    let errors = compile_as("c23", "constexpr", source).unwrap_err();
+    assert!(
+        errors.contains("The initializer of `constexpr` object `c` doesn't fit in `unsigned char`")
+    );
+    let errors = compile_as("c23", "constexpr", "constexpr unsigned u = -1;\n").unwrap_err();
+    assert!(errors.contains("doesn't fit in `unsigned int`"));
+    let errors = compile_as("c23", "constexpr", "constexpr int f = 1.5;\n").unwrap_err();
+    assert!(errors.contains("doesn't fit in `int`"));
+}
+
+#[test]
+fn c23_directives() {
+    let data = std::env::temp_dir().join(format!("zcc_std_{}_embed.bin", std::process::id()));
+    std::fs::write(&data, [1, 2, 3]).expect("Failed to write data");
+    let source = format!(
+        "#ifdef X\n#error\n#elifndef X\n\
+        #if __has_embed(\"{0}\") != 1\n#error\n#endif\n\
+        int data[] = {{\n#embed \"{0}\" limit(2) suffix(, 9)\n}};\n\
+        #endif\n",
+        data.display()
+    );
+    let result = compile_as("c23", "c23_directives", &source);
+    std::fs::remove_file(&data).ok();
+    result.unwrap();
+    let source = "#ifndef X\n#elifdef Y\n#endif\n";
+    assert!(compile_as("c17", "c23_directives", source).is_err());
+}
+
+#[test]
+fn version_macro_follows_standard() {
+    let source = "#if __STDC_VERSION__ != 199901L\n#error\n#endif\n";
+    assert!(compile_as("c99", "version", source).is_ok());
+    let source = "#if __STDC_VERSION__ != 201112L\n#error\n#endif\n";
+    assert!(compile_as("c11", "version", source).is_ok());
+    let source = "#if __STDC_VERSION__ != 202311L\n#error\n#endif\n";
+    assert!(compile_as("c23", "version", source).is_ok());
+    let source = "#ifdef __STDC_VERSION__\n#error\n#endif\n";
+    assert!(compile_as("c89", "version", source).is_ok());
+}
+
+#[test]
+fn unknown_standard_is_rejected() {
+    let errors = compile_as("c77", "unknown", "").unwrap_err();
+    assert!(errors.contains("Unknown language standard `c77`"));
+}
+
+#[test]
+fn gnu_extensions_need_gnu_standard() {
+    let source = "int f(int x) {\n    int y = ({ x; }) ?: 1;\n    switch (y) {\n    \
+        case 1 ... 3:\n        return 0;\n    }\n    int empty[0];\n    return y;\n}\n";
+    assert!(compile_as("gnu11", "gnu_extensions", source).is_ok());
+    let errors = compile_as("c11", "gnu_extensions", source).unwrap_err();
+    assert!(errors.contains("A statement expression is a GNU extension, which needs `-std=gnu11`"));
+    assert!(errors.contains("`?:` without a middle operand is a GNU extension"));
+    assert!(errors.contains("A case range is a GNU extension"));
+    assert!(errors.contains("A zero-length array is a GNU extension"));
+    let errors = compile_as("gnu11", "gnu_extensions", "int x = ({ 1; });\n").unwrap_err();
+    assert!(errors.contains("A statement expression can only be used inside of a function"));
+}
+
+#[test]
+fn gnu_spellings_work_in_every_mode() {
+    let source = "#include <stdarg.h>\n__extension__ typedef __typeof__(int) word;\n\
+        static __inline__ int first(int n, ...) {\n    va_list args;\n    \
+        va_start(args, n);\n    int x = va_arg(args, int);\n    va_end(args);\n    return x;\n}\n\
+        #ifndef __STRICT_ANSI__\n#error\n#endif\n";
+    assert!(compile_as("c99", "gnu_spellings", source).is_ok());
+    let source = "#ifdef __STRICT_ANSI__\n#error\n#endif\n";
+    assert!(compile_as("gnu99", "gnu_spellings", source).is_ok());
+    assert!(compile_as("gnu99", "gnu_spellings", "int typeof;\n").is_err());
+}
+
+#[test]
+fn gnu_attributes_are_checked() {
+    let source = "struct __attribute__((packed, aligned(16))) s { int x; };\n\
+        void log_error(int level, const char *format, ...) __attribute__((format(printf, 2, 3)));\n\
+        int x __attribute__((section(\".data\"), deprecated(\"unknown attributes are ignored\")));\n";
+    assert!(compile_as("c17", "gnu_attributes", source).is_ok());
+    let ignored = warnings("gnu_attributes", source);
+    assert!(ignored.contains("Attribute `deprecated` is unknown, so it's ignored"));
+    assert!(!ignored.contains("`packed`"), "{}", ignored);
+    let source = "void f(void) __attribute__((hot, __cold__));\n";
+    let ignored = warnings("gnu_attributes", source);
+    assert!(ignored.contains("Attribute `hot` is unknown"));
+    assert!(ignored.contains("Attribute `__cold__` is unknown"));
+    assert_eq!(ignored.matches("is unknown").count(), 2, "{}", ignored);
+    let errors = compile_as(
+        "c17",
+        "gnu_attributes",
+        "int x __attribute__((aligned(3)));\n",
+    )
+    .unwrap_err();
+    assert!(errors.contains("Alignment 3 is not a power of two"));
+    let source = "void f(const char *s, ...) __attribute__((format(printf, 2, 1)));\n";
+    let errors = compile_as("c17", "gnu_attributes", source).unwrap_err();
+    assert!(errors.contains("The arguments `format` checks have to come after the format string"));
+    let source = "void f(void) __attribute__((noreturn(1), section(x)));\n";
+    let errors = compile_as("c17", "gnu_attributes", source).unwrap_err();
+    assert!(errors.contains("`noreturn` takes no arguments"));
+    assert!(errors.contains("`section` takes a string literal as its argument"));
+}
+
+#[test]
+fn gnu_attributes_change_the_layout() {
+    let source = "struct __attribute__((packed)) p { char c; int i; };\n\
+        _Static_assert(sizeof(struct p) == 5 && _Alignof(struct p) == 1, \"packed\");\n\
+        struct q { char c; int i; } __attribute__((packed));\n\
+        _Static_assert(sizeof(struct q) == 5, \"packed after the body\");\n\
+        struct r { char c; int i __attribute__((packed)); };\n\
+        _Static_assert(sizeof(struct r) == 5, \"packed member\");\n\
+        struct m { char c; int i __attribute__((aligned(16))); };\n\
+        _Static_assert(sizeof(struct m) == 32 && _Alignof(struct m) == 16, \"aligned member\");\n\
+        struct a { char c; } __attribute__((aligned));\n\
+        _Static_assert(_Alignof(struct a) == 16, \"biggest alignment\");\n\
+        typedef int t __attribute__((aligned(8)));\n\
+        _Static_assert(_Alignof(t) == 8 && sizeof(t) == 4, \"aligned typedef\");\n";
+    assert!(compile_as("gnu17", "gnu_layout", source).is_ok());
+    let source = "struct [[gnu::packed]] s { char c; long l; };\n\
+        static_assert(sizeof(struct s) == 9);\n\
+        struct u { char c; [[gnu::aligned(8)]] char d; };\n\
+        static_assert(sizeof(struct u) == 16);\n";
+    assert!(compile_as("c23", "gnu_layout", source).is_ok());
+    let output = warnings("gnu_layout", "int x __attribute__((packed));\n");
+    assert!(output.contains("`packed` only applies to structs, unions and their members"));
+}
+
+#[test]
+fn implicit_int_is_a_warning_until_c99() {
+    let source = "static count;\nmain() {\n    register i = count;\n    return i;\n}\n";
+    assert!(compile_as("c89", "implicit_int", source).is_ok());
+    assert!(compile_as("gnu11", "implicit_int", source).is_ok());
+    let errors = compile_as("c99", "implicit_int", source).unwrap_err();
+    assert!(errors.contains("The declaration has no type specifier, which C99 doesn't allow"));
+    assert!(!errors.contains("Compilation Warning"));
+}
+
+#[test]
+fn old_style_definitions() {
+    let source = "int add(a, b)\n    int a;\n    long b;\n{\n    return a + b;\n}\n";
+    assert!(compile_as("c17", "old_style", source).is_ok());
+    let errors = compile_as("c23", "old_style", source).unwrap_err();
+    assert!(errors.contains("Parameters without types were removed in C23"));
+    let errors = compile_as("c17", "old_style", "int f(a, b);\n").unwrap_err();
+    assert!(errors.contains("Parameters without types are only allowed in a function definition"));
+    let source = "int f(a)\n    int a, c;\n    char a;\n{\n    return a;\n}\n";
+    let errors = compile_as("c17", "old_style", source).unwrap_err();
+    assert!(errors.contains("`c` is not a parameter"));
+    assert_eq!(errors.matches("is declared more than once").count(), 1);
+    assert!(errors.contains("`a` is declared more than once"));
+    let source = "int m(a) int a; int a; {}\n";
+    let errors = compile_as("c17", "old_style", source).unwrap_err();
+    assert_eq!(errors.matches("is declared more than once").count(), 1);
+}
@@ -0,0 +1,333 @@
+// What the types of declarations are checked against
+mod common;
+
+use common::{compile, compile_as, run};
+
+#[test]
+fn records_are_laid_out() {
+    let source = "struct node { int value; struct node *next; };\n\
+                  _Static_assert(sizeof(struct node) == 16, \"padded\");\n\
+                  struct bits { unsigned a : 3, b : 30; char c; };\n\
+                  _Static_assert(sizeof(struct bits) == 12, \"bit-fields\");\n\
+                  union u { char c[5]; int i; };\n\
+                  _Static_assert(sizeof(union u) == 8, \"union\");\n\
+                  _Static_assert(_Alignof(union u) == 4, \"union\");\n\
+                  typedef struct { char c; double d; } pair;\n\
+                  _Static_assert(sizeof(const pair[2]) == 32, \"typedef\");\n";
+    assert_eq!(compile("layout", source), Ok(()));
+}
+
+#[test]
+fn type_specifiers_combine() {
+    let source = "long int unsigned long a;\n\
+                  _Static_assert(_Generic(a, unsigned long long: 1), \"any order\");\n\
+                  signed char b;\n\
+                  _Static_assert(_Generic(b, signed char: 1), \"not char\");\n";
+    assert_eq!(compile("specifiers", source), Ok(()));
+
+    let invalid = [
+        ("short long a;\n", "`short long`"),
+        ("unsigned signed b;\n", "`unsigned signed`"),
+        ("long long long c;\n", "`long long long`"),
+    ];
+    for (i, (source, specifiers)) in invalid.into_iter().enumerate() {
+        let errors = compile(&format!("invalid_specifiers_{}", i), source).unwrap_err();
+        let message = format!("Invalid combination of type specifiers {}", specifiers);
+        assert!(errors.contains(&message), "{}", errors);
+    }
+}
+
+#[test]
+fn declarators_nest_from_the_inside_out() {
+    let source = "int (*(*fp)(int))[10];\n\
+                  _Static_assert(_Generic(fp, int (*(*)(int))[10]: 1), \"fp\");\n\
+                  _Static_assert(_Generic((*fp)(1), int (*)[10]: 1), \"call\");\n\
+                  _Static_assert(sizeof *(*fp)(1) == 40, \"array\");\n\
+                  void (*signal(int, void (*)(int)))(int);\n\
+                  _Static_assert(_Generic(signal(0, 0), void (*)(int): 1), \"handler\");\n";
+    assert_eq!(compile("nested_declarators", source), Ok(()));
+
+    let dump = run("nested_dump", source, &["-ast-dump"]).unwrap();
+    assert!(dump.contains(
+        "InitDeclarator 'pointer to function (int) returning pointer to array of 10 int'"
+    ));
+    assert!(dump.contains(
+        "InitDeclarator 'function (int, pointer to function (int) returning void) \
+         returning pointer to function (int) returning void'"
+    ));
+}
+
+#[test]
+fn alignment_specifiers_change_the_layout() {
+    let source = "struct g { char a; _Alignas(8) char b; };\n\
+                  _Static_assert(sizeof(struct g) == 16, \"padded to 8\");\n\
+                  _Static_assert(_Alignof(struct g) == 8, \"aligned like b\");\n\
+                  struct h { _Alignas(16) char a[3]; _Alignas(0) int b; _Alignas(long) short c; };\n\
+                  _Static_assert(sizeof(struct h) == 16, \"c at 8\");\n\
+                  _Static_assert(_Alignof(struct h) == 16, \"aligned like a\");\n\
+                  union u { char c; _Alignas(32) char d; };\n\
+                  _Static_assert(sizeof(union u) == 32, \"union\");\n";
+    assert_eq!(compile("alignas", source), Ok(()));
+}
+
+#[test]
+fn alignment_specifiers_are_checked() {
+    let errors = compile("alignas_weaker", "_Alignas(2) int x;\n").unwrap_err();
+    assert!(errors.contains("`_Alignas` asks for an alignment of 2, less than the 4 of `int`"));
+
+    let errors = compile("alignas_member", "struct s { _Alignas(char) long l; };\n").unwrap_err();
+    assert!(errors.contains("less than the 8 of `long`"));
+
+    let errors = compile("alignas_parameter", "void f(_Alignas(8) int y);\n").unwrap_err();
+    assert!(errors.contains("`_Alignas` can't be used on a parameter"));
+
+    let errors = compile(
+        "alignas_bit_field",
+        "struct s { _Alignas(8) int w : 3; };\n",
+    )
+    .unwrap_err();
+    assert!(errors.contains("`_Alignas` can't be used on a bit-field"));
+
+    let errors = compile("alignas_function", "_Alignas(8) void g(void);\n").unwrap_err();
+    assert!(errors.contains("`_Alignas` can't be used on a function"));
+}
+
+#[test]
+fn atomic_type_specifiers() {
+    let source = "_Atomic(int) a;\n\
+                  _Atomic int b;\n\
+                  _Atomic(long) *p;\n\
+                  _Static_assert(_Generic(&a, _Atomic int *: 1), \"same as the qualifier\");\n\
+                  _Static_assert(_Generic(&b, _Atomic(int) *: 1), \"both ways\");\n";
+    assert_eq!(compile("atomic", source), Ok(()));
+
+    let errors = compile("atomic_array", "_Atomic(int[2]) a;\n").unwrap_err();
+    assert!(errors.contains("`_Atomic(...)` can't be applied to an array type, `array of 2 int`"));
+
+    let errors = compile("atomic_qualified", "_Atomic(const int) c;\n").unwrap_err();
+    assert!(errors.contains("can't be applied to a qualified type, `const int`"));
+}
+
+#[test]
+fn compatible_redeclarations_are_merged() {
+    let source = "int f();\n\
+                  int f(int, long);\n\
+                  int f(int a, long b);\n\
+                  extern int a[];\n\
+                  extern int a[4];\n\
+                  _Static_assert(sizeof a == 16, \"composite\");\n\
+                  enum e { X, Y };\n\
+                  enum e g(void);\n\
+                  int g(void);\n\
+                  typedef int T;\n\
+                  typedef int T;\n";
+    assert_eq!(compile("compatible", source), Ok(()));
+}
+
+#[test]
+fn conflicting_redeclarations() {
+    let errors = compile("conflict_object", "int x;\nlong x;\n").unwrap_err();
+    assert!(errors.contains("Conflicting types for `x`, `int` and `long`"));
+
+    let errors = compile("conflict_const", "int *p;\nconst int *p;\n").unwrap_err();
+    assert!(errors.contains("Conflicting types for `p`"));
+
+    // `float` is promoted to `double` without a prototype
+    let errors = compile("conflict_promoted", "void h(float);\nvoid h();\n").unwrap_err();
+    assert!(errors.contains(
+        "Conflicting types for `h`, `function (float) returning void` and \
+         `function () returning void`"
+    ));
+
+    let errors = compile("conflict_variadic", "int f(int);\nint f(int, ...);\n").unwrap_err();
+    assert!(errors.contains("Conflicting types for `f`"));
+
+    let errors = compile("conflict_array", "extern int a[3];\nextern int a[4];\n").unwrap_err();
+    assert!(errors.contains("`array of 3 int` and `array of 4 int`"));
+}
+
+#[test]
+fn tags_are_checked() {
+    let source = "struct s { int a; };\nstruct s { int b; };\n";
+    let errors = compile("tag_redefined", source).unwrap_err();
+    assert!(errors.contains("`struct s` is defined more than once"));
+
+    let source = "struct s;\nunion s *p;\n";
+    let errors = compile("tag_kind", source).unwrap_err();
+    assert!(errors.contains("`s` was declared as a `struct`, not a `union`"));
+
+    let source = "struct t { struct t inner; };\n";
+    let errors = compile("tag_incomplete", source).unwrap_err();
+    assert!(errors.contains("Member `inner` of struct has incomplete type `struct t`"));
+
+    // A tag in a block is a different type from the one outside
+    let source = "struct s { int a; };\n\
+                  void f(void) { struct s { char c; } x; _Static_assert(sizeof x == 1, \"\"); }\n";
+    assert_eq!(compile("tag_scope", source), Ok(()));
+}
+
+#[test]
+fn bit_fields_are_checked() {
+    let errors = compile("bit_field_type", "struct w { float f : 3; };\n").unwrap_err();
+    assert!(errors.contains("Bit-field `f` has type `float`, which is not an integer type"));
+
+    let errors = compile("bit_field_wide", "struct w { int z : 40; };\n").unwrap_err();
+    assert!(errors.contains("Bit-field `z` is 40 bits wide, which is wider than its type `int`"));
+
+    let errors = compile("bit_field_zero", "struct w { int z : 0; };\n").unwrap_err();
+    assert!(errors.contains("Bit-field `z` has width 0"));
+}
+
+#[test]
+fn flexible_array_members_come_last() {
+    let source = "struct f { int n; int a[]; };\n\
+                  _Static_assert(sizeof(struct f) == 4, \"no size of its own\");\n\
+                  struct g { int x; struct f f; };\n\
+                  union u { struct f f; int i; };\n";
+    assert_eq!(compile_as("gnu17", "flexible", source), Ok(()));
+
+    let errors = compile("flexible_array", &format!("{}struct f a[2];\n", source)).unwrap_err();
+    assert!(errors.contains(
+        "An array can't have elements of type `struct f`, which has a flexible array member"
+    ));
+
+    let errors = compile(
+        "flexible_union_array",
+        &format!("{}union u a[2];\n", source),
+    )
+    .unwrap_err();
+    assert!(errors.contains("elements of type `union u`, which has a flexible array member"));
+
+    let errors = compile(
+        "flexible_member",
+        &format!("{}struct h {{ struct f f; int y; }};\n", source),
+    )
+    .unwrap_err();
+    assert!(errors.contains(
+        "Member `f` of struct comes before other members, but has a flexible array member in `struct f`"
+    ));
+}
+
+#[test]
+fn enumerators_are_checked() {
+    // Each bad enumerator is reported once, and still has a value
+    let source = "int x;\n\
+                  enum e { A = x, B, C = 2147483648, D };\n\
+                  _Static_assert(B == 1, \"after A\");\n\
+                  int f(void) { return A + B + D; }\n";
+    let errors = compile("enumerators", source).unwrap_err();
+    assert!(
+        errors.contains("Value of enumerator `A` could not be evaluated as an integer constant")
+    );
+    assert!(errors.contains("Value of enumerator `C` is 2147483648, which doesn't fit in `int`"));
+    assert_eq!(errors.matches("Compilation Error").count(), 2, "{}", errors);
+}
+
+#[test]
+fn offsetof_is_an_integer_constant() {
+    let source = "#include <stddef.h>\n\
+                  struct s { char c; int i; struct { int a[4]; long l; } in; union { short u; double d; }; };\n\
+                  _Static_assert(offsetof(struct s, i) == 4, \"after c\");\n\
+                  _Static_assert(offsetof(struct s, in.a[2]) == 16, \"nested\");\n\
+                  _Static_assert(offsetof(struct s, d) == 32, \"anonymous union\");\n\
+                  char a[offsetof(struct s, i)];\n\
+                  _Static_assert(sizeof a == 4, \"array size\");\n\
+                  _Static_assert(_Generic(offsetof(struct s, c), size_t: 1), \"size_t\");\n";
+    assert_eq!(compile("offsetof", source), Ok(()));
+
+    let source = "#include <stddef.h>\nstruct s { char c; int bits : 3; };\n";
+    let errors = compile(
+        "offsetof_bits",
+        &format!("{}int x = offsetof(struct s, bits);\n", source),
+    )
+    .unwrap_err();
+    assert!(errors.contains("`offsetof` can't be applied to bit-field `bits`"));
+    let errors = compile(
+        "offsetof_member",
+        &format!("{}int x = offsetof(struct s, n);\n", source),
+    )
+    .unwrap_err();
+    assert!(errors.contains("`struct s` has no member named `n`"));
+    let errors = compile(
+        "offsetof_type",
+        &format!("{}int x = offsetof(int, n);\n", source),
+    )
+    .unwrap_err();
+    assert!(errors.contains("`offsetof` needs a complete struct or union, not `int`"));
+}
+
+#[test]
+fn initializers_follow_the_object() {
+    let source = "struct s { int a; union { int u; float f; }; char c[4]; };\n\
+                  struct t { struct s s; int b[2]; };\n\
+                  struct t x = { 1, 2, \"abc\", 3, 4 };\n\
+                  struct t y = { { 1, { 2 }, { \"ab\" } }, { 3 } };\n\
+                  struct t z = { .b[1] = 4, .s.f = 1.5, .s.a = 1 };\n\
+                  int m[2][2] = { 1, 2, [1][1] = 4 };\n\
+                  char c[4] = { \"abc\" };\n\
+                  int *p = { 0 };\n";
+    assert_eq!(compile("initializers", source), Ok(()));
+
+    let errors = compile("initializer_pointer", "int *p = { 3.0 };\n").unwrap_err();
+    assert!(errors.contains("Can't convert `double` to `pointer to int` in an initializer"));
+
+    let errors = compile("initializer_excess", "int x[2] = { 1, 2, 3 };\n").unwrap_err();
+    assert!(errors.contains("Excess elements in the initializer of `array of 2 int`"));
+
+    let errors = compile("initializer_scalar", "int x = { 1, 2 };\n").unwrap_err();
+    assert!(errors.contains("Excess elements in the initializer of `int`"));
+
+    let errors = compile(
+        "initializer_member",
+        "struct s { int a; };\nstruct s s = { .b = 1 };\n",
+    )
+    .unwrap_err();
+    assert!(errors.contains("`struct s` has no member named `b`"));
+
+    let errors = compile("initializer_index", "int x[2] = { [2] = 1 };\n").unwrap_err();
+    assert!(errors.contains("Array index 2 in an initializer is past the end of `array of 2 int`"));
+
+    let errors = compile("initializer_string", "char c[2] = \"abc\";\n").unwrap_err();
+    assert!(errors.contains("The string has 3 characters, which is too many for `array of 2 char`"));
+
+    let errors = compile("initializer_array", "int a[2] = 1;\n").unwrap_err();
+    assert!(errors.contains("`array of 2 int` has to be initialized with a list in braces"));
+}
+
+#[test]
+fn arrays_are_sized_by_their_initializers() {
+    let source = "int a[] = { 1, 2, 3 };\n\
+                  _Static_assert(sizeof a / sizeof a[0] == 3, \"elements\");\n\
+                  char buf[] = \"hi\";\n\
+                  _Static_assert(sizeof buf == 3, \"string with its null\");\n\
+                  int d[] = { 1, [9] = 2, 3 };\n\
+                  _Static_assert(sizeof d == 44, \"highest index\");\n\
+                  extern int e[];\n\
+                  int e[] = { 1, 2 };\n\
+                  _Static_assert(sizeof e == 8, \"redeclared\");\n\
+                  _Static_assert(sizeof (int[]){ 1, [4] = 2 } == 20, \"compound literal\");\n\
+                  void f(void) {\n\
+                  \x20   char s[] = { \"abcd\" };\n\
+                  \x20   _Static_assert(sizeof s == 5, \"string in braces\");\n\
+                  }\n";
+    assert_eq!(compile("array_sizes", source), Ok(()));
+}
+
+#[test]
+fn functions_return_neither_arrays_nor_functions() {
+    let errors = compile("return_array", "int f(void)[3];\n").unwrap_err();
+    assert!(errors.contains("A function can't return an array, `array of 3 int`"));
+
+    let errors = compile("return_typedef", "typedef int A[3];\nA f(void);\n").unwrap_err();
+    assert!(errors.contains("A function can't return an array, `array of 3 int`"));
+
+    let errors = compile("return_function", "int f(void)(void);\n").unwrap_err();
+    assert!(errors.contains("A function can't return a function"));
+
+    // Reported once, though the parameter is part of the type of `g`
+    let errors = compile("return_parameter", "void g(int h(void)[2]) {}\n").unwrap_err();
+    assert_eq!(errors.matches("Compilation Error").count(), 1, "{}", errors);
+
+    let source = "int (*f(void))[3];\nint (*g(void))(void);\n";
+    assert_eq!(compile("return_pointers", source), Ok(()));
+}
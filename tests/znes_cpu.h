@@ -0,0 +1,106 @@
+#ifndef ZNES_CPU_H
+#define ZNES_CPU_H
+
+#include <stdint.h>
+
+#include "znes_memorybus.h"
+#include "znes_result.h"
+
+/* Bits of the status register, counted from the most significant */
+typedef enum ZNES_Flag {
+    ZNES_FLAG_NEGATIVE,
+    ZNES_FLAG_OVERFLOW,
+    ZNES_FLAG_UNUSED,
+    ZNES_FLAG_BREAK,
+    ZNES_FLAG_DECIMAL,
+    ZNES_FLAG_INTERRUPT_DISABLE,
+    ZNES_FLAG_ZERO,
+    ZNES_FLAG_CARRY
+} ZNES_Flag;
+
+typedef enum ZNES_AddressingMode {
+    ZNES_ADDRESSING_IMPLIED,
+    ZNES_ADDRESSING_ACCUMULATOR,
+    ZNES_ADDRESSING_IMMEDIATE,
+    ZNES_ADDRESSING_ABSOLUTE,
+    ZNES_ADDRESSING_ZERO_PAGE,
+    ZNES_ADDRESSING_RELATIVE,
+    ZNES_ADDRESSING_ZERO_X,
+    ZNES_ADDRESSING_ZERO_Y,
+    ZNES_ADDRESSING_ABS_X,
+    ZNES_ADDRESSING_ABS_Y,
+    ZNES_ADDRESSING_INDIRECT,
+    ZNES_ADDRESSING_INDIRECT_X,
+    ZNES_ADDRESSING_INDIRECT_Y
+} ZNES_AddressingMode;
+
+typedef struct ZNES_CPU {
+    uint16_t PC;
+    uint8_t SP;
+    uint8_t A;
+    uint8_t X;
+    uint8_t Y;
+    uint8_t P;
+    uint8_t flags;
+    ZNES_MemoryBus memory;
+} ZNES_CPU;
+
+ZNES_Result znes_cpu_new(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_free(ZNES_CPU *cpu);
+void znes_cpu_set_flag(ZNES_CPU *cpu, ZNES_Flag flag, uint8_t value);
+uint8_t znes_cpu_get_flag(const ZNES_CPU *cpu, ZNES_Flag flag);
+ZNES_Result znes_cpu_immediate8(ZNES_CPU *cpu, uint8_t *byte);
+ZNES_Result znes_cpu_process_addr(ZNES_CPU *cpu, ZNES_ByteRef *ref, ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_exec_next(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_adc(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_and(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_asl(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bcc(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bcs(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_beq(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bit(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bmi(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bne(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bpl(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_brk(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_bvc(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_bvs(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_flag_clear(ZNES_CPU *cpu, const ZNES_Flag flag);
+ZNES_Result znes_cpu_instr_cmp(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_cpx(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_cpy(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_dec(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_dex(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_dey(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_eor(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_inc(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_inx(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_iny(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_jmp(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_jsr(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_lda(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_ldx(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_ldy(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_lsr(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_ora(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_pha(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_php(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_pla(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_plp(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_rol(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_ror(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_rti(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_rts(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_sbc(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_flag_set(ZNES_CPU *cpu, const ZNES_Flag flag);
+ZNES_Result znes_cpu_instr_sta(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_stx(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_sty(ZNES_CPU *cpu, const ZNES_AddressingMode mode);
+ZNES_Result znes_cpu_instr_tax(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_tay(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_tsx(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_txa(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_txs(ZNES_CPU *cpu);
+ZNES_Result znes_cpu_instr_tya(ZNES_CPU *cpu);
+
+#endif
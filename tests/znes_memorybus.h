@@ -0,0 +1,42 @@
+#ifndef ZNES_MEMORYBUS_H
+#define ZNES_MEMORYBUS_H
+
+#include <stdint.h>
+
+#include "znes_result.h"
+
+#define ZNES_RAM_SIZE 0x800
+
+typedef struct ZNES_MemoryBus {
+    uint8_t *ram;
+} ZNES_MemoryBus;
+
+/* A byte that an instruction operates on, which is either a register,
+   a literal from the instruction stream or somewhere in memory */
+typedef enum ZNES_ByteRefAddressing {
+    ZNES_BYTEREF_REGISTER,
+    ZNES_BYTEREF_LITERAL,
+    ZNES_BYTEREF_MEMORY
+} ZNES_ByteRefAddressing;
+
+typedef struct ZNES_ByteRef {
+    ZNES_ByteRefAddressing addressing;
+    uint8_t literal;
+    struct {
+        uint8_t *ref;
+    } reg;
+    struct {
+        ZNES_MemoryBus *bus;
+        uint16_t address;
+    } memory;
+} ZNES_ByteRef;
+
+ZNES_Result znes_memorybus_new(ZNES_MemoryBus *bus);
+ZNES_Result znes_memorybus_free(ZNES_MemoryBus *bus);
+ZNES_Result znes_memorybus_read8(const ZNES_MemoryBus *bus, uint16_t address, uint8_t *byte);
+ZNES_Result znes_memorybus_read8ref(ZNES_MemoryBus *bus, uint16_t address, ZNES_ByteRef *ref);
+
+ZNES_Result znes_byteref_read(const ZNES_ByteRef *ref, uint8_t *byte);
+ZNES_Result znes_byteref_write(ZNES_ByteRef *ref, uint8_t byte);
+
+#endif
@@ -0,0 +1,26 @@
+#ifndef ZNES_RESULT_H
+#define ZNES_RESULT_H
+
+typedef enum ZNES_Result {
+    ZNES_RESULT_OK = 0,
+    ZNES_RESULT_OUT_OF_MEMORY,
+    ZNES_RESULT_INVALID_ADDRESS,
+    ZNES_RESULT_INVALID_INSTRUCTION
+} ZNES_Result;
+
+/* Evaluates `expr` and, if it failed, stores the error in `result` and
+   jumps to `label`. Both default to `result` and `finish`, so
+   `znes_catch(expr)` works in any function following that pattern. */
+#define znes_catch(...) \
+    ZNES_CATCH_SELECT(__VA_ARGS__, ZNES_CATCH3, ZNES_CATCH2, ZNES_CATCH1, _)(__VA_ARGS__)
+#define ZNES_CATCH_SELECT(_1, _2, _3, NAME, ...) NAME
+#define ZNES_CATCH1(expr) ZNES_CATCH3(expr, result, finish)
+#define ZNES_CATCH2(expr, res) ZNES_CATCH3(expr, res, finish)
+#define ZNES_CATCH3(expr, res, label)                \
+    {                                                \
+        if (((res) = (expr)) != ZNES_RESULT_OK) {    \
+            goto label;                              \
+        }                                            \
+    }
+
+#endif